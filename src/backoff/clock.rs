@@ -0,0 +1,95 @@
+//! Calibrated cycle counter for nanosecond backoff delays.
+//!
+//! x86 reads the TSC and calibrates it against `std::time::Instant` once, on
+//! first use. aarch64 reads `cntvct_el0`, whose frequency the architecture
+//! reports in `cntfrq_el0`. Other targets count nanoseconds with `Instant`.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Counter ticks per 2^16 ns; zero until calibrated.
+static SCALE: AtomicU64 = AtomicU64::new(0);
+
+#[inline]
+fn now() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: rdtsc is available on every x86_64 processor.
+    return unsafe { core::arch::x86_64::_rdtsc() };
+    #[cfg(target_arch = "x86")]
+    // SAFETY: every processor with SSE2 has rdtsc.
+    return unsafe { core::arch::x86::_rdtsc() };
+    #[cfg(target_arch = "aarch64")]
+    {
+        let v: u64;
+        // SAFETY: EL0 access to the virtual counter is always enabled.
+        unsafe { core::arch::asm!("mrs {}, cntvct_el0", out(reg) v, options(nomem, nostack)) };
+        return v;
+    }
+    #[allow(unreachable_code)]
+    {
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_nanos() as u64
+    }
+}
+
+#[cold]
+fn calibrate() -> u64 {
+    #[cfg(target_arch = "aarch64")]
+    let scale = {
+        let freq: u64;
+        // SAFETY: cntfrq_el0 is readable from EL0.
+        unsafe { core::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq, options(nomem, nostack)) };
+        (freq << 16) / 1_000_000_000
+    };
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    let scale = {
+        let (t0, c0) = (Instant::now(), now());
+        while t0.elapsed() < std::time::Duration::from_millis(2) {
+            core::hint::spin_loop();
+        }
+        let (ns, c1) = (t0.elapsed().as_nanos() as u64, now());
+        ((c1 - c0) << 16) / ns.max(1)
+    };
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    let scale = 1 << 16;
+    let scale = scale.max(1);
+    SCALE.store(scale, Ordering::Relaxed);
+    scale
+}
+
+/// Converts nanoseconds to counter ticks, calibrating on first use.
+#[inline]
+fn ticks(ns: u32) -> u64 {
+    let scale = match SCALE.load(Ordering::Relaxed) {
+        0 => calibrate(),
+        s => s,
+    };
+    (ns as u64 * scale) >> 16
+}
+
+/// Busy-waits for about `ns` nanoseconds, stalling between reads.
+#[inline]
+pub(super) fn delay(ns: u32, stall: &crate::pr::StallPolicy) {
+    let end = now().wrapping_add(ticks(ns));
+    while (end.wrapping_sub(now()) as i64) > 0 {
+        stall.stall();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pr::StallPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn delay_is_roughly_calibrated() {
+        let t = Instant::now();
+        delay(2_000_000, &StallPolicy::DEFAULT);
+        let e = t.elapsed();
+        assert!(e >= Duration::from_micros(1500), "{e:?}");
+        assert!(e < Duration::from_millis(500), "{e:?}");
+    }
+}
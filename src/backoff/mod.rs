@@ -0,0 +1,464 @@
+//! Exponential backoff modelled on `ck_backoff`.
+//!
+//! A [`Backoff`] waits for a number of stalls that doubles on every
+//! [`spin`](Backoff::spin), from [`BACKOFF_INITIAL`] up to
+//! [`BACKOFF_CEILING`]. With jitter enabled each wait is drawn at random
+//! from the upper half of the current window, so threads that collided once
+//! do not retry in lockstep.
+//!
+//! With the `std` feature, [`snooze`](Backoff::snooze) and
+//! `snooze_on` escalate once spinning has gone on for
+//! [`BackoffConfig::spin_rounds`] calls: first to `thread::yield_now`, then,
+//! after [`BackoffConfig::yield_rounds`] more, to parking on a futex word via
+//! `pr::wait`.
+//!
+//! With the `calibrated-backoff` feature, `DelayUnit::Nanoseconds` makes
+//! the window a duration measured with the CPU's cycle counter, calibrated
+//! on first use, instead of a count of stalls whose length varies by CPU.
+//!
+//! Locks wait through the [`BackoffPolicy`] trait: the spinlocks and
+//! reader-writer locks take a `B: BackoffPolicy` parameter defaulting to
+//! [`Backoff`], and delay with a fresh `B` between polls of a busy lock.
+//! [`NoBackoff`] retries immediately for latency-critical paths.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::pr::{AtomicLoad, StallPolicy};
+
+#[cfg(feature = "calibrated-backoff")]
+mod clock;
+
+/// Initial number of stalls per [`Backoff::spin`], as in `CK_BACKOFF_INITIAL`.
+pub const BACKOFF_INITIAL: u32 = 1 << 9;
+
+/// Largest number of stalls per [`Backoff::spin`], as in `CK_BACKOFF_CEILING`.
+pub const BACKOFF_CEILING: u32 = (1 << 20) - 1;
+
+/// Initial wait in nanoseconds for [`BackoffConfig::NANOSECONDS`].
+#[cfg(feature = "calibrated-backoff")]
+pub const BACKOFF_INITIAL_NS: u32 = 1_000;
+
+/// Longest wait in nanoseconds for [`BackoffConfig::NANOSECONDS`].
+#[cfg(feature = "calibrated-backoff")]
+pub const BACKOFF_CEILING_NS: u32 = 1_000_000;
+
+/// What a [`BackoffConfig`] window counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DelayUnit {
+    /// Executions of the stall policy.
+    #[default]
+    Stalls,
+    /// Nanoseconds measured with the calibrated cycle counter.
+    #[cfg(feature = "calibrated-backoff")]
+    Nanoseconds,
+}
+
+/// Parameters of a [`Backoff`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackoffConfig {
+    /// Stalls in the first wait.
+    pub initial: u32,
+    /// Upper bound on stalls per wait.
+    pub ceiling: u32,
+    /// Randomize each wait within the upper half of the current window.
+    pub jitter: bool,
+    /// How to stall.
+    pub stall: StallPolicy,
+    /// Unit of `initial` and `ceiling`.
+    pub unit: DelayUnit,
+    /// Snoozes that spin before escalating to yields.
+    pub spin_rounds: u32,
+    /// Snoozes that yield before escalating to parking.
+    pub yield_rounds: u32,
+}
+
+impl BackoffConfig {
+    /// Deterministic exponential backoff with the `ck_backoff` bounds.
+    pub const DETERMINISTIC: BackoffConfig = BackoffConfig {
+        initial: BACKOFF_INITIAL,
+        ceiling: BACKOFF_CEILING,
+        jitter: false,
+        stall: StallPolicy::DEFAULT,
+        unit: DelayUnit::Stalls,
+        spin_rounds: u32::MAX,
+        yield_rounds: 0,
+    };
+
+    /// Jittered exponential backoff with the `ck_backoff` bounds.
+    pub const JITTERED: BackoffConfig = BackoffConfig {
+        jitter: true,
+        ..Self::DETERMINISTIC
+    };
+
+    /// Short spins, then yields, then parking; for locks held for long.
+    pub const ESCALATING: BackoffConfig = BackoffConfig {
+        initial: 1 << 4,
+        ceiling: 1 << 10,
+        jitter: false,
+        stall: StallPolicy::DEFAULT,
+        unit: DelayUnit::Stalls,
+        spin_rounds: 10,
+        yield_rounds: 10,
+    };
+
+    /// Deterministic backoff from 1 µs to 1 ms.
+    #[cfg(feature = "calibrated-backoff")]
+    pub const NANOSECONDS: BackoffConfig = BackoffConfig {
+        initial: BACKOFF_INITIAL_NS,
+        ceiling: BACKOFF_CEILING_NS,
+        unit: DelayUnit::Nanoseconds,
+        ..Self::DETERMINISTIC
+    };
+
+    /// Returns the configuration with different escalation thresholds.
+    pub const fn with_escalation(self, spin_rounds: u32, yield_rounds: u32) -> Self {
+        BackoffConfig {
+            spin_rounds,
+            yield_rounds,
+            ..self
+        }
+    }
+
+    /// Returns the configuration with different wait bounds.
+    pub const fn with_bounds(self, initial: u32, ceiling: u32) -> Self {
+        BackoffConfig {
+            initial,
+            ceiling,
+            ..self
+        }
+    }
+
+    /// Returns the configuration with a different stall policy.
+    pub const fn with_stall(self, stall: StallPolicy) -> Self {
+        BackoffConfig { stall, ..self }
+    }
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self::DETERMINISTIC
+    }
+}
+
+/// Exponential backoff state for one waiter.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    config: BackoffConfig,
+    current: u32,
+    rng: u32,
+    rounds: u32,
+}
+
+impl Backoff {
+    /// Creates a deterministic backoff with the default configuration.
+    pub const fn new() -> Self {
+        Backoff {
+            config: BackoffConfig::DETERMINISTIC,
+            current: BACKOFF_INITIAL,
+            rng: 1,
+            rounds: 0,
+        }
+    }
+
+    /// Creates a backoff with the given configuration.
+    ///
+    /// Jittered backoffs get a distinct seed per instance.
+    pub fn with_config(config: BackoffConfig) -> Self {
+        static SEED: AtomicU32 = AtomicU32::new(0);
+        let rng = if config.jitter {
+            // Weyl sequence through a murmur3 finalizer; never zero.
+            let mut x = SEED
+                .fetch_add(0x9e37_79b9, Ordering::Relaxed)
+                .wrapping_add(0x9e37_79b9);
+            x ^= x >> 16;
+            x = x.wrapping_mul(0x85eb_ca6b);
+            x ^= x >> 13;
+            x = x.wrapping_mul(0xc2b2_ae35);
+            x ^= x >> 16;
+            x | 1
+        } else {
+            1
+        };
+        Backoff {
+            config,
+            current: config.initial.max(1),
+            rng,
+            rounds: 0,
+        }
+    }
+
+    /// Returns the configuration.
+    pub const fn config(&self) -> &BackoffConfig {
+        &self.config
+    }
+
+    /// Returns the current wait window in stalls.
+    pub const fn current(&self) -> u32 {
+        self.current
+    }
+
+    /// Returns `true` once the window has reached the ceiling.
+    pub const fn is_completed(&self) -> bool {
+        self.current >= self.config.ceiling
+    }
+
+    /// Restarts from the initial window and the spinning phase.
+    pub fn reset(&mut self) {
+        self.current = self.config.initial.max(1);
+        self.rounds = 0;
+    }
+
+    /// Returns `true` once `snooze_on` would park.
+    pub const fn is_parking(&self) -> bool {
+        self.rounds
+            >= self
+                .config
+                .spin_rounds
+                .saturating_add(self.config.yield_rounds)
+    }
+
+    #[inline]
+    fn next_random(&mut self) -> u32 {
+        // xorshift32
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        x
+    }
+
+    /// Returns how many stalls the next [`spin`](Self::spin) will wait.
+    fn delay(&mut self) -> u32 {
+        let window = self.current;
+        if !self.config.jitter {
+            return window;
+        }
+        let half = window / 2;
+        half + self.next_random() % (window - half + 1)
+    }
+
+    /// Waits for the current window, then doubles it up to the ceiling.
+    #[inline]
+    pub fn spin(&mut self) {
+        let delay = self.delay();
+        match self.config.unit {
+            DelayUnit::Stalls => {
+                for _ in 0..delay {
+                    self.config.stall.stall();
+                }
+            }
+            #[cfg(feature = "calibrated-backoff")]
+            DelayUnit::Nanoseconds => clock::delay(delay, &self.config.stall),
+        }
+        self.current = self.current.saturating_mul(2).min(self.config.ceiling);
+    }
+}
+
+#[cfg(feature = "std")]
+impl Backoff {
+    /// Spins, or yields once past [`BackoffConfig::spin_rounds`].
+    ///
+    /// Without an address to park on, the parking phase keeps yielding.
+    pub fn snooze(&mut self) {
+        if self.rounds < self.config.spin_rounds {
+            self.spin();
+        } else {
+            std::thread::yield_now();
+        }
+        self.rounds = self.rounds.saturating_add(1);
+    }
+
+    /// Like [`snooze`](Self::snooze), but parks while `target` holds
+    /// `expected` once the yielding phase is over.
+    ///
+    /// Whoever changes `target` must call [`pr::wake_one`](crate::pr::wake_one)
+    /// or [`pr::wake_all`](crate::pr::wake_all) on it.
+    pub fn snooze_on(&mut self, target: &core::sync::atomic::AtomicU32, expected: u32) {
+        if self.is_parking() {
+            crate::pr::wait(target, expected);
+        } else {
+            self.snooze();
+        }
+    }
+}
+
+/// How a waiter delays between retries.
+///
+/// Locks create a fresh policy with `Default` for every contended
+/// acquisition.
+pub trait BackoffPolicy: Default {
+    /// Waits before the next retry.
+    fn spin(&mut self);
+
+    /// Waits before the next retry, possibly yielding the processor.
+    #[inline]
+    fn snooze(&mut self) {
+        self.spin()
+    }
+
+    /// Returns `true` once waiting longer is unlikely to help and the
+    /// caller should consider blocking.
+    fn is_completed(&self) -> bool;
+
+    /// Restarts from the shortest delay.
+    fn reset(&mut self);
+}
+
+impl BackoffPolicy for Backoff {
+    #[inline]
+    fn spin(&mut self) {
+        Backoff::spin(self)
+    }
+
+    #[inline]
+    fn snooze(&mut self) {
+        #[cfg(feature = "std")]
+        Backoff::snooze(self);
+        #[cfg(not(feature = "std"))]
+        Backoff::spin(self);
+    }
+
+    #[inline]
+    fn is_completed(&self) -> bool {
+        Backoff::is_completed(self)
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        Backoff::reset(self)
+    }
+}
+
+/// A policy that retries immediately, never delaying or completing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NoBackoff;
+
+impl BackoffPolicy for NoBackoff {
+    #[inline(always)]
+    fn spin(&mut self) {}
+
+    #[inline(always)]
+    fn is_completed(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn reset(&mut self) {}
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Like [`pr::spin_while`](crate::pr::spin_while), delaying with a fresh `B`
+/// between polls.
+#[inline]
+pub(crate) fn spin_while<B, A>(target: &A, mut cond: impl FnMut(A::Value) -> bool) -> A::Value
+where
+    B: BackoffPolicy,
+    A: AtomicLoad + ?Sized,
+{
+    let mut backoff = B::default();
+    loop {
+        let v = target.atomic_load(Ordering::Acquire);
+        if !cond(v) {
+            return v;
+        }
+        backoff.spin();
+    }
+}
+
+/// Like [`pr::load_when`](crate::pr::load_when), delaying with a fresh `B`
+/// between polls.
+#[inline]
+pub(crate) fn load_when<B, A>(target: &A, expected: A::Value)
+where
+    B: BackoffPolicy,
+    A: AtomicLoad + ?Sized,
+{
+    spin_while::<B, A>(target, |v| v != expected);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_to_ceiling() {
+        let mut b = Backoff::with_config(BackoffConfig::DETERMINISTIC.with_bounds(1, 6));
+        let windows: [u32; 5] = core::array::from_fn(|_| {
+            let w = b.current();
+            b.spin();
+            w
+        });
+        assert_eq!(windows, [1, 2, 4, 6, 6]);
+        assert!(b.is_completed());
+        b.reset();
+        assert_eq!(b.current(), 1);
+        assert_eq!(Backoff::new().current(), BACKOFF_INITIAL);
+    }
+
+    #[test]
+    fn jitter_stays_in_window_and_varies() {
+        let cfg = BackoffConfig::JITTERED.with_bounds(64, 64);
+        let mut a = Backoff::with_config(cfg);
+        let mut b = Backoff::with_config(cfg);
+        let da: [u32; 16] = core::array::from_fn(|_| a.delay());
+        let db: [u32; 16] = core::array::from_fn(|_| b.delay());
+        assert!(da.iter().chain(&db).all(|d| (32..=64).contains(d)));
+        assert_ne!(da, db);
+        let mut d = Backoff::with_config(BackoffConfig::DETERMINISTIC);
+        assert_eq!(d.delay(), BACKOFF_INITIAL);
+    }
+
+    fn retry<B: BackoffPolicy>(tries: u32) -> bool {
+        let mut b = B::default();
+        for _ in 0..tries {
+            b.snooze();
+        }
+        let done = b.is_completed();
+        b.reset();
+        done
+    }
+
+    #[test]
+    fn policies() {
+        assert!(!retry::<NoBackoff>(100));
+        assert!(!retry::<Backoff>(1));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn escalates_to_park() {
+        use core::sync::atomic::AtomicU32;
+        use std::sync::Arc;
+        use std::thread;
+        use std::time::Duration;
+
+        let mut b = Backoff::with_config(BackoffConfig::ESCALATING.with_escalation(2, 2));
+        let word = Arc::new(AtomicU32::new(0));
+        for _ in 0..4 {
+            assert!(!b.is_parking());
+            b.snooze_on(&word, 0);
+        }
+        assert!(b.is_parking());
+        let waker = {
+            let word = word.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                word.store(1, Ordering::Release);
+                crate::pr::wake_all(&word);
+            })
+        };
+        while word.load(Ordering::Acquire) == 0 {
+            b.snooze_on(&word, 0);
+        }
+        waker.join().unwrap();
+        b.reset();
+        assert!(!b.is_parking());
+        assert!(!Backoff::new().is_parking());
+    }
+}
@@ -0,0 +1,102 @@
+//! Word-at-a-time iteration over set or clear bits.
+
+use core::iter::FusedIterator;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{BitmapWord, WORD_BITS};
+use crate::cc::Bits;
+
+/// Iterator over the indices of set (or clear) bits of a bitmap.
+///
+/// Each word is loaded once when the iterator reaches it; bits changed in
+/// a word already loaded are not seen.
+#[derive(Debug)]
+pub struct BitIter<'a, S: BitmapWord = AtomicUsize> {
+    words: &'a [S],
+    len: usize,
+    invert: bool,
+    /// Index of the word `pending` was loaded from.
+    word: usize,
+    /// Remaining matching bits of that word.
+    pending: usize,
+}
+
+impl<'a, S: BitmapWord> BitIter<'a, S> {
+    pub(super) fn new(words: &'a [S], len: usize, invert: bool) -> Self {
+        let mut it = BitIter {
+            words,
+            len,
+            invert,
+            word: 0,
+            pending: 0,
+        };
+        it.pending = it.load(0);
+        it
+    }
+
+    /// Loads word `w` as a mask of matching bits below `len`.
+    #[inline]
+    fn load(&self, w: usize) -> usize {
+        let Some(word) = self.words.get(w) else {
+            return 0;
+        };
+        let mut bits = word.atomic().load(Ordering::Acquire);
+        if self.invert {
+            bits = !bits;
+        }
+        let rem = self.len.saturating_sub(w * WORD_BITS);
+        if rem < WORD_BITS {
+            bits &= (1 << rem) - 1;
+        }
+        bits
+    }
+}
+
+impl<S: BitmapWord> Iterator for BitIter<'_, S> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        while self.pending == 0 {
+            if self.word + 1 >= self.words.len() {
+                self.word = self.words.len();
+                return None;
+            }
+            self.word += 1;
+            self.pending = self.load(self.word);
+        }
+        let bit = self.pending.ctz() as usize;
+        self.pending &= self.pending - 1;
+        Some(self.word * WORD_BITS + bit)
+    }
+}
+
+impl<S: BitmapWord> FusedIterator for BitIter<'_, S> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitmap::{Bitmap, WORD_BITS};
+    use std::vec::Vec;
+
+    #[test]
+    fn set_and_clear_partition_the_map() {
+        let b = Bitmap::<{ 3 * WORD_BITS }, 3>::new();
+        let set = [
+            0,
+            5,
+            WORD_BITS - 1,
+            WORD_BITS,
+            2 * WORD_BITS + 7,
+            3 * WORD_BITS - 1,
+        ];
+        for &i in &set {
+            b.set(i);
+        }
+        assert_eq!(b.iter_set().collect::<Vec<_>>(), set);
+        let clear: Vec<_> = b.iter_clear().collect();
+        assert_eq!(clear.len(), b.len() - set.len());
+        assert!(clear.iter().all(|i| !set.contains(i)));
+        assert_eq!(clear[..3], [1, 2, 3]);
+        assert_eq!(Bitmap::<0, 0>::new().iter_clear().next(), None);
+    }
+}
@@ -0,0 +1,590 @@
+//! Fixed-size atomic bitmaps modelled on `ck_bitmap`.
+//!
+//! A [`Bitmap`] of `BITS` bits is an array of machine words updated with
+//! atomic read-modify-write operations, so any number of threads may set,
+//! clear and test bits concurrently. Multi-bit queries read one word at a time and
+//! are not atomic snapshots of the whole map.
+//!
+//! [`PaddedBitmap`] gives every word its own cache line, so threads working
+//! on neighbouring words do not contend.
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::cc::{Bits, CachePadded};
+
+mod iter;
+mod word;
+
+pub use self::iter::BitIter;
+pub use self::word::BitmapWord;
+
+/// Bits per bitmap word.
+pub const WORD_BITS: usize = usize::BITS as usize;
+
+/// Returns the number of words needed for `bits` bits.
+pub const fn words(bits: usize) -> usize {
+    bits.div_ceil(WORD_BITS)
+}
+
+/// A bit index was past the end of a bitmap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutOfRange {
+    /// The offending index.
+    pub bit: usize,
+    /// The bitmap's length in bits.
+    pub len: usize,
+}
+
+impl fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bit {} out of range for bitmap of {} bits",
+            self.bit, self.len
+        )
+    }
+}
+
+/// A bitmap of `BITS` bits stored in `W` words of type `S`.
+///
+/// Stable Rust cannot compute an array length from `BITS`, so `W` must be
+/// spelled out as [`words(BITS)`](words), e.g. `Bitmap<100, { words(100) }>`;
+/// any other value fails to compile.
+///
+/// Single-bit operations panic on an index past the end; their `try_`
+/// forms return [`OutOfRange`] instead. Range operations panic like slice
+/// indexing, while searches treat the end as "not found".
+#[derive(Debug)]
+pub struct Bitmap<const BITS: usize, const W: usize, S: BitmapWord = AtomicUsize> {
+    words: [S; W],
+}
+
+/// A [`Bitmap`] with each word on its own cache line.
+pub type PaddedBitmap<const BITS: usize, const W: usize> =
+    Bitmap<BITS, W, CachePadded<AtomicUsize>>;
+
+#[inline(always)]
+const fn split(bit: usize) -> (usize, usize) {
+    (bit / WORD_BITS, 1 << (bit % WORD_BITS))
+}
+
+/// Yields `(word, mask)` pairs covering bits `start..end`.
+#[inline]
+fn range_masks(start: usize, end: usize) -> impl Iterator<Item = (usize, usize)> {
+    let words = if start < end {
+        start / WORD_BITS..end.div_ceil(WORD_BITS)
+    } else {
+        0..0
+    };
+    words.map(move |w| {
+        let base = w * WORD_BITS;
+        let lo = start.max(base) - base;
+        let hi = end.min(base + WORD_BITS) - base;
+        let mask = if hi - lo == WORD_BITS {
+            !0
+        } else {
+            ((1 << (hi - lo)) - 1) << lo
+        };
+        (w, mask)
+    })
+}
+
+impl<const BITS: usize, const W: usize, S: BitmapWord> Bitmap<BITS, W, S> {
+    /// Creates a bitmap with every bit clear.
+    pub const fn new() -> Self {
+        const { assert!(W == words(BITS), "W must be words(BITS)") };
+        Bitmap {
+            words: [const { S::ZERO }; W],
+        }
+    }
+
+    /// Creates a bitmap from words as written by
+    /// [`as_words_snapshot`](Self::as_words_snapshot). Missing words are
+    /// zero; extra words and bits past `BITS` are ignored.
+    pub fn from_words(words: &[usize]) -> Self {
+        let b = Self::new();
+        for (i, (w, &v)) in b.iter_words().zip(words).enumerate() {
+            w.store(v & Self::word_mask(i), Ordering::Relaxed);
+        }
+        b
+    }
+
+    /// Mask of the bits of word `w` that lie below `BITS`.
+    #[inline(always)]
+    const fn word_mask(w: usize) -> usize {
+        let rem = BITS - w * WORD_BITS;
+        if rem >= WORD_BITS {
+            !0
+        } else {
+            (1 << rem) - 1
+        }
+    }
+
+    /// Copies the words into `out`, returning how many were copied (the
+    /// smaller of `W` and `out.len()`).
+    ///
+    /// Each word is read atomically, but not the bitmap as a whole.
+    pub fn as_words_snapshot(&self, out: &mut [usize]) -> usize {
+        let mut n = 0;
+        for (o, w) in out.iter_mut().zip(self.iter_words()) {
+            *o = w.load(Ordering::Acquire);
+            n += 1;
+        }
+        n
+    }
+
+    #[inline(always)]
+    fn word(&self, w: usize) -> &AtomicUsize {
+        self.words[w].atomic()
+    }
+
+    /// Locates `bit`, failing if it is past the end.
+    #[inline(always)]
+    fn locate(&self, bit: usize) -> Result<(&AtomicUsize, usize), OutOfRange> {
+        if bit >= BITS {
+            return Err(OutOfRange { bit, len: BITS });
+        }
+        let (w, mask) = split(bit);
+        Ok((self.word(w), mask))
+    }
+
+    #[inline(always)]
+    fn iter_words(&self) -> impl Iterator<Item = &AtomicUsize> {
+        self.words.iter().map(S::atomic)
+    }
+
+    /// Returns the number of bits.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        BITS
+    }
+
+    /// Returns `true` if the bitmap has no bits at all.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        BITS == 0
+    }
+
+    /// Returns bit `bit`, or [`OutOfRange`] past the end.
+    #[inline]
+    pub fn try_get(&self, bit: usize) -> Result<bool, OutOfRange> {
+        let (w, mask) = self.locate(bit)?;
+        Ok(w.load(Ordering::Acquire) & mask != 0)
+    }
+
+    /// Sets bit `bit`, or returns [`OutOfRange`] past the end.
+    #[inline]
+    pub fn try_set(&self, bit: usize) -> Result<(), OutOfRange> {
+        let (w, mask) = self.locate(bit)?;
+        w.fetch_or(mask, Ordering::AcqRel);
+        Ok(())
+    }
+
+    /// Clears bit `bit` (`ck_bitmap_reset`), or returns [`OutOfRange`]
+    /// past the end.
+    #[inline]
+    pub fn try_clear(&self, bit: usize) -> Result<(), OutOfRange> {
+        let (w, mask) = self.locate(bit)?;
+        w.fetch_and(!mask, Ordering::AcqRel);
+        Ok(())
+    }
+
+    /// Sets bit `bit`, returning its previous value (`ck_bitmap_bts`), or
+    /// returns [`OutOfRange`] past the end.
+    #[inline]
+    pub fn try_test_and_set(&self, bit: usize) -> Result<bool, OutOfRange> {
+        let (w, mask) = self.locate(bit)?;
+        Ok(w.fetch_or(mask, Ordering::AcqRel) & mask != 0)
+    }
+
+    /// Returns bit `bit`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bit >= BITS`.
+    #[inline]
+    #[track_caller]
+    pub fn get(&self, bit: usize) -> bool {
+        unwrap(self.try_get(bit))
+    }
+
+    /// Sets bit `bit`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bit >= BITS`.
+    #[inline]
+    #[track_caller]
+    pub fn set(&self, bit: usize) {
+        unwrap(self.try_set(bit))
+    }
+
+    /// Clears bit `bit`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bit >= BITS`.
+    #[inline]
+    #[track_caller]
+    pub fn clear(&self, bit: usize) {
+        unwrap(self.try_clear(bit))
+    }
+
+    /// Sets bit `bit`, returning its previous value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bit >= BITS`.
+    #[inline]
+    #[track_caller]
+    pub fn test_and_set(&self, bit: usize) -> bool {
+        unwrap(self.try_test_and_set(bit))
+    }
+
+    /// Word masks for `start..end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start > end` or `end > BITS`.
+    #[inline]
+    #[track_caller]
+    fn masks(&self, start: usize, end: usize) -> impl Iterator<Item = (&AtomicUsize, usize)> {
+        assert!(
+            start <= end && end <= BITS,
+            "range {start}..{end} out of range for bitmap of {BITS} bits"
+        );
+        range_masks(start, end).map(|(w, m)| (self.word(w), m))
+    }
+
+    /// Sets bits `start..end`, one atomic operation per word.
+    #[track_caller]
+    pub fn set_range(&self, start: usize, end: usize) {
+        for (w, mask) in self.masks(start, end) {
+            w.fetch_or(mask, Ordering::AcqRel);
+        }
+    }
+
+    /// Clears bits `start..end`, one atomic operation per word.
+    #[track_caller]
+    pub fn clear_range(&self, start: usize, end: usize) {
+        for (w, mask) in self.masks(start, end) {
+            w.fetch_and(!mask, Ordering::AcqRel);
+        }
+    }
+
+    /// Returns `true` if every bit in `start..end` is set.
+    #[track_caller]
+    pub fn all_set_in_range(&self, start: usize, end: usize) -> bool {
+        self.masks(start, end)
+            .all(|(w, mask)| w.load(Ordering::Acquire) & mask == mask)
+    }
+
+    /// Returns `true` if any bit in `start..end` is set.
+    #[track_caller]
+    pub fn any_set_in_range(&self, start: usize, end: usize) -> bool {
+        self.masks(start, end)
+            .any(|(w, mask)| w.load(Ordering::Acquire) & mask != 0)
+    }
+
+    /// Sets every bit that is set in `other` (`ck_bitmap_union`).
+    pub fn union_with(&self, other: &Self) {
+        for (w, o) in self.iter_words().zip(other.iter_words()) {
+            w.fetch_or(o.load(Ordering::Acquire), Ordering::AcqRel);
+        }
+    }
+
+    /// Clears every bit that is clear in `other` (`ck_bitmap_intersection`).
+    pub fn intersect_with(&self, other: &Self) {
+        for (w, o) in self.iter_words().zip(other.iter_words()) {
+            w.fetch_and(o.load(Ordering::Acquire), Ordering::AcqRel);
+        }
+    }
+
+    /// Clears every bit that is set in `other`
+    /// (`ck_bitmap_intersection_negate`).
+    pub fn difference_with(&self, other: &Self) {
+        for (w, o) in self.iter_words().zip(other.iter_words()) {
+            w.fetch_and(!o.load(Ordering::Acquire), Ordering::AcqRel);
+        }
+    }
+
+    /// Returns `true` if every bit set here is also set in `other`.
+    pub fn is_subset_of(&self, other: &Self) -> bool {
+        self.iter_words()
+            .zip(other.iter_words())
+            .all(|(w, o)| w.load(Ordering::Acquire) & !o.load(Ordering::Acquire) == 0)
+    }
+
+    /// Loads word `w`, inverted when searching for clear bits.
+    #[inline(always)]
+    fn load_for(&self, w: usize, clear: bool) -> usize {
+        let v = self.word(w).load(Ordering::Acquire);
+        if clear {
+            !v
+        } else {
+            v
+        }
+    }
+
+    fn next_bit(&self, from: usize, clear: bool) -> Option<usize> {
+        if from >= self.len() {
+            return None;
+        }
+        let (mut w, low) = split(from);
+        // Drop the bits below `from` in its word.
+        let mut bits = self.load_for(w, clear) & !(low - 1);
+        loop {
+            if bits != 0 {
+                let bit = w * WORD_BITS + bits.ctz() as usize;
+                // Clear bits past the end are padding.
+                return (bit < BITS).then_some(bit);
+            }
+            w += 1;
+            if w == W {
+                return None;
+            }
+            bits = self.load_for(w, clear);
+        }
+    }
+
+    fn prev_bit(&self, from: usize, clear: bool) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        let (mut w, high) = split(from.min(BITS - 1));
+        // Keep the bits up to and including `from` in its word.
+        let mut bits = self.load_for(w, clear) & (high | (high - 1));
+        loop {
+            if bits != 0 {
+                return Some(w * WORD_BITS + WORD_BITS - 1 - bits.clz() as usize);
+            }
+            if w == 0 {
+                return None;
+            }
+            w -= 1;
+            bits = self.load_for(w, clear);
+        }
+    }
+
+    /// Returns the first set bit at or after `from`.
+    #[inline]
+    pub fn next_set_bit(&self, from: usize) -> Option<usize> {
+        self.next_bit(from, false)
+    }
+
+    /// Returns the first clear bit at or after `from`.
+    #[inline]
+    pub fn next_clear_bit(&self, from: usize) -> Option<usize> {
+        self.next_bit(from, true)
+    }
+
+    /// Returns the last set bit at or before `from`.
+    #[inline]
+    pub fn prev_set_bit(&self, from: usize) -> Option<usize> {
+        self.prev_bit(from, false)
+    }
+
+    /// Returns the last clear bit at or before `from`.
+    #[inline]
+    pub fn prev_clear_bit(&self, from: usize) -> Option<usize> {
+        self.prev_bit(from, true)
+    }
+
+    /// Returns the number of set bits in `start..end`.
+    #[track_caller]
+    pub fn popcount_range(&self, start: usize, end: usize) -> usize {
+        self.masks(start, end)
+            .map(|(w, mask)| (w.load(Ordering::Acquire) & mask).popcount() as usize)
+            .sum()
+    }
+
+    /// Clears every bit.
+    pub fn clear_all(&self) {
+        for w in self.iter_words() {
+            w.store(0, Ordering::Release);
+        }
+    }
+
+    /// Returns the number of set bits.
+    pub fn count_ones(&self) -> usize {
+        self.iter_words()
+            .map(|w| w.load(Ordering::Acquire).count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns an iterator over the indices of set bits, in ascending order.
+    #[inline]
+    pub fn iter_set(&self) -> BitIter<'_, S> {
+        BitIter::new(&self.words, self.len(), false)
+    }
+
+    /// Returns an iterator over the indices of clear bits, in ascending order.
+    #[inline]
+    pub fn iter_clear(&self) -> BitIter<'_, S> {
+        BitIter::new(&self.words, self.len(), true)
+    }
+}
+
+#[inline(always)]
+#[track_caller]
+fn unwrap<T>(r: Result<T, OutOfRange>) -> T {
+    match r {
+        Ok(v) => v,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+impl<const BITS: usize, const W: usize, S: BitmapWord> Default for Bitmap<BITS, W, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn set_clear_and_bounds() {
+        let b = Bitmap::<{ 2 * WORD_BITS }, 2>::new();
+        assert_eq!(b.len(), 2 * WORD_BITS);
+        assert!(!b.test_and_set(3));
+        assert!(b.test_and_set(3));
+        b.set(WORD_BITS + 1);
+        assert!(b.get(3) && b.get(WORD_BITS + 1) && !b.get(4));
+        b.clear(3);
+        assert!(!b.get(3));
+        let past = OutOfRange {
+            bit: b.len(),
+            len: b.len(),
+        };
+        assert_eq!(b.try_set(b.len()), Err(past));
+        assert_eq!(b.try_get(b.len()), Err(past));
+        assert_eq!(b.try_test_and_set(3), Ok(false));
+        b.clear(3);
+        assert_eq!(b.count_ones(), 1);
+        b.clear_all();
+        assert_eq!(b.count_ones(), 0);
+    }
+
+    #[test]
+    fn ranges() {
+        let b = Bitmap::<{ 3 * WORD_BITS }, 3>::new();
+        b.set_range(5, 2 * WORD_BITS + 3);
+        assert_eq!(b.count_ones(), 2 * WORD_BITS - 2);
+        assert!(b.all_set_in_range(5, 2 * WORD_BITS + 3));
+        assert!(!b.all_set_in_range(4, 10));
+        assert!(b.any_set_in_range(0, 6) && !b.any_set_in_range(0, 5));
+        assert_eq!(b.popcount_range(0, 8), 3);
+        assert_eq!(
+            b.popcount_range(WORD_BITS - 4, 3 * WORD_BITS),
+            WORD_BITS + 7
+        );
+        b.clear_range(WORD_BITS - 1, WORD_BITS + 1);
+        assert_eq!(b.popcount_range(WORD_BITS - 4, WORD_BITS + 4), 6);
+        assert!(!b.get(WORD_BITS - 1) && !b.get(WORD_BITS) && b.get(WORD_BITS + 1));
+        assert!(b.all_set_in_range(7, 7) && !b.any_set_in_range(9, 9));
+        b.set_range(0, b.len());
+        assert_eq!(b.count_ones(), b.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "bit 100 out of range for bitmap of 100 bits")]
+    fn get_past_end_panics() {
+        Bitmap::<100, { words(100) }>::new().get(100);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn range_past_end_panics() {
+        Bitmap::<100, { words(100) }>::new().set_range(0, 101);
+    }
+
+    #[test]
+    fn partial_last_word() {
+        let b = Bitmap::<100, { words(100) }>::new();
+        b.set_range(90, 100);
+        assert_eq!(b.next_clear_bit(90), None);
+        assert_eq!(b.iter_clear().last(), Some(89));
+        let c = Bitmap::<100, { words(100) }>::from_words(&[0, !0]);
+        assert_eq!(c.count_ones(), 100 - WORD_BITS);
+    }
+
+    #[test]
+    fn boolean_operations() {
+        let (a, b) = (
+            Bitmap::<{ 2 * WORD_BITS }, 2>::new(),
+            Bitmap::<{ 2 * WORD_BITS }, 2>::new(),
+        );
+        a.set_range(0, 10);
+        b.set_range(5, WORD_BITS + 5);
+        let u = Bitmap::<{ 2 * WORD_BITS }, 2>::new();
+        u.union_with(&a);
+        u.union_with(&b);
+        assert_eq!(u.count_ones(), WORD_BITS + 5);
+        assert!(a.is_subset_of(&u) && b.is_subset_of(&u) && !u.is_subset_of(&a));
+        u.intersect_with(&a);
+        assert!(u.all_set_in_range(0, 10) && u.count_ones() == 10);
+        u.difference_with(&b);
+        assert!(u.all_set_in_range(0, 5) && u.count_ones() == 5);
+    }
+
+    #[test]
+    fn directional_search() {
+        let b = Bitmap::<{ 3 * WORD_BITS }, 3>::new();
+        for i in [3, WORD_BITS + 9, 2 * WORD_BITS + 1] {
+            b.set(i);
+        }
+        assert_eq!(b.next_set_bit(0), Some(3));
+        assert_eq!(b.next_set_bit(3), Some(3));
+        assert_eq!(b.next_set_bit(4), Some(WORD_BITS + 9));
+        assert_eq!(b.next_set_bit(2 * WORD_BITS + 2), None);
+        assert_eq!(b.prev_set_bit(usize::MAX), Some(2 * WORD_BITS + 1));
+        assert_eq!(b.prev_set_bit(WORD_BITS + 8), Some(3));
+        assert_eq!(b.prev_set_bit(2), None);
+        assert_eq!(b.next_clear_bit(3), Some(4));
+        assert_eq!(b.prev_clear_bit(3), Some(2));
+        b.set_range(0, b.len());
+        assert_eq!(b.next_clear_bit(0), None);
+        assert_eq!(b.prev_clear_bit(b.len()), None);
+        assert_eq!(Bitmap::<0, 0>::new().prev_set_bit(0), None);
+    }
+
+    #[test]
+    fn padded_words() {
+        let b = PaddedBitmap::<{ 2 * WORD_BITS }, 2>::new();
+        assert!(core::mem::size_of_val(&b) >= 2 * crate::cc::CACHELINE);
+        b.set_range(WORD_BITS - 2, WORD_BITS + 2);
+        assert_eq!(b.iter_set().count(), 4);
+        assert_eq!(b.next_clear_bit(WORD_BITS - 2), Some(WORD_BITS + 2));
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let b = Bitmap::<{ 3 * WORD_BITS }, 3>::new();
+        b.set_range(3, WORD_BITS + 4);
+        let mut words = [0; 4];
+        assert_eq!(b.as_words_snapshot(&mut words), 3);
+        assert_eq!(words[1], 0b1111);
+        let c = PaddedBitmap::<{ 3 * WORD_BITS }, 3>::from_words(&words[..2]);
+        assert_eq!(
+            c.iter_set().collect::<std::vec::Vec<_>>(),
+            b.iter_set().collect::<std::vec::Vec<_>>()
+        );
+        assert_eq!(b.as_words_snapshot(&mut words[..1]), 1);
+    }
+
+    #[test]
+    fn concurrent_claims_are_unique() {
+        let b = Bitmap::<{ 4 * WORD_BITS }, 4>::new();
+        let claimed: usize = thread::scope(|s| {
+            let handles: [_; 4] = core::array::from_fn(|_| {
+                s.spawn(|| (0..b.len()).filter(|&i| !b.test_and_set(i)).count())
+            });
+            handles.map(|h| h.join().unwrap()).iter().sum()
+        });
+        assert_eq!(claimed, b.len());
+    }
+}
@@ -0,0 +1,44 @@
+//! Storage for one bitmap word.
+
+use core::sync::atomic::AtomicUsize;
+
+use crate::cc::CachePadded;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// How a bitmap stores each of its words: packed ([`AtomicUsize`]) or one
+/// per cache line ([`CachePadded<AtomicUsize>`]).
+pub trait BitmapWord: private::Sealed {
+    #[doc(hidden)]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: Self;
+
+    #[doc(hidden)]
+    fn atomic(&self) -> &AtomicUsize;
+}
+
+impl private::Sealed for AtomicUsize {}
+
+impl BitmapWord for AtomicUsize {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: Self = AtomicUsize::new(0);
+
+    #[inline(always)]
+    fn atomic(&self) -> &AtomicUsize {
+        self
+    }
+}
+
+impl private::Sealed for CachePadded<AtomicUsize> {}
+
+impl BitmapWord for CachePadded<AtomicUsize> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: Self = CachePadded::new(AtomicUsize::new(0));
+
+    #[inline(always)]
+    fn atomic(&self) -> &AtomicUsize {
+        self
+    }
+}
@@ -0,0 +1,106 @@
+//! Width-generic bit operations.
+
+/// Bit operations available on every unsigned integer width.
+///
+/// Conventions match the free functions in [`cc`](super): `ffs`/`fls` are
+/// 1-based and return 0 for zero, `ctz`/`clz` return the width for zero.
+pub trait Bits: Copy {
+    /// Width of the type in bits.
+    const BITS: u32;
+
+    /// 1-based index of the least significant set bit, or 0.
+    fn ffs(self) -> u32;
+    /// 1-based index of the most significant set bit, or 0.
+    fn fls(self) -> u32;
+    /// Number of trailing zero bits.
+    fn ctz(self) -> u32;
+    /// Number of leading zero bits.
+    fn clz(self) -> u32;
+    /// Number of set bits.
+    fn popcount(self) -> u32;
+    /// Rotates left by `n` bits.
+    fn rotl(self, n: u32) -> Self;
+    /// Rotates right by `n` bits.
+    fn rotr(self, n: u32) -> Self;
+    /// Reverses the byte order.
+    fn byteswap(self) -> Self;
+}
+
+macro_rules! impl_bits {
+    ($($t:ty),*) => {
+        $(
+            impl Bits for $t {
+                const BITS: u32 = <$t>::BITS;
+
+                #[inline(always)]
+                fn ffs(self) -> u32 {
+                    if self == 0 {
+                        0
+                    } else {
+                        self.trailing_zeros() + 1
+                    }
+                }
+
+                #[inline(always)]
+                fn fls(self) -> u32 {
+                    <$t>::BITS - self.leading_zeros()
+                }
+
+                #[inline(always)]
+                fn ctz(self) -> u32 {
+                    self.trailing_zeros()
+                }
+
+                #[inline(always)]
+                fn clz(self) -> u32 {
+                    self.leading_zeros()
+                }
+
+                #[inline(always)]
+                fn popcount(self) -> u32 {
+                    self.count_ones()
+                }
+
+                #[inline(always)]
+                fn rotl(self, n: u32) -> Self {
+                    self.rotate_left(n)
+                }
+
+                #[inline(always)]
+                fn rotr(self, n: u32) -> Self {
+                    self.rotate_right(n)
+                }
+
+                #[inline(always)]
+                fn byteswap(self) -> Self {
+                    self.swap_bytes()
+                }
+            }
+        )*
+    };
+}
+
+impl_bits!(u8, u16, u32, u64, u128, usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lowest_and_highest<T: Bits>(v: T) -> (u32, u32) {
+        (v.ffs(), v.fls())
+    }
+
+    #[test]
+    fn generic_over_widths() {
+        assert_eq!(lowest_and_highest(0u8), (0, 0));
+        assert_eq!(lowest_and_highest(0b0110u16), (2, 3));
+        assert_eq!(lowest_and_highest(1u128 << 100), (101, 101));
+        assert_eq!(0u64.ctz(), 64);
+        assert_eq!(1usize.clz(), usize::BITS - 1);
+        assert_eq!(0xf0f0u16.popcount(), 8);
+        assert_eq!(0x80u8.rotl(1), 1);
+        assert_eq!(1u32.rotr(1), 0x8000_0000);
+        assert_eq!(0x1234u16.byteswap(), 0x3412);
+        assert_eq!(<u64 as Bits>::BITS, 64);
+    }
+}
@@ -0,0 +1,191 @@
+//! Cache line size and padding.
+//!
+//! [`CACHELINE`] is chosen per target at compile time. It is the coherence
+//! granule to pad against, which on several parts is larger than the L1 line
+//! because of adjacent-line prefetching:
+//!
+//! - 128 bytes on Apple aarch64 and powerpc64,
+//! - 256 bytes on s390x,
+//! - 32 bytes on 32-bit arm, mips, riscv32, sparc and hexagon,
+//! - 64 bytes everywhere else.
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+/// Cache line size in bytes assumed for padding on this target.
+#[cfg(any(
+    all(target_arch = "aarch64", target_vendor = "apple"),
+    target_arch = "powerpc64"
+))]
+pub const CACHELINE: usize = 128;
+/// Cache line size in bytes assumed for padding on this target.
+#[cfg(target_arch = "s390x")]
+pub const CACHELINE: usize = 256;
+/// Cache line size in bytes assumed for padding on this target.
+#[cfg(any(
+    target_arch = "arm",
+    target_arch = "mips",
+    target_arch = "mips32r6",
+    target_arch = "riscv32",
+    target_arch = "sparc",
+    target_arch = "hexagon"
+))]
+pub const CACHELINE: usize = 32;
+/// Cache line size in bytes assumed for padding on this target.
+#[cfg(not(any(
+    all(target_arch = "aarch64", target_vendor = "apple"),
+    target_arch = "powerpc64",
+    target_arch = "s390x",
+    target_arch = "arm",
+    target_arch = "mips",
+    target_arch = "mips32r6",
+    target_arch = "riscv32",
+    target_arch = "sparc",
+    target_arch = "hexagon"
+)))]
+pub const CACHELINE: usize = 64;
+
+/// Pads and aligns a value to [`CACHELINE`] bytes to prevent false sharing.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    any(
+        all(target_arch = "aarch64", target_vendor = "apple"),
+        target_arch = "powerpc64"
+    ),
+    repr(align(128))
+)]
+#[cfg_attr(target_arch = "s390x", repr(align(256)))]
+#[cfg_attr(
+    any(
+        target_arch = "arm",
+        target_arch = "mips",
+        target_arch = "mips32r6",
+        target_arch = "riscv32",
+        target_arch = "sparc",
+        target_arch = "hexagon"
+    ),
+    repr(align(32))
+)]
+#[cfg_attr(
+    not(any(
+        all(target_arch = "aarch64", target_vendor = "apple"),
+        target_arch = "powerpc64",
+        target_arch = "s390x",
+        target_arch = "arm",
+        target_arch = "mips",
+        target_arch = "mips32r6",
+        target_arch = "riscv32",
+        target_arch = "sparc",
+        target_arch = "hexagon"
+    )),
+    repr(align(64))
+)]
+pub struct CachePadded<T> {
+    value: T,
+}
+
+const _: () = assert!(core::mem::align_of::<CachePadded<u8>>() == CACHELINE);
+
+impl<T> CachePadded<T> {
+    /// Pads `value`.
+    pub const fn new(value: T) -> Self {
+        CachePadded { value }
+    }
+
+    /// Returns the inner value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> From<T> for CachePadded<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CachePadded<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CachePadded").field(&self.value).finish()
+    }
+}
+
+/// Queries the L1 data cache line size of the running processor.
+///
+/// Uses `sysconf` on glibc Linux and Android, `cpuid` on x86 and `CTR_EL0`
+/// on aarch64, and falls back to [`CACHELINE`] when none of these answer. The result can be
+/// smaller than [`CACHELINE`], which also accounts for adjacent-line
+/// prefetching; keep padding at the compile-time value.
+#[cfg(feature = "std")]
+pub fn detect_cacheline() -> usize {
+    // Other C libraries, musl among them, lack the sysconf name.
+    #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "android"))]
+    {
+        // SAFETY: sysconf has no memory-safety preconditions.
+        let n = unsafe { libc::sysconf(libc::_SC_LEVEL1_DCACHE_LINESIZE) };
+        if n > 0 {
+            return n as usize;
+        }
+    }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        #[cfg(target_arch = "x86")]
+        use core::arch::x86::__cpuid;
+        #[cfg(target_arch = "x86_64")]
+        use core::arch::x86_64::__cpuid;
+
+        // CLFLUSH line size, in 8-byte units.
+        let n = ((__cpuid(1).ebx >> 8) & 0xff) as usize * 8;
+        if n > 0 {
+            return n;
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ctr: u64;
+        // SAFETY: CTR_EL0 is readable from EL0 on the supported OSes.
+        unsafe {
+            core::arch::asm!("mrs {}, ctr_el0", out(reg) ctr, options(nomem, nostack, preserves_flags));
+        }
+        // DminLine: log2 of the smallest data line size in 4-byte words.
+        return 4 << ((ctr >> 16) & 0xf);
+    }
+    #[allow(unreachable_code)]
+    CACHELINE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padding() {
+        assert!(CACHELINE.is_power_of_two());
+        assert_eq!(core::mem::size_of::<CachePadded<u8>>(), CACHELINE);
+        let mut p = CachePadded::new(5u32);
+        *p += 1;
+        assert_eq!(p.into_inner(), 6);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn detection() {
+        let n = detect_cacheline();
+        assert!(n.is_power_of_two());
+        assert!(n >= 16);
+    }
+}
@@ -0,0 +1,56 @@
+//! Branch prediction hints.
+//!
+//! On stable Rust the hints call an empty `#[cold]` function on the
+//! unexpected path, which steers block placement but is not always honoured.
+//! The `nightly` feature switches to `core::hint::likely`/`unlikely`, which
+//! LLVM treats as branch weights.
+
+/// Hints that `b` is usually `true`. Returns `b`.
+#[inline(always)]
+pub fn likely(b: bool) -> bool {
+    #[cfg(feature = "nightly")]
+    {
+        core::hint::likely(b)
+    }
+    #[cfg(not(feature = "nightly"))]
+    {
+        if !b {
+            cold();
+        }
+        b
+    }
+}
+
+/// Hints that `b` is usually `false`. Returns `b`.
+#[inline(always)]
+pub fn unlikely(b: bool) -> bool {
+    #[cfg(feature = "nightly")]
+    {
+        core::hint::unlikely(b)
+    }
+    #[cfg(not(feature = "nightly"))]
+    {
+        if b {
+            cold();
+        }
+        b
+    }
+}
+
+#[cfg(not(feature = "nightly"))]
+#[cold]
+#[inline(always)]
+fn cold() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity() {
+        assert!(likely(true));
+        assert!(!likely(false));
+        assert!(unlikely(true));
+        assert!(!unlikely(false));
+    }
+}
@@ -0,0 +1,153 @@
+//! Owner/link projection for intrusive data structures.
+//!
+//! Intrusive containers store a link field inside each element and need to
+//! get from a link back to the element that contains it. Rather than a raw
+//! `container_of` computation at each use, an [`IntrusiveAdapter`] records
+//! the owner type, link type and field offset once, checked by the compiler
+//! through [`intrusive_adapter!`](crate::intrusive_adapter). Projections
+//! from owner to link tie the returned reference to the lifetime of their
+//! input. Projections back to the owner take a raw link pointer instead: a
+//! reference to the link only grants access to the link, so the pointer
+//! must come from the owner, as [`link_ptr`](IntrusiveAdapter::link_ptr)
+//! gives it.
+
+/// Describes where a `Link` lives inside an `Owner`.
+///
+/// # Safety
+///
+/// `OFFSET` must be the byte offset of a field of type `Link` within
+/// `Owner`. Use [`intrusive_adapter!`](crate::intrusive_adapter) to derive it.
+pub unsafe trait IntrusiveAdapter {
+    /// The element type containing the link.
+    type Owner;
+    /// The link type embedded in the owner.
+    type Link;
+    /// Byte offset of the link within the owner.
+    const OFFSET: usize;
+
+    /// Returns the link embedded in `owner`.
+    #[inline(always)]
+    fn link(owner: &Self::Owner) -> &Self::Link {
+        // SAFETY: `OFFSET` locates a `Link` field inside `Owner`.
+        unsafe { &*Self::link_ptr(owner) }
+    }
+
+    /// Returns the link embedded in `owner` mutably.
+    #[inline(always)]
+    fn link_mut(owner: &mut Self::Owner) -> &mut Self::Link {
+        // SAFETY: as in `link`; the borrow of `owner` is exclusive.
+        unsafe { &mut *(Self::link_ptr(owner) as *mut Self::Link) }
+    }
+
+    /// Projects an owner pointer to its link without dereferencing it.
+    #[inline(always)]
+    fn link_ptr(owner: *const Self::Owner) -> *const Self::Link {
+        (owner as *const u8).wrapping_add(Self::OFFSET) as *const Self::Link
+    }
+
+    /// Recovers the owner pointer from a link pointer without dereferencing it.
+    #[inline(always)]
+    fn owner_ptr(link: *const Self::Link) -> *const Self::Owner {
+        (link as *const u8).wrapping_sub(Self::OFFSET) as *const Self::Owner
+    }
+
+    /// Returns the owner containing `link`.
+    ///
+    /// # Safety
+    ///
+    /// `link` must point to the link field of an `Owner` that is live for
+    /// `'a`, and must have been derived from a pointer to that whole owner,
+    /// as with [`link_ptr`](Self::link_ptr), not from a `&Link`.
+    #[inline(always)]
+    unsafe fn owner<'a>(link: *const Self::Link) -> &'a Self::Owner {
+        &*Self::owner_ptr(link)
+    }
+
+    /// Returns the owner containing `link` mutably.
+    ///
+    /// # Safety
+    ///
+    /// As for [`owner`](Self::owner), and the pointer must carry write
+    /// access to the whole owner, which nothing else may access for `'a`.
+    #[inline(always)]
+    unsafe fn owner_mut<'a>(link: *mut Self::Link) -> &'a mut Self::Owner {
+        &mut *(Self::owner_ptr(link) as *mut Self::Owner)
+    }
+}
+
+/// Defines a unit type implementing [`IntrusiveAdapter`](crate::cc::IntrusiveAdapter)
+/// for a named field.
+///
+/// ```
+/// use concurrencykit::cc::IntrusiveAdapter;
+///
+/// struct Link {
+///     next: *mut Link,
+/// }
+///
+/// struct Task {
+///     id: u32,
+///     link: Link,
+/// }
+///
+/// concurrencykit::intrusive_adapter!(TaskLink = Task { link: Link });
+///
+/// let task = Task { id: 7, link: Link { next: core::ptr::null_mut() } };
+/// assert!(TaskLink::link(&task).next.is_null());
+/// let link = TaskLink::link_ptr(&task);
+/// assert_eq!(unsafe { TaskLink::owner(link) }.id, 7);
+/// ```
+#[macro_export]
+macro_rules! intrusive_adapter {
+    ($(#[$attr:meta])* $vis:vis $adapter:ident = $owner:ty { $field:ident : $link:ty }) => {
+        $(#[$attr])*
+        $vis struct $adapter;
+
+        // SAFETY: the offset is computed by the compiler and the field type
+        // is checked below.
+        unsafe impl $crate::cc::IntrusiveAdapter for $adapter {
+            type Owner = $owner;
+            type Link = $link;
+            const OFFSET: usize = {
+                let _: fn(&$owner) -> &$link = |o| &o.$field;
+                ::core::mem::offset_of!($owner, $field)
+            };
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Link(u64);
+
+    struct Node {
+        _pad: [u8; 3],
+        value: u32,
+        link: Link,
+    }
+
+    crate::intrusive_adapter!(NodeLink = Node { link: Link });
+
+    #[test]
+    fn projection_round_trip() {
+        let mut n = Node {
+            _pad: [0; 3],
+            value: 9,
+            link: Link(1),
+        };
+        assert_eq!(NodeLink::OFFSET, core::mem::offset_of!(Node, link));
+        NodeLink::link_mut(&mut n).0 = 2;
+        assert_eq!(NodeLink::link(&n).0, 2);
+        let l = NodeLink::link_ptr(core::ptr::addr_of_mut!(n)) as *mut Link;
+        unsafe { NodeLink::owner_mut(l) }.value += 1;
+        let owner = unsafe { NodeLink::owner(l) };
+        assert_eq!(owner.value, 10);
+        assert!(core::ptr::eq(owner, &n));
+        assert_eq!(
+            NodeLink::owner_ptr(NodeLink::link_ptr(&n)),
+            &n as *const Node
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Compiler and bit-manipulation helpers modelled on `ck_cc`.
+//!
+//! The free bit helpers are `const fn` so they can be used in capacity and layout
+//! calculations. Find-first/last-set return 1-based bit positions and `0` for
+//! a zero argument, as in C; the count helpers return the type width for
+//! zero.
+
+mod bits;
+mod cacheline;
+mod hint;
+mod intrusive;
+mod prefetch;
+
+pub use self::bits::Bits;
+#[cfg(feature = "std")]
+pub use self::cacheline::detect_cacheline;
+pub use self::cacheline::{CachePadded, CACHELINE};
+pub use self::hint::{likely, unlikely};
+pub use self::intrusive::IntrusiveAdapter;
+pub use self::prefetch::{
+    prefetch_read, prefetch_read_with, prefetch_write, prefetch_write_with, Locality,
+};
+
+/// Find first set: 1-based index of the least significant set bit, or 0.
+#[inline(always)]
+pub const fn ffs(v: u32) -> u32 {
+    if v == 0 {
+        0
+    } else {
+        v.trailing_zeros() + 1
+    }
+}
+
+/// [`ffs`] for `usize`.
+#[inline(always)]
+pub const fn ffsl(v: usize) -> u32 {
+    if v == 0 {
+        0
+    } else {
+        v.trailing_zeros() + 1
+    }
+}
+
+/// [`ffs`] for `u64`.
+#[inline(always)]
+pub const fn ffsll(v: u64) -> u32 {
+    if v == 0 {
+        0
+    } else {
+        v.trailing_zeros() + 1
+    }
+}
+
+/// Count trailing zero bits.
+#[inline(always)]
+pub const fn ctz(v: u32) -> u32 {
+    v.trailing_zeros()
+}
+
+/// Count set bits.
+#[inline(always)]
+pub const fn popcount(v: u32) -> u32 {
+    v.count_ones()
+}
+
+/// Find last set: 1-based index of the most significant set bit, or 0.
+#[inline(always)]
+pub const fn fls(v: u32) -> u32 {
+    u32::BITS - v.leading_zeros()
+}
+
+/// [`fls`] for `u64`.
+#[inline(always)]
+pub const fn fls64(v: u64) -> u32 {
+    u64::BITS - v.leading_zeros()
+}
+
+/// Count leading zero bits.
+#[inline(always)]
+pub const fn clz(v: u32) -> u32 {
+    v.leading_zeros()
+}
+
+/// [`clz`] for `u64`.
+#[inline(always)]
+pub const fn clz64(v: u64) -> u32 {
+    v.leading_zeros()
+}
+
+/// Integer base-2 logarithm, rounded down.
+///
+/// # Panics
+///
+/// Panics if `v` is zero (at compile time in const contexts).
+#[inline(always)]
+pub const fn ilog2(v: u32) -> u32 {
+    v.ilog2()
+}
+
+/// [`ilog2`] for `u64`.
+///
+/// # Panics
+///
+/// Panics if `v` is zero (at compile time in const contexts).
+#[inline(always)]
+pub const fn ilog2_64(v: u64) -> u32 {
+    v.ilog2()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_first() {
+        assert_eq!(ffs(0), 0);
+        assert_eq!(ffs(1), 1);
+        assert_eq!(ffs(0b1000), 4);
+        assert_eq!(ffsl(1 << 20), 21);
+        assert_eq!(ffsll(1 << 63), 64);
+        assert_eq!(ctz(0b100), 2);
+        assert_eq!(popcount(0xff), 8);
+    }
+
+    #[test]
+    fn find_last() {
+        assert_eq!(fls(0), 0);
+        assert_eq!(fls(1), 1);
+        assert_eq!(fls(0x8000_0001), 32);
+        assert_eq!(fls64(1 << 40), 41);
+        assert_eq!(clz(1), 31);
+        assert_eq!(clz64(0), 64);
+        assert_eq!(ilog2(1), 0);
+        assert_eq!(ilog2(1023), 9);
+        assert_eq!(ilog2_64(1 << 50), 50);
+        const SHIFT: u32 = ilog2(4096);
+        assert_eq!(SHIFT, 12);
+    }
+}
@@ -0,0 +1,160 @@
+//! Software prefetch hints.
+//!
+//! Compiled to `prefetcht0/1/2`/`prefetchnta` (reads) and `prefetchw`
+//! (writes) on x86, `prfm` on aarch64 and nothing elsewhere. Prefetches never
+//! fault, so any pointer value is acceptable.
+
+/// Expected temporal locality of prefetched data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Locality {
+    /// Used once; minimize cache pollution (`nta`, `strm`).
+    None,
+    /// Keep in the outermost cache level (`t2`, `l3keep`).
+    Low,
+    /// Keep in the middle cache levels (`t1`, `l2keep`).
+    Moderate,
+    /// Keep in all cache levels (`t0`, `l1keep`).
+    #[default]
+    High,
+}
+
+/// Hints that `ptr` will soon be read; equivalent to high locality.
+#[inline(always)]
+pub fn prefetch_read<T>(ptr: *const T) {
+    prefetch_read_with(ptr, Locality::High)
+}
+
+/// Hints that `ptr` will soon be written; equivalent to high locality.
+#[inline(always)]
+pub fn prefetch_write<T>(ptr: *const T) {
+    prefetch_write_with(ptr, Locality::High)
+}
+
+/// Hints that `ptr` will soon be read with the given locality.
+#[inline(always)]
+pub fn prefetch_read_with<T>(ptr: *const T, locality: Locality) {
+    arch::read(ptr as *const u8, locality)
+}
+
+/// Hints that `ptr` will soon be written with the given locality.
+///
+/// x86 has a single write prefetch, so the locality only matters on aarch64.
+#[inline(always)]
+pub fn prefetch_write_with<T>(ptr: *const T, locality: Locality) {
+    arch::write(ptr as *const u8, locality)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod arch {
+    use super::Locality;
+    use core::arch::asm;
+
+    // SAFETY (all blocks): prefetch instructions never fault.
+
+    #[inline(always)]
+    pub(super) fn read(p: *const u8, locality: Locality) {
+        unsafe {
+            match locality {
+                Locality::None => {
+                    asm!("prefetchnta [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+                Locality::Low => {
+                    asm!("prefetcht2 [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+                Locality::Moderate => {
+                    asm!("prefetcht1 [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+                Locality::High => {
+                    asm!("prefetcht0 [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn write(p: *const u8, _locality: Locality) {
+        // prefetchw decodes as a no-op on processors that predate it.
+        unsafe { asm!("prefetchw [{}]", in(reg) p, options(nostack, preserves_flags, readonly)) }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    use super::Locality;
+    use core::arch::asm;
+
+    // SAFETY (all blocks): prefetch instructions never fault.
+
+    #[inline(always)]
+    pub(super) fn read(p: *const u8, locality: Locality) {
+        unsafe {
+            match locality {
+                Locality::None => {
+                    asm!("prfm pldl1strm, [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+                Locality::Low => {
+                    asm!("prfm pldl3keep, [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+                Locality::Moderate => {
+                    asm!("prfm pldl2keep, [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+                Locality::High => {
+                    asm!("prfm pldl1keep, [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn write(p: *const u8, locality: Locality) {
+        unsafe {
+            match locality {
+                Locality::None => {
+                    asm!("prfm pstl1strm, [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+                Locality::Low => {
+                    asm!("prfm pstl3keep, [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+                Locality::Moderate => {
+                    asm!("prfm pstl2keep, [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+                Locality::High => {
+                    asm!("prfm pstl1keep, [{}]", in(reg) p, options(nostack, preserves_flags, readonly))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+mod arch {
+    use super::Locality;
+
+    #[inline(always)]
+    pub(super) fn read(_p: *const u8, _locality: Locality) {}
+
+    #[inline(always)]
+    pub(super) fn write(_p: *const u8, _locality: Locality) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_localities() {
+        let v = [0u8; 64];
+        for l in [
+            Locality::None,
+            Locality::Low,
+            Locality::Moderate,
+            Locality::High,
+        ] {
+            prefetch_read_with(v.as_ptr(), l);
+            prefetch_write_with(v.as_ptr(), l);
+        }
+        prefetch_read(core::ptr::null::<u64>());
+        prefetch_write(usize::MAX as *const u64);
+        assert_eq!(Locality::default(), Locality::High);
+    }
+}
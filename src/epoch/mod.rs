@@ -0,0 +1,279 @@
+//! Epoch-based reclamation modelled on `ck_epoch`.
+//!
+//! Readers [`pin`](Local::pin) a participant record for the duration of a
+//! read-side section. A global epoch advances only once every pinned record
+//! has observed the current value, so anything unlinked while the epoch was
+//! `e` is unreachable by readers once the epoch reaches `e + 2`.
+//!
+//! This module tracks grace periods; what to do once one has elapsed is up
+//! to the caller (see [`malloc::DeferredAllocator`](crate::malloc::DeferredAllocator)).
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+use alloc::boxed::Box;
+
+use crate::pr;
+
+struct Record {
+    /// `epoch << 1 | pinned`.
+    state: AtomicUsize,
+    in_use: AtomicBool,
+    next: *mut Record,
+}
+
+/// An epoch reclamation domain.
+pub struct Epoch {
+    epoch: AtomicUsize,
+    records: AtomicPtr<Record>,
+}
+
+unsafe impl Send for Epoch {}
+unsafe impl Sync for Epoch {}
+
+impl Epoch {
+    /// Creates a domain with no participants.
+    pub const fn new() -> Self {
+        Epoch {
+            epoch: AtomicUsize::new(0),
+            records: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the current global epoch.
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` once a grace period has passed since `stamp` was read
+    /// from [`current`](Self::current).
+    #[inline]
+    pub fn is_safe(&self, stamp: usize) -> bool {
+        self.current().wrapping_sub(stamp) >= 2
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Record> {
+        let mut r = self.records.load(Ordering::Acquire);
+        core::iter::from_fn(move || {
+            // SAFETY: records are never freed while the domain is shared.
+            let rec = unsafe { r.as_ref()? };
+            r = rec.next;
+            Some(rec)
+        })
+    }
+
+    /// Registers a participant, reusing a released record when possible.
+    pub fn register(&self) -> Local<'_> {
+        let record = match self.iter().find(|r| {
+            !r.in_use.load(Ordering::Relaxed)
+                && r.in_use
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+        }) {
+            Some(r) => r,
+            None => {
+                let r = Box::into_raw(Box::new(Record {
+                    state: AtomicUsize::new(0),
+                    in_use: AtomicBool::new(true),
+                    next: ptr::null_mut(),
+                }));
+                let mut head = self.records.load(Ordering::Relaxed);
+                loop {
+                    // SAFETY: `r` is not yet shared.
+                    unsafe { (*r).next = head };
+                    match self.records.compare_exchange_weak(
+                        head,
+                        r,
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => break,
+                        Err(h) => head = h,
+                    }
+                }
+                // SAFETY: just published; lives as long as the domain.
+                unsafe { &*r }
+            }
+        };
+        Local {
+            epoch: self,
+            record,
+            nest: Cell::new(0),
+        }
+    }
+
+    /// Advances the global epoch if every pinned participant has observed
+    /// it. Returns `true` if the epoch moved.
+    pub fn try_advance(&self) -> bool {
+        let e = self.current();
+        let behind = self.iter().any(|r| {
+            let s = r.state.load(Ordering::SeqCst);
+            s & 1 == 1 && s >> 1 != e
+        });
+        !behind
+            && self
+                .epoch
+                .compare_exchange(e, e.wrapping_add(1), Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok()
+    }
+
+    /// Waits until a grace period has elapsed.
+    ///
+    /// Never call this while pinned: the caller's own record would keep the
+    /// epoch from advancing.
+    pub fn synchronize(&self) {
+        let stamp = self.current();
+        while !self.is_safe(stamp) {
+            if !self.try_advance() {
+                pr::stall();
+            }
+        }
+    }
+}
+
+/// Returns the process-wide domain used when no other is given.
+pub fn global() -> &'static Epoch {
+    static GLOBAL: Epoch = Epoch::new();
+    &GLOBAL
+}
+
+impl Default for Epoch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Epoch {
+    fn drop(&mut self) {
+        let mut r = *self.records.get_mut();
+        while !r.is_null() {
+            // SAFETY: no `Local` outlives the domain.
+            let rec = unsafe { Box::from_raw(r) };
+            r = rec.next;
+        }
+    }
+}
+
+/// A registered participant of an [`Epoch`], owned by one thread.
+pub struct Local<'a> {
+    epoch: &'a Epoch,
+    record: &'a Record,
+    nest: Cell<usize>,
+}
+
+unsafe impl Send for Local<'_> {}
+
+impl<'a> Local<'a> {
+    /// Enters a read-side section; sections nest.
+    pub fn pin(&self) -> Guard<'_> {
+        let n = self.nest.get();
+        if n == 0 {
+            let e = self.epoch.epoch.load(Ordering::Relaxed);
+            self.record.state.store(e << 1 | 1, Ordering::Relaxed);
+            pr::fence_memory();
+        }
+        self.nest.set(n + 1);
+        Guard {
+            local: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns `true` if a [`Guard`] from this participant is live.
+    pub fn is_pinned(&self) -> bool {
+        self.nest.get() != 0
+    }
+
+    /// Returns the domain this participant belongs to.
+    pub fn epoch(&self) -> &'a Epoch {
+        self.epoch
+    }
+}
+
+impl Drop for Local<'_> {
+    fn drop(&mut self) {
+        self.record.state.store(0, Ordering::Release);
+        self.record.in_use.store(false, Ordering::Release);
+    }
+}
+
+/// A pinned read-side section; unpins when the last nested guard drops.
+pub struct Guard<'a> {
+    local: &'a Local<'a>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<'a> Guard<'a> {
+    /// Returns the domain this section is pinned in.
+    pub fn epoch(&self) -> &'a Epoch {
+        self.local.epoch
+    }
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        let n = self.local.nest.get() - 1;
+        self.local.nest.set(n);
+        if n == 0 {
+            let s = self.local.record.state.load(Ordering::Relaxed);
+            self.local.record.state.store(s & !1, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    #[test]
+    fn pinned_reader_blocks_advance() {
+        let d = Epoch::new();
+        let l = d.register();
+        let stamp = d.current();
+        {
+            let _g = l.pin();
+            let _g2 = l.pin();
+            assert!(d.try_advance());
+            assert!(!d.try_advance());
+            assert!(!d.is_safe(stamp));
+        }
+        assert!(!l.is_pinned());
+        d.synchronize();
+        assert!(d.is_safe(stamp));
+    }
+
+    #[test]
+    fn records_are_reused() {
+        let d = Epoch::new();
+        drop(d.register());
+        drop(d.register());
+        assert_eq!(d.iter().count(), 1);
+    }
+
+    #[test]
+    fn synchronize_with_readers() {
+        let d = Epoch::new();
+        let stop = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let l = d.register();
+                    while !stop.load(Ordering::Relaxed) {
+                        let _g = l.pin();
+                    }
+                });
+            }
+            for _ in 0..100 {
+                let stamp = d.current();
+                d.synchronize();
+                assert!(d.is_safe(stamp));
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+    }
+}
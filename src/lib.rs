@@ -1,6 +1,32 @@
 //! Modern concurrency primitives and building blocks for high performance applications.
 //!
 //! This is a placeholder for a library in progress.
+
+#![no_std]
+#![cfg_attr(
+    feature = "nightly",
+    feature(likely_unlikely, allocator_api, slice_ptr_get)
+)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+
+pub mod backoff;
+pub mod bitmap;
+pub mod cc;
+#[cfg(feature = "alloc")]
+pub mod epoch;
+pub mod malloc;
+pub mod pr;
+pub mod queue;
+pub mod ring;
+pub mod rwlock;
+pub mod sequence;
+pub mod spinlock;
+pub mod stack;
+
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
 }
@@ -0,0 +1,295 @@
+//! Bump allocation with wholesale reclamation.
+
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+use super::{AllocError, Allocator, MIN_ALIGN};
+use crate::pr;
+
+struct Chunk {
+    next: *mut Chunk,
+    size: usize,
+    used: AtomicUsize,
+}
+
+const HEADER: usize = (core::mem::size_of::<Chunk>() + MIN_ALIGN - 1) & !(MIN_ALIGN - 1);
+
+impl Chunk {
+    fn capacity(&self) -> usize {
+        self.size - HEADER
+    }
+
+    fn data(this: *mut Chunk) -> *mut u8 {
+        (this as *mut u8).wrapping_add(HEADER)
+    }
+}
+
+#[inline]
+fn round_up(size: usize) -> Option<usize> {
+    size.checked_add(MIN_ALIGN - 1)
+        .map(|s| s & !(MIN_ALIGN - 1))
+}
+
+/// A bump allocator that frees everything at once.
+///
+/// Memory comes from a backing [`Allocator`] in chunks; allocation is a
+/// lock-free bump of the current chunk, taking a short spinlock only to add a
+/// chunk. `free` does nothing: blocks are reclaimed together by
+/// [`reset`](Arena::reset) or when the arena is dropped.
+///
+/// For request-scoped allocation shared with concurrent readers, retire a
+/// whole arena generation by waiting for a grace period (e.g. an epoch
+/// synchronize) and then calling `reset`; [`generation`](Arena::generation)
+/// identifies which generation a block was allocated in.
+pub struct Arena<A: Allocator> {
+    alloc: A,
+    chunk_size: usize,
+    current: AtomicPtr<Chunk>,
+    grow: AtomicBool,
+    generation: AtomicUsize,
+}
+
+unsafe impl<A: Allocator + Send> Send for Arena<A> {}
+unsafe impl<A: Allocator + Sync> Sync for Arena<A> {}
+
+impl<A: Allocator> Arena<A> {
+    /// Default chunk size in bytes.
+    pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+    /// Creates an arena drawing [`DEFAULT_CHUNK_SIZE`](Self::DEFAULT_CHUNK_SIZE)
+    /// chunks from `alloc`.
+    pub const fn new(alloc: A) -> Self {
+        Self::with_chunk_size(alloc, Self::DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Creates an arena drawing chunks of `chunk_size` bytes from `alloc`.
+    ///
+    /// Requests larger than a chunk get a dedicated chunk of their own.
+    pub const fn with_chunk_size(alloc: A, chunk_size: usize) -> Self {
+        let min = HEADER + MIN_ALIGN;
+        Arena {
+            alloc,
+            chunk_size: if chunk_size < min { min } else { chunk_size },
+            current: AtomicPtr::new(ptr::null_mut()),
+            grow: AtomicBool::new(false),
+            generation: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of times the arena has been reset.
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Returns the backing allocator.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    fn new_chunk(&self, size: usize, next: *mut Chunk) -> Result<*mut Chunk, AllocError> {
+        let p = self.alloc.malloc(size)?.as_ptr() as *mut Chunk;
+        // SAFETY: the block is at least `size >= HEADER` bytes and aligned.
+        unsafe {
+            p.write(Chunk {
+                next,
+                size,
+                used: AtomicUsize::new(0),
+            });
+        }
+        Ok(p)
+    }
+
+    #[inline]
+    fn bump(chunk: *mut Chunk, size: usize) -> Option<NonNull<u8>> {
+        // SAFETY: chunks stay allocated until `reset`/`drop`, which need
+        // exclusive access.
+        let c = unsafe { &*chunk };
+        let mut used = c.used.load(Ordering::Relaxed);
+        loop {
+            if size > c.capacity() - used {
+                return None;
+            }
+            match c.used.compare_exchange_weak(
+                used,
+                used + size,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return NonNull::new(Chunk::data(chunk).wrapping_add(used)),
+                Err(u) => used = u,
+            }
+        }
+    }
+
+    #[cold]
+    fn malloc_slow(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        while self
+            .grow
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            pr::stall();
+        }
+        let r = self.grow_locked(size);
+        self.grow.store(false, Ordering::Release);
+        r
+    }
+
+    fn grow_locked(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        let current = self.current.load(Ordering::Acquire);
+        // Another thread may have added a chunk while we waited.
+        if !current.is_null() {
+            if let Some(p) = Self::bump(current, size) {
+                return Ok(p);
+            }
+        }
+        if size > self.chunk_size - HEADER {
+            // Dedicated chunk, linked behind the current one so the current
+            // chunk keeps serving small requests.
+            let total = size.checked_add(HEADER).ok_or(AllocError)?;
+            if current.is_null() {
+                let c = self.new_chunk(total, ptr::null_mut())?;
+                self.current.store(c, Ordering::Release);
+                return Self::bump(c, size).ok_or(AllocError);
+            }
+            // SAFETY: the grow lock serializes updates to `next`.
+            let c = unsafe {
+                let c = self.new_chunk(total, (*current).next)?;
+                (*current).next = c;
+                c
+            };
+            return Self::bump(c, size).ok_or(AllocError);
+        }
+        let c = self.new_chunk(self.chunk_size, current)?;
+        let p = Self::bump(c, size).ok_or(AllocError);
+        self.current.store(c, Ordering::Release);
+        p
+    }
+
+    /// Reclaims every block allocated from the arena.
+    ///
+    /// All chunks but the most recent are returned to the backing allocator.
+    /// Pointers obtained before the reset must no longer be used.
+    pub fn reset(&mut self) {
+        let current = *self.current.get_mut();
+        if current.is_null() {
+            return;
+        }
+        // SAFETY: `&mut self` excludes concurrent allocation.
+        unsafe {
+            self.free_chain((*current).next);
+            (*current).next = ptr::null_mut();
+            *(*current).used.get_mut() = 0;
+        }
+        *self.generation.get_mut() += 1;
+    }
+
+    unsafe fn free_chain(&self, mut c: *mut Chunk) {
+        while !c.is_null() {
+            let next = (*c).next;
+            let size = (*c).size;
+            self.alloc
+                .free(NonNull::new_unchecked(c as *mut u8), size, false);
+            c = next;
+        }
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for Arena<A> {
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        let size = round_up(size.max(1)).ok_or(AllocError)?;
+        let current = self.current.load(Ordering::Acquire);
+        if !current.is_null() {
+            if let Some(p) = Self::bump(current, size) {
+                return Ok(p);
+            }
+        }
+        self.malloc_slow(size)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        // Shrinking in place is free: the tail is simply never reused.
+        if new_size <= old_size {
+            return Ok(ptr);
+        }
+        if !may_move {
+            return Err(AllocError);
+        }
+        let p = self.malloc(new_size)?;
+        core::ptr::copy_nonoverlapping(ptr.as_ptr(), p.as_ptr(), old_size);
+        Ok(p)
+    }
+
+    unsafe fn free(&self, _ptr: NonNull<u8>, _size: usize, _defer: bool) {}
+}
+
+impl<A: Allocator> Drop for Arena<A> {
+    fn drop(&mut self) {
+        let current = *self.current.get_mut();
+        // SAFETY: no blocks can be in use once the arena is dropped.
+        unsafe { self.free_chain(current) }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::malloc::GlobalAllocator;
+    use std::sync::Arc;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn bump_and_reset() {
+        let mut a = Arena::with_chunk_size(GlobalAllocator, 256);
+        let p = a.malloc(10).unwrap();
+        let q = a.malloc(10).unwrap();
+        assert_eq!(q.as_ptr() as usize - p.as_ptr() as usize, MIN_ALIGN);
+        let big = a.malloc(4096).unwrap();
+        unsafe { big.as_ptr().write_bytes(1, 4096) };
+        let r = a.malloc(10).unwrap();
+        assert_eq!(r.as_ptr() as usize - q.as_ptr() as usize, MIN_ALIGN);
+        for _ in 0..100 {
+            a.malloc(64).unwrap();
+        }
+        unsafe {
+            assert_eq!(a.realloc(r, 10, 5, false), Ok(r));
+            assert_eq!(a.realloc(r, 10, 500, false), Err(AllocError));
+        }
+        assert_eq!(a.generation(), 0);
+        a.reset();
+        assert_eq!(a.generation(), 1);
+        a.malloc(8).unwrap();
+    }
+
+    #[test]
+    fn concurrent_allocations_are_disjoint() {
+        let a = Arc::new(Arena::with_chunk_size(GlobalAllocator, 1024));
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let a = a.clone();
+                thread::spawn(move || {
+                    (0..500)
+                        .map(|_| {
+                            let p = a.malloc(24).unwrap();
+                            unsafe { p.as_ptr().write_bytes(t, 24) };
+                            p.as_ptr() as usize
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut all: Vec<usize> = handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect();
+        all.sort_unstable();
+        assert!(all.windows(2).all(|w| w[1] - w[0] >= 24));
+    }
+}
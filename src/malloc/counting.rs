@@ -0,0 +1,218 @@
+//! Allocator wrapper that keeps usage statistics.
+
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use super::{AllocError, Allocator};
+
+/// Number of buckets in a [`CountingAllocator`] size histogram.
+pub const HISTOGRAM_BUCKETS: usize = usize::BITS as usize + 1;
+
+/// A snapshot of [`CountingAllocator`] counters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    /// Successful `malloc` calls.
+    pub allocations: usize,
+    /// `free` calls.
+    pub frees: usize,
+    /// `free` calls made with `defer` set.
+    pub deferred_frees: usize,
+    /// Successful `realloc` calls.
+    pub reallocations: usize,
+    /// Failed `malloc` and `realloc` calls.
+    pub failures: usize,
+    /// Bytes currently allocated.
+    pub live_bytes: usize,
+    /// Highest value `live_bytes` has reached.
+    pub peak_bytes: usize,
+}
+
+impl AllocStats {
+    /// Blocks allocated and not yet freed.
+    pub const fn live_blocks(&self) -> usize {
+        self.allocations.wrapping_sub(self.frees)
+    }
+}
+
+/// Wraps an [`Allocator`] and counts what passes through it.
+///
+/// Counters are relaxed atomics, so a [`stats`](Self::stats) snapshot taken
+/// while other threads allocate is approximate. Bucket `i` of the
+/// [`histogram`](Self::histogram) counts requests of `2^(i-1) + 1 ..= 2^i`
+/// bytes, with sizes 0 and 1 in bucket 0.
+pub struct CountingAllocator<A> {
+    inner: A,
+    allocations: AtomicUsize,
+    frees: AtomicUsize,
+    deferred_frees: AtomicUsize,
+    reallocations: AtomicUsize,
+    failures: AtomicUsize,
+    live_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    histogram: [AtomicUsize; HISTOGRAM_BUCKETS],
+}
+
+#[inline]
+fn bucket(size: usize) -> usize {
+    match size {
+        0 | 1 => 0,
+        n => (usize::BITS - (n - 1).leading_zeros()) as usize,
+    }
+}
+
+impl<A> CountingAllocator<A> {
+    /// Wraps `inner` with all counters at zero.
+    pub const fn new(inner: A) -> Self {
+        CountingAllocator {
+            inner,
+            allocations: AtomicUsize::new(0),
+            frees: AtomicUsize::new(0),
+            deferred_frees: AtomicUsize::new(0),
+            reallocations: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+            live_bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+            histogram: [const { AtomicUsize::new(0) }; HISTOGRAM_BUCKETS],
+        }
+    }
+
+    /// Returns the wrapped allocator.
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Unwraps the allocator, discarding the counters.
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+
+    /// Returns the current counters.
+    pub fn stats(&self) -> AllocStats {
+        AllocStats {
+            allocations: self.allocations.load(Relaxed),
+            frees: self.frees.load(Relaxed),
+            deferred_frees: self.deferred_frees.load(Relaxed),
+            reallocations: self.reallocations.load(Relaxed),
+            failures: self.failures.load(Relaxed),
+            live_bytes: self.live_bytes.load(Relaxed),
+            peak_bytes: self.peak_bytes.load(Relaxed),
+        }
+    }
+
+    /// Returns the number of `malloc` requests per size bucket.
+    pub fn histogram(&self) -> [usize; HISTOGRAM_BUCKETS] {
+        core::array::from_fn(|i| self.histogram[i].load(Relaxed))
+    }
+
+    /// Lowers the peak to the current live byte count.
+    pub fn reset_peak(&self) {
+        self.peak_bytes
+            .store(self.live_bytes.load(Relaxed), Relaxed);
+    }
+
+    fn grow(&self, bytes: usize) {
+        let live = self
+            .live_bytes
+            .fetch_add(bytes, Relaxed)
+            .wrapping_add(bytes);
+        self.peak_bytes.fetch_max(live, Relaxed);
+    }
+
+    fn shrink(&self, bytes: usize) {
+        self.live_bytes.fetch_sub(bytes, Relaxed);
+    }
+
+    fn failed<T>(&self, r: Result<T, AllocError>) -> Result<T, AllocError> {
+        if r.is_err() {
+            self.failures.fetch_add(1, Relaxed);
+        }
+        r
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for CountingAllocator<A> {
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        self.histogram[bucket(size)].fetch_add(1, Relaxed);
+        let p = self.failed(self.inner.malloc(size))?;
+        self.allocations.fetch_add(1, Relaxed);
+        self.grow(size);
+        Ok(p)
+    }
+
+    fn malloc_zeroed(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        self.histogram[bucket(size)].fetch_add(1, Relaxed);
+        let p = self.failed(self.inner.malloc_zeroed(size))?;
+        self.allocations.fetch_add(1, Relaxed);
+        self.grow(size);
+        Ok(p)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let p = self.failed(self.inner.realloc(ptr, old_size, new_size, may_move))?;
+        self.reallocations.fetch_add(1, Relaxed);
+        if new_size >= old_size {
+            self.grow(new_size - old_size);
+        } else {
+            self.shrink(old_size - new_size);
+        }
+        Ok(p)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, defer: bool) {
+        self.frees.fetch_add(1, Relaxed);
+        if defer {
+            self.deferred_frees.fetch_add(1, Relaxed);
+        }
+        self.shrink(size);
+        self.inner.free(ptr, size, defer)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::malloc::GlobalAllocator;
+
+    #[test]
+    fn buckets() {
+        assert_eq!(bucket(0), 0);
+        assert_eq!(bucket(1), 0);
+        assert_eq!(bucket(2), 1);
+        assert_eq!(bucket(3), 2);
+        assert_eq!(bucket(4), 2);
+        assert_eq!(bucket(5), 3);
+        assert_eq!(bucket(usize::MAX), HISTOGRAM_BUCKETS - 1);
+    }
+
+    #[test]
+    fn counts_and_peak() {
+        let a = CountingAllocator::new(GlobalAllocator);
+        let p = a.malloc(100).unwrap();
+        let q = a.malloc(28).unwrap();
+        unsafe {
+            let p = a.realloc(p, 100, 200, true).unwrap();
+            a.free(q, 28, true);
+            assert_eq!(a.malloc(usize::MAX), Err(AllocError));
+            let s = a.stats();
+            assert_eq!(s.allocations, 2);
+            assert_eq!(s.live_blocks(), 1);
+            assert_eq!(s.deferred_frees, 1);
+            assert_eq!(s.reallocations, 1);
+            assert_eq!(s.failures, 1);
+            assert_eq!(s.live_bytes, 200);
+            assert_eq!(s.peak_bytes, 228);
+            a.reset_peak();
+            assert_eq!(a.stats().peak_bytes, 200);
+            a.free(p, 200, false);
+        }
+        let h = a.histogram();
+        assert_eq!((h[5], h[7], h[HISTOGRAM_BUCKETS - 1]), (1, 1, 1));
+        assert_eq!(a.stats().live_bytes, 0);
+    }
+}
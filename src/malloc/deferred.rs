@@ -0,0 +1,209 @@
+//! Honouring `defer` frees with epoch reclamation.
+
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use alloc::boxed::Box;
+
+use super::{AllocError, Allocator};
+use crate::epoch::Epoch;
+
+struct Pending {
+    ptr: NonNull<u8>,
+    size: usize,
+    stamp: usize,
+    next: *mut Pending,
+}
+
+/// Routes `free(.., defer: true)` through an [`Epoch`] grace period.
+///
+/// Immediate frees go straight to the wrapped allocator. Deferred frees are
+/// stamped with the current epoch and queued; [`collect`](Self::collect)
+/// returns those whose grace period has elapsed. Collection also runs
+/// automatically once [`COLLECT_THRESHOLD`](Self::COLLECT_THRESHOLD) frees
+/// are pending, so callers only need to collect explicitly to bound memory.
+///
+/// Dropping the allocator frees everything still pending: by then no data
+/// structure borrowing it, and so no reader, can remain.
+pub struct DeferredAllocator<'e, A: Allocator> {
+    inner: A,
+    epoch: &'e Epoch,
+    pending: AtomicPtr<Pending>,
+    count: AtomicUsize,
+}
+
+unsafe impl<A: Allocator + Send> Send for DeferredAllocator<'_, A> {}
+unsafe impl<A: Allocator + Sync> Sync for DeferredAllocator<'_, A> {}
+
+impl<'e, A: Allocator> DeferredAllocator<'e, A> {
+    /// Pending frees that trigger an automatic [`collect`](Self::collect).
+    pub const COLLECT_THRESHOLD: usize = 64;
+
+    /// Wraps `inner`, deferring frees until `epoch` allows them.
+    pub const fn new(inner: A, epoch: &'e Epoch) -> Self {
+        DeferredAllocator {
+            inner,
+            epoch,
+            pending: AtomicPtr::new(ptr::null_mut()),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the epoch domain frees wait on.
+    pub fn epoch(&self) -> &'e Epoch {
+        self.epoch
+    }
+
+    /// Returns the number of deferred frees not yet performed.
+    pub fn pending(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    fn push_chain(&self, first: *mut Pending, last: *mut Pending) {
+        let mut head = self.pending.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: the chain is owned by the caller until published.
+            unsafe { (*last).next = head };
+            match self.pending.compare_exchange_weak(
+                head,
+                first,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Tries to advance the epoch and frees every block whose grace period
+    /// has elapsed. Returns how many blocks were freed.
+    pub fn collect(&self) -> usize {
+        self.epoch.try_advance();
+        let mut p = self.pending.swap(ptr::null_mut(), Ordering::Acquire);
+        let (mut first, mut last) = (ptr::null_mut::<Pending>(), ptr::null_mut::<Pending>());
+        let mut freed = 0;
+        while !p.is_null() {
+            // SAFETY: the swap gave us exclusive ownership of the chain.
+            let node = unsafe { &mut *p };
+            let next = node.next;
+            if self.epoch.is_safe(node.stamp) {
+                // SAFETY: no reader can still reach the block.
+                unsafe {
+                    self.inner.free(node.ptr, node.size, false);
+                    drop(Box::from_raw(p));
+                }
+                freed += 1;
+            } else {
+                node.next = first;
+                if first.is_null() {
+                    last = p;
+                }
+                first = p;
+            }
+            p = next;
+        }
+        if !first.is_null() {
+            self.push_chain(first, last);
+        }
+        self.count.fetch_sub(freed, Ordering::Relaxed);
+        freed
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for DeferredAllocator<'_, A> {
+    #[inline]
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        self.inner.malloc(size)
+    }
+
+    #[inline]
+    fn malloc_zeroed(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        self.inner.malloc_zeroed(size)
+    }
+
+    #[inline]
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        self.inner.realloc(ptr, old_size, new_size, may_move)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, defer: bool) {
+        if !defer {
+            return self.inner.free(ptr, size, false);
+        }
+        let node = Box::into_raw(Box::new(Pending {
+            ptr,
+            size,
+            stamp: self.epoch.current(),
+            next: ptr::null_mut(),
+        }));
+        // Counted before it is published, so a collector that frees the
+        // block always decrements after this increment.
+        let pending = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+        self.push_chain(node, node);
+        if pending >= Self::COLLECT_THRESHOLD {
+            self.collect();
+        }
+    }
+}
+
+impl<A: Allocator> Drop for DeferredAllocator<'_, A> {
+    fn drop(&mut self) {
+        let mut p = *self.pending.get_mut();
+        while !p.is_null() {
+            // SAFETY: we have exclusive access and no borrower remains.
+            let node = unsafe { Box::from_raw(p) };
+            unsafe { self.inner.free(node.ptr, node.size, false) };
+            p = node.next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::malloc::{CountingAllocator, GlobalAllocator};
+
+    #[test]
+    fn deferred_until_grace_period() {
+        let epoch = Epoch::new();
+        let counting = CountingAllocator::new(GlobalAllocator);
+        let a = DeferredAllocator::new(&counting, &epoch);
+        let reader = epoch.register();
+        let p = a.malloc(32).unwrap();
+        let q = a.malloc(32).unwrap();
+        let g = reader.pin();
+        unsafe {
+            a.free(p, 32, false);
+            a.free(q, 32, true);
+        }
+        assert_eq!(counting.stats().frees, 1);
+        assert_eq!(a.pending(), 1);
+        assert_eq!(a.collect(), 0);
+        assert_eq!(a.collect(), 0);
+        drop(g);
+        assert_eq!(a.collect(), 1);
+        assert_eq!(a.pending(), 0);
+        assert_eq!(counting.stats().live_bytes, 0);
+    }
+
+    #[test]
+    fn threshold_and_drop() {
+        let epoch = Epoch::new();
+        let counting = CountingAllocator::new(GlobalAllocator);
+        let a = DeferredAllocator::new(&counting, &epoch);
+        for _ in 0..DeferredAllocator::<GlobalAllocator>::COLLECT_THRESHOLD * 3 {
+            let p = a.malloc(8).unwrap();
+            unsafe { a.free(p, 8, true) };
+        }
+        assert!(a.pending() < DeferredAllocator::<GlobalAllocator>::COLLECT_THRESHOLD);
+        drop(a);
+        assert_eq!(counting.stats().live_bytes, 0);
+    }
+}
@@ -0,0 +1,143 @@
+//! Deterministic allocation failure for exercising out-of-memory paths.
+
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use super::{AllocError, Allocator};
+
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    Every(usize),
+    After(usize),
+}
+
+/// Wraps an [`Allocator`] and fails requests on a fixed schedule.
+///
+/// `malloc`, `malloc_zeroed` and `realloc` each count as one request;
+/// `free` always succeeds. Requests are numbered from 1 in the order they
+/// reach the allocator, so single-threaded tests see the same failures on
+/// every run.
+pub struct FailingAllocator<A> {
+    inner: A,
+    mode: Mode,
+    requests: AtomicUsize,
+    failures: AtomicUsize,
+}
+
+impl<A> FailingAllocator<A> {
+    /// Fails every `n`th request; `n == 0` never fails.
+    pub const fn every(inner: A, n: usize) -> Self {
+        Self::with_mode(inner, Mode::Every(n))
+    }
+
+    /// Lets the first `n` requests through and fails all later ones.
+    pub const fn after(inner: A, n: usize) -> Self {
+        Self::with_mode(inner, Mode::After(n))
+    }
+
+    const fn with_mode(inner: A, mode: Mode) -> Self {
+        FailingAllocator {
+            inner,
+            mode,
+            requests: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of requests seen so far.
+    pub fn requests(&self) -> usize {
+        self.requests.load(Relaxed)
+    }
+
+    /// Returns the number of requests failed so far.
+    pub fn failures(&self) -> usize {
+        self.failures.load(Relaxed)
+    }
+
+    /// Restarts the schedule from the first request.
+    pub fn reset(&self) {
+        self.requests.store(0, Relaxed);
+        self.failures.store(0, Relaxed);
+    }
+
+    /// Returns the wrapped allocator.
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    fn admit(&self) -> Result<(), AllocError> {
+        let n = self.requests.fetch_add(1, Relaxed) + 1;
+        let fail = match self.mode {
+            Mode::Every(k) => n.is_multiple_of(k),
+            Mode::After(k) => n > k,
+        };
+        if fail {
+            self.failures.fetch_add(1, Relaxed);
+            return Err(AllocError);
+        }
+        Ok(())
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for FailingAllocator<A> {
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        self.admit()?;
+        self.inner.malloc(size)
+    }
+
+    fn malloc_zeroed(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        self.admit()?;
+        self.inner.malloc_zeroed(size)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        self.admit()?;
+        self.inner.realloc(ptr, old_size, new_size, may_move)
+    }
+
+    #[inline]
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, defer: bool) {
+        self.inner.free(ptr, size, defer)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::malloc::GlobalAllocator;
+
+    #[test]
+    fn every_third() {
+        let a = FailingAllocator::every(GlobalAllocator, 3);
+        let r: [bool; 6] = core::array::from_fn(|_| match a.malloc(8) {
+            Ok(p) => {
+                unsafe { a.free(p, 8, false) };
+                true
+            }
+            Err(_) => false,
+        });
+        assert_eq!(r, [true, true, false, true, true, false]);
+        assert_eq!((a.requests(), a.failures()), (6, 2));
+        a.reset();
+        assert_eq!(a.requests(), 0);
+    }
+
+    #[test]
+    fn after_budget() {
+        let a = FailingAllocator::after(GlobalAllocator, 2);
+        let p = a.malloc(8).unwrap();
+        unsafe {
+            let p = a.realloc(p, 8, 16, true).unwrap();
+            assert_eq!(a.realloc(p, 16, 32, true), Err(AllocError));
+            assert_eq!(a.malloc_zeroed(8), Err(AllocError));
+            a.free(p, 16, false);
+        }
+        assert_eq!(a.failures(), 2);
+    }
+}
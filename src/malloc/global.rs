@@ -0,0 +1,168 @@
+//! [`Allocator`] backed by the Rust global allocator.
+
+use core::ptr::NonNull;
+
+use alloc::alloc::{self as rust_alloc, Layout};
+
+use super::{AllocError, Allocator, MIN_ALIGN};
+
+/// Allocates from the registered `#[global_allocator]`.
+///
+/// Requests are rounded up to a size class (four per power of two above 64
+/// bytes, see [`usable_size`](Self::usable_size)), so `realloc` with
+/// `may_move == false` succeeds whenever the new size fits the same class
+/// and never touches the global allocator in that case. Zero-sized requests
+/// do not reach the global allocator: `malloc(0)` returns a dangling,
+/// [`MIN_ALIGN`]-aligned pointer and freeing a zero-sized block does nothing.
+/// Deferred frees are performed immediately.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GlobalAllocator;
+
+impl GlobalAllocator {
+    /// Returns the number of bytes actually reserved for a `size`-byte block.
+    ///
+    /// A block can be resized in place to any size with the same usable size.
+    /// Returns `None` if `size` is too large to allocate.
+    pub const fn usable_size(size: usize) -> Option<usize> {
+        let Some(s) = size.checked_add(MIN_ALIGN - 1) else {
+            return None;
+        };
+        let s = s & !(MIN_ALIGN - 1);
+        if s <= 64 {
+            return Some(s);
+        }
+        let granule = 1 << (usize::BITS - (s - 1).leading_zeros() - 3);
+        match s.checked_add(granule - 1) {
+            Some(n) if n & !(granule - 1) <= isize::MAX as usize => Some(n & !(granule - 1)),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn layout(size: usize) -> Result<Layout, AllocError> {
+        let size = Self::usable_size(size).ok_or(AllocError)?;
+        Layout::from_size_align(size, MIN_ALIGN).map_err(|_| AllocError)
+    }
+
+    #[inline]
+    fn dangling() -> NonNull<u8> {
+        // SAFETY: MIN_ALIGN is non-zero.
+        unsafe { NonNull::new_unchecked(MIN_ALIGN as *mut u8) }
+    }
+}
+
+unsafe impl Allocator for GlobalAllocator {
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        if size == 0 {
+            return Ok(Self::dangling());
+        }
+        let layout = Self::layout(size)?;
+        // SAFETY: the layout has a non-zero size.
+        NonNull::new(unsafe { rust_alloc::alloc(layout) }).ok_or(AllocError)
+    }
+
+    fn malloc_zeroed(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        if size == 0 {
+            return Ok(Self::dangling());
+        }
+        let layout = Self::layout(size)?;
+        // SAFETY: the layout has a non-zero size.
+        NonNull::new(unsafe { rust_alloc::alloc_zeroed(layout) }).ok_or(AllocError)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        if old_size == new_size {
+            return Ok(ptr);
+        }
+        if old_size != 0
+            && new_size != 0
+            && Self::usable_size(old_size) == Self::usable_size(new_size)
+        {
+            return Ok(ptr);
+        }
+        if !may_move {
+            return Err(AllocError);
+        }
+        if old_size == 0 {
+            return self.malloc(new_size);
+        }
+        if new_size == 0 {
+            self.free(ptr, old_size, false);
+            return Ok(Self::dangling());
+        }
+        let new = Self::layout(new_size)?;
+        let old = Self::layout(old_size)?;
+        NonNull::new(rust_alloc::realloc(ptr.as_ptr(), old, new.size())).ok_or(AllocError)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, _defer: bool) {
+        if size == 0 {
+            return;
+        }
+        // The layout was valid when the block was allocated.
+        let layout = Self::layout(size).unwrap_unchecked();
+        rust_alloc::dealloc(ptr.as_ptr(), layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malloc_realloc_free() {
+        let a = GlobalAllocator;
+        let p = a.malloc(24).unwrap();
+        assert_eq!(p.as_ptr() as usize % MIN_ALIGN, 0);
+        unsafe {
+            p.as_ptr().write_bytes(0xab, 24);
+            assert_eq!(a.realloc(p, 24, 48, false), Err(AllocError));
+            let q = a.realloc(p, 24, 48, true).unwrap();
+            assert_eq!(*q.as_ptr().add(23), 0xab);
+            a.free(q, 48, false);
+        }
+    }
+
+    #[test]
+    fn zero_size_and_overflow() {
+        let a = GlobalAllocator;
+        let z = a.malloc(0).unwrap();
+        assert_eq!(z.as_ptr() as usize % MIN_ALIGN, 0);
+        unsafe {
+            let p = a.realloc(z, 0, 8, true).unwrap();
+            let z = a.realloc(p, 8, 0, true).unwrap();
+            a.free(z, 0, true);
+        }
+        assert_eq!(a.malloc(usize::MAX), Err(AllocError));
+    }
+
+    #[test]
+    fn usable_sizes() {
+        let u = GlobalAllocator::usable_size;
+        assert_eq!(u(0), Some(0));
+        assert_eq!(u(1), Some(MIN_ALIGN));
+        assert_eq!(u(64), Some(64));
+        assert_eq!(u(65), Some(80));
+        assert_eq!(u(1000), Some(1024));
+        assert_eq!(u(1025), Some(1280));
+        assert_eq!(u(usize::MAX), None);
+    }
+
+    #[test]
+    fn grow_in_place_and_zeroed() {
+        let a = GlobalAllocator;
+        let p = a.malloc_zeroed(1000).unwrap();
+        unsafe {
+            assert!((0..1000).all(|i| *p.as_ptr().add(i) == 0));
+            assert_eq!(a.realloc(p, 1000, 1024, false), Ok(p));
+            assert_eq!(a.realloc(p, 1024, 1025, false), Err(AllocError));
+            a.free(p, 1024, false);
+        }
+    }
+}
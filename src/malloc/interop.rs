@@ -0,0 +1,167 @@
+//! Adapters between [`Allocator`] and the unstable `core::alloc::Allocator`.
+//!
+//! [`FromCoreAllocator`] lets any `allocator_api` allocator (jemalloc or
+//! mimalloc wrappers, bump arenas, ...) back this crate's data structures;
+//! [`AsCoreAllocator`] lets a crate allocator back standard collections such
+//! as `Vec::new_in`. Both require the `nightly` feature.
+
+use core::alloc::{AllocError as CoreAllocError, Allocator as CoreAllocator, Layout};
+use core::ptr::NonNull;
+
+use super::{AllocError, Allocator, MIN_ALIGN};
+
+/// Uses a `core::alloc::Allocator` as a [`malloc::Allocator`](Allocator).
+///
+/// Every block is requested with [`MIN_ALIGN`] alignment. Deferred frees
+/// are performed immediately.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FromCoreAllocator<A>(pub A);
+
+impl<A> FromCoreAllocator<A> {
+    #[inline]
+    fn layout(size: usize) -> Result<Layout, AllocError> {
+        Layout::from_size_align(size, MIN_ALIGN).map_err(|_| AllocError)
+    }
+}
+
+unsafe impl<A: CoreAllocator> Allocator for FromCoreAllocator<A> {
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        self.0
+            .allocate(Self::layout(size)?)
+            .map(NonNull::as_non_null_ptr)
+            .map_err(|_| AllocError)
+    }
+
+    fn malloc_zeroed(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        self.0
+            .allocate_zeroed(Self::layout(size)?)
+            .map(NonNull::as_non_null_ptr)
+            .map_err(|_| AllocError)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        if old_size == new_size {
+            return Ok(ptr);
+        }
+        if !may_move {
+            return Err(AllocError);
+        }
+        let old = Self::layout(old_size)?;
+        let new = Self::layout(new_size)?;
+        let r = if new_size > old_size {
+            self.0.grow(ptr, old, new)
+        } else {
+            self.0.shrink(ptr, old, new)
+        };
+        r.map(NonNull::as_non_null_ptr).map_err(|_| AllocError)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, _defer: bool) {
+        self.0
+            .deallocate(ptr, Layout::from_size_align_unchecked(size, MIN_ALIGN));
+    }
+}
+
+/// Uses a [`malloc::Allocator`](Allocator) as a `core::alloc::Allocator`.
+///
+/// Requests aligned to more than [`MIN_ALIGN`] fail, since the underlying
+/// interface has no alignment parameter.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AsCoreAllocator<A>(pub A);
+
+unsafe impl<A: Allocator> CoreAllocator for AsCoreAllocator<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, CoreAllocError> {
+        if layout.align() > MIN_ALIGN {
+            return Err(CoreAllocError);
+        }
+        let p = self.0.malloc(layout.size()).map_err(|_| CoreAllocError)?;
+        Ok(NonNull::slice_from_raw_parts(p, layout.size()))
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, CoreAllocError> {
+        if layout.align() > MIN_ALIGN {
+            return Err(CoreAllocError);
+        }
+        let p = self
+            .0
+            .malloc_zeroed(layout.size())
+            .map_err(|_| CoreAllocError)?;
+        Ok(NonNull::slice_from_raw_parts(p, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0.free(ptr, layout.size(), false)
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old: Layout,
+        new: Layout,
+    ) -> Result<NonNull<[u8]>, CoreAllocError> {
+        if new.align() > MIN_ALIGN {
+            return Err(CoreAllocError);
+        }
+        let p = self
+            .0
+            .realloc(ptr, old.size(), new.size(), true)
+            .map_err(|_| CoreAllocError)?;
+        Ok(NonNull::slice_from_raw_parts(p, new.size()))
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old: Layout,
+        new: Layout,
+    ) -> Result<NonNull<[u8]>, CoreAllocError> {
+        if new.align() > MIN_ALIGN {
+            return Err(CoreAllocError);
+        }
+        let p = self
+            .0
+            .realloc(ptr, old.size(), new.size(), true)
+            .map_err(|_| CoreAllocError)?;
+        Ok(NonNull::slice_from_raw_parts(p, new.size()))
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::malloc::GlobalAllocator;
+    use std::alloc::Global;
+    use std::vec::Vec;
+
+    #[test]
+    fn vec_in_crate_allocator() {
+        let mut v = Vec::new_in(AsCoreAllocator(GlobalAllocator));
+        v.extend(0..1000u32);
+        assert_eq!(v.iter().sum::<u32>(), 499_500);
+    }
+
+    #[test]
+    fn core_allocator_as_crate_allocator() {
+        let a = FromCoreAllocator(Global);
+        let p = a.malloc(16).unwrap();
+        unsafe {
+            p.as_ptr().write_bytes(7, 16);
+            let q = a.realloc(p, 16, 4096, true).unwrap();
+            assert_eq!(*q.as_ptr().add(15), 7);
+            a.free(q, 4096, false);
+        }
+    }
+
+    #[test]
+    fn over_aligned_requests_fail() {
+        let a = AsCoreAllocator(GlobalAllocator);
+        let layout = Layout::from_size_align(64, MIN_ALIGN * 2).unwrap();
+        assert!(a.allocate(layout).is_err());
+    }
+}
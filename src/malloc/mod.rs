@@ -0,0 +1,137 @@
+//! Allocator interface modelled on `ck_malloc`.
+//!
+//! Data structures that allocate take an [`Allocator`] rather than calling the
+//! global allocator directly, so callers can supply arenas, slabs or
+//! instrumented allocators. As in C, callers pass the size back on `realloc`
+//! and `free`, and `free` carries a `defer` flag saying whether concurrent
+//! readers may still hold references to the block.
+//!
+//! Blocks are aligned to at least [`MIN_ALIGN`], matching what C `malloc`
+//! guarantees.
+
+use core::fmt;
+use core::ptr::NonNull;
+
+mod arena;
+mod counting;
+#[cfg(feature = "alloc")]
+mod deferred;
+mod failing;
+#[cfg(feature = "alloc")]
+mod global;
+#[cfg(feature = "nightly")]
+mod interop;
+#[cfg(feature = "std")]
+mod numa;
+mod slab;
+
+pub use self::arena::Arena;
+pub use self::counting::{AllocStats, CountingAllocator, HISTOGRAM_BUCKETS};
+#[cfg(feature = "alloc")]
+pub use self::deferred::DeferredAllocator;
+pub use self::failing::FailingAllocator;
+#[cfg(feature = "alloc")]
+pub use self::global::GlobalAllocator;
+#[cfg(feature = "nightly")]
+pub use self::interop::{AsCoreAllocator, FromCoreAllocator};
+#[cfg(feature = "std")]
+pub use self::numa::{cpu_node, current_node, numa_nodes, NumaAllocator};
+pub use self::slab::{Slab, SlabCache};
+
+/// Alignment of every block returned by an [`Allocator`].
+pub const MIN_ALIGN: usize = if core::mem::size_of::<usize>() >= 8 {
+    16
+} else {
+    8
+};
+
+/// The allocator could not satisfy a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+/// A `ck_malloc`-style allocator.
+///
+/// # Safety
+///
+/// Implementations must return blocks of at least the requested size, aligned
+/// to [`MIN_ALIGN`], that stay valid until passed to `free` or moved by
+/// `realloc`.
+pub unsafe trait Allocator {
+    /// Allocates `size` bytes.
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError>;
+
+    /// Allocates `size` zero-filled bytes, like C `calloc`.
+    ///
+    /// The default clears a block from [`malloc`](Self::malloc); allocators
+    /// that get zeroed memory for free should override it.
+    fn malloc_zeroed(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        let p = self.malloc(size)?;
+        // SAFETY: the block is valid for `size` bytes.
+        unsafe { p.as_ptr().write_bytes(0, size) };
+        Ok(p)
+    }
+
+    /// Resizes a block from `old_size` to `new_size` bytes.
+    ///
+    /// If `may_move` is `false` the block must be resized in place or the
+    /// call fails, leaving the block untouched. On success the contents up
+    /// to `min(old_size, new_size)` are preserved.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by this allocator for a block of
+    /// `old_size` bytes that has not been freed.
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError>;
+
+    /// Releases a block of `size` bytes.
+    ///
+    /// `defer` is `true` when other threads may still be reading the block,
+    /// in which case an allocator that supports it postpones reuse until
+    /// they are done. Allocators without such support free immediately.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by this allocator for a block of `size`
+    /// bytes that has not been freed.
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, defer: bool);
+}
+
+unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+    #[inline]
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        (**self).malloc(size)
+    }
+
+    #[inline]
+    fn malloc_zeroed(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        (**self).malloc_zeroed(size)
+    }
+
+    #[inline]
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        (**self).realloc(ptr, old_size, new_size, may_move)
+    }
+
+    #[inline]
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, defer: bool) {
+        (**self).free(ptr, size, defer)
+    }
+}
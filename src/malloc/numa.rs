@@ -0,0 +1,322 @@
+//! NUMA-aware allocation and topology queries.
+//!
+//! On Linux [`NumaAllocator`] maps fresh anonymous pages and binds them to a
+//! node with `mbind(2)`, as `numa_alloc_onnode` does. Elsewhere it falls back
+//! to [`GlobalAllocator`] and the topology helpers report a single node.
+
+use core::ptr::NonNull;
+
+use std::vec::Vec;
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+use super::GlobalAllocator;
+use super::{AllocError, Allocator};
+
+/// Allocates memory placed on a given NUMA node.
+///
+/// By default the node is preferred: if binding fails or the node runs out of
+/// memory the kernel falls back to other nodes. [`strict`](Self::strict)
+/// makes placement mandatory. Every block occupies whole pages, so this is
+/// meant for large per-node structures rather than small nodes; put a
+/// [`Slab`](super::Slab) or [`Arena`](super::Arena) in front of it for those.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumaAllocator {
+    node: usize,
+    strict: bool,
+}
+
+impl NumaAllocator {
+    /// Creates an allocator preferring `node`.
+    pub const fn new(node: usize) -> Self {
+        NumaAllocator {
+            node,
+            strict: false,
+        }
+    }
+
+    /// Creates an allocator preferring the node of the calling CPU.
+    pub fn local() -> Self {
+        Self::new(current_node())
+    }
+
+    /// Makes allocation fail rather than fall back to another node.
+    pub const fn strict(self) -> Self {
+        NumaAllocator {
+            strict: true,
+            ..self
+        }
+    }
+
+    /// Returns the target node.
+    pub const fn node(&self) -> usize {
+        self.node
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sys {
+    use core::ptr::{self, NonNull};
+
+    use super::NumaAllocator;
+    use crate::malloc::{AllocError, MIN_ALIGN};
+
+    const MPOL_PREFERRED: libc::c_int = 1;
+    const MPOL_BIND: libc::c_int = 2;
+    const MAX_NODES: usize = 1024;
+    const WORD: usize = libc::c_ulong::BITS as usize;
+
+    fn page_size() -> usize {
+        // SAFETY: sysconf has no preconditions.
+        match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+            n if n > 0 => n as usize,
+            _ => 4096,
+        }
+    }
+
+    /// Rounds `size` up to whole pages; zero stays zero.
+    pub(super) fn pages(size: usize) -> Option<usize> {
+        let page = page_size();
+        size.checked_add(page - 1).map(|n| n & !(page - 1))
+    }
+
+    pub(super) fn dangling() -> NonNull<u8> {
+        // SAFETY: MIN_ALIGN is non-zero.
+        unsafe { NonNull::new_unchecked(MIN_ALIGN as *mut u8) }
+    }
+
+    pub(super) fn map(a: &NumaAllocator, len: usize) -> Result<NonNull<u8>, AllocError> {
+        // SAFETY: an anonymous private mapping has no preconditions.
+        let p = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if p == libc::MAP_FAILED {
+            return Err(AllocError);
+        }
+        let bound = a.node < MAX_NODES && {
+            let mut mask = [0 as libc::c_ulong; MAX_NODES / WORD];
+            mask[a.node / WORD] = 1 << (a.node % WORD);
+            let mode = if a.strict { MPOL_BIND } else { MPOL_PREFERRED };
+            // SAFETY: the range is our own mapping and the mask covers
+            // MAX_NODES bits (the kernel wants one more than that).
+            unsafe {
+                libc::syscall(
+                    libc::SYS_mbind,
+                    p,
+                    len,
+                    mode,
+                    mask.as_ptr(),
+                    MAX_NODES + 1,
+                    0,
+                ) == 0
+            }
+        };
+        if !bound && a.strict {
+            // SAFETY: the mapping was created above.
+            unsafe { libc::munmap(p, len) };
+            return Err(AllocError);
+        }
+        // SAFETY: mmap never returns null on success.
+        Ok(unsafe { NonNull::new_unchecked(p as *mut u8) })
+    }
+
+    pub(super) unsafe fn unmap(p: NonNull<u8>, len: usize) {
+        libc::munmap(p.as_ptr() as *mut libc::c_void, len);
+    }
+
+    pub(super) fn current_node() -> Option<usize> {
+        let (mut cpu, mut node) = (0 as libc::c_uint, 0 as libc::c_uint);
+        // SAFETY: both out-pointers are valid; the cache argument is unused.
+        let r = unsafe {
+            libc::syscall(
+                libc::SYS_getcpu,
+                &mut cpu as *mut libc::c_uint,
+                &mut node as *mut libc::c_uint,
+                ptr::null_mut::<libc::c_void>(),
+            )
+        };
+        (r == 0).then_some(node as usize)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe impl Allocator for NumaAllocator {
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        match sys::pages(size).ok_or(AllocError)? {
+            0 => Ok(sys::dangling()),
+            len => sys::map(self, len),
+        }
+    }
+
+    fn malloc_zeroed(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        // Fresh anonymous mappings are already zero.
+        self.malloc(size)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let new_len = sys::pages(new_size).ok_or(AllocError)?;
+        if sys::pages(old_size) == Some(new_len) {
+            return Ok(ptr);
+        }
+        if !may_move {
+            return Err(AllocError);
+        }
+        let p = self.malloc(new_size)?;
+        core::ptr::copy_nonoverlapping(ptr.as_ptr(), p.as_ptr(), old_size.min(new_size));
+        self.free(ptr, old_size, false);
+        Ok(p)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, _defer: bool) {
+        match sys::pages(size) {
+            Some(0) | None => {}
+            Some(len) => sys::unmap(ptr, len),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+unsafe impl Allocator for NumaAllocator {
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        if self.strict && self.node != 0 {
+            return Err(AllocError);
+        }
+        GlobalAllocator.malloc(size)
+    }
+
+    fn malloc_zeroed(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        if self.strict && self.node != 0 {
+            return Err(AllocError);
+        }
+        GlobalAllocator.malloc_zeroed(size)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        GlobalAllocator.realloc(ptr, old_size, new_size, may_move)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, defer: bool) {
+        GlobalAllocator.free(ptr, size, defer)
+    }
+}
+
+/// Parses a sysfs list such as `0-3,8,10-11`.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+fn parse_list(s: &str) -> Vec<usize> {
+    let mut out = Vec::new();
+    for part in s.trim().split(',').filter(|p| !p.is_empty()) {
+        let mut bounds = part.splitn(2, '-').map(|n| n.trim().parse::<usize>());
+        match (bounds.next(), bounds.next()) {
+            (Some(Ok(a)), None) => out.push(a),
+            (Some(Ok(a)), Some(Ok(b))) => out.extend(a..=b),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Returns the online NUMA nodes, or `[0]` if the topology is unknown.
+pub fn numa_nodes() -> Vec<usize> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Ok(s) = std::fs::read_to_string("/sys/devices/system/node/online") {
+        let nodes = parse_list(&s);
+        if !nodes.is_empty() {
+            return nodes;
+        }
+    }
+    std::vec![0]
+}
+
+/// Returns the NUMA node of `cpu`, or `None` if it is unknown.
+pub fn cpu_node(cpu: usize) -> Option<usize> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let dir = std::format!("/sys/devices/system/cpu/cpu{cpu}");
+        std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .find_map(|e| e.file_name().to_str()?.strip_prefix("node")?.parse().ok())
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        let _ = cpu;
+        Some(0)
+    }
+}
+
+/// Returns the NUMA node the calling thread is running on.
+///
+/// The answer may be stale as soon as it is returned if the thread migrates.
+pub fn current_node() -> usize {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(node) = sys::current_node() {
+        return node;
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::malloc::MIN_ALIGN;
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_list("0\n"), [0]);
+        assert_eq!(parse_list("0-2,5,7-8"), [0, 1, 2, 5, 7, 8]);
+        assert!(parse_list("").is_empty());
+    }
+
+    #[test]
+    fn topology() {
+        let nodes = numa_nodes();
+        assert!(!nodes.is_empty());
+        assert!(nodes.contains(&current_node()));
+        if let Some(n) = cpu_node(0) {
+            assert!(nodes.contains(&n));
+        }
+    }
+
+    #[test]
+    fn allocate_on_local_node() {
+        let a = NumaAllocator::local();
+        let p = a.malloc(10_000).unwrap();
+        assert_eq!(p.as_ptr() as usize % MIN_ALIGN, 0);
+        unsafe {
+            p.as_ptr().write_bytes(0x5a, 10_000);
+            assert_eq!(a.realloc(p, 10_000, 10_001, false), Ok(p));
+            let q = a.realloc(p, 10_000, 100_000, true).unwrap();
+            assert_eq!(*q.as_ptr().add(9_999), 0x5a);
+            a.free(q, 100_000, false);
+        }
+        let z = a.malloc(0).unwrap();
+        unsafe { a.free(z, 0, false) };
+        assert_eq!(a.malloc(usize::MAX), Err(AllocError));
+    }
+
+    #[test]
+    fn strict_on_missing_node_fails() {
+        assert_eq!(
+            NumaAllocator::new(1 << 20).strict().malloc(64),
+            Err(AllocError)
+        );
+    }
+}
@@ -0,0 +1,401 @@
+//! Size-class slab allocator with magazine caching.
+//!
+//! Requests up to [`Slab::MAX_CLASS`] bytes are rounded up to a power-of-two
+//! size class. Each class keeps a depot: a spinlock-protected free list fed
+//! by carving [`Slab::PAGE_SIZE`] pages obtained from the backing allocator.
+//! Larger requests go straight to the backing allocator. Pages are returned
+//! to the backing allocator only when the slab is dropped.
+//!
+//! The depot lock is taken on every operation through `&Slab`. Threads that
+//! allocate heavily should each hold a [`SlabCache`], which keeps a small
+//! magazine of free blocks per class and exchanges them with the depot in
+//! batches. There are no implicit thread-locals, so this works on `no_std`.
+
+use core::cell::UnsafeCell;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+use super::{AllocError, Allocator, MIN_ALIGN};
+use crate::cc::CachePadded;
+use crate::pr;
+
+const MIN_SHIFT: u32 = 4;
+const CLASSES: usize = 9;
+const MAGAZINE: usize = 32;
+
+struct FreeBlock {
+    next: *mut FreeBlock,
+}
+
+struct Page {
+    next: *mut Page,
+}
+
+const PAGE_HEADER: usize = (core::mem::size_of::<Page>() + MIN_ALIGN - 1) & !(MIN_ALIGN - 1);
+
+struct Depot {
+    lock: AtomicBool,
+    head: UnsafeCell<*mut FreeBlock>,
+}
+
+impl Depot {
+    const fn new() -> Self {
+        Depot {
+            lock: AtomicBool::new(false),
+            head: UnsafeCell::new(ptr::null_mut()),
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut *mut FreeBlock) -> R) -> R {
+        while self
+            .lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.lock.load(Ordering::Relaxed) {
+                pr::stall();
+            }
+        }
+        // SAFETY: the lock grants exclusive access to the list head.
+        let r = f(unsafe { &mut *self.head.get() });
+        self.lock.store(false, Ordering::Release);
+        r
+    }
+
+    /// Moves up to `out.len()` blocks into `out`, returning how many.
+    fn pop_batch(&self, out: &mut [*mut u8]) -> usize {
+        self.with(|head| {
+            let mut n = 0;
+            while n < out.len() && !head.is_null() {
+                out[n] = *head as *mut u8;
+                // SAFETY: blocks on the list are free and hold a `FreeBlock`.
+                *head = unsafe { (**head).next };
+                n += 1;
+            }
+            n
+        })
+    }
+
+    /// Pushes a pre-linked chain `first..=last` onto the list.
+    fn push_chain(&self, first: *mut FreeBlock, last: *mut FreeBlock) {
+        self.with(|head| {
+            // SAFETY: the caller owns the chain.
+            unsafe { (*last).next = *head };
+            *head = first;
+        })
+    }
+
+    fn push_batch(&self, blocks: &[*mut u8]) {
+        if blocks.is_empty() {
+            return;
+        }
+        for w in blocks.windows(2) {
+            // SAFETY: the blocks are free and owned by the caller.
+            unsafe { (*(w[0] as *mut FreeBlock)).next = w[1] as *mut FreeBlock };
+        }
+        self.push_chain(
+            blocks[0] as *mut FreeBlock,
+            blocks[blocks.len() - 1] as *mut FreeBlock,
+        );
+    }
+}
+
+#[inline]
+fn class_of(size: usize) -> Option<usize> {
+    if size > class_size(CLASSES - 1) {
+        return None;
+    }
+    let size = size.max(1 << MIN_SHIFT);
+    Some((usize::BITS - (size - 1).leading_zeros() - MIN_SHIFT) as usize)
+}
+
+#[inline]
+const fn class_size(class: usize) -> usize {
+    1 << (class as u32 + MIN_SHIFT)
+}
+
+/// A size-class slab allocator on top of a backing [`Allocator`].
+pub struct Slab<A: Allocator> {
+    alloc: A,
+    depots: [CachePadded<Depot>; CLASSES],
+    pages: AtomicPtr<Page>,
+}
+
+unsafe impl<A: Allocator + Send> Send for Slab<A> {}
+unsafe impl<A: Allocator + Sync> Sync for Slab<A> {}
+
+impl<A: Allocator> Slab<A> {
+    /// Largest request served from a size class.
+    pub const MAX_CLASS: usize = class_size(CLASSES - 1);
+    /// Size of the pages carved into blocks.
+    pub const PAGE_SIZE: usize = 64 * 1024;
+
+    /// Creates a slab allocator drawing pages from `alloc`.
+    pub const fn new(alloc: A) -> Self {
+        Slab {
+            alloc,
+            depots: [const { CachePadded::new(Depot::new()) }; CLASSES],
+            pages: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns a per-thread magazine cache for this slab.
+    pub fn cache(&self) -> SlabCache<'_, A> {
+        SlabCache {
+            slab: self,
+            magazines: UnsafeCell::new([const { Magazine::new() }; CLASSES]),
+        }
+    }
+
+    /// Carves a fresh page for `class`, fills `out` from it and pushes the
+    /// remaining blocks to the depot. Returns how many blocks went to `out`.
+    #[cold]
+    fn refill(&self, class: usize, out: &mut [*mut u8]) -> Result<usize, AllocError> {
+        let page = self.alloc.malloc(Self::PAGE_SIZE)?.as_ptr() as *mut Page;
+        let mut head = self.pages.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: the page is freshly allocated and owned by us.
+            unsafe { (*page).next = head };
+            match self
+                .pages
+                .compare_exchange_weak(head, page, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(h) => head = h,
+            }
+        }
+        let size = class_size(class);
+        let base = (page as *mut u8).wrapping_add(PAGE_HEADER);
+        let count = (Self::PAGE_SIZE - PAGE_HEADER) / size;
+        let block = |i: usize| base.wrapping_add(i * size);
+        let taken = out.len().min(count);
+        for (i, slot) in out.iter_mut().enumerate().take(taken) {
+            *slot = block(i);
+        }
+        if taken < count {
+            for i in taken..count - 1 {
+                // SAFETY: the blocks lie inside the page.
+                unsafe { (*(block(i) as *mut FreeBlock)).next = block(i + 1) as *mut FreeBlock };
+            }
+            self.depots[class].push_chain(
+                block(taken) as *mut FreeBlock,
+                block(count - 1) as *mut FreeBlock,
+            );
+        }
+        Ok(taken)
+    }
+
+    fn take(&self, class: usize, out: &mut [*mut u8]) -> Result<usize, AllocError> {
+        match self.depots[class].pop_batch(out) {
+            0 => self.refill(class, out),
+            n => Ok(n),
+        }
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for Slab<A> {
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        let Some(class) = class_of(size) else {
+            return self.alloc.malloc(size);
+        };
+        let mut out = [ptr::null_mut(); 1];
+        self.take(class, &mut out)?;
+        NonNull::new(out[0]).ok_or(AllocError)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        slab_realloc(self, ptr, old_size, new_size, may_move)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, defer: bool) {
+        match class_of(size) {
+            Some(class) => self.depots[class].push_batch(&[ptr.as_ptr()]),
+            None => self.alloc.free(ptr, size, defer),
+        }
+    }
+}
+
+unsafe fn slab_realloc<S: Allocator + ?Sized>(
+    s: &S,
+    ptr: NonNull<u8>,
+    old_size: usize,
+    new_size: usize,
+    may_move: bool,
+) -> Result<NonNull<u8>, AllocError> {
+    let (old, new) = (class_of(old_size), class_of(new_size));
+    if old.is_some() && old == new {
+        return Ok(ptr);
+    }
+    if !may_move {
+        return Err(AllocError);
+    }
+    let p = s.malloc(new_size)?;
+    core::ptr::copy_nonoverlapping(ptr.as_ptr(), p.as_ptr(), old_size.min(new_size));
+    s.free(ptr, old_size, false);
+    Ok(p)
+}
+
+impl<A: Allocator> Drop for Slab<A> {
+    fn drop(&mut self) {
+        let mut page = *self.pages.get_mut();
+        while !page.is_null() {
+            // SAFETY: all blocks are dead once the slab is dropped.
+            unsafe {
+                let next = (*page).next;
+                self.alloc.free(
+                    NonNull::new_unchecked(page as *mut u8),
+                    Self::PAGE_SIZE,
+                    false,
+                );
+                page = next;
+            }
+        }
+    }
+}
+
+struct Magazine {
+    len: usize,
+    items: [*mut u8; MAGAZINE],
+}
+
+impl Magazine {
+    const fn new() -> Self {
+        Magazine {
+            len: 0,
+            items: [ptr::null_mut(); MAGAZINE],
+        }
+    }
+}
+
+/// A thread-local front end to a [`Slab`].
+///
+/// Keeps up to 32 free blocks per size class, refilling from and flushing
+/// to the shared depot half a magazine at a time. Cached blocks go back to
+/// the depot when the cache is dropped. The cache is `Send` but not `Sync`:
+/// give each thread its own.
+pub struct SlabCache<'a, A: Allocator> {
+    slab: &'a Slab<A>,
+    magazines: UnsafeCell<[Magazine; CLASSES]>,
+}
+
+unsafe impl<A: Allocator + Sync> Send for SlabCache<'_, A> {}
+
+impl<A: Allocator> SlabCache<'_, A> {
+    #[allow(clippy::mut_from_ref)]
+    fn magazine(&self, class: usize) -> &mut Magazine {
+        // SAFETY: the cache is !Sync and no reference escapes a method call.
+        unsafe { &mut (*self.magazines.get())[class] }
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for SlabCache<'_, A> {
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        let Some(class) = class_of(size) else {
+            return self.slab.alloc.malloc(size);
+        };
+        let m = self.magazine(class);
+        if m.len == 0 {
+            m.len = self.slab.take(class, &mut m.items[..MAGAZINE / 2])?;
+        }
+        m.len -= 1;
+        NonNull::new(m.items[m.len]).ok_or(AllocError)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        slab_realloc(self, ptr, old_size, new_size, may_move)
+    }
+
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, defer: bool) {
+        let Some(class) = class_of(size) else {
+            return self.slab.alloc.free(ptr, size, defer);
+        };
+        let m = self.magazine(class);
+        if m.len == MAGAZINE {
+            self.slab.depots[class].push_batch(&m.items[MAGAZINE / 2..]);
+            m.len = MAGAZINE / 2;
+        }
+        m.items[m.len] = ptr.as_ptr();
+        m.len += 1;
+    }
+}
+
+impl<A: Allocator> Drop for SlabCache<'_, A> {
+    fn drop(&mut self) {
+        for (class, m) in self.magazines.get_mut().iter().enumerate() {
+            self.slab.depots[class].push_batch(&m.items[..m.len]);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::malloc::GlobalAllocator;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn size_classes() {
+        assert_eq!(class_of(0), Some(0));
+        assert_eq!(class_of(16), Some(0));
+        assert_eq!(class_of(17), Some(1));
+        assert_eq!(class_of(4096), Some(8));
+        assert_eq!(class_of(4097), None);
+        assert_eq!(Slab::<GlobalAllocator>::MAX_CLASS, 4096);
+    }
+
+    #[test]
+    fn reuse_and_realloc() {
+        let s = Slab::new(GlobalAllocator);
+        let p = s.malloc(24).unwrap();
+        unsafe {
+            assert_eq!(s.realloc(p, 24, 32, false), Ok(p));
+            assert_eq!(s.realloc(p, 24, 33, false), Err(AllocError));
+            s.free(p, 24, false);
+        }
+        assert_eq!(s.malloc(20).unwrap(), p);
+        let big = s.malloc(10_000).unwrap();
+        unsafe { s.free(big, 10_000, false) };
+    }
+
+    #[test]
+    fn caches_on_many_threads() {
+        let s = Slab::new(GlobalAllocator);
+        thread::scope(|scope| {
+            for t in 0..4u8 {
+                let s = &s;
+                scope.spawn(move || {
+                    let c = s.cache();
+                    let mut live = Vec::new();
+                    for i in 0..2000usize {
+                        let size = 8 + (i % 200);
+                        let p = c.malloc(size).unwrap();
+                        unsafe { p.as_ptr().write_bytes(t, size) };
+                        live.push((p, size));
+                        if i % 3 == 0 {
+                            let (p, size) = live.swap_remove(i % live.len());
+                            assert!(unsafe { *p.as_ptr() } == t);
+                            unsafe { c.free(p, size, false) };
+                        }
+                    }
+                    for (p, size) in live {
+                        assert!(unsafe { *p.as_ptr().add(size - 1) } == t);
+                        unsafe { c.free(p, size, false) };
+                    }
+                });
+            }
+        });
+    }
+}
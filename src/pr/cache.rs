@@ -0,0 +1,160 @@
+//! Cache-line maintenance and software prefetch.
+//!
+//! `clflush` writes a line back to memory and invalidates it; `clwb` writes it
+//! back and may keep it cached. Neither is ordered with later stores on its
+//! own: follow a batch of flushes with [`fence_memory`](super::fence_memory)
+//! before publishing. On targets without cache maintenance instructions the
+//! flushes are compiler barriers and the prefetches are no-ops.
+
+/// Writes back and invalidates the cache line containing `ptr`.
+///
+/// Uses `clflush` on x86 and `dc civac` on aarch64.
+///
+/// # Safety
+///
+/// `ptr` must point into mapped memory; flushing an unmapped address faults.
+#[inline(always)]
+pub unsafe fn clflush<T>(ptr: *const T) {
+    arch::clflush(ptr as *const u8)
+}
+
+/// Writes back the cache line containing `ptr` without necessarily evicting it.
+///
+/// Uses `clwb` (or `clflushopt`/`clflush` on processors lacking it) on x86 and
+/// `dc cvac` on aarch64.
+///
+/// # Safety
+///
+/// `ptr` must point into mapped memory; flushing an unmapped address faults.
+#[inline(always)]
+pub unsafe fn clwb<T>(ptr: *const T) {
+    arch::clwb(ptr as *const u8)
+}
+
+/// Hints that the cache line containing `ptr` will soon be read.
+///
+/// Never faults, whatever `ptr` is. See [`cc::prefetch_read_with`] to choose
+/// a locality level.
+///
+/// [`cc::prefetch_read_with`]: crate::cc::prefetch_read_with
+#[inline(always)]
+pub fn prefetch_read<T>(ptr: *const T) {
+    crate::cc::prefetch_read(ptr)
+}
+
+/// Hints that the cache line containing `ptr` will soon be written.
+///
+/// Never faults, whatever `ptr` is. See [`cc::prefetch_write_with`] to choose
+/// a locality level.
+///
+/// [`cc::prefetch_write_with`]: crate::cc::prefetch_write_with
+#[inline(always)]
+pub fn prefetch_write<T>(ptr: *const T) {
+    crate::cc::prefetch_write(ptr)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod arch {
+    use core::arch::asm;
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    const UNKNOWN: u8 = 0;
+    const CLFLUSH: u8 = 1;
+    const CLFLUSHOPT: u8 = 2;
+    const CLWB: u8 = 3;
+
+    static WRITEBACK: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+    #[cold]
+    fn detect() -> u8 {
+        #[cfg(target_arch = "x86")]
+        use core::arch::x86::{__cpuid, __cpuid_count};
+        #[cfg(target_arch = "x86_64")]
+        use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+        let kind = if __cpuid(0).eax < 7 {
+            CLFLUSH
+        } else {
+            let ebx = __cpuid_count(7, 0).ebx;
+            if ebx & (1 << 24) != 0 {
+                CLWB
+            } else if ebx & (1 << 23) != 0 {
+                CLFLUSHOPT
+            } else {
+                CLFLUSH
+            }
+        };
+        WRITEBACK.store(kind, Ordering::Relaxed);
+        kind
+    }
+
+    #[inline(always)]
+    pub(super) unsafe fn clflush(ptr: *const u8) {
+        asm!("clflush [{}]", in(reg) ptr, options(nostack, preserves_flags));
+    }
+
+    #[inline(always)]
+    pub(super) unsafe fn clwb(ptr: *const u8) {
+        let kind = match WRITEBACK.load(Ordering::Relaxed) {
+            UNKNOWN => detect(),
+            k => k,
+        };
+        match kind {
+            CLWB => asm!("clwb [{}]", in(reg) ptr, options(nostack, preserves_flags)),
+            CLFLUSHOPT => asm!("clflushopt [{}]", in(reg) ptr, options(nostack, preserves_flags)),
+            _ => clflush(ptr),
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    use core::arch::asm;
+
+    #[inline(always)]
+    pub(super) unsafe fn clflush(ptr: *const u8) {
+        asm!("dc civac, {}", in(reg) ptr, options(nostack, preserves_flags));
+    }
+
+    #[inline(always)]
+    pub(super) unsafe fn clwb(ptr: *const u8) {
+        asm!("dc cvac, {}", in(reg) ptr, options(nostack, preserves_flags));
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+mod arch {
+    use core::sync::atomic::{compiler_fence, Ordering};
+
+    #[inline(always)]
+    pub(super) unsafe fn clflush(_ptr: *const u8) {
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    #[inline(always)]
+    pub(super) unsafe fn clwb(_ptr: *const u8) {
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flush_and_prefetch() {
+        let mut buf = [0u64; 32];
+        prefetch_read(buf.as_ptr());
+        prefetch_write(buf.as_ptr());
+        prefetch_read(core::ptr::null::<u8>());
+        for (i, v) in buf.iter_mut().enumerate() {
+            *v = i as u64;
+        }
+        unsafe {
+            clwb(&buf[0]);
+            clflush(&buf[16]);
+        }
+        super::super::fence_memory();
+        assert_eq!(buf[17], 17);
+    }
+}
@@ -0,0 +1,345 @@
+//! Atomic access to small `Copy` values.
+//!
+//! Values of a [`NoPadding`] type whose size matches a native atomic integer
+//! (1, 2, 4 or, where the target supports it, 8 bytes) are accessed with
+//! that integer's atomic instructions. Everything else falls back to a
+//! per-cell sequence lock: readers retry while a write is in progress and
+//! writers serialize among themselves.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::{self, size_of, MaybeUninit};
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{self, AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+
+#[cfg(target_has_atomic = "64")]
+use core::sync::atomic::AtomicU64;
+
+/// Marks `Copy` types with no padding bytes, whose values [`AtomicCell`]
+/// may move around as plain integers.
+///
+/// # Safety
+///
+/// Every byte of every value of the type must be initialized.
+pub unsafe trait NoPadding: Copy {}
+
+macro_rules! no_padding {
+    ($($t:ty),*) => {
+        $(unsafe impl NoPadding for $t {})*
+    };
+}
+
+no_padding!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+no_padding!(f32, f64, bool, char, ());
+
+unsafe impl<T> NoPadding for *const T {}
+unsafe impl<T> NoPadding for *mut T {}
+unsafe impl<T> NoPadding for NonNull<T> {}
+unsafe impl<T> NoPadding for Option<NonNull<T>> {}
+unsafe impl<T: NoPadding, const N: usize> NoPadding for [T; N] {}
+
+// Aligned for the widest atomic overlaid on it, on every target.
+#[repr(C, align(8))]
+union Storage<T: Copy> {
+    value: T,
+}
+
+/// A `Copy` value that can be shared and updated atomically.
+///
+/// Loads use `Acquire`, stores `Release` and read-modify-write operations
+/// `AcqRel` ordering.
+pub struct AtomicCell<T: Copy> {
+    storage: UnsafeCell<Storage<T>>,
+    seq: AtomicUsize,
+    lock_free: bool,
+}
+
+unsafe impl<T: Copy + Send> Send for AtomicCell<T> {}
+unsafe impl<T: Copy + Send> Sync for AtomicCell<T> {}
+
+/// Runs `$native` with `$a` bound to the native atomic overlaying the cell,
+/// or `$locked` if the cell was not made lock-free.
+macro_rules! with_atomic {
+    ($self:ident, |$a:ident| $native:expr, $locked:expr) => {
+        match if $self.lock_free { size_of::<T>() } else { 0 } {
+            // SAFETY: `Storage` is 8-byte aligned, and `lock_free` is only set
+            // for padding-free `T` of exactly the overlaid integer's size.
+            1 => {
+                let $a = unsafe { &*($self.storage.get() as *const AtomicU8) };
+                $native
+            }
+            2 => {
+                let $a = unsafe { &*($self.storage.get() as *const AtomicU16) };
+                $native
+            }
+            4 => {
+                let $a = unsafe { &*($self.storage.get() as *const AtomicU32) };
+                $native
+            }
+            #[cfg(target_has_atomic = "64")]
+            8 => {
+                let $a = unsafe { &*($self.storage.get() as *const AtomicU64) };
+                $native
+            }
+            _ => $locked,
+        }
+    };
+}
+
+#[inline(always)]
+fn to_bits<T: Copy, I: Copy>(value: T) -> I {
+    debug_assert_eq!(size_of::<T>(), size_of::<I>());
+    // SAFETY: callers only pair `T` with an integer of identical size.
+    unsafe { mem::transmute_copy(&value) }
+}
+
+#[inline(always)]
+fn from_bits<I: Copy, T: Copy>(bits: I) -> T {
+    debug_assert_eq!(size_of::<T>(), size_of::<I>());
+    // SAFETY: the bits were produced from a valid `T`.
+    unsafe { mem::transmute_copy(&bits) }
+}
+
+impl<T: NoPadding> AtomicCell<T> {
+    /// Creates a new cell, lock-free if `T` has the size of a native atomic
+    /// integer.
+    pub const fn new(value: T) -> Self {
+        let size = size_of::<T>();
+        let lock_free =
+            size == 1 || size == 2 || size == 4 || (size == 8 && cfg!(target_has_atomic = "64"));
+        AtomicCell {
+            storage: UnsafeCell::new(Storage { value }),
+            seq: AtomicUsize::new(0),
+            lock_free,
+        }
+    }
+}
+
+impl<T: Copy> AtomicCell<T> {
+    /// Creates a new cell behind the sequence lock, for types that may
+    /// contain padding.
+    pub const fn new_locked(value: T) -> Self {
+        AtomicCell {
+            storage: UnsafeCell::new(Storage { value }),
+            seq: AtomicUsize::new(0),
+            lock_free: false,
+        }
+    }
+
+    /// Returns `true` if operations on this cell are lock-free.
+    pub const fn is_lock_free(&self) -> bool {
+        self.lock_free
+    }
+
+    /// Consumes the cell and returns the contained value.
+    pub fn into_inner(self) -> T {
+        // SAFETY: `value` is the only initialized field.
+        unsafe { self.storage.into_inner().value }
+    }
+
+    /// Returns a mutable reference to the contained value.
+    pub fn get_mut(&mut self) -> &mut T {
+        // SAFETY: `value` is the only initialized field.
+        unsafe { &mut self.storage.get_mut().value }
+    }
+
+    #[inline]
+    fn value_ptr(&self) -> *mut T {
+        self.storage.get() as *mut T
+    }
+
+    fn write_lock(&self) -> usize {
+        let mut seq = self.seq.load(Ordering::Relaxed);
+        loop {
+            if seq & 1 == 0 {
+                match self.seq.compare_exchange_weak(
+                    seq,
+                    seq.wrapping_add(1),
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(s) => seq = s,
+                }
+            } else {
+                super::stall();
+                seq = self.seq.load(Ordering::Relaxed);
+            }
+        }
+        // Keep the data writes below from becoming visible before the odd count.
+        atomic::fence(Ordering::Release);
+        seq
+    }
+
+    fn write_unlock(&self, seq: usize) {
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    fn locked_load(&self) -> T {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 != 0 {
+                super::stall();
+                continue;
+            }
+            // SAFETY: a concurrent writer may tear this copy, so it stays
+            // uninitialised until the sequence shows no write overlapped it.
+            let value = unsafe { ptr::read_volatile(self.value_ptr() as *const MaybeUninit<T>) };
+            atomic::fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                // SAFETY: no write overlapped the copy, so it holds the
+                // value a writer stored whole.
+                return unsafe { value.assume_init() };
+            }
+        }
+    }
+
+    fn locked_swap(&self, value: T) -> T {
+        let seq = self.write_lock();
+        // SAFETY: the sequence lock excludes other writers.
+        let old = unsafe {
+            let old = ptr::read(self.value_ptr());
+            ptr::write_volatile(self.value_ptr(), value);
+            old
+        };
+        self.write_unlock(seq);
+        old
+    }
+
+    /// Loads the value.
+    #[inline]
+    pub fn load(&self) -> T {
+        with_atomic!(
+            self,
+            |a| from_bits(a.load(Ordering::Acquire)),
+            self.locked_load()
+        )
+    }
+
+    /// Stores `value`.
+    #[inline]
+    pub fn store(&self, value: T) {
+        with_atomic!(
+            self,
+            |a| a.store(to_bits(value), Ordering::Release),
+            drop(self.locked_swap(value))
+        )
+    }
+
+    /// Stores `value`, returning the previous value.
+    #[inline]
+    pub fn swap(&self, value: T) -> T {
+        with_atomic!(
+            self,
+            |a| from_bits(a.swap(to_bits(value), Ordering::AcqRel)),
+            self.locked_swap(value)
+        )
+    }
+}
+
+impl<T: Copy + PartialEq> AtomicCell<T> {
+    /// Stores `new` if the current value equals `current`.
+    ///
+    /// Returns the previous value on success and the observed value on
+    /// failure. Equality is `PartialEq`, not bitwise.
+    pub fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+        with_atomic!(
+            self,
+            |a| {
+                let mut observed = a.load(Ordering::Acquire);
+                loop {
+                    let value: T = from_bits(observed);
+                    if value != current {
+                        break Err(value);
+                    }
+                    match a.compare_exchange_weak(
+                        observed,
+                        to_bits(new),
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    ) {
+                        Ok(_) => break Ok(value),
+                        Err(o) => observed = o,
+                    }
+                }
+            },
+            {
+                let seq = self.write_lock();
+                // SAFETY: the sequence lock excludes other writers.
+                let r = unsafe {
+                    let old = ptr::read(self.value_ptr());
+                    if old == current {
+                        ptr::write_volatile(self.value_ptr(), new);
+                        Ok(old)
+                    } else {
+                        Err(old)
+                    }
+                };
+                self.write_unlock(seq);
+                r
+            }
+        )
+    }
+}
+
+impl<T: NoPadding + Default> Default for AtomicCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for AtomicCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicCell").field(&self.load()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn pair_is_lock_free() {
+        assert!(!AtomicCell::new([0u64; 3]).is_lock_free());
+        let c = AtomicCell::new([1u16, 2]);
+        assert!(c.is_lock_free());
+        assert_eq!(c.swap([3, 4]), [1, 2]);
+        assert_eq!(c.compare_exchange([0, 0], [5, 6]), Err([3, 4]));
+        assert_eq!(c.compare_exchange([3, 4], [5, 6]), Ok([3, 4]));
+        assert_eq!(c.load(), [5, 6]);
+        #[cfg(target_has_atomic = "64")]
+        assert!(AtomicCell::new(0u64).is_lock_free());
+    }
+
+    #[test]
+    fn padded_values_use_the_lock() {
+        let c = AtomicCell::new_locked((1u8, 2u32));
+        assert!(!c.is_lock_free());
+        assert_eq!(c.swap((3, 4)), (1, 2));
+        assert_eq!(c.compare_exchange((3, 4), (5, 6)), Ok((3, 4)));
+        assert_eq!(c.load(), (5, 6));
+        assert_eq!(core::mem::align_of::<AtomicCell<u8>>() % 8, 0);
+    }
+
+    #[test]
+    fn large_values_are_never_torn() {
+        let c = Arc::new(AtomicCell::new([0u64; 4]));
+        let writer = {
+            let c = c.clone();
+            thread::spawn(move || {
+                for i in 1..=10_000u64 {
+                    c.store([i; 4]);
+                }
+            })
+        };
+        for _ in 0..10_000 {
+            let v = c.load();
+            assert!(v.iter().all(|&x| x == v[0]));
+        }
+        writer.join().unwrap();
+        assert_eq!(c.compare_exchange([10_000; 4], [1; 4]), Ok([10_000; 4]));
+        assert_eq!(Arc::try_unwrap(c).unwrap().into_inner(), [1; 4]);
+    }
+}
@@ -0,0 +1,89 @@
+//! Barriers for device and DMA memory.
+//!
+//! The ordinary `fence_*` functions only order accesses to normal cacheable
+//! memory between CPUs. Drivers talking to devices need the stronger
+//! barriers below, which follow the Linux kernel's `mb`/`rmb`/`wmb` and
+//! `dma_rmb`/`dma_wmb` semantics:
+//!
+//! | function        | aarch64       | x86      | riscv             |
+//! |-----------------|---------------|----------|-------------------|
+//! | `fence_mb`      | `dsb sy`      | `mfence` | `fence iorw,iorw` |
+//! | `fence_rmb`     | `dsb ld`      | `lfence` | `fence ri,ri`     |
+//! | `fence_wmb`     | `dsb st`      | `sfence` | `fence wo,wo`     |
+//! | `fence_dma_rmb` | `dmb oshld`   | compiler | `fence r,r`       |
+//! | `fence_dma_wmb` | `dmb oshst`   | compiler | `fence w,w`       |
+//!
+//! Other targets use a sequentially consistent fence.
+
+macro_rules! device_fence {
+    ($(#[$attr:meta])* $name:ident, aarch64: $a64:literal, x86: $x86:tt, riscv: $rv:literal) => {
+        $(#[$attr])*
+        #[inline(always)]
+        pub fn $name() {
+            #[cfg(target_arch = "aarch64")]
+            // SAFETY: barrier instructions have no operands.
+            unsafe {
+                core::arch::asm!($a64, options(nostack, preserves_flags));
+            }
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            device_fence!(@x86 $x86);
+            #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+            // SAFETY: barrier instructions have no operands.
+            unsafe {
+                core::arch::asm!($rv, options(nostack, preserves_flags));
+            }
+            #[cfg(not(any(
+                target_arch = "aarch64",
+                target_arch = "x86",
+                target_arch = "x86_64",
+                target_arch = "riscv32",
+                target_arch = "riscv64"
+            )))]
+            core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+        }
+    };
+    (@x86 compiler) => {
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst)
+    };
+    (@x86 $insn:literal) => {
+        // SAFETY: barrier instructions have no operands.
+        unsafe {
+            core::arch::asm!($insn, options(nostack, preserves_flags))
+        }
+    };
+}
+
+device_fence!(
+    /// Orders all prior memory and device accesses before all later ones.
+    fence_mb, aarch64: "dsb sy", x86: "mfence", riscv: "fence iorw,iorw"
+);
+device_fence!(
+    /// Orders prior loads, including device reads, before later loads.
+    fence_rmb, aarch64: "dsb ld", x86: "lfence", riscv: "fence ri,ri"
+);
+device_fence!(
+    /// Orders prior stores, including device writes, before later stores.
+    fence_wmb, aarch64: "dsb st", x86: "sfence", riscv: "fence wo,wo"
+);
+device_fence!(
+    /// Orders loads from coherent DMA memory shared with a device.
+    fence_dma_rmb, aarch64: "dmb oshld", x86: compiler, riscv: "fence r,r"
+);
+device_fence!(
+    /// Orders stores to coherent DMA memory shared with a device.
+    fence_dma_wmb, aarch64: "dmb oshst", x86: compiler, riscv: "fence w,w"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn barriers_execute() {
+        fence_mb();
+        fence_rmb();
+        fence_wmb();
+        fence_dma_rmb();
+        fence_dma_wmb();
+    }
+}
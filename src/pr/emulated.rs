@@ -0,0 +1,187 @@
+//! Lock-based 64-bit atomics for targets without native support.
+//!
+//! Each value is protected by its own test-and-test-and-set spinlock. This is
+//! not lock-free: a holder that is preempted (or interrupted by a handler that
+//! touches the same value) stalls every other accessor.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{self, AtomicBool, Ordering};
+
+/// Spinlock-protected replacement for `core::sync::atomic::AtomicU64`.
+///
+/// Exposes the subset of the `AtomicU64` API used by this crate with the same
+/// signatures, so it can stand in for the native type.
+#[repr(C, align(8))]
+pub struct AtomicU64 {
+    value: UnsafeCell<u64>,
+    locked: AtomicBool,
+}
+
+unsafe impl Sync for AtomicU64 {}
+
+impl AtomicU64 {
+    /// Creates a new atomic integer.
+    pub const fn new(value: u64) -> Self {
+        AtomicU64 {
+            value: UnsafeCell::new(value),
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    /// Consumes the atomic and returns the contained value.
+    pub fn into_inner(self) -> u64 {
+        self.value.into_inner()
+    }
+
+    /// Returns a mutable reference to the underlying integer.
+    pub fn get_mut(&mut self) -> &mut u64 {
+        self.value.get_mut()
+    }
+
+    #[inline]
+    fn with<R>(&self, order: Ordering, f: impl FnOnce(&mut u64) -> R) -> R {
+        if order == Ordering::SeqCst {
+            atomic::fence(Ordering::SeqCst);
+        }
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                core::hint::spin_loop();
+            }
+        }
+        // SAFETY: the lock grants exclusive access to the value.
+        let r = f(unsafe { &mut *self.value.get() });
+        self.locked.store(false, Ordering::Release);
+        if order == Ordering::SeqCst {
+            atomic::fence(Ordering::SeqCst);
+        }
+        r
+    }
+
+    /// Loads the value.
+    pub fn load(&self, order: Ordering) -> u64 {
+        self.with(order, |v| *v)
+    }
+
+    /// Stores a value.
+    pub fn store(&self, value: u64, order: Ordering) {
+        self.with(order, |v| *v = value)
+    }
+
+    /// Stores a value, returning the previous one.
+    pub fn swap(&self, value: u64, order: Ordering) -> u64 {
+        self.with(order, |v| core::mem::replace(v, value))
+    }
+
+    /// Stores `new` if the current value equals `current`.
+    pub fn compare_exchange(
+        &self,
+        current: u64,
+        new: u64,
+        success: Ordering,
+        _failure: Ordering,
+    ) -> Result<u64, u64> {
+        self.with(success, |v| {
+            if *v == current {
+                *v = new;
+                Ok(current)
+            } else {
+                Err(*v)
+            }
+        })
+    }
+
+    /// Same as [`compare_exchange`](Self::compare_exchange); never fails spuriously.
+    pub fn compare_exchange_weak(
+        &self,
+        current: u64,
+        new: u64,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<u64, u64> {
+        self.compare_exchange(current, new, success, failure)
+    }
+
+    /// Wrapping add, returning the previous value.
+    pub fn fetch_add(&self, delta: u64, order: Ordering) -> u64 {
+        self.with(order, |v| core::mem::replace(v, v.wrapping_add(delta)))
+    }
+
+    /// Wrapping subtract, returning the previous value.
+    pub fn fetch_sub(&self, delta: u64, order: Ordering) -> u64 {
+        self.with(order, |v| core::mem::replace(v, v.wrapping_sub(delta)))
+    }
+
+    /// Bitwise AND, returning the previous value.
+    pub fn fetch_and(&self, bits: u64, order: Ordering) -> u64 {
+        self.with(order, |v| core::mem::replace(v, *v & bits))
+    }
+
+    /// Bitwise OR, returning the previous value.
+    pub fn fetch_or(&self, bits: u64, order: Ordering) -> u64 {
+        self.with(order, |v| core::mem::replace(v, *v | bits))
+    }
+
+    /// Bitwise XOR, returning the previous value.
+    pub fn fetch_xor(&self, bits: u64, order: Ordering) -> u64 {
+        self.with(order, |v| core::mem::replace(v, *v ^ bits))
+    }
+}
+
+impl Default for AtomicU64 {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl core::fmt::Debug for AtomicU64 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basic() {
+        let a = AtomicU64::new(u64::MAX - 1);
+        assert_eq!(a.fetch_add(2, Ordering::SeqCst), u64::MAX - 1);
+        assert_eq!(a.load(Ordering::Acquire), 0);
+        assert_eq!(
+            a.compare_exchange(1, 2, Ordering::AcqRel, Ordering::Acquire),
+            Err(0)
+        );
+        assert_eq!(
+            a.compare_exchange(0, 1 << 40, Ordering::AcqRel, Ordering::Acquire),
+            Ok(0)
+        );
+        assert_eq!(a.swap(3, Ordering::Relaxed), 1 << 40);
+        assert_eq!(a.into_inner(), 3);
+    }
+
+    #[test]
+    fn concurrent_increments() {
+        let a = Arc::new(AtomicU64::new(0));
+        let handles: std::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let a = a.clone();
+                thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        a.fetch_add(1 << 32, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(a.load(Ordering::SeqCst), 40_000 << 32);
+    }
+}
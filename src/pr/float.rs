@@ -0,0 +1,315 @@
+//! Atomic floating-point values stored as their bit representation.
+//!
+//! Comparisons in `compare_exchange`/`cas` are on bit patterns, so `0.0` and
+//! `-0.0` are distinct and a NaN compares equal to an identical NaN.
+//! Arithmetic is implemented with a compare-and-swap loop.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use super::AtomicU64;
+
+macro_rules! define_float {
+    ($(#[$attr:meta])* $name:ident, $float:ty, $bits:ty, $ops:ident) => {
+        $(#[$attr])*
+        #[repr(transparent)]
+        pub struct $name {
+            bits: $bits,
+        }
+
+        impl $name {
+            /// Creates a new atomic float.
+            pub const fn new(value: $float) -> Self {
+                $name {
+                    bits: <$bits>::new(value.to_bits()),
+                }
+            }
+
+            /// Consumes the atomic and returns the contained value.
+            pub fn into_inner(self) -> $float {
+                <$float>::from_bits(self.bits.into_inner())
+            }
+
+            /// Loads the value.
+            #[inline]
+            pub fn load(&self, order: Ordering) -> $float {
+                <$float>::from_bits(self.bits.load(order))
+            }
+
+            /// Stores a value.
+            #[inline]
+            pub fn store(&self, value: $float, order: Ordering) {
+                self.bits.store(value.to_bits(), order)
+            }
+
+            /// Stores a value, returning the previous one.
+            #[inline]
+            pub fn swap(&self, value: $float, order: Ordering) -> $float {
+                <$float>::from_bits(self.bits.swap(value.to_bits(), order))
+            }
+
+            /// Stores `new` if the current value has the same bits as `current`.
+            #[inline]
+            pub fn compare_exchange(
+                &self,
+                current: $float,
+                new: $float,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$float, $float> {
+                self.bits
+                    .compare_exchange(current.to_bits(), new.to_bits(), success, failure)
+                    .map(<$float>::from_bits)
+                    .map_err(<$float>::from_bits)
+            }
+
+            /// Like [`compare_exchange`](Self::compare_exchange) but may fail spuriously.
+            #[inline]
+            pub fn compare_exchange_weak(
+                &self,
+                current: $float,
+                new: $float,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$float, $float> {
+                self.bits
+                    .compare_exchange_weak(current.to_bits(), new.to_bits(), success, failure)
+                    .map(<$float>::from_bits)
+                    .map_err(<$float>::from_bits)
+            }
+
+            /// Adds `delta`, returning the previous value.
+            #[inline]
+            pub fn fetch_add(&self, delta: $float, order: Ordering) -> $float {
+                let failure = match order {
+                    Ordering::AcqRel => Ordering::Acquire,
+                    Ordering::Release => Ordering::Relaxed,
+                    o => o,
+                };
+                let mut current = self.bits.load(Ordering::Relaxed);
+                loop {
+                    let new = (<$float>::from_bits(current) + delta).to_bits();
+                    match self.bits.compare_exchange_weak(current, new, order, failure) {
+                        Ok(v) => return <$float>::from_bits(v),
+                        Err(v) => current = v,
+                    }
+                }
+            }
+
+            /// Subtracts `delta`, returning the previous value.
+            #[inline]
+            pub fn fetch_sub(&self, delta: $float, order: Ordering) -> $float {
+                self.fetch_add(-delta, order)
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new(0.0)
+            }
+        }
+
+        impl core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+            }
+        }
+
+        #[doc = concat!("Operations on [`", stringify!($name), "`].")]
+        pub mod $ops {
+            use core::sync::atomic::Ordering;
+
+            use super::$name;
+
+            /// Atomic type operated on by this module.
+            pub type Atomic = $name;
+
+            /// Atomically loads the value of `target`.
+            #[inline(always)]
+            pub fn load(target: &$name) -> $float {
+                target.load(Ordering::SeqCst)
+            }
+
+            /// [`load`] with an explicit ordering.
+            #[inline(always)]
+            pub fn load_explicit(target: &$name, order: Ordering) -> $float {
+                target.load(order)
+            }
+
+            /// Atomically stores `value` into `target`.
+            #[inline(always)]
+            pub fn store(target: &$name, value: $float) {
+                target.store(value, Ordering::SeqCst)
+            }
+
+            /// [`store`] with an explicit ordering.
+            #[inline(always)]
+            pub fn store_explicit(target: &$name, value: $float, order: Ordering) {
+                target.store(value, order)
+            }
+
+            /// Fetch-and-add: adds `delta` to `target`, returning the previous value.
+            #[inline(always)]
+            pub fn faa(target: &$name, delta: $float) -> $float {
+                target.fetch_add(delta, Ordering::SeqCst)
+            }
+
+            /// [`faa`] with an explicit ordering.
+            #[inline(always)]
+            pub fn faa_explicit(target: &$name, delta: $float, order: Ordering) -> $float {
+                target.fetch_add(delta, order)
+            }
+
+            /// Fetch-and-store: replaces `target` with `value`, returning the previous value.
+            #[inline(always)]
+            pub fn fas(target: &$name, value: $float) -> $float {
+                target.swap(value, Ordering::SeqCst)
+            }
+
+            /// [`fas`] with an explicit ordering.
+            #[inline(always)]
+            pub fn fas_explicit(target: &$name, value: $float, order: Ordering) -> $float {
+                target.swap(value, order)
+            }
+
+            /// Compare-and-swap on the bit pattern. Returns `true` on success.
+            #[inline(always)]
+            pub fn cas(target: &$name, compare: $float, new: $float) -> bool {
+                cas_explicit(target, compare, new, Ordering::SeqCst, Ordering::SeqCst)
+            }
+
+            /// [`cas`] with explicit success and failure orderings.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `failure` is `Release` or `AcqRel`.
+            #[inline(always)]
+            pub fn cas_explicit(
+                target: &$name,
+                compare: $float,
+                new: $float,
+                success: Ordering,
+                failure: Ordering,
+            ) -> bool {
+                target.compare_exchange(compare, new, success, failure).is_ok()
+            }
+
+            /// Compare-and-swap that also returns the value observed in `target`.
+            #[inline(always)]
+            pub fn cas_value(target: &$name, compare: $float, new: $float) -> (bool, $float) {
+                cas_value_explicit(target, compare, new, Ordering::SeqCst, Ordering::SeqCst)
+            }
+
+            /// [`cas_value`] with explicit success and failure orderings.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `failure` is `Release` or `AcqRel`.
+            #[inline(always)]
+            pub fn cas_value_explicit(
+                target: &$name,
+                compare: $float,
+                new: $float,
+                success: Ordering,
+                failure: Ordering,
+            ) -> (bool, $float) {
+                match target.compare_exchange(compare, new, success, failure) {
+                    Ok(v) => (true, v),
+                    Err(v) => (false, v),
+                }
+            }
+
+            /// Compare-and-swap returning `Ok(previous)` on success and
+            /// `Err(observed)` on failure, like `compare_exchange`.
+            #[inline(always)]
+            pub fn cas_result(target: &$name, compare: $float, new: $float) -> Result<$float, $float> {
+                cas_result_explicit(target, compare, new, Ordering::SeqCst, Ordering::SeqCst)
+            }
+
+            /// [`cas_result`] with explicit success and failure orderings.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `failure` is `Release` or `AcqRel`.
+            #[inline(always)]
+            pub fn cas_result_explicit(
+                target: &$name,
+                compare: $float,
+                new: $float,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$float, $float> {
+                target.compare_exchange(compare, new, success, failure)
+            }
+
+            /// Like [`cas_result`] but may fail spuriously; intended for retry loops.
+            #[inline(always)]
+            pub fn cas_weak_result(target: &$name, compare: $float, new: $float) -> Result<$float, $float> {
+                cas_weak_result_explicit(target, compare, new, Ordering::SeqCst, Ordering::SeqCst)
+            }
+
+            /// [`cas_weak_result`] with explicit success and failure orderings.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `failure` is `Release` or `AcqRel`.
+            #[inline(always)]
+            pub fn cas_weak_result_explicit(
+                target: &$name,
+                compare: $float,
+                new: $float,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$float, $float> {
+                target.compare_exchange_weak(compare, new, success, failure)
+            }
+        }
+    };
+}
+
+define_float!(
+    /// An `f32` that can be shared between threads.
+    AtomicF32, f32, AtomicU32, f32_ops
+);
+define_float!(
+    /// An `f64` that can be shared between threads.
+    AtomicF64, f64, AtomicU64, f64_ops
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn ops() {
+        let a = AtomicF64::new(1.5);
+        assert_eq!(f64_ops::faa(&a, 2.0), 1.5);
+        assert_eq!(f64_ops::load(&a), 3.5);
+        assert!(!f64_ops::cas(&a, 1.0, 0.0));
+        assert_eq!(f64_ops::cas_value(&a, 3.5, -0.0), (true, 3.5));
+        assert!(!f64_ops::cas(&a, 0.0, 1.0));
+        assert_eq!(f64_ops::fas(&a, 7.0).to_bits(), (-0.0f64).to_bits());
+        assert_eq!(f64_ops::cas_result(&a, 1.0, 2.0), Err(7.0));
+        assert_eq!(f64_ops::cas_result(&a, 7.0, 2.0), Ok(7.0));
+    }
+
+    #[test]
+    fn concurrent_accumulation() {
+        let a = Arc::new(AtomicF32::new(0.0));
+        let handles: std::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let a = a.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        f32_ops::faa_explicit(&a, 0.5, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(f32_ops::load(&a), 2000.0);
+    }
+}
@@ -0,0 +1,124 @@
+//! Atomic primitives modelled on `ck_pr`.
+//!
+//! This module provides the fence vocabulary, the spin-wait `stall` hint and
+//! per-width operation modules (`u32_ops`, `usize_ops`, ...) that the rest of
+//! the crate is built on. The plain operations use sequentially consistent
+//! ordering; every operation also has an `_explicit` variant taking the
+//! ordering(s) as arguments.
+
+use core::sync::atomic::{self, Ordering};
+
+mod cache;
+mod cell;
+#[cfg(feature = "device-fences")]
+mod device;
+#[cfg_attr(target_has_atomic = "64", allow(dead_code))]
+mod emulated;
+mod float;
+mod nonnull;
+mod ops;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub mod rtm;
+mod spin;
+mod stall;
+mod tagged;
+pub mod u128;
+#[cfg(feature = "std")]
+mod wait;
+
+pub use self::cache::{clflush, clwb, prefetch_read, prefetch_write};
+pub use self::cell::{AtomicCell, NoPadding};
+#[cfg(feature = "device-fences")]
+pub use self::device::{fence_dma_rmb, fence_dma_wmb, fence_mb, fence_rmb, fence_wmb};
+pub use self::float::{f32_ops, f64_ops, AtomicF32, AtomicF64};
+pub use self::nonnull::AtomicOptionNonNull;
+pub use self::ops::{u128_ops, u16_ops, u32_ops, u64_ops, u8_ops, usize_ops};
+pub use self::spin::{load_when, load_when_with, spin_while, spin_while_with, AtomicLoad};
+pub use self::stall::{StallInstruction, StallPolicy};
+pub use self::tagged::{AtomicTaggedPtr, TaggedPtr};
+pub use self::u128::AtomicU128;
+#[cfg(feature = "std")]
+pub use self::wait::{wait, wait_timeout, wake_all, wake_one};
+
+/// 64-bit atomic integer: the native type where the target supports it,
+/// otherwise a spinlock-protected emulation with the same API.
+#[cfg(not(target_has_atomic = "64"))]
+pub use self::emulated::AtomicU64;
+/// 64-bit atomic integer: the native type where the target supports it,
+/// otherwise a spinlock-protected emulation with the same API.
+#[cfg(target_has_atomic = "64")]
+pub use core::sync::atomic::AtomicU64;
+
+/// Full memory fence.
+#[inline(always)]
+pub fn fence_memory() {
+    atomic::fence(Ordering::SeqCst);
+}
+
+/// Orders prior loads and stores before subsequent loads and stores.
+#[inline(always)]
+pub fn fence_acquire() {
+    atomic::fence(Ordering::Acquire);
+}
+
+/// Orders prior loads and stores before subsequent stores.
+#[inline(always)]
+pub fn fence_release() {
+    atomic::fence(Ordering::Release);
+}
+
+/// Orders prior and subsequent accesses in both directions.
+#[inline(always)]
+pub fn fence_acqrel() {
+    atomic::fence(Ordering::AcqRel);
+}
+
+/// Orders prior loads before subsequent loads.
+#[inline(always)]
+pub fn fence_load() {
+    atomic::fence(Ordering::Acquire);
+}
+
+/// Orders prior stores before subsequent stores.
+#[inline(always)]
+pub fn fence_store() {
+    atomic::fence(Ordering::Release);
+}
+
+/// Prevents the compiler from reordering memory accesses across this point.
+#[inline(always)]
+pub fn barrier() {
+    atomic::compiler_fence(Ordering::SeqCst);
+}
+
+/// Hint to the processor that the caller is in a spin-wait loop.
+#[inline(always)]
+pub fn stall() {
+    core::hint::spin_loop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicU32;
+
+    #[test]
+    fn fences_and_stall() {
+        fence_memory();
+        fence_acquire();
+        fence_release();
+        fence_acqrel();
+        fence_load();
+        fence_store();
+        barrier();
+        stall();
+    }
+
+    #[test]
+    fn seq_cst_and_explicit_agree() {
+        let a = AtomicU32::new(1);
+        assert_eq!(u32_ops::faa(&a, 2), 1);
+        assert_eq!(u32_ops::faa_explicit(&a, 2, Ordering::Relaxed), 3);
+        assert_eq!(u32_ops::load_explicit(&a, Ordering::Acquire), 5);
+    }
+}
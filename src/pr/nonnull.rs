@@ -0,0 +1,142 @@
+//! Atomic nullable pointer expressed as `Option<NonNull<T>>`.
+
+use core::fmt;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[inline]
+fn to_raw<T>(value: Option<NonNull<T>>) -> *mut T {
+    value.map_or(ptr::null_mut(), NonNull::as_ptr)
+}
+
+/// An `AtomicPtr<T>` whose values are `Option<NonNull<T>>`.
+///
+/// Null is represented as `None`, so callers match on the result instead of
+/// checking `is_null()` on raw pointers.
+#[repr(transparent)]
+pub struct AtomicOptionNonNull<T> {
+    ptr: AtomicPtr<T>,
+}
+
+impl<T> AtomicOptionNonNull<T> {
+    /// Creates a new atomic pointer.
+    pub const fn new(value: Option<NonNull<T>>) -> Self {
+        let raw = match value {
+            Some(p) => p.as_ptr(),
+            None => ptr::null_mut(),
+        };
+        AtomicOptionNonNull {
+            ptr: AtomicPtr::new(raw),
+        }
+    }
+
+    /// Creates an atomic pointer holding `None`.
+    pub const fn none() -> Self {
+        Self::new(None)
+    }
+
+    /// Consumes the atomic and returns the contained value.
+    pub fn into_inner(self) -> Option<NonNull<T>> {
+        NonNull::new(self.ptr.into_inner())
+    }
+
+    /// Returns a mutable reference to the underlying raw pointer.
+    pub fn get_mut(&mut self) -> &mut *mut T {
+        self.ptr.get_mut()
+    }
+
+    /// Loads the value.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> Option<NonNull<T>> {
+        NonNull::new(self.ptr.load(order))
+    }
+
+    /// Stores a value.
+    #[inline]
+    pub fn store(&self, value: Option<NonNull<T>>, order: Ordering) {
+        self.ptr.store(to_raw(value), order)
+    }
+
+    /// Stores a value, returning the previous one.
+    #[inline]
+    pub fn swap(&self, value: Option<NonNull<T>>, order: Ordering) -> Option<NonNull<T>> {
+        NonNull::new(self.ptr.swap(to_raw(value), order))
+    }
+
+    /// Replaces the value with `None`, returning the previous one.
+    #[inline]
+    pub fn take(&self, order: Ordering) -> Option<NonNull<T>> {
+        self.swap(None, order)
+    }
+
+    /// Stores `new` if the current value equals `current`.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: Option<NonNull<T>>,
+        new: Option<NonNull<T>>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Option<NonNull<T>>, Option<NonNull<T>>> {
+        self.ptr
+            .compare_exchange(to_raw(current), to_raw(new), success, failure)
+            .map(NonNull::new)
+            .map_err(NonNull::new)
+    }
+
+    /// Like [`compare_exchange`](Self::compare_exchange) but may fail spuriously.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: Option<NonNull<T>>,
+        new: Option<NonNull<T>>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Option<NonNull<T>>, Option<NonNull<T>>> {
+        self.ptr
+            .compare_exchange_weak(to_raw(current), to_raw(new), success, failure)
+            .map(NonNull::new)
+            .map_err(NonNull::new)
+    }
+}
+
+impl<T> Default for AtomicOptionNonNull<T> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl<T> From<Option<NonNull<T>>> for AtomicOptionNonNull<T> {
+    fn from(value: Option<NonNull<T>>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> fmt::Debug for AtomicOptionNonNull<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_store_cas() {
+        let mut x = 1u32;
+        let p = NonNull::from(&mut x);
+        let a = AtomicOptionNonNull::none();
+        assert_eq!(a.load(Ordering::Acquire), None);
+        assert_eq!(
+            a.compare_exchange(None, Some(p), Ordering::AcqRel, Ordering::Acquire),
+            Ok(None)
+        );
+        assert_eq!(
+            a.compare_exchange(None, None, Ordering::AcqRel, Ordering::Acquire),
+            Err(Some(p))
+        );
+        assert_eq!(a.take(Ordering::AcqRel), Some(p));
+        assert_eq!(a.into_inner(), None);
+    }
+}
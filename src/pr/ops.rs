@@ -0,0 +1,421 @@
+//! Per-width atomic operation modules.
+//!
+//! Each module is generated by `define_ops!` and mirrors the `ck_pr_*_<type>`
+//! family: `load`, `store`, `faa`, `fas`, `cas`, `cas_value`, `cas_result`,
+//! `cas_weak_result`, `add`, `sub`, `inc`, `dec`, `inc_is_zero`,
+//! `dec_is_zero`, `and`, `or` and `xor`, plus the saturating
+//! `faa_saturating` and `fas_sub_saturating`.
+
+macro_rules! define_ops {
+    ($(#[$attr:meta])* $name:ident, $atomic:ty, $int:ty) => {
+        $(#[$attr])*
+        pub mod $name {
+            use core::sync::atomic::Ordering;
+
+            /// Atomic type operated on by this module.
+            pub type Atomic = $atomic;
+
+            /// Atomically loads the value of `target`.
+            #[inline(always)]
+            pub fn load(target: &$atomic) -> $int {
+                load_explicit(target, Ordering::SeqCst)
+            }
+
+            /// [`load`] with an explicit ordering.
+            #[inline(always)]
+            pub fn load_explicit(target: &$atomic, order: Ordering) -> $int {
+                target.load(order)
+            }
+
+            /// Atomically stores `value` into `target`.
+            #[inline(always)]
+            pub fn store(target: &$atomic, value: $int) {
+                store_explicit(target, value, Ordering::SeqCst)
+            }
+
+            /// [`store`] with an explicit ordering.
+            #[inline(always)]
+            pub fn store_explicit(target: &$atomic, value: $int, order: Ordering) {
+                target.store(value, order)
+            }
+
+            /// Fetch-and-add: adds `delta` to `target`, returning the previous value.
+            #[inline(always)]
+            pub fn faa(target: &$atomic, delta: $int) -> $int {
+                faa_explicit(target, delta, Ordering::SeqCst)
+            }
+
+            /// [`faa`] with an explicit ordering.
+            #[inline(always)]
+            pub fn faa_explicit(target: &$atomic, delta: $int, order: Ordering) -> $int {
+                target.fetch_add(delta, order)
+            }
+
+            /// Fetch-and-store: replaces `target` with `value`, returning the previous value.
+            #[inline(always)]
+            pub fn fas(target: &$atomic, value: $int) -> $int {
+                fas_explicit(target, value, Ordering::SeqCst)
+            }
+
+            /// [`fas`] with an explicit ordering.
+            #[inline(always)]
+            pub fn fas_explicit(target: &$atomic, value: $int, order: Ordering) -> $int {
+                target.swap(value, order)
+            }
+
+            /// Compare-and-swap: stores `new` if `target` equals `compare`.
+            ///
+            /// Returns `true` on success.
+            #[inline(always)]
+            pub fn cas(target: &$atomic, compare: $int, new: $int) -> bool {
+                cas_explicit(target, compare, new, Ordering::SeqCst, Ordering::SeqCst)
+            }
+
+            /// [`cas`] with explicit success and failure orderings.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `failure` is `Release` or `AcqRel`.
+            #[inline(always)]
+            pub fn cas_explicit(
+                target: &$atomic,
+                compare: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> bool {
+                target.compare_exchange(compare, new, success, failure).is_ok()
+            }
+
+            /// Compare-and-swap that also returns the value observed in `target`.
+            #[inline(always)]
+            pub fn cas_value(target: &$atomic, compare: $int, new: $int) -> (bool, $int) {
+                cas_value_explicit(target, compare, new, Ordering::SeqCst, Ordering::SeqCst)
+            }
+
+            /// [`cas_value`] with explicit success and failure orderings.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `failure` is `Release` or `AcqRel`.
+            #[inline(always)]
+            pub fn cas_value_explicit(
+                target: &$atomic,
+                compare: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> (bool, $int) {
+                match target.compare_exchange(compare, new, success, failure) {
+                    Ok(v) => (true, v),
+                    Err(v) => (false, v),
+                }
+            }
+
+            /// Compare-and-swap returning `Ok(previous)` on success and
+            /// `Err(observed)` on failure, like `compare_exchange`.
+            #[inline(always)]
+            pub fn cas_result(target: &$atomic, compare: $int, new: $int) -> Result<$int, $int> {
+                cas_result_explicit(target, compare, new, Ordering::SeqCst, Ordering::SeqCst)
+            }
+
+            /// [`cas_result`] with explicit success and failure orderings.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `failure` is `Release` or `AcqRel`.
+            #[inline(always)]
+            pub fn cas_result_explicit(
+                target: &$atomic,
+                compare: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                target.compare_exchange(compare, new, success, failure)
+            }
+
+            /// Like [`cas_result`] but may fail spuriously; intended for retry loops.
+            #[inline(always)]
+            pub fn cas_weak_result(target: &$atomic, compare: $int, new: $int) -> Result<$int, $int> {
+                cas_weak_result_explicit(target, compare, new, Ordering::SeqCst, Ordering::SeqCst)
+            }
+
+            /// [`cas_weak_result`] with explicit success and failure orderings.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `failure` is `Release` or `AcqRel`.
+            #[inline(always)]
+            pub fn cas_weak_result_explicit(
+                target: &$atomic,
+                compare: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                target.compare_exchange_weak(compare, new, success, failure)
+            }
+
+            /// Saturating fetch-and-add: adds `delta`, clamping at the maximum
+            /// value instead of wrapping. Returns the previous value.
+            #[inline(always)]
+            pub fn faa_saturating(target: &$atomic, delta: $int) -> $int {
+                faa_saturating_explicit(target, delta, Ordering::SeqCst)
+            }
+
+            /// [`faa_saturating`] with an explicit ordering.
+            #[inline(always)]
+            pub fn faa_saturating_explicit(target: &$atomic, delta: $int, order: Ordering) -> $int {
+                update(target, order, |v| v.saturating_add(delta))
+            }
+
+            /// Saturating fetch-and-subtract: subtracts `delta`, clamping at
+            /// zero instead of wrapping. Returns the previous value.
+            #[inline(always)]
+            pub fn fas_sub_saturating(target: &$atomic, delta: $int) -> $int {
+                fas_sub_saturating_explicit(target, delta, Ordering::SeqCst)
+            }
+
+            /// [`fas_sub_saturating`] with an explicit ordering.
+            #[inline(always)]
+            pub fn fas_sub_saturating_explicit(
+                target: &$atomic,
+                delta: $int,
+                order: Ordering,
+            ) -> $int {
+                update(target, order, |v| v.saturating_sub(delta))
+            }
+
+            #[inline(always)]
+            fn update(target: &$atomic, order: Ordering, f: impl Fn($int) -> $int) -> $int {
+                let failure = match order {
+                    Ordering::Release => Ordering::Relaxed,
+                    Ordering::AcqRel => Ordering::Acquire,
+                    o => o,
+                };
+                let mut current = target.load(failure);
+                loop {
+                    // A saturated value is still written back, so the
+                    // operation keeps `order` instead of becoming a load.
+                    let new = f(current);
+                    match target.compare_exchange_weak(current, new, order, failure) {
+                        Ok(v) => return v,
+                        Err(v) => current = v,
+                    }
+                }
+            }
+
+            /// Atomically adds `delta` to `target`.
+            #[inline(always)]
+            pub fn add(target: &$atomic, delta: $int) {
+                add_explicit(target, delta, Ordering::SeqCst)
+            }
+
+            /// [`add`] with an explicit ordering.
+            #[inline(always)]
+            pub fn add_explicit(target: &$atomic, delta: $int, order: Ordering) {
+                target.fetch_add(delta, order);
+            }
+
+            /// Atomically subtracts `delta` from `target`.
+            #[inline(always)]
+            pub fn sub(target: &$atomic, delta: $int) {
+                sub_explicit(target, delta, Ordering::SeqCst)
+            }
+
+            /// [`sub`] with an explicit ordering.
+            #[inline(always)]
+            pub fn sub_explicit(target: &$atomic, delta: $int, order: Ordering) {
+                target.fetch_sub(delta, order);
+            }
+
+            /// Atomically increments `target`.
+            #[inline(always)]
+            pub fn inc(target: &$atomic) {
+                inc_explicit(target, Ordering::SeqCst)
+            }
+
+            /// [`inc`] with an explicit ordering.
+            #[inline(always)]
+            pub fn inc_explicit(target: &$atomic, order: Ordering) {
+                target.fetch_add(1, order);
+            }
+
+            /// Atomically decrements `target`.
+            #[inline(always)]
+            pub fn dec(target: &$atomic) {
+                dec_explicit(target, Ordering::SeqCst)
+            }
+
+            /// [`dec`] with an explicit ordering.
+            #[inline(always)]
+            pub fn dec_explicit(target: &$atomic, order: Ordering) {
+                target.fetch_sub(1, order);
+            }
+
+            /// Atomically increments `target`, returning `true` if the result is zero.
+            #[inline(always)]
+            pub fn inc_is_zero(target: &$atomic) -> bool {
+                inc_is_zero_explicit(target, Ordering::SeqCst)
+            }
+
+            /// [`inc_is_zero`] with an explicit ordering.
+            #[inline(always)]
+            pub fn inc_is_zero_explicit(target: &$atomic, order: Ordering) -> bool {
+                target.fetch_add(1, order).wrapping_add(1) == 0
+            }
+
+            /// Atomically decrements `target`, returning `true` if the result is zero.
+            #[inline(always)]
+            pub fn dec_is_zero(target: &$atomic) -> bool {
+                dec_is_zero_explicit(target, Ordering::SeqCst)
+            }
+
+            /// [`dec_is_zero`] with an explicit ordering.
+            #[inline(always)]
+            pub fn dec_is_zero_explicit(target: &$atomic, order: Ordering) -> bool {
+                target.fetch_sub(1, order) == 1
+            }
+
+            /// Atomically ANDs `value` into `target`.
+            #[inline(always)]
+            pub fn and(target: &$atomic, value: $int) {
+                and_explicit(target, value, Ordering::SeqCst)
+            }
+
+            /// [`and`] with an explicit ordering.
+            #[inline(always)]
+            pub fn and_explicit(target: &$atomic, value: $int, order: Ordering) {
+                target.fetch_and(value, order);
+            }
+
+            /// Atomically ORs `value` into `target`.
+            #[inline(always)]
+            pub fn or(target: &$atomic, value: $int) {
+                or_explicit(target, value, Ordering::SeqCst)
+            }
+
+            /// [`or`] with an explicit ordering.
+            #[inline(always)]
+            pub fn or_explicit(target: &$atomic, value: $int, order: Ordering) {
+                target.fetch_or(value, order);
+            }
+
+            /// Atomically XORs `value` into `target`.
+            #[inline(always)]
+            pub fn xor(target: &$atomic, value: $int) {
+                xor_explicit(target, value, Ordering::SeqCst)
+            }
+
+            /// [`xor`] with an explicit ordering.
+            #[inline(always)]
+            pub fn xor_explicit(target: &$atomic, value: $int, order: Ordering) {
+                target.fetch_xor(value, order);
+            }
+        }
+    };
+}
+
+define_ops!(
+    /// Operations on `AtomicU8`.
+    u8_ops, core::sync::atomic::AtomicU8, u8
+);
+define_ops!(
+    /// Operations on `AtomicU16`.
+    u16_ops, core::sync::atomic::AtomicU16, u16
+);
+define_ops!(
+    /// Operations on `AtomicU32`.
+    u32_ops, core::sync::atomic::AtomicU32, u32
+);
+define_ops!(
+    /// Operations on [`pr::AtomicU64`](crate::pr::AtomicU64).
+    u64_ops, crate::pr::AtomicU64, u64
+);
+define_ops!(
+    /// Operations on [`pr::AtomicU128`](crate::pr::AtomicU128).
+    ///
+    /// Only usable where [`pr::u128::SUPPORTED`](crate::pr::u128::SUPPORTED)
+    /// is `true`; elsewhere the atomic type cannot be constructed.
+    u128_ops, crate::pr::AtomicU128, u128
+);
+define_ops!(
+    /// Operations on `AtomicUsize`.
+    usize_ops, core::sync::atomic::AtomicUsize, usize
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+    #[test]
+    fn arithmetic() {
+        let a = AtomicU32::new(0);
+        u32_ops::inc(&a);
+        u32_ops::add(&a, 4);
+        u32_ops::sub(&a, 2);
+        assert_eq!(u32_ops::load(&a), 3);
+        assert_eq!(u32_ops::fas(&a, 1), 3);
+        assert!(u32_ops::dec_is_zero(&a));
+        u32_ops::store(&a, u32::MAX);
+        assert!(u32_ops::inc_is_zero(&a));
+    }
+
+    #[test]
+    fn compare_and_swap() {
+        let a = AtomicUsize::new(7);
+        assert!(!usize_ops::cas(&a, 1, 2));
+        assert_eq!(usize_ops::cas_value(&a, 1, 2), (false, 7));
+        assert!(usize_ops::cas_explicit(
+            &a,
+            7,
+            8,
+            Ordering::AcqRel,
+            Ordering::Acquire
+        ));
+        assert_eq!(
+            usize_ops::cas_value_explicit(&a, 8, 9, Ordering::Release, Ordering::Relaxed),
+            (true, 8)
+        );
+        assert_eq!(usize_ops::load(&a), 9);
+        assert_eq!(usize_ops::cas_result(&a, 1, 2), Err(9));
+        assert_eq!(usize_ops::cas_result(&a, 9, 10), Ok(9));
+        let mut cur = usize_ops::load(&a);
+        while let Err(v) = usize_ops::cas_weak_result_explicit(
+            &a,
+            cur,
+            cur * 2,
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        ) {
+            cur = v;
+        }
+        assert_eq!(usize_ops::load(&a), 20);
+    }
+
+    #[test]
+    fn saturating() {
+        let a = AtomicU32::new(u32::MAX - 1);
+        assert_eq!(u32_ops::faa_saturating(&a, 5), u32::MAX - 1);
+        assert_eq!(u32_ops::faa_saturating(&a, 5), u32::MAX);
+        assert_eq!(u32_ops::load(&a), u32::MAX);
+        u32_ops::store(&a, 3);
+        assert_eq!(u32_ops::fas_sub_saturating(&a, 2), 3);
+        assert_eq!(
+            u32_ops::fas_sub_saturating_explicit(&a, 2, Ordering::AcqRel),
+            1
+        );
+        assert_eq!(u32_ops::load(&a), 0);
+    }
+
+    #[test]
+    fn bitwise() {
+        let a = AtomicU32::new(0b1100);
+        u32_ops::and_explicit(&a, 0b0100, Ordering::Release);
+        u32_ops::or(&a, 0b0001);
+        u32_ops::xor_explicit(&a, 0b0011, Ordering::Relaxed);
+        assert_eq!(u32_ops::load_explicit(&a, Ordering::Acquire), 0b0110);
+    }
+}
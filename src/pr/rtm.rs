@@ -0,0 +1,205 @@
+//! Intel RTM (restricted transactional memory) intrinsics.
+//!
+//! Thin wrappers over `xbegin`, `xend`, `xabort` and `xtest`. Most processors
+//! ship with TSX disabled, so check [`supported`] once before taking a
+//! transactional path; executing these instructions without RTM raises `#UD`.
+
+use core::arch::asm;
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+static SUPPORT: AtomicU8 = AtomicU8::new(0);
+
+/// Returns `true` if the processor implements RTM.
+pub fn supported() -> bool {
+    match SUPPORT.load(Ordering::Relaxed) {
+        0 => {
+            #[cfg(target_arch = "x86")]
+            use core::arch::x86::{__cpuid, __cpuid_count};
+            #[cfg(target_arch = "x86_64")]
+            use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+            let rtm = __cpuid(0).eax >= 7 && __cpuid_count(7, 0).ebx & (1 << 11) != 0;
+            SUPPORT.store(if rtm { 2 } else { 1 }, Ordering::Relaxed);
+            rtm
+        }
+        s => s == 2,
+    }
+}
+
+/// Reason a transaction aborted, decoded from the `xbegin` status word.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Abort(u32);
+
+impl Abort {
+    const EXPLICIT: u32 = 1 << 0;
+    const RETRY: u32 = 1 << 1;
+    const CONFLICT: u32 = 1 << 2;
+    const CAPACITY: u32 = 1 << 3;
+    const DEBUG: u32 = 1 << 4;
+    const NESTED: u32 = 1 << 5;
+
+    /// Wraps a raw status word.
+    pub const fn from_bits(bits: u32) -> Self {
+        Abort(bits)
+    }
+
+    /// Returns the raw status word.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// The abort was requested by `xabort`.
+    pub const fn is_explicit(self) -> bool {
+        self.0 & Self::EXPLICIT != 0
+    }
+
+    /// The code passed to `xabort`, if the abort was explicit.
+    pub const fn code(self) -> Option<u8> {
+        if self.is_explicit() {
+            Some((self.0 >> 24) as u8)
+        } else {
+            None
+        }
+    }
+
+    /// The hardware indicates the transaction may succeed on retry.
+    pub const fn may_retry(self) -> bool {
+        self.0 & Self::RETRY != 0
+    }
+
+    /// Another processor conflicted with the transaction's read or write set.
+    pub const fn is_conflict(self) -> bool {
+        self.0 & Self::CONFLICT != 0
+    }
+
+    /// The transaction overflowed an internal buffer.
+    pub const fn is_capacity(self) -> bool {
+        self.0 & Self::CAPACITY != 0
+    }
+
+    /// A debug breakpoint was hit.
+    pub const fn is_debug(self) -> bool {
+        self.0 & Self::DEBUG != 0
+    }
+
+    /// The abort happened in a nested transaction.
+    pub const fn is_nested(self) -> bool {
+        self.0 & Self::NESTED != 0
+    }
+}
+
+impl fmt::Debug for Abort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Abort")
+            .field("code", &self.code())
+            .field("retry", &self.may_retry())
+            .field("conflict", &self.is_conflict())
+            .field("capacity", &self.is_capacity())
+            .field("debug", &self.is_debug())
+            .field("nested", &self.is_nested())
+            .finish()
+    }
+}
+
+const STARTED: u32 = u32::MAX;
+
+/// Starts a transaction.
+///
+/// Returns `Ok(())` inside the transaction. If it later aborts, execution
+/// resumes here with all transactional effects discarded and `Err` describing
+/// the cause.
+///
+/// # Safety
+///
+/// The processor must support RTM (see [`supported`]).
+#[inline(always)]
+pub unsafe fn xbegin() -> Result<(), Abort> {
+    let status: u32;
+    asm!(
+        "xbegin 2f",
+        "2:",
+        inlateout("eax") STARTED => status,
+        options(nostack),
+    );
+    if status == STARTED {
+        Ok(())
+    } else {
+        Err(Abort(status))
+    }
+}
+
+/// Commits the current transaction.
+///
+/// # Safety
+///
+/// The processor must support RTM and a transaction must be active; `xend`
+/// outside a transaction raises `#GP`.
+#[inline(always)]
+pub unsafe fn xend() {
+    asm!("xend", options(nostack));
+}
+
+/// Aborts the current transaction with `CODE`; a no-op outside a transaction.
+///
+/// # Safety
+///
+/// The processor must support RTM.
+#[inline(always)]
+pub unsafe fn xabort<const CODE: u8>() {
+    asm!("xabort {}", const CODE, options(nostack));
+}
+
+/// Returns `true` if executing inside a transaction.
+///
+/// # Safety
+///
+/// The processor must support RTM.
+#[inline(always)]
+pub unsafe fn xtest() -> bool {
+    let r: u8;
+    asm!("xtest", "setnz {}", out(reg_byte) r, options(nostack, nomem));
+    r != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abort_decoding() {
+        let a = Abort::from_bits(0x2a00_0003);
+        assert!(a.is_explicit());
+        assert!(a.may_retry());
+        assert_eq!(a.code(), Some(0x2a));
+        let b = Abort::from_bits(1 << 2);
+        assert_eq!(b.code(), None);
+        assert!(b.is_conflict() && !b.is_capacity());
+    }
+
+    #[test]
+    fn transaction_if_supported() {
+        if !supported() {
+            return;
+        }
+        unsafe {
+            assert!(!xtest());
+            // Transactions can abort for many reasons; only check coherence.
+            match xbegin() {
+                Ok(()) => {
+                    let inside = xtest();
+                    xend();
+                    assert!(inside);
+                }
+                Err(_) => assert!(!xtest()),
+            }
+            match xbegin() {
+                Ok(()) => {
+                    xabort::<7>();
+                    xend();
+                }
+                Err(a) => assert!(a.code().is_none_or(|c| c == 7)),
+            }
+        }
+    }
+}
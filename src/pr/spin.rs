@@ -0,0 +1,136 @@
+//! Wait-for-value loops.
+//!
+//! Every lock in the crate ends up spinning on an acquire load until a word
+//! reaches some value. [`spin_while`] and [`load_when`] capture that loop;
+//! the `_with` variants stall according to a [`StallPolicy`] instead of a
+//! single [`stall`](super::stall).
+
+use core::sync::atomic::{
+    AtomicBool, AtomicI16, AtomicI32, AtomicI8, AtomicIsize, AtomicPtr, AtomicU16, AtomicU32,
+    AtomicU8, AtomicUsize, Ordering,
+};
+
+use super::StallPolicy;
+
+/// Atomic types that can be polled by [`spin_while`] and [`load_when`].
+pub trait AtomicLoad {
+    /// The value type loaded from the atomic.
+    type Value: Copy + PartialEq;
+
+    /// Loads the current value with the given ordering.
+    fn atomic_load(&self, order: Ordering) -> Self::Value;
+}
+
+macro_rules! impl_atomic_load {
+    ($($atomic:ty => $value:ty),* $(,)?) => {
+        $(
+            impl AtomicLoad for $atomic {
+                type Value = $value;
+
+                #[inline(always)]
+                fn atomic_load(&self, order: Ordering) -> $value {
+                    self.load(order)
+                }
+            }
+        )*
+    };
+}
+
+impl_atomic_load!(
+    AtomicBool => bool,
+    AtomicU8 => u8,
+    AtomicU16 => u16,
+    AtomicU32 => u32,
+    super::AtomicU64 => u64,
+    AtomicUsize => usize,
+    AtomicI8 => i8,
+    AtomicI16 => i16,
+    AtomicI32 => i32,
+    AtomicIsize => isize,
+    super::AtomicU128 => u128,
+);
+
+impl<T> AtomicLoad for AtomicPtr<T> {
+    type Value = *mut T;
+
+    #[inline(always)]
+    fn atomic_load(&self, order: Ordering) -> *mut T {
+        self.load(order)
+    }
+}
+
+/// Spins while `cond` holds for the value of `target`.
+///
+/// Returns the first value (loaded with `Acquire`) for which `cond` is false.
+#[inline]
+pub fn spin_while<A, F>(target: &A, cond: F) -> A::Value
+where
+    A: AtomicLoad + ?Sized,
+    F: FnMut(A::Value) -> bool,
+{
+    spin_while_with(target, &StallPolicy::DEFAULT, cond)
+}
+
+/// [`spin_while`] stalling according to `policy` between polls.
+#[inline]
+pub fn spin_while_with<A, F>(target: &A, policy: &StallPolicy, mut cond: F) -> A::Value
+where
+    A: AtomicLoad + ?Sized,
+    F: FnMut(A::Value) -> bool,
+{
+    loop {
+        let v = target.atomic_load(Ordering::Acquire);
+        if !cond(v) {
+            return v;
+        }
+        policy.stall_on(target, v);
+    }
+}
+
+/// Spins until `target` holds `expected`, with `Acquire` ordering.
+#[inline]
+pub fn load_when<A>(target: &A, expected: A::Value)
+where
+    A: AtomicLoad + ?Sized,
+{
+    spin_while(target, |v| v != expected);
+}
+
+/// [`load_when`] stalling according to `policy` between polls.
+#[inline]
+pub fn load_when_with<A>(target: &A, expected: A::Value, policy: &StallPolicy)
+where
+    A: AtomicLoad + ?Sized,
+{
+    spin_while_with(target, policy, |v| v != expected);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn returns_first_failing_value() {
+        let a = AtomicU32::new(5);
+        assert_eq!(spin_while(&a, |v| v < 3), 5);
+        load_when(&a, 5);
+    }
+
+    #[test]
+    fn waits_for_other_thread() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let data = Arc::new(AtomicUsize::new(0));
+        let t = {
+            let (flag, data) = (flag.clone(), data.clone());
+            thread::spawn(move || {
+                data.store(42, Ordering::Relaxed);
+                flag.store(true, Ordering::Release);
+            })
+        };
+        load_when_with(&*flag, true, &StallPolicy::pause(4));
+        assert_eq!(data.load(Ordering::Relaxed), 42);
+        t.join().unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+//! Configurable spin-wait stalls.
+//!
+//! [`pr::stall`](super::stall) is a single spin-loop hint. A [`StallPolicy`]
+//! selects the instruction used while waiting and how many times it repeats,
+//! so a call site can trade wake-up latency against power and SMT sibling
+//! throughput. Instructions the processor or target lacks degrade to the
+//! spin-loop hint.
+
+use core::sync::atomic::Ordering;
+
+use super::AtomicLoad;
+
+/// Instruction executed by one step of a [`StallPolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StallInstruction {
+    /// `core::hint::spin_loop` (`pause` on x86, `yield`/`isb` on aarch64).
+    Hint,
+    /// x86 `tpause` for up to the given number of TSC cycles (WAITPKG).
+    Tpause(u32),
+    /// x86 `umonitor`/`umwait` for up to the given number of TSC cycles
+    /// (WAITPKG). Only [`StallPolicy::stall_on`] arms the monitor; a plain
+    /// [`StallPolicy::stall`] behaves like `Tpause`.
+    Umwait(u32),
+    /// aarch64 `wfe`: sleep until an event.
+    ///
+    /// [`StallPolicy::stall_on`] first loads the watched word exclusively,
+    /// so a write to its cache line clears the monitor and sends the event
+    /// that ends the wait, then re-checks the value and skips the `wfe` if
+    /// it has already changed. A plain [`StallPolicy::stall`] has no word
+    /// to watch and pairs the `wfe` with `sevl`, so it returns at once.
+    Wfe,
+    /// aarch64 `isb`: a longer, fixed-latency delay than `yield`.
+    Isb,
+}
+
+/// How to stall in a spin-wait loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StallPolicy {
+    instruction: StallInstruction,
+    repeat: u32,
+}
+
+impl StallPolicy {
+    /// One spin-loop hint; equivalent to [`pr::stall`](super::stall).
+    pub const DEFAULT: StallPolicy = StallPolicy::new(StallInstruction::Hint, 1);
+
+    /// Creates a policy executing `instruction` `repeat` times per stall.
+    pub const fn new(instruction: StallInstruction, repeat: u32) -> Self {
+        StallPolicy {
+            instruction,
+            repeat,
+        }
+    }
+
+    /// `repeat` spin-loop hints per stall.
+    pub const fn pause(repeat: u32) -> Self {
+        Self::new(StallInstruction::Hint, repeat)
+    }
+
+    /// The instruction used by this policy.
+    pub const fn instruction(&self) -> StallInstruction {
+        self.instruction
+    }
+
+    /// How many times the instruction executes per stall.
+    pub const fn repeat(&self) -> u32 {
+        self.repeat
+    }
+
+    /// Stalls once according to the policy.
+    #[inline]
+    pub fn stall(&self) {
+        for _ in 0..self.repeat {
+            arch::step(self.instruction);
+        }
+    }
+
+    /// Stalls once while `target` still holds `current`, waking early if
+    /// its cache line is written.
+    ///
+    /// Only [`StallInstruction::Umwait`] and [`StallInstruction::Wfe`] watch
+    /// the word; they arm the monitor, then return at once if `target` no
+    /// longer holds `current`, so a write that lands before the monitor is
+    /// armed is not slept through. Other instructions behave as in
+    /// [`stall`](Self::stall).
+    #[inline]
+    pub fn stall_on<A>(&self, target: &A, current: A::Value)
+    where
+        A: AtomicLoad + ?Sized,
+    {
+        let addr = target as *const A as *const u8;
+        let changed = || target.atomic_load(Ordering::Relaxed) != current;
+        for _ in 0..self.repeat {
+            arch::step_on(self.instruction, addr, changed);
+        }
+    }
+}
+
+impl Default for StallPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod arch {
+    use super::StallInstruction;
+    use core::arch::asm;
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    static WAITPKG: AtomicU8 = AtomicU8::new(0);
+
+    fn waitpkg() -> bool {
+        match WAITPKG.load(Ordering::Relaxed) {
+            0 => {
+                #[cfg(target_arch = "x86")]
+                use core::arch::x86::{__cpuid, __cpuid_count};
+                #[cfg(target_arch = "x86_64")]
+                use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+                let ok = __cpuid(0).eax >= 7 && __cpuid_count(7, 0).ecx & (1 << 5) != 0;
+                WAITPKG.store(if ok { 2 } else { 1 }, Ordering::Relaxed);
+                ok
+            }
+            s => s == 2,
+        }
+    }
+
+    #[inline(always)]
+    fn deadline(cycles: u32) -> (u32, u32) {
+        #[cfg(target_arch = "x86")]
+        use core::arch::x86::_rdtsc;
+        #[cfg(target_arch = "x86_64")]
+        use core::arch::x86_64::_rdtsc;
+
+        // SAFETY: rdtsc is available on every x86 processor Rust targets.
+        let t = unsafe { _rdtsc() }.wrapping_add(cycles as u64);
+        (t as u32, (t >> 32) as u32)
+    }
+
+    #[inline(always)]
+    fn tpause(cycles: u32) {
+        let (lo, hi) = deadline(cycles);
+        // SAFETY: callers checked for WAITPKG; control 0 selects the C0.2
+        // state.
+        unsafe {
+            asm!("tpause {0:e}", in(reg) 0u32, in("eax") lo, in("edx") hi, options(nostack));
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn step(instruction: StallInstruction) {
+        match instruction {
+            StallInstruction::Tpause(cycles) | StallInstruction::Umwait(cycles) if waitpkg() => {
+                tpause(cycles)
+            }
+            _ => core::hint::spin_loop(),
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn step_on(
+        instruction: StallInstruction,
+        addr: *const u8,
+        changed: impl Fn() -> bool,
+    ) {
+        match instruction {
+            StallInstruction::Umwait(cycles) if waitpkg() => {
+                // SAFETY: WAITPKG is present; umonitor only arms address
+                // monitoring and never faults on the address.
+                unsafe { asm!("umonitor {0}", in(reg) addr, options(nostack, preserves_flags)) };
+                if changed() {
+                    return;
+                }
+                let (lo, hi) = deadline(cycles);
+                // SAFETY: WAITPKG is present; control 0 selects the C0.2
+                // state.
+                unsafe {
+                    asm!("umwait {0:e}", in(reg) 0u32, in("eax") lo, in("edx") hi, options(nostack));
+                }
+            }
+            _ => step(instruction),
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    use super::StallInstruction;
+    use core::arch::asm;
+
+    #[inline(always)]
+    pub(super) fn step(instruction: StallInstruction) {
+        // SAFETY: sevl, wfe and isb have no memory effects.
+        unsafe {
+            match instruction {
+                StallInstruction::Wfe => {
+                    asm!("sevl", "wfe", options(nomem, nostack, preserves_flags))
+                }
+                StallInstruction::Isb => asm!("isb", options(nomem, nostack, preserves_flags)),
+                _ => core::hint::spin_loop(),
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn step_on(
+        instruction: StallInstruction,
+        addr: *const u8,
+        changed: impl Fn() -> bool,
+    ) {
+        if instruction != StallInstruction::Wfe {
+            return step(instruction);
+        }
+        // SAFETY: `addr` points into the atomic the caller borrowed, so the
+        // exclusive load reads valid memory.
+        unsafe {
+            asm!(
+                "ldxrb {t:w}, [{a}]",
+                a = in(reg) addr,
+                t = out(reg) _,
+                options(readonly, nostack, preserves_flags),
+            )
+        };
+        // A write before the monitor was armed sent no event; one after it
+        // clears the monitor and ends the `wfe` below.
+        if changed() {
+            return;
+        }
+        // SAFETY: wfe has no memory effects.
+        unsafe { asm!("wfe", options(nomem, nostack, preserves_flags)) };
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+mod arch {
+    use super::StallInstruction;
+
+    #[inline(always)]
+    pub(super) fn step(_instruction: StallInstruction) {
+        core::hint::spin_loop();
+    }
+
+    #[inline(always)]
+    pub(super) fn step_on(
+        _instruction: StallInstruction,
+        _addr: *const u8,
+        _changed: impl Fn() -> bool,
+    ) {
+        core::hint::spin_loop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_instruction_returns() {
+        let word = core::sync::atomic::AtomicU64::new(0);
+        for i in [
+            StallInstruction::Hint,
+            StallInstruction::Tpause(1000),
+            StallInstruction::Umwait(1000),
+            StallInstruction::Wfe,
+            StallInstruction::Isb,
+        ] {
+            let p = StallPolicy::new(i, 3);
+            p.stall();
+            p.stall_on(&word, 0);
+            p.stall_on(&word, 1);
+        }
+        assert_eq!(StallPolicy::default(), StallPolicy::pause(1));
+    }
+}
@@ -0,0 +1,260 @@
+//! Pointers carrying a generation tag in otherwise unused bits.
+//!
+//! On 64-bit targets the tag occupies the top 16 bits of the word, which
+//! assumes addresses fit in 48 bits. That holds for x86_64 with 4-level
+//! paging and for aarch64 with 48-bit virtual addresses; it does not hold
+//! under x86_64 5-level paging once the kernel hands out high addresses, nor
+//! for pointers carrying hardware tags in the top byte (aarch64 TBI, MTE).
+//! Elsewhere the tag lives in the low bits left free by the alignment of
+//! `T`, so an `AtomicTaggedPtr<u8>` has no tag bits at all on 32-bit
+//! targets. Tags wrap modulo `2^TAG_BITS`.
+//!
+//! [`TaggedPtr::new`] checks the pointer against the tag bits in every
+//! build, so an unsupported address panics instead of losing bits.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A pointer paired with a generation tag.
+pub struct TaggedPtr<T> {
+    ptr: *mut T,
+    tag: usize,
+}
+
+impl<T> TaggedPtr<T> {
+    /// Number of tag bits available for `T` on this target.
+    #[cfg(target_pointer_width = "64")]
+    pub const TAG_BITS: u32 = 16;
+    /// Number of tag bits available for `T` on this target.
+    #[cfg(not(target_pointer_width = "64"))]
+    pub const TAG_BITS: u32 = core::mem::align_of::<T>().trailing_zeros();
+
+    const TAG_MASK: usize = (1usize << Self::TAG_BITS) - 1;
+
+    #[cfg(target_pointer_width = "64")]
+    const SHIFT: u32 = usize::BITS - Self::TAG_BITS;
+    #[cfg(not(target_pointer_width = "64"))]
+    const SHIFT: u32 = 0;
+
+    /// Creates a tagged pointer. `tag` is truncated to [`Self::TAG_BITS`] bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the address uses any of the tag bits: above bit 47 on
+    /// 64-bit targets, or below the alignment of `T` elsewhere.
+    #[inline]
+    pub fn new(ptr: *mut T, tag: usize) -> Self {
+        assert_eq!(
+            ptr as usize & (Self::TAG_MASK << Self::SHIFT),
+            0,
+            "pointer overlaps the tag bits"
+        );
+        TaggedPtr {
+            ptr,
+            tag: tag & Self::TAG_MASK,
+        }
+    }
+
+    /// A null pointer with tag zero.
+    #[inline]
+    pub fn null() -> Self {
+        Self::new(core::ptr::null_mut(), 0)
+    }
+
+    /// Returns the pointer part.
+    #[inline]
+    pub fn ptr(self) -> *mut T {
+        self.ptr
+    }
+
+    /// Returns the tag part.
+    #[inline]
+    pub fn tag(self) -> usize {
+        self.tag
+    }
+
+    /// Returns `ptr` tagged with this pointer's tag plus one.
+    ///
+    /// This is the usual way to build the replacement value in an ABA-safe
+    /// compare-and-swap loop.
+    #[inline]
+    pub fn successor(self, ptr: *mut T) -> Self {
+        Self::new(ptr, self.tag.wrapping_add(1))
+    }
+
+    #[inline]
+    fn pack(self) -> usize {
+        // `new` and `unpack` keep the pointer clear of the tag bits.
+        self.ptr as usize | (self.tag << Self::SHIFT)
+    }
+
+    #[inline]
+    fn unpack(word: usize) -> Self {
+        let mask = Self::TAG_MASK << Self::SHIFT;
+        TaggedPtr {
+            ptr: (word & !mask) as *mut T,
+            tag: (word & mask) >> Self::SHIFT,
+        }
+    }
+}
+
+impl<T> Clone for TaggedPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for TaggedPtr<T> {}
+
+impl<T> PartialEq for TaggedPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr == other.ptr && self.tag == other.tag
+    }
+}
+
+impl<T> Eq for TaggedPtr<T> {}
+
+impl<T> fmt::Debug for TaggedPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaggedPtr")
+            .field("ptr", &self.ptr)
+            .field("tag", &self.tag)
+            .finish()
+    }
+}
+
+/// An atomic (pointer, tag) pair stored in a single word.
+pub struct AtomicTaggedPtr<T> {
+    word: AtomicUsize,
+    _marker: PhantomData<*mut T>,
+}
+
+// Like `AtomicPtr`, the cell only stores an address; it never dereferences it.
+unsafe impl<T> Send for AtomicTaggedPtr<T> {}
+unsafe impl<T> Sync for AtomicTaggedPtr<T> {}
+
+impl<T> AtomicTaggedPtr<T> {
+    /// Creates a new atomic tagged pointer.
+    pub fn new(value: TaggedPtr<T>) -> Self {
+        AtomicTaggedPtr {
+            word: AtomicUsize::new(value.pack()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a null atomic tagged pointer with tag zero.
+    pub const fn null() -> Self {
+        AtomicTaggedPtr {
+            word: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Loads the current (pointer, tag) pair.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> TaggedPtr<T> {
+        TaggedPtr::unpack(self.word.load(order))
+    }
+
+    /// Stores a new (pointer, tag) pair.
+    #[inline]
+    pub fn store(&self, value: TaggedPtr<T>, order: Ordering) {
+        self.word.store(value.pack(), order);
+    }
+
+    /// Stores a new pair, returning the previous one.
+    #[inline]
+    pub fn swap(&self, value: TaggedPtr<T>, order: Ordering) -> TaggedPtr<T> {
+        TaggedPtr::unpack(self.word.swap(value.pack(), order))
+    }
+
+    /// Stores `new` if the current pair equals `current`.
+    ///
+    /// Returns the previous pair on success and the observed pair on failure.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: TaggedPtr<T>,
+        new: TaggedPtr<T>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<TaggedPtr<T>, TaggedPtr<T>> {
+        self.word
+            .compare_exchange(current.pack(), new.pack(), success, failure)
+            .map(TaggedPtr::unpack)
+            .map_err(TaggedPtr::unpack)
+    }
+
+    /// Like [`compare_exchange`](Self::compare_exchange) but may fail spuriously.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: TaggedPtr<T>,
+        new: TaggedPtr<T>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<TaggedPtr<T>, TaggedPtr<T>> {
+        self.word
+            .compare_exchange_weak(current.pack(), new.pack(), success, failure)
+            .map(TaggedPtr::unpack)
+            .map_err(TaggedPtr::unpack)
+    }
+}
+
+impl<T> Default for AtomicTaggedPtr<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> fmt::Debug for AtomicTaggedPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut x = 5u64;
+        let p = &mut x as *mut u64;
+        let a = AtomicTaggedPtr::new(TaggedPtr::new(p, 3));
+        let v = a.load(Ordering::Acquire);
+        assert_eq!(v.ptr(), p);
+        assert_eq!(v.tag(), 3);
+    }
+
+    #[test]
+    fn tag_wraps() {
+        let max = (1usize << TaggedPtr::<u64>::TAG_BITS) - 1;
+        let v = TaggedPtr::<u64>::new(core::ptr::null_mut(), max);
+        assert_eq!(v.successor(core::ptr::null_mut()).tag(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "pointer overlaps the tag bits")]
+    fn rejects_pointer_in_tag_bits() {
+        let bad = TaggedPtr::<u64>::TAG_MASK << TaggedPtr::<u64>::SHIFT;
+        TaggedPtr::<u64>::new(bad as *mut u64, 0);
+    }
+
+    #[test]
+    fn cas_detects_stale_tag() {
+        let mut x = 1u64;
+        let p = &mut x as *mut u64;
+        let a = AtomicTaggedPtr::new(TaggedPtr::new(p, 0));
+        let old = a.load(Ordering::Relaxed);
+        a.store(old.successor(p), Ordering::Relaxed);
+        let r = a.compare_exchange(old, old.successor(p), Ordering::AcqRel, Ordering::Acquire);
+        assert_eq!(r, Err(TaggedPtr::new(p, 1)));
+        let cur = a.load(Ordering::Relaxed);
+        assert!(a
+            .compare_exchange(cur, cur.successor(p), Ordering::AcqRel, Ordering::Acquire)
+            .is_ok());
+        assert_eq!(a.load(Ordering::Relaxed).tag(), 2);
+    }
+}
@@ -0,0 +1,391 @@
+//! 128-bit atomics on targets with a double-word compare-and-swap.
+//!
+//! Native support is compiled in on x86_64 when `cmpxchg16b` is enabled
+//! (e.g. `-C target-cpu=x86-64-v2`) and on aarch64 when both `lse` and `lse2`
+//! are enabled. Elsewhere [`AtomicU128`] is uninhabited: it can only be
+//! obtained from [`AtomicU128::try_new`], which returns [`Unsupported`], so
+//! portable code can select a fallback at run time without `cfg`s.
+//!
+//! On x86_64 loads are implemented with `cmpxchg16b` and therefore write to
+//! the cache line; avoid read-mostly 128-bit words there.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+/// `true` if this build has native 128-bit atomics.
+pub const SUPPORTED: bool = cfg!(any(
+    all(target_arch = "x86_64", target_feature = "cmpxchg16b"),
+    all(
+        target_arch = "aarch64",
+        target_feature = "lse",
+        target_feature = "lse2"
+    )
+));
+
+/// Error returned by [`AtomicU128::try_new`] when the target lacks native
+/// 128-bit atomics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Unsupported;
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("128-bit atomics are not supported on this target")
+    }
+}
+
+/// A 16-byte aligned `u128` accessed with native double-word atomics.
+#[repr(C, align(16))]
+pub struct AtomicU128 {
+    value: UnsafeCell<u128>,
+    #[cfg(not(any(
+        all(target_arch = "x86_64", target_feature = "cmpxchg16b"),
+        all(
+            target_arch = "aarch64",
+            target_feature = "lse",
+            target_feature = "lse2"
+        )
+    )))]
+    _never: core::convert::Infallible,
+}
+
+unsafe impl Sync for AtomicU128 {}
+
+impl AtomicU128 {
+    /// Creates a new atomic integer.
+    #[cfg(any(
+        all(target_arch = "x86_64", target_feature = "cmpxchg16b"),
+        all(
+            target_arch = "aarch64",
+            target_feature = "lse",
+            target_feature = "lse2"
+        )
+    ))]
+    pub const fn new(value: u128) -> Self {
+        AtomicU128 {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Creates a new atomic integer if the target supports it.
+    pub fn try_new(value: u128) -> Result<Self, Unsupported> {
+        #[cfg(any(
+            all(target_arch = "x86_64", target_feature = "cmpxchg16b"),
+            all(
+                target_arch = "aarch64",
+                target_feature = "lse",
+                target_feature = "lse2"
+            )
+        ))]
+        return Ok(Self::new(value));
+        #[allow(unreachable_code)]
+        {
+            let _ = value;
+            Err(Unsupported)
+        }
+    }
+
+    /// Consumes the atomic and returns the contained value.
+    pub fn into_inner(self) -> u128 {
+        self.value.into_inner()
+    }
+
+    /// Returns a mutable reference to the underlying integer.
+    pub fn get_mut(&mut self) -> &mut u128 {
+        self.value.get_mut()
+    }
+
+    /// Loads the value.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> u128 {
+        // SAFETY: the cell is 16-byte aligned and only accessed atomically.
+        unsafe { imp::load(self.value.get(), order) }
+    }
+
+    /// Stores a value.
+    #[inline]
+    pub fn store(&self, value: u128, order: Ordering) {
+        // SAFETY: as in `load`.
+        unsafe { imp::store(self.value.get(), value, order) }
+    }
+
+    /// Stores `new` if the current value equals `current`.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: u128,
+        new: u128,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<u128, u128> {
+        // SAFETY: as in `load`.
+        unsafe { imp::cas(self.value.get(), current, new, success, failure) }
+    }
+
+    /// Same as [`compare_exchange`](Self::compare_exchange); never fails spuriously.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: u128,
+        new: u128,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<u128, u128> {
+        self.compare_exchange(current, new, success, failure)
+    }
+
+    #[inline]
+    fn fetch_update(&self, order: Ordering, f: impl Fn(u128) -> u128) -> u128 {
+        let failure = match order {
+            Ordering::Release => Ordering::Relaxed,
+            Ordering::AcqRel => Ordering::Acquire,
+            o => o,
+        };
+        let mut current = self.load(Ordering::Relaxed);
+        loop {
+            match self.compare_exchange(current, f(current), order, failure) {
+                Ok(v) => return v,
+                Err(v) => current = v,
+            }
+        }
+    }
+
+    /// Stores a value, returning the previous one.
+    #[inline]
+    pub fn swap(&self, value: u128, order: Ordering) -> u128 {
+        self.fetch_update(order, |_| value)
+    }
+
+    /// Wrapping add, returning the previous value.
+    #[inline]
+    pub fn fetch_add(&self, delta: u128, order: Ordering) -> u128 {
+        self.fetch_update(order, |v| v.wrapping_add(delta))
+    }
+
+    /// Wrapping subtract, returning the previous value.
+    #[inline]
+    pub fn fetch_sub(&self, delta: u128, order: Ordering) -> u128 {
+        self.fetch_update(order, |v| v.wrapping_sub(delta))
+    }
+
+    /// Bitwise AND, returning the previous value.
+    #[inline]
+    pub fn fetch_and(&self, bits: u128, order: Ordering) -> u128 {
+        self.fetch_update(order, |v| v & bits)
+    }
+
+    /// Bitwise OR, returning the previous value.
+    #[inline]
+    pub fn fetch_or(&self, bits: u128, order: Ordering) -> u128 {
+        self.fetch_update(order, |v| v | bits)
+    }
+
+    /// Bitwise XOR, returning the previous value.
+    #[inline]
+    pub fn fetch_xor(&self, bits: u128, order: Ordering) -> u128 {
+        self.fetch_update(order, |v| v ^ bits)
+    }
+}
+
+impl fmt::Debug for AtomicU128 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "cmpxchg16b"))]
+mod imp {
+    use core::arch::x86_64::cmpxchg16b;
+    use core::sync::atomic::Ordering;
+
+    #[inline(always)]
+    pub(super) unsafe fn cas(
+        dst: *mut u128,
+        old: u128,
+        new: u128,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<u128, u128> {
+        // `lock cmpxchg16b` is sequentially consistent whatever is requested.
+        let prev = cmpxchg16b(dst, old, new, success, failure);
+        if prev == old {
+            Ok(prev)
+        } else {
+            Err(prev)
+        }
+    }
+
+    #[inline(always)]
+    pub(super) unsafe fn load(src: *mut u128, _order: Ordering) -> u128 {
+        match cas(src, 0, 0, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(v) | Err(v) => v,
+        }
+    }
+
+    #[inline(always)]
+    pub(super) unsafe fn store(dst: *mut u128, value: u128, _order: Ordering) {
+        let mut current = load(dst, Ordering::Relaxed);
+        while let Err(v) = cas(dst, current, value, Ordering::SeqCst, Ordering::SeqCst) {
+            current = v;
+        }
+    }
+}
+
+#[cfg(all(
+    target_arch = "aarch64",
+    target_feature = "lse",
+    target_feature = "lse2"
+))]
+mod imp {
+    use core::arch::asm;
+    use core::sync::atomic::Ordering;
+
+    // With LSE2, 16-byte aligned `ldp`/`stp` are single-copy atomic.
+
+    #[inline(always)]
+    pub(super) unsafe fn load(src: *mut u128, order: Ordering) -> u128 {
+        let (lo, hi): (u64, u64);
+        match order {
+            Ordering::Relaxed => asm!(
+                "ldp {lo}, {hi}, [{src}]",
+                src = in(reg) src,
+                lo = out(reg) lo,
+                hi = out(reg) hi,
+                options(nostack, preserves_flags),
+            ),
+            _ => asm!(
+                "ldp {lo}, {hi}, [{src}]",
+                "dmb ishld",
+                src = in(reg) src,
+                lo = out(reg) lo,
+                hi = out(reg) hi,
+                options(nostack, preserves_flags),
+            ),
+        }
+        (hi as u128) << 64 | lo as u128
+    }
+
+    #[inline(always)]
+    pub(super) unsafe fn store(dst: *mut u128, value: u128, order: Ordering) {
+        let (lo, hi) = (value as u64, (value >> 64) as u64);
+        match order {
+            Ordering::Relaxed => asm!(
+                "stp {lo}, {hi}, [{dst}]",
+                dst = in(reg) dst,
+                lo = in(reg) lo,
+                hi = in(reg) hi,
+                options(nostack, preserves_flags),
+            ),
+            Ordering::SeqCst => asm!(
+                "dmb ish",
+                "stp {lo}, {hi}, [{dst}]",
+                "dmb ish",
+                dst = in(reg) dst,
+                lo = in(reg) lo,
+                hi = in(reg) hi,
+                options(nostack, preserves_flags),
+            ),
+            _ => asm!(
+                "dmb ish",
+                "stp {lo}, {hi}, [{dst}]",
+                dst = in(reg) dst,
+                lo = in(reg) lo,
+                hi = in(reg) hi,
+                options(nostack, preserves_flags),
+            ),
+        }
+    }
+
+    #[inline(always)]
+    pub(super) unsafe fn cas(
+        dst: *mut u128,
+        old: u128,
+        new: u128,
+        _success: Ordering,
+        _failure: Ordering,
+    ) -> Result<u128, u128> {
+        // `caspal` is acquire-release; the register pairs must be even/odd.
+        let (mut lo, mut hi) = (old as u64, (old >> 64) as u64);
+        asm!(
+            "caspal x0, x1, x2, x3, [x4]",
+            inout("x0") lo,
+            inout("x1") hi,
+            in("x2") new as u64,
+            in("x3") (new >> 64) as u64,
+            in("x4") dst,
+            options(nostack, preserves_flags),
+        );
+        let prev = (hi as u128) << 64 | lo as u128;
+        if prev == old {
+            Ok(prev)
+        } else {
+            Err(prev)
+        }
+    }
+}
+
+#[cfg(not(any(
+    all(target_arch = "x86_64", target_feature = "cmpxchg16b"),
+    all(
+        target_arch = "aarch64",
+        target_feature = "lse",
+        target_feature = "lse2"
+    )
+)))]
+mod imp {
+    //! `AtomicU128` is uninhabited here, so none of these can be reached.
+
+    use core::sync::atomic::Ordering;
+
+    pub(super) unsafe fn load(_: *mut u128, _: Ordering) -> u128 {
+        unreachable!()
+    }
+
+    pub(super) unsafe fn store(_: *mut u128, _: u128, _: Ordering) {
+        unreachable!()
+    }
+
+    pub(super) unsafe fn cas(
+        _: *mut u128,
+        _: u128,
+        _: u128,
+        _: Ordering,
+        _: Ordering,
+    ) -> Result<u128, u128> {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_new_matches_support() {
+        assert_eq!(AtomicU128::try_new(1).is_ok(), SUPPORTED);
+        assert_eq!(
+            std::format!("{}", Unsupported),
+            "128-bit atomics are not supported on this target"
+        );
+    }
+
+    #[test]
+    fn operations() {
+        let Ok(a) = AtomicU128::try_new(u64::MAX as u128) else {
+            return;
+        };
+        assert_eq!(super::super::u128_ops::faa(&a, 1), u64::MAX as u128);
+        assert_eq!(a.load(Ordering::Acquire), 1 << 64);
+        assert_eq!(
+            a.compare_exchange(0, 1, Ordering::AcqRel, Ordering::Acquire),
+            Err(1 << 64)
+        );
+        assert_eq!(
+            super::super::u128_ops::cas_result(&a, 1 << 64, u128::MAX),
+            Ok(1 << 64)
+        );
+        a.store(7, Ordering::SeqCst);
+        assert_eq!(a.swap(8, Ordering::Relaxed), 7);
+        assert_eq!(a.into_inner(), 8);
+    }
+}
@@ -0,0 +1,248 @@
+//! Futex-style blocking on an `AtomicU32`.
+//!
+//! [`wait`] blocks the calling thread while the target still holds the
+//! expected value; [`wake_one`] and [`wake_all`] release threads blocked on
+//! it. Linux uses `futex`, Windows `WaitOnAddress` and macOS `__ulock_*`.
+//! Other platforms fall back to a hashed table of condition variables. Waits
+//! may return spuriously, so callers must re-check their condition.
+
+use core::sync::atomic::AtomicU32;
+use core::time::Duration;
+
+/// Blocks while `target` holds `expected`.
+///
+/// Returns immediately if the value differs, and may also return spuriously.
+#[inline]
+pub fn wait(target: &AtomicU32, expected: u32) {
+    imp::wait(target, expected, None)
+}
+
+/// Like [`wait`] but gives up after roughly `timeout`.
+#[inline]
+pub fn wait_timeout(target: &AtomicU32, expected: u32, timeout: Duration) {
+    imp::wait(target, expected, Some(timeout))
+}
+
+/// Wakes at least one thread blocked in [`wait`] on `target`, if any.
+#[inline]
+pub fn wake_one(target: &AtomicU32) {
+    imp::wake(target, false)
+}
+
+/// Wakes every thread blocked in [`wait`] on `target`.
+#[inline]
+pub fn wake_all(target: &AtomicU32) {
+    imp::wake(target, true)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use core::ptr;
+    use core::sync::atomic::AtomicU32;
+    use core::time::Duration;
+
+    pub(super) fn wait(target: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        let ts = timeout.map(|t| libc::timespec {
+            tv_sec: t.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+            tv_nsec: t.subsec_nanos() as _,
+        });
+        let ts_ptr = ts
+            .as_ref()
+            .map_or(ptr::null(), |t| t as *const libc::timespec);
+        // SAFETY: `target` is a valid, aligned u32 for the duration of the call.
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                target.as_ptr(),
+                libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                expected,
+                ts_ptr,
+            );
+        }
+    }
+
+    pub(super) fn wake(target: &AtomicU32, all: bool) {
+        let n = if all { i32::MAX } else { 1 };
+        // SAFETY: FUTEX_WAKE only uses the address as a key.
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                target.as_ptr(),
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                n,
+            );
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use core::ffi::c_void;
+    use core::sync::atomic::AtomicU32;
+    use core::time::Duration;
+
+    const INFINITE: u32 = u32::MAX;
+
+    #[link(name = "synchronization")]
+    extern "system" {
+        fn WaitOnAddress(
+            address: *const c_void,
+            compare: *const c_void,
+            size: usize,
+            milliseconds: u32,
+        ) -> i32;
+        fn WakeByAddressSingle(address: *const c_void);
+        fn WakeByAddressAll(address: *const c_void);
+    }
+
+    pub(super) fn wait(target: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        let ms = timeout.map_or(INFINITE, |t| t.as_millis().min(INFINITE as u128 - 1) as u32);
+        // SAFETY: both addresses are valid u32s for the duration of the call.
+        unsafe {
+            WaitOnAddress(
+                target.as_ptr() as *const c_void,
+                &expected as *const u32 as *const c_void,
+                4,
+                ms,
+            );
+        }
+    }
+
+    pub(super) fn wake(target: &AtomicU32, all: bool) {
+        let addr = target.as_ptr() as *const c_void;
+        // SAFETY: the address is only used as a key.
+        unsafe {
+            if all {
+                WakeByAddressAll(addr)
+            } else {
+                WakeByAddressSingle(addr)
+            }
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod imp {
+    use core::ffi::{c_int, c_void};
+    use core::sync::atomic::AtomicU32;
+    use core::time::Duration;
+
+    const UL_COMPARE_AND_WAIT: u32 = 1;
+    const ULF_WAKE_ALL: u32 = 0x0000_0100;
+    const ULF_NO_ERRNO: u32 = 0x0100_0000;
+
+    extern "C" {
+        fn __ulock_wait(operation: u32, addr: *mut c_void, value: u64, timeout_us: u32) -> c_int;
+        fn __ulock_wake(operation: u32, addr: *mut c_void, wake_value: u64) -> c_int;
+    }
+
+    pub(super) fn wait(target: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        // A zero timeout means "wait forever" to the kernel.
+        let us = timeout.map_or(0, |t| t.as_micros().clamp(1, u32::MAX as u128) as u32);
+        // SAFETY: `target` is a valid, aligned u32 for the duration of the call.
+        unsafe {
+            __ulock_wait(
+                UL_COMPARE_AND_WAIT | ULF_NO_ERRNO,
+                target.as_ptr() as *mut c_void,
+                expected as u64,
+                us,
+            );
+        }
+    }
+
+    pub(super) fn wake(target: &AtomicU32, all: bool) {
+        let mut op = UL_COMPARE_AND_WAIT | ULF_NO_ERRNO;
+        if all {
+            op |= ULF_WAKE_ALL;
+        }
+        // SAFETY: the address is only used as a key.
+        unsafe {
+            __ulock_wake(op, target.as_ptr() as *mut c_void, 0);
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    windows,
+    target_os = "macos",
+    target_os = "ios"
+)))]
+mod imp {
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use core::time::Duration;
+    use std::sync::{Condvar, Mutex};
+
+    const BUCKETS: usize = 64;
+
+    struct Bucket {
+        lock: Mutex<()>,
+        cond: Condvar,
+    }
+
+    static TABLE: [Bucket; BUCKETS] = [const {
+        Bucket {
+            lock: Mutex::new(()),
+            cond: Condvar::new(),
+        }
+    }; BUCKETS];
+
+    fn bucket(target: &AtomicU32) -> &'static Bucket {
+        let addr = target.as_ptr() as usize;
+        &TABLE[(addr >> 2).wrapping_mul(0x9e37_79b9) % BUCKETS]
+    }
+
+    pub(super) fn wait(target: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        let b = bucket(target);
+        let guard = b.lock.lock().unwrap_or_else(|e| e.into_inner());
+        // Wakers take the bucket lock, so this check cannot miss a wake-up.
+        if target.load(Ordering::SeqCst) != expected {
+            return;
+        }
+        match timeout {
+            Some(t) => drop(b.cond.wait_timeout(guard, t)),
+            None => drop(b.cond.wait(guard)),
+        }
+    }
+
+    pub(super) fn wake(target: &AtomicU32, _all: bool) {
+        let b = bucket(target);
+        drop(b.lock.lock().unwrap_or_else(|e| e.into_inner()));
+        // Buckets are shared between addresses, so always wake everyone.
+        b.cond.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn returns_when_value_differs() {
+        let a = AtomicU32::new(1);
+        wait(&a, 0);
+        wait_timeout(&a, 1, Duration::from_millis(1));
+    }
+
+    #[test]
+    fn wake_releases_waiter() {
+        let a = Arc::new(AtomicU32::new(0));
+        let waiter = {
+            let a = a.clone();
+            thread::spawn(move || {
+                while a.load(Ordering::Acquire) == 0 {
+                    wait(&a, 0);
+                }
+            })
+        };
+        thread::sleep(Duration::from_millis(10));
+        a.store(1, Ordering::Release);
+        wake_all(&a);
+        waiter.join().unwrap();
+        wake_one(&a);
+    }
+}
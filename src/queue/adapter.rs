@@ -0,0 +1,113 @@
+//! Adapters tying a list entry to the field that embeds it.
+//!
+//! `define_*_adapter!` macros are the queue counterparts of C's
+//! `SLIST_ENTRY(foo) link;`: they name the entry field once and yield an
+//! [`IntrusiveAdapter`](crate::cc::IntrusiveAdapter) whose `link` function is
+//! the `field` argument every list operation takes, and whose `owner` maps an
+//! entry back to its element.
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __queue_adapter {
+    ($entry:ident, $(#[$attr:meta])* $vis:vis $adapter:ident = $owner:ty { $field:ident }) => {
+        $crate::intrusive_adapter!(
+            $(#[$attr])* $vis $adapter = $owner { $field: $crate::queue::$entry<$owner> }
+        );
+    };
+}
+
+/// Defines an adapter for an [`SlistEntry`](crate::queue::SlistEntry) field.
+///
+/// ```
+/// use concurrencykit::cc::IntrusiveAdapter;
+/// use concurrencykit::queue::{SlistEntry, SlistHead};
+///
+/// struct Task {
+///     id: u32,
+///     link: SlistEntry<Task>,
+/// }
+///
+/// concurrencykit::define_slist_adapter!(TaskLink = Task { link });
+///
+/// let task = Task { id: 7, link: SlistEntry::new() };
+/// let head = SlistHead::new();
+/// unsafe { head.insert_head((&task).into(), TaskLink::link) };
+/// let first = unsafe { &*head.first() };
+/// assert_eq!(unsafe { TaskLink::owner(TaskLink::link_ptr(first)) }.id, 7);
+/// ```
+#[macro_export]
+macro_rules! define_slist_adapter {
+    ($($t:tt)*) => { $crate::__queue_adapter!(SlistEntry, $($t)*); };
+}
+
+/// Defines an adapter for a [`ListEntry`](crate::queue::ListEntry) field.
+#[macro_export]
+macro_rules! define_list_adapter {
+    ($($t:tt)*) => { $crate::__queue_adapter!(ListEntry, $($t)*); };
+}
+
+/// Defines an adapter for a [`StailqEntry`](crate::queue::StailqEntry) field.
+#[macro_export]
+macro_rules! define_stailq_adapter {
+    ($($t:tt)*) => { $crate::__queue_adapter!(StailqEntry, $($t)*); };
+}
+
+/// Defines an adapter for a [`TailqEntry`](crate::queue::TailqEntry) field.
+#[macro_export]
+macro_rules! define_tailq_adapter {
+    ($($t:tt)*) => { $crate::__queue_adapter!(TailqEntry, $($t)*); };
+}
+
+/// Defines an adapter for a [`CircleqEntry`](crate::queue::CircleqEntry) field.
+#[macro_export]
+macro_rules! define_circleq_adapter {
+    ($($t:tt)*) => { $crate::__queue_adapter!(CircleqEntry, $($t)*); };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cc::IntrusiveAdapter;
+    use crate::queue::{CircleqEntry, StailqEntry, StailqHead, TailqEntry, TailqHead};
+    use core::ptr::NonNull;
+    use std::vec::Vec;
+
+    #[derive(Default)]
+    struct Job {
+        id: u32,
+        ready: StailqEntry<Job>,
+        all: TailqEntry<Job>,
+        ring: CircleqEntry<Job>,
+    }
+
+    crate::define_stailq_adapter!(ReadyLink = Job { ready });
+    crate::define_tailq_adapter!(AllLink = Job { all });
+    crate::define_circleq_adapter!(RingLink = Job { ring });
+
+    #[test]
+    fn one_element_on_two_lists() {
+        let jobs: [Job; 3] = core::array::from_fn(|i| Job {
+            id: i as u32,
+            ..Default::default()
+        });
+        let ready = StailqHead::new();
+        let all = TailqHead::new();
+        for j in &jobs {
+            unsafe {
+                all.insert_tail(NonNull::from(j), AllLink::link);
+                if j.id != 1 {
+                    ready.insert_tail(NonNull::from(j), ReadyLink::link);
+                }
+            }
+        }
+        let ids: Vec<u32> = unsafe { ready.iter(ReadyLink::link) }
+            .map(|j| j.id)
+            .collect();
+        assert_eq!(ids, [0, 2]);
+        let mut n = 0;
+        unsafe { all.foreach(AllLink::link, |_| n += 1) };
+        assert_eq!(n, 3);
+        let entry = ReadyLink::link_ptr(&jobs[2]);
+        assert!(core::ptr::eq(unsafe { ReadyLink::owner(entry) }, &jobs[2]));
+        assert_eq!(RingLink::OFFSET, core::mem::offset_of!(Job, ring));
+    }
+}
@@ -0,0 +1,307 @@
+//! Circular doubly-linked queue (`CIRCLEQ`).
+//!
+//! The last element links forward to the first and the first back to the
+//! last, so [`CircleqEntry::next`] and [`CircleqEntry::prev`] never return
+//! null for a linked element and [`CircleqHead::rotate`] advances the front
+//! in O(1), as round-robin schedulers want. A traversal stops when it comes
+//! back to its starting element, so, unlike the other queues, readers must
+//! not run concurrently with a writer removing elements.
+
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Link embedded in each element of a [`CircleqHead`].
+#[derive(Debug)]
+pub struct CircleqEntry<T> {
+    next: AtomicPtr<T>,
+    prev: AtomicPtr<T>,
+}
+
+impl<T> CircleqEntry<T> {
+    /// Creates an unlinked entry.
+    pub const fn new() -> Self {
+        CircleqEntry {
+            next: AtomicPtr::new(ptr::null_mut()),
+            prev: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the following element, wrapping from last to first.
+    #[inline]
+    pub fn next(&self) -> *mut T {
+        self.next.load(Ordering::Acquire)
+    }
+
+    /// Returns the preceding element, wrapping from first to last.
+    #[inline]
+    pub fn prev(&self) -> *mut T {
+        self.prev.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Default for CircleqEntry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Head of a circular queue of `T`.
+#[derive(Debug)]
+pub struct CircleqHead<T> {
+    first: AtomicPtr<T>,
+}
+
+impl<T> Default for CircleqHead<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline(always)]
+unsafe fn link<'a, T>(elm: *mut T, field: fn(&T) -> &CircleqEntry<T>) -> &'a CircleqEntry<T> {
+    field(&*elm)
+}
+
+impl<T> CircleqHead<T> {
+    /// Creates an empty queue.
+    pub const fn new() -> Self {
+        CircleqHead {
+            first: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns `true` if the queue has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.first.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Returns the first element, or null.
+    #[inline]
+    pub fn first(&self) -> *mut T {
+        self.first.load(Ordering::Acquire)
+    }
+
+    /// Returns the last element, or null.
+    ///
+    /// # Safety
+    ///
+    /// The first element, if any, must be valid.
+    #[inline]
+    pub unsafe fn last(&self, field: fn(&T) -> &CircleqEntry<T>) -> *mut T {
+        match self.first() {
+            f if f.is_null() => f,
+            f => link(f, field).prev(),
+        }
+    }
+
+    /// Links `elm` between `prev` and `next`, which are adjacent.
+    #[inline(always)]
+    unsafe fn splice(prev: *mut T, elm: *mut T, next: *mut T, field: fn(&T) -> &CircleqEntry<T>) {
+        let e = link(elm, field);
+        e.next.store(next, Ordering::Relaxed);
+        e.prev.store(prev, Ordering::Relaxed);
+        link(prev, field).next.store(elm, Ordering::Release);
+        link(next, field).prev.store(elm, Ordering::Release);
+    }
+
+    /// Links `elm` as the only element.
+    #[inline(always)]
+    unsafe fn init_with(&self, elm: *mut T, field: fn(&T) -> &CircleqEntry<T>) {
+        let e = link(elm, field);
+        e.next.store(elm, Ordering::Relaxed);
+        e.prev.store(elm, Ordering::Relaxed);
+        self.first.store(elm, Ordering::Release);
+    }
+
+    /// Inserts `elm` at the front.
+    ///
+    /// # Safety
+    ///
+    /// `elm` must be valid and on no queue, every element on this queue
+    /// must be valid, and the caller must be the only writer.
+    #[inline]
+    pub unsafe fn insert_head(&self, elm: NonNull<T>, field: fn(&T) -> &CircleqEntry<T>) {
+        self.insert_tail(elm, field);
+        self.first.store(elm.as_ptr(), Ordering::Release);
+    }
+
+    /// Inserts `elm` at the back.
+    ///
+    /// # Safety
+    ///
+    /// As for [`insert_head`](Self::insert_head).
+    #[inline]
+    pub unsafe fn insert_tail(&self, elm: NonNull<T>, field: fn(&T) -> &CircleqEntry<T>) {
+        let first = self.first.load(Ordering::Relaxed);
+        if first.is_null() {
+            return self.init_with(elm.as_ptr(), field);
+        }
+        let last = link(first, field).prev.load(Ordering::Relaxed);
+        Self::splice(last, elm.as_ptr(), first, field);
+    }
+
+    /// Inserts `elm` directly after `listelm`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`insert_head`](Self::insert_head), with `listelm` on this
+    /// queue.
+    #[inline]
+    pub unsafe fn insert_after(
+        listelm: NonNull<T>,
+        elm: NonNull<T>,
+        field: fn(&T) -> &CircleqEntry<T>,
+    ) {
+        let next = link(listelm.as_ptr(), field).next.load(Ordering::Relaxed);
+        Self::splice(listelm.as_ptr(), elm.as_ptr(), next, field);
+    }
+
+    /// Inserts `elm` directly before `listelm`, becoming the new first
+    /// element if `listelm` was first.
+    ///
+    /// # Safety
+    ///
+    /// As for [`insert_after`](Self::insert_after).
+    #[inline]
+    pub unsafe fn insert_before(
+        &self,
+        listelm: NonNull<T>,
+        elm: NonNull<T>,
+        field: fn(&T) -> &CircleqEntry<T>,
+    ) {
+        let prev = link(listelm.as_ptr(), field).prev.load(Ordering::Relaxed);
+        Self::splice(prev, elm.as_ptr(), listelm.as_ptr(), field);
+        if self.first.load(Ordering::Relaxed) == listelm.as_ptr() {
+            self.first.store(elm.as_ptr(), Ordering::Release);
+        }
+    }
+
+    /// Unlinks `elm`.
+    ///
+    /// # Safety
+    ///
+    /// `elm` must be on this queue, every element on it must be valid, no
+    /// reader may be traversing, and the caller must be the only writer.
+    #[inline]
+    pub unsafe fn remove(&self, elm: NonNull<T>, field: fn(&T) -> &CircleqEntry<T>) {
+        let e = link(elm.as_ptr(), field);
+        let (prev, next) = (
+            e.prev.load(Ordering::Relaxed),
+            e.next.load(Ordering::Relaxed),
+        );
+        if next == elm.as_ptr() {
+            self.first.store(ptr::null_mut(), Ordering::Release);
+            return;
+        }
+        link(prev, field).next.store(next, Ordering::Release);
+        link(next, field).prev.store(prev, Ordering::Release);
+        if self.first.load(Ordering::Relaxed) == elm.as_ptr() {
+            self.first.store(next, Ordering::Release);
+        }
+    }
+
+    /// Makes the second element the first, moving the old first to the
+    /// back, and returns the new first element.
+    ///
+    /// # Safety
+    ///
+    /// The first element, if any, must be valid; the caller must be the only
+    /// writer.
+    #[inline]
+    pub unsafe fn rotate(&self, field: fn(&T) -> &CircleqEntry<T>) -> Option<NonNull<T>> {
+        let first = NonNull::new(self.first.load(Ordering::Relaxed))?;
+        let next = link(first.as_ptr(), field).next.load(Ordering::Relaxed);
+        self.first.store(next, Ordering::Release);
+        NonNull::new(next)
+    }
+
+    /// Calls `f` on each element from first to last.
+    ///
+    /// # Safety
+    ///
+    /// Every element must be valid, and `f` must not unlink elements.
+    pub unsafe fn foreach(&self, field: fn(&T) -> &CircleqEntry<T>, mut f: impl FnMut(NonNull<T>)) {
+        let Some(start) = NonNull::new(self.first()) else {
+            return;
+        };
+        let mut cur = start;
+        loop {
+            f(cur);
+            cur = NonNull::new_unchecked(link(cur.as_ptr(), field).next());
+            if cur == start {
+                return;
+            }
+        }
+    }
+
+    /// Calls `f` on each element from last to first.
+    ///
+    /// # Safety
+    ///
+    /// As for [`foreach`](Self::foreach).
+    pub unsafe fn foreach_reverse(
+        &self,
+        field: fn(&T) -> &CircleqEntry<T>,
+        mut f: impl FnMut(NonNull<T>),
+    ) {
+        let Some(start) = NonNull::new(self.last(field)) else {
+            return;
+        };
+        let mut cur = start;
+        loop {
+            f(cur);
+            cur = NonNull::new_unchecked(link(cur.as_ptr(), field).prev());
+            if cur == start {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::fixture::{self, items, values};
+
+    type Item = fixture::Item<CircleqEntry<()>>;
+
+    #[test]
+    fn insert_remove() {
+        let it: [Item; 5] = items();
+        let p = |i: usize| NonNull::from(&it[i]);
+        let h = CircleqHead::new();
+        unsafe {
+            h.insert_tail(p(2), |e| &e.link);
+            h.insert_head(p(1), |e| &e.link);
+            h.insert_tail(p(4), |e| &e.link);
+            CircleqHead::insert_after(p(2), p(3), |e| &e.link);
+            h.insert_before(p(1), p(0), |e| &e.link);
+            assert_eq!(values(&h), [0, 1, 2, 3, 4]);
+            assert_eq!(h.last(|e| &e.link), p(4).as_ptr());
+            assert_eq!(it[4].link.next(), p(0).as_ptr());
+            h.remove(p(0), |e| &e.link);
+            h.remove(p(4), |e| &e.link);
+            h.remove(p(2), |e| &e.link);
+            assert_eq!(values(&h), [1, 3]);
+            h.remove(p(1), |e| &e.link);
+            h.remove(p(3), |e| &e.link);
+        }
+        assert!(h.is_empty());
+        assert!(values(&h).is_empty());
+    }
+
+    #[test]
+    fn rotate() {
+        let it: [Item; 3] = items();
+        let h = CircleqHead::<Item>::new();
+        unsafe {
+            assert_eq!(h.rotate(|e| &e.link), None);
+            for i in &it {
+                h.insert_tail(NonNull::from(i), |e| &e.link);
+            }
+            assert_eq!(h.rotate(|e| &e.link), Some(NonNull::from(&it[1])));
+        }
+        assert_eq!(values(&h), [1, 2, 0]);
+    }
+}
@@ -0,0 +1,256 @@
+//! Borrowing iterators and cursors over the intrusive lists.
+//!
+//! Creating an [`Iter`] or [`CursorMut`] is `unsafe` because the heads do
+//! not own their elements: the caller promises every element stays valid
+//! for the borrow. After that, walking and unlinking need no raw pointers.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+
+use super::{ListEntry, ListHead, SlistEntry, SlistHead, StailqEntry, StailqHead};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A list head that [`Iter`] and [`CursorMut`] can walk.
+pub trait Linked<T>: private::Sealed {
+    /// The entry type embedded in each element.
+    type Entry;
+
+    #[doc(hidden)]
+    fn first_ptr(&self) -> *mut T;
+
+    #[doc(hidden)]
+    fn next_ptr(entry: &Self::Entry) -> *mut T;
+}
+
+macro_rules! linked {
+    ($head:ident, $entry:ident) => {
+        impl<T> private::Sealed for $head<T> {}
+
+        impl<T> Linked<T> for $head<T> {
+            type Entry = $entry<T>;
+
+            #[inline(always)]
+            fn first_ptr(&self) -> *mut T {
+                self.first()
+            }
+
+            #[inline(always)]
+            fn next_ptr(entry: &$entry<T>) -> *mut T {
+                entry.next()
+            }
+        }
+
+        impl<T> $head<T> {
+            /// Returns an iterator over the elements in order.
+            ///
+            /// # Safety
+            ///
+            /// Every element on the list must stay valid while the iterator
+            /// is in use.
+            #[inline]
+            pub unsafe fn iter(&self, field: fn(&T) -> &$entry<T>) -> Iter<'_, T, Self> {
+                Iter {
+                    cur: self.first(),
+                    field,
+                    _marker: PhantomData,
+                }
+            }
+
+            /// Returns a cursor positioned on the first element.
+            ///
+            /// # Safety
+            ///
+            /// Every element on the list must stay valid while the cursor is
+            /// in use, and the cursor's holder must be the only writer.
+            #[inline]
+            pub unsafe fn cursor_mut(
+                &mut self,
+                field: fn(&T) -> &$entry<T>,
+            ) -> CursorMut<'_, T, Self> {
+                CursorMut {
+                    cur: self.first(),
+                    prev: ptr::null_mut(),
+                    head: self,
+                    field,
+                }
+            }
+        }
+    };
+}
+
+linked!(SlistHead, SlistEntry);
+linked!(ListHead, ListEntry);
+linked!(StailqHead, StailqEntry);
+
+/// Iterator over the elements of a list, from first to last.
+pub struct Iter<'a, T, H: Linked<T>> {
+    cur: *mut T,
+    field: fn(&T) -> &H::Entry,
+    _marker: PhantomData<&'a H>,
+}
+
+impl<'a, T: 'a, H: Linked<T>> Iterator for Iter<'a, T, H> {
+    type Item = &'a T;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a T> {
+        // SAFETY: elements stay valid for 'a, as promised by `iter`.
+        let elm = unsafe { self.cur.as_ref()? };
+        self.cur = H::next_ptr((self.field)(elm));
+        Some(elm)
+    }
+}
+
+impl<'a, T: 'a, H: Linked<T>> core::iter::FusedIterator for Iter<'a, T, H> {}
+
+impl<T, H: Linked<T>> fmt::Debug for Iter<'_, T, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Iter").field("cur", &self.cur).finish()
+    }
+}
+
+/// A cursor that can unlink the element it is on.
+pub struct CursorMut<'a, T, H: Linked<T>> {
+    head: &'a mut H,
+    prev: *mut T,
+    cur: *mut T,
+    field: fn(&T) -> &H::Entry,
+}
+
+impl<'a, T, H: Linked<T>> CursorMut<'a, T, H> {
+    /// Returns the current element, or `None` past the end.
+    #[inline]
+    pub fn current(&self) -> Option<&T> {
+        // SAFETY: elements stay valid for 'a, as promised by `cursor_mut`.
+        unsafe { self.cur.as_ref() }
+    }
+
+    /// Returns a pointer to the current element.
+    #[inline]
+    pub fn current_ptr(&self) -> Option<NonNull<T>> {
+        NonNull::new(self.cur)
+    }
+
+    /// Returns the element after the current one.
+    #[inline]
+    pub fn peek_next(&self) -> Option<&T> {
+        let next = H::next_ptr((self.field)(self.current()?));
+        // SAFETY: as for `current`.
+        unsafe { next.as_ref() }
+    }
+
+    /// Moves to the next element; does nothing past the end.
+    #[inline]
+    pub fn move_next(&mut self) {
+        if let Some(c) = self.current() {
+            let next = H::next_ptr((self.field)(c));
+            self.prev = self.cur;
+            self.cur = next;
+        }
+    }
+
+    /// Returns the list the cursor walks.
+    #[inline]
+    pub fn head(&self) -> &H {
+        self.head
+    }
+}
+
+impl<T> CursorMut<'_, T, SlistHead<T>> {
+    /// Unlinks the current element, returning it and moving to its
+    /// successor.
+    pub fn remove_current(&mut self) -> Option<NonNull<T>> {
+        let cur = NonNull::new(self.cur)?;
+        // SAFETY: the cursor's holder is the only writer and the elements
+        // are valid, as promised by `cursor_mut`.
+        unsafe {
+            self.cur = (self.field)(cur.as_ref()).next();
+            match NonNull::new(self.prev) {
+                None => self.head.remove_head(self.field),
+                Some(prev) => SlistHead::remove_after(prev, self.field),
+            }
+        }
+    }
+}
+
+impl<T> CursorMut<'_, T, ListHead<T>> {
+    /// Unlinks the current element, returning it and moving to its
+    /// successor.
+    pub fn remove_current(&mut self) -> Option<NonNull<T>> {
+        let cur = NonNull::new(self.cur)?;
+        // SAFETY: as for the SLIST cursor.
+        unsafe {
+            self.cur = (self.field)(cur.as_ref()).next();
+            ListHead::remove(cur, self.field);
+        }
+        Some(cur)
+    }
+}
+
+impl<T> CursorMut<'_, T, StailqHead<T>> {
+    /// Unlinks the current element, returning it and moving to its
+    /// successor.
+    pub fn remove_current(&mut self) -> Option<NonNull<T>> {
+        let cur = NonNull::new(self.cur)?;
+        // SAFETY: as for the SLIST cursor.
+        unsafe {
+            self.cur = (self.field)(cur.as_ref()).next();
+            self.head.remove_after(NonNull::new(self.prev), self.field)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::fixture::{items, Item};
+    use std::vec::Vec;
+
+    macro_rules! check {
+        ($head:ident, $entry:ident, $insert:ident) => {{
+            type I = Item<$entry<()>>;
+            let it: [I; 5] = items();
+            let mut h = $head::new();
+            for i in it.iter().rev() {
+                unsafe { h.$insert(NonNull::from(i), |e| &e.link) };
+            }
+            let values = |h: &$head<I>| -> Vec<u32> {
+                unsafe { h.iter(|e| &e.link) }.map(|e| e.value).collect()
+            };
+            assert_eq!(values(&h), [0, 1, 2, 3, 4]);
+            let mut c = unsafe { h.cursor_mut(|e| &e.link) };
+            assert_eq!(
+                c.remove_current().map(|e| unsafe { e.as_ref().value }),
+                Some(0)
+            );
+            assert_eq!(c.peek_next().map(|e| e.value), Some(2));
+            c.move_next();
+            c.remove_current();
+            c.move_next();
+            assert_eq!(c.current().map(|e| e.value), Some(4));
+            c.remove_current();
+            assert!(c.current().is_none() && c.remove_current().is_none());
+            c.move_next();
+            assert_eq!(values(c.head()), [1, 3]);
+        }};
+    }
+
+    #[test]
+    fn slist() {
+        check!(SlistHead, SlistEntry, insert_head);
+    }
+
+    #[test]
+    fn list() {
+        check!(ListHead, ListEntry, insert_head);
+    }
+
+    #[test]
+    fn stailq() {
+        check!(StailqHead, StailqEntry, insert_head);
+    }
+}
@@ -0,0 +1,296 @@
+//! Singly-linked list of owned values with epoch-protected readers.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+use crate::epoch::{self, Epoch, Guard};
+use crate::malloc::{Allocator, DeferredAllocator, GlobalAllocator, MIN_ALIGN};
+use crate::pr;
+use crate::stack::EpochReclaimer;
+
+struct Node<T> {
+    next: AtomicPtr<Node<T>>,
+    value: T,
+}
+
+/// A `CK_SLIST` of `T` that owns its elements.
+///
+/// Any number of threads may [`insert_head`](Self::insert_head) (a CAS
+/// loop) and traverse under an [`epoch::Guard`] while one thread at a time
+/// removes; concurrent removers serialize on an internal flag. Removed nodes
+/// are freed after a grace period through an [`EpochReclaimer`].
+pub struct EpochSlist<'e, T> {
+    head: AtomicPtr<Node<T>>,
+    removing: AtomicBool,
+    alloc: EpochReclaimer<'e>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for EpochSlist<'_, T> {}
+unsafe impl<T: Send + Sync> Sync for EpochSlist<'_, T> {}
+
+struct Removing<'a>(&'a AtomicBool);
+
+impl Drop for Removing<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl<T> EpochSlist<'static, T> {
+    /// Creates an empty list reclaiming through [`epoch::global`].
+    pub fn new() -> Self {
+        Self::with_epoch(epoch::global())
+    }
+}
+
+impl<T> Default for EpochSlist<'static, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'e, T> EpochSlist<'e, T> {
+    const SIZE: usize = mem::size_of::<Node<T>>();
+
+    /// Creates an empty list reclaiming through `epoch`.
+    pub fn with_epoch(epoch: &'e Epoch) -> Self {
+        const { assert!(mem::align_of::<Node<T>>() <= MIN_ALIGN) };
+        EpochSlist {
+            head: AtomicPtr::new(ptr::null_mut()),
+            removing: AtomicBool::new(false),
+            alloc: DeferredAllocator::new(GlobalAllocator, epoch),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the epoch domain readers must pin in.
+    pub fn epoch(&self) -> &'e Epoch {
+        self.alloc.epoch()
+    }
+
+    /// Returns `true` if the list has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Inserts `value` at the front.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node cannot be allocated.
+    pub fn insert_head(&self, value: T) {
+        let node = self
+            .alloc
+            .malloc(Self::SIZE)
+            .expect("EpochSlist node allocation failed")
+            .cast::<Node<T>>()
+            .as_ptr();
+        let mut first = self.head.load(Ordering::Relaxed);
+        // SAFETY: the block is large and aligned enough and not yet shared.
+        unsafe {
+            node.write(Node {
+                next: AtomicPtr::new(first),
+                value,
+            })
+        };
+        loop {
+            match self
+                .head
+                .compare_exchange_weak(first, node, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(f) => {
+                    first = f;
+                    // SAFETY: still unpublished.
+                    unsafe { (*node).next.store(f, Ordering::Relaxed) };
+                }
+            }
+        }
+    }
+
+    /// Returns an iterator over the elements, valid while `guard` is pinned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `guard` belongs to a different epoch domain.
+    pub fn iter<'g>(&'g self, guard: &'g Guard<'_>) -> EpochSlistIter<'g, T> {
+        assert!(
+            ptr::eq(guard.epoch(), self.epoch()),
+            "guard pinned in another epoch domain"
+        );
+        EpochSlistIter {
+            cur: self.head.load(Ordering::Acquire),
+            _marker: PhantomData,
+        }
+    }
+
+    fn lock_removal(&self) -> Removing<'_> {
+        while self
+            .removing
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            pr::stall();
+        }
+        Removing(&self.removing)
+    }
+
+    /// Unlinks and returns the first element.
+    ///
+    /// See [`remove_first`](Self::remove_first) for when this blocks.
+    pub fn remove_head(&self) -> Option<T> {
+        self.remove_first(|_| true)
+    }
+
+    /// Unlinks and returns the first element matching `pred`.
+    ///
+    /// Readers may still hold a reference to the removed element, so for
+    /// types with drop glue this waits for a grace period before handing
+    /// the value back; never call it while pinned in the list's epoch.
+    /// Other types return at once and the node is freed later.
+    pub fn remove_first(&self, mut pred: impl FnMut(&T) -> bool) -> Option<T> {
+        let _removing = self.lock_removal();
+        let mut prev: *mut Node<T> = ptr::null_mut();
+        let mut cur = self.head.load(Ordering::Acquire);
+        // SAFETY: nodes are freed only by removers, and we are the only one,
+        // so every node reached here stays valid.
+        unsafe {
+            while !cur.is_null() && !pred(&(*cur).value) {
+                prev = cur;
+                cur = (*cur).next.load(Ordering::Acquire);
+            }
+            let node = NonNull::new(cur)?;
+            let next = (*cur).next.load(Ordering::Acquire);
+            if prev.is_null() {
+                // Inserters may have moved the head past `cur`; find it again.
+                // Nodes pushed since were published with Release, so the
+                // walk over them acquires too.
+                if let Err(mut p) =
+                    self.head
+                        .compare_exchange(cur, next, Ordering::Release, Ordering::Acquire)
+                {
+                    while (*p).next.load(Ordering::Acquire) != cur {
+                        p = (*p).next.load(Ordering::Acquire);
+                    }
+                    (*p).next.store(next, Ordering::Release);
+                }
+            } else {
+                (*prev).next.store(next, Ordering::Release);
+            }
+            if mem::needs_drop::<T>() {
+                self.epoch().synchronize();
+                let value = ptr::read(&(*cur).value);
+                self.alloc.free(node.cast(), Self::SIZE, false);
+                Some(value)
+            } else {
+                let value = ptr::read(&(*cur).value);
+                self.alloc.free(node.cast(), Self::SIZE, true);
+                Some(value)
+            }
+        }
+    }
+}
+
+impl<T> Drop for EpochSlist<'_, T> {
+    fn drop(&mut self) {
+        let mut p = *self.head.get_mut();
+        while let Some(node) = NonNull::new(p) {
+            // SAFETY: `&mut self` rules out readers and writers.
+            unsafe {
+                p = *(*p).next.get_mut();
+                ptr::drop_in_place(&mut (*node.as_ptr()).value);
+                self.alloc.free(node.cast(), Self::SIZE, false);
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for EpochSlist<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let local = self.epoch().register();
+        let guard = local.pin();
+        f.debug_list().entries(self.iter(&guard)).finish()
+    }
+}
+
+/// Iterator returned by [`EpochSlist::iter`].
+pub struct EpochSlistIter<'g, T> {
+    cur: *mut Node<T>,
+    _marker: PhantomData<&'g T>,
+}
+
+impl<'g, T> Iterator for EpochSlistIter<'g, T> {
+    type Item = &'g T;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'g T> {
+        // SAFETY: the guard keeps reachable nodes from being freed for 'g.
+        let node = unsafe { self.cur.as_ref()? };
+        self.cur = node.next.load(Ordering::Acquire);
+        Some(&node.value)
+    }
+}
+
+impl<T> core::iter::FusedIterator for EpochSlistIter<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::boxed::Box;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn insert_iter_remove() {
+        let epoch = Epoch::new();
+        let l = EpochSlist::with_epoch(&epoch);
+        for i in 0..5u32 {
+            l.insert_head(Box::new(i));
+        }
+        assert_eq!(l.remove_first(|v| **v == 2).as_deref(), Some(&2));
+        assert_eq!(l.remove_head().as_deref(), Some(&4));
+        let local = epoch.register();
+        let g = local.pin();
+        let v: Vec<u32> = l.iter(&g).map(|b| **b).collect();
+        assert_eq!(v, [3, 1, 0]);
+        assert_eq!(l.remove_first(|v| **v == 9), None);
+    }
+
+    #[test]
+    fn readers_during_writes() {
+        const N: u64 = 2000;
+        let epoch = Epoch::new();
+        let l = EpochSlist::with_epoch(&epoch);
+        thread::scope(|s| {
+            for t in 0..2 {
+                let l = &l;
+                s.spawn(move || {
+                    for i in 0..N {
+                        l.insert_head(t * N + i);
+                        if i % 3 == 0 {
+                            l.remove_head();
+                        }
+                    }
+                });
+            }
+            for _ in 0..2 {
+                let (l, epoch) = (&l, &epoch);
+                s.spawn(move || {
+                    let local = epoch.register();
+                    for _ in 0..200 {
+                        let g = local.pin();
+                        assert!(l.iter(&g).all(|&v| v < 2 * N));
+                    }
+                });
+            }
+        });
+        let local = epoch.register();
+        let g = local.pin();
+        assert_eq!(l.iter(&g).count() as u64, 2 * (N - N.div_ceil(3)));
+    }
+}
@@ -0,0 +1,255 @@
+//! Doubly-linked list (`CK_LIST`).
+//!
+//! As in BSD, each entry's `prev` points at the `next` slot that points to
+//! it (the head's `first` for the first element), so elements can be
+//! inserted next to or removed from any position in O(1) without the head.
+//! The first element points into the head, so a non-empty head must not be
+//! moved.
+
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Link embedded in each element of a [`ListHead`].
+#[derive(Debug)]
+pub struct ListEntry<T> {
+    next: AtomicPtr<T>,
+    prev: AtomicPtr<AtomicPtr<T>>,
+}
+
+impl<T> ListEntry<T> {
+    /// Creates an unlinked entry.
+    pub const fn new() -> Self {
+        ListEntry {
+            next: AtomicPtr::new(ptr::null_mut()),
+            prev: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the following element, or null.
+    #[inline]
+    pub fn next(&self) -> *mut T {
+        self.next.load(Ordering::Acquire)
+    }
+
+    #[inline(always)]
+    fn next_slot(&self) -> *mut AtomicPtr<T> {
+        &self.next as *const AtomicPtr<T> as *mut AtomicPtr<T>
+    }
+}
+
+impl<T> Default for ListEntry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Head of a doubly-linked list of `T`.
+#[derive(Debug)]
+pub struct ListHead<T> {
+    first: AtomicPtr<T>,
+}
+
+impl<T> Default for ListHead<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline(always)]
+unsafe fn link<'a, T>(elm: *mut T, field: fn(&T) -> &ListEntry<T>) -> &'a ListEntry<T> {
+    field(&*elm)
+}
+
+impl<T> ListHead<T> {
+    /// Creates an empty list.
+    pub const fn new() -> Self {
+        ListHead {
+            first: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns `true` if the list has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.first.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Returns the first element, or null.
+    #[inline]
+    pub fn first(&self) -> *mut T {
+        self.first.load(Ordering::Acquire)
+    }
+
+    /// Links `elm` into the slot `prev`, ahead of `next` (may be null).
+    #[inline(always)]
+    unsafe fn splice(
+        prev: *mut AtomicPtr<T>,
+        elm: *mut T,
+        next: *mut T,
+        field: fn(&T) -> &ListEntry<T>,
+    ) {
+        let e = link(elm, field);
+        e.next.store(next, Ordering::Relaxed);
+        e.prev.store(prev, Ordering::Relaxed);
+        if !next.is_null() {
+            link(next, field)
+                .prev
+                .store(e.next_slot(), Ordering::Relaxed);
+        }
+        (*prev).store(elm, Ordering::Release);
+    }
+
+    /// Inserts `elm` at the front.
+    ///
+    /// # Safety
+    ///
+    /// `elm` must be valid and on no list, every element on this list must
+    /// be valid, the head must not move while the list is non-empty, and
+    /// the caller must be the only writer.
+    #[inline]
+    pub unsafe fn insert_head(&self, elm: NonNull<T>, field: fn(&T) -> &ListEntry<T>) {
+        let slot = &self.first as *const AtomicPtr<T> as *mut AtomicPtr<T>;
+        Self::splice(
+            slot,
+            elm.as_ptr(),
+            self.first.load(Ordering::Relaxed),
+            field,
+        );
+    }
+
+    /// Inserts `elm` directly after `listelm`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`insert_head`](Self::insert_head), with `listelm` on a list.
+    #[inline]
+    pub unsafe fn insert_after(
+        listelm: NonNull<T>,
+        elm: NonNull<T>,
+        field: fn(&T) -> &ListEntry<T>,
+    ) {
+        let at = link(listelm.as_ptr(), field);
+        Self::splice(
+            at.next_slot(),
+            elm.as_ptr(),
+            at.next.load(Ordering::Relaxed),
+            field,
+        );
+    }
+
+    /// Inserts `elm` directly before `listelm`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`insert_after`](Self::insert_after).
+    #[inline]
+    pub unsafe fn insert_before(
+        listelm: NonNull<T>,
+        elm: NonNull<T>,
+        field: fn(&T) -> &ListEntry<T>,
+    ) {
+        let prev = link(listelm.as_ptr(), field).prev.load(Ordering::Relaxed);
+        Self::splice(prev, elm.as_ptr(), listelm.as_ptr(), field);
+    }
+
+    /// Unlinks `elm` from whatever list holds it.
+    ///
+    /// Readers positioned on `elm` can still follow its `next` link.
+    ///
+    /// # Safety
+    ///
+    /// `elm` must be on a list whose elements and head are valid, and the
+    /// caller must be the only writer.
+    #[inline]
+    pub unsafe fn remove(elm: NonNull<T>, field: fn(&T) -> &ListEntry<T>) {
+        let e = link(elm.as_ptr(), field);
+        let (prev, next) = (
+            e.prev.load(Ordering::Relaxed),
+            e.next.load(Ordering::Relaxed),
+        );
+        if !next.is_null() {
+            link(next, field).prev.store(prev, Ordering::Relaxed);
+        }
+        (*prev).store(next, Ordering::Release);
+    }
+
+    /// Calls `f` on each element in order.
+    ///
+    /// The successor is read before `f` runs, so `f` may unlink the element
+    /// it is given.
+    ///
+    /// # Safety
+    ///
+    /// Every element on the list must stay valid until visited.
+    pub unsafe fn foreach(&self, field: fn(&T) -> &ListEntry<T>, mut f: impl FnMut(NonNull<T>)) {
+        let mut cur = self.first();
+        while let Some(c) = NonNull::new(cur) {
+            cur = link(c.as_ptr(), field).next();
+            f(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::fixture::{self, items, values};
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    type Item = fixture::Item<ListEntry<()>>;
+
+    #[test]
+    fn insert_remove() {
+        let it: [Item; 4] = items();
+        let p = |i: usize| NonNull::from(&it[i]);
+        let h = ListHead::new();
+        unsafe {
+            h.insert_head(p(2), |e| &e.link);
+            h.insert_head(p(0), |e| &e.link);
+            ListHead::insert_after(p(2), p(3), |e| &e.link);
+            ListHead::insert_before(p(2), p(1), |e| &e.link);
+            assert_eq!(values(&h), [0, 1, 2, 3]);
+            ListHead::remove(p(0), |e| &e.link);
+            ListHead::remove(p(3), |e| &e.link);
+            assert_eq!(values(&h), [1, 2]);
+            ListHead::insert_before(p(1), p(0), |e| &e.link);
+            ListHead::remove(p(2), |e| &e.link);
+            ListHead::remove(p(1), |e| &e.link);
+            assert_eq!(values(&h), [0]);
+            ListHead::remove(p(0), |e| &e.link);
+        }
+        assert!(h.is_empty());
+    }
+
+    #[test]
+    fn readers_during_writes() {
+        let it: [Item; 8] = items();
+        let h = ListHead::new();
+        let stop = AtomicBool::new(false);
+        unsafe { h.insert_head(NonNull::from(&it[0]), |e| &e.link) };
+        thread::scope(|s| {
+            s.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    let v = values(&h);
+                    assert!(v.windows(2).all(|w| w[0] < w[1]), "{v:?}");
+                }
+            });
+            for _ in 0..2000 {
+                for i in 1..8 {
+                    unsafe {
+                        ListHead::insert_after(
+                            NonNull::from(&it[i - 1]),
+                            NonNull::from(&it[i]),
+                            |e| &e.link,
+                        )
+                    };
+                }
+                for i in (1..8).rev() {
+                    unsafe { ListHead::remove(NonNull::from(&it[i]), |e| &e.link) };
+                }
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+    }
+}
@@ -0,0 +1,103 @@
+//! BSD-style intrusive lists modelled on `ck_queue`.
+//!
+//! Each list links elements of type `T` through an entry field embedded in
+//! `T`. As with the C macros, operations that need to reach that field take
+//! it as an argument: a `fn(&T) -> &Entry<T>` projecting an element to its
+//! entry, for example `|e| &e.link`, or the `link` function of an adapter
+//! from [`define_slist_adapter!`](crate::define_slist_adapter) and friends.
+//!
+//! For SLIST, LIST and STAILQ, [`Iter`] and [`CursorMut`] wrap the pointer
+//! walking behind a single `unsafe` constructor.
+//!
+//! Lists follow the `ck_queue` concurrency contract: a single writer may
+//! mutate a list while any number of readers traverse it. Writers publish
+//! links with release stores and readers follow them with acquire loads.
+//! Everything else (several writers, freeing removed elements while readers
+//! may hold them) needs external synchronization and reclamation, which
+//! `EpochSlist` provides for an owning SLIST.
+
+mod adapter;
+mod circleq;
+mod cursor;
+#[cfg(feature = "alloc")]
+mod epoch_slist;
+mod list;
+mod slist;
+mod stailq;
+mod tailq;
+
+pub use self::circleq::{CircleqEntry, CircleqHead};
+pub use self::cursor::{CursorMut, Iter, Linked};
+#[cfg(feature = "alloc")]
+pub use self::epoch_slist::{EpochSlist, EpochSlistIter};
+pub use self::list::{ListEntry, ListHead};
+pub use self::slist::{SlistEntry, SlistHead};
+pub use self::stailq::{StailqEntry, StailqHead};
+pub use self::tailq::{TailqEntry, TailqHead};
+
+#[cfg(test)]
+mod fixture {
+    //! Elements shared by the list tests, generic over the entry flavour.
+
+    use super::*;
+    use std::vec::Vec;
+
+    /// Names the entry type an [`Item`] embeds; implemented on each entry
+    /// type for any parameter, so tests write `Item<SlistEntry<()>>`.
+    pub(super) trait Entry {
+        type Of<T>: Default;
+    }
+
+    /// A list element holding `value` and one `link` of flavour `E`.
+    pub(super) struct Item<E: Entry> {
+        pub(super) value: u32,
+        pub(super) link: E::Of<Item<E>>,
+    }
+
+    /// Returns `N` unlinked items valued `0..N`.
+    pub(super) fn items<E: Entry, const N: usize>() -> [Item<E>; N] {
+        core::array::from_fn(|i| Item {
+            value: i as u32,
+            link: Default::default(),
+        })
+    }
+
+    /// A head [`values`] can walk.
+    pub(super) trait Walk {
+        fn values(&self) -> Vec<u32>;
+    }
+
+    /// Returns the values on `h` front to back. Doubly linked heads are
+    /// also walked back to front, and the two orders must agree.
+    pub(super) fn values(h: &impl Walk) -> Vec<u32> {
+        h.values()
+    }
+
+    macro_rules! fixture {
+        ($head:ident, $entry:ident $(, $reverse:ident)?) => {
+            impl<U> Entry for $entry<U> {
+                type Of<T> = $entry<T>;
+            }
+
+            impl Walk for $head<Item<$entry<()>>> {
+                fn values(&self) -> Vec<u32> {
+                    let mut v = Vec::new();
+                    unsafe { self.foreach(|e| &e.link, |e| v.push(e.as_ref().value)) };
+                    $(
+                        let mut b = Vec::new();
+                        unsafe { self.$reverse(|e| &e.link, |e| b.push(e.as_ref().value)) };
+                        b.reverse();
+                        assert_eq!(v, b);
+                    )?
+                    v
+                }
+            }
+        };
+    }
+
+    fixture!(SlistHead, SlistEntry);
+    fixture!(ListHead, ListEntry);
+    fixture!(StailqHead, StailqEntry);
+    fixture!(TailqHead, TailqEntry, foreach_reverse);
+    fixture!(CircleqHead, CircleqEntry, foreach_reverse);
+}
@@ -0,0 +1,229 @@
+//! Singly-linked list (`CK_SLIST`).
+
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Link embedded in each element of an [`SlistHead`].
+#[derive(Debug)]
+pub struct SlistEntry<T> {
+    next: AtomicPtr<T>,
+}
+
+impl<T> SlistEntry<T> {
+    /// Creates an unlinked entry.
+    pub const fn new() -> Self {
+        SlistEntry {
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the following element, or null.
+    #[inline]
+    pub fn next(&self) -> *mut T {
+        self.next.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Default for SlistEntry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Head of a singly-linked list of `T`.
+#[derive(Debug)]
+pub struct SlistHead<T> {
+    first: AtomicPtr<T>,
+}
+
+impl<T> Default for SlistHead<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline(always)]
+unsafe fn link<'a, T>(elm: *mut T, field: fn(&T) -> &SlistEntry<T>) -> &'a SlistEntry<T> {
+    field(&*elm)
+}
+
+impl<T> SlistHead<T> {
+    /// Creates an empty list.
+    pub const fn new() -> Self {
+        SlistHead {
+            first: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns `true` if the list has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.first.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Returns the first element, or null.
+    #[inline]
+    pub fn first(&self) -> *mut T {
+        self.first.load(Ordering::Acquire)
+    }
+
+    /// Inserts `elm` at the front.
+    ///
+    /// # Safety
+    ///
+    /// `elm` must be valid and on no list; the caller must be the only
+    /// writer.
+    #[inline]
+    pub unsafe fn insert_head(&self, elm: NonNull<T>, field: fn(&T) -> &SlistEntry<T>) {
+        let elm = elm.as_ptr();
+        link(elm, field)
+            .next
+            .store(self.first.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.first.store(elm, Ordering::Release);
+    }
+
+    /// Inserts `elm` directly after `listelm`.
+    ///
+    /// # Safety
+    ///
+    /// `listelm` must be on a list and `elm` valid and on none; the caller
+    /// must be the only writer.
+    #[inline]
+    pub unsafe fn insert_after(
+        listelm: NonNull<T>,
+        elm: NonNull<T>,
+        field: fn(&T) -> &SlistEntry<T>,
+    ) {
+        let at = link(listelm.as_ptr(), field);
+        link(elm.as_ptr(), field)
+            .next
+            .store(at.next.load(Ordering::Relaxed), Ordering::Relaxed);
+        at.next.store(elm.as_ptr(), Ordering::Release);
+    }
+
+    /// Unlinks and returns the first element.
+    ///
+    /// # Safety
+    ///
+    /// Every element on the list must be valid; the caller must be the only
+    /// writer.
+    #[inline]
+    pub unsafe fn remove_head(&self, field: fn(&T) -> &SlistEntry<T>) -> Option<NonNull<T>> {
+        let first = NonNull::new(self.first.load(Ordering::Relaxed))?;
+        self.first.store(
+            link(first.as_ptr(), field).next.load(Ordering::Relaxed),
+            Ordering::Release,
+        );
+        Some(first)
+    }
+
+    /// Unlinks the element following `elm`, returning it.
+    ///
+    /// # Safety
+    ///
+    /// As for [`remove_head`](Self::remove_head), with `elm` on the list.
+    #[inline]
+    pub unsafe fn remove_after(
+        elm: NonNull<T>,
+        field: fn(&T) -> &SlistEntry<T>,
+    ) -> Option<NonNull<T>> {
+        let at = link(elm.as_ptr(), field);
+        let next = NonNull::new(at.next.load(Ordering::Relaxed))?;
+        at.next.store(
+            link(next.as_ptr(), field).next.load(Ordering::Relaxed),
+            Ordering::Release,
+        );
+        Some(next)
+    }
+
+    /// Unlinks `elm`, searching from the front. Returns `false` if `elm` is
+    /// not on the list.
+    ///
+    /// # Safety
+    ///
+    /// As for [`remove_head`](Self::remove_head).
+    pub unsafe fn remove(&self, elm: NonNull<T>, field: fn(&T) -> &SlistEntry<T>) -> bool {
+        let mut cur = self.first.load(Ordering::Relaxed);
+        if cur == elm.as_ptr() {
+            self.remove_head(field);
+            return true;
+        }
+        while let Some(c) = NonNull::new(cur) {
+            let next = link(c.as_ptr(), field).next.load(Ordering::Relaxed);
+            if next == elm.as_ptr() {
+                Self::remove_after(c, field);
+                return true;
+            }
+            cur = next;
+        }
+        false
+    }
+
+    /// Calls `f` on each element in order.
+    ///
+    /// The successor is read before `f` runs, so `f` may unlink or reuse
+    /// the element it is given (`CK_SLIST_FOREACH_SAFE`).
+    ///
+    /// # Safety
+    ///
+    /// Every element on the list must stay valid until visited.
+    pub unsafe fn foreach(&self, field: fn(&T) -> &SlistEntry<T>, mut f: impl FnMut(NonNull<T>)) {
+        let mut cur = self.first();
+        while let Some(c) = NonNull::new(cur) {
+            cur = link(c.as_ptr(), field).next();
+            f(c);
+        }
+    }
+
+    /// Exchanges the contents of two lists.
+    ///
+    /// Exclusive access is required because readers could otherwise see
+    /// the lists half-swapped.
+    #[inline]
+    pub fn swap(&mut self, other: &mut Self) {
+        core::mem::swap(self.first.get_mut(), other.first.get_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::fixture::{self, items, values};
+
+    type Item = fixture::Item<SlistEntry<()>>;
+
+    #[test]
+    fn insert_and_remove() {
+        let it: [Item; 4] = items();
+        let p = |i: usize| NonNull::from(&it[i]);
+        let h = SlistHead::new();
+        assert!(h.is_empty());
+        unsafe {
+            h.insert_head(p(0), |e| &e.link);
+            h.insert_head(p(2), |e| &e.link);
+            SlistHead::insert_after(p(0), p(3), |e| &e.link);
+            SlistHead::insert_after(p(2), p(1), |e| &e.link);
+            assert_eq!(values(&h), [2, 1, 0, 3]);
+            assert!(h.remove(p(0), |e| &e.link));
+            assert!(!h.remove(p(0), |e| &e.link));
+            assert_eq!(SlistHead::remove_after(p(1), |e| &e.link), Some(p(3)));
+            assert_eq!(h.remove_head(|e| &e.link), Some(p(2)));
+            assert_eq!(values(&h), [1]);
+            assert!(h.remove(p(1), |e| &e.link));
+            assert_eq!(h.remove_head(|e| &e.link), None);
+        }
+        assert!(h.first().is_null());
+    }
+
+    #[test]
+    fn swap_lists() {
+        let it: [Item; 2] = items();
+        let (mut a, mut b) = (SlistHead::new(), SlistHead::new());
+        unsafe {
+            a.insert_head(NonNull::from(&it[0]), |e| &e.link);
+            b.insert_head(NonNull::from(&it[1]), |e| &e.link);
+        }
+        a.swap(&mut b);
+        assert_eq!((values(&a), values(&b)), (std::vec![1], std::vec![0]));
+    }
+}
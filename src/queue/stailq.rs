@@ -0,0 +1,319 @@
+//! Singly-linked tail queue (`CK_STAILQ`).
+//!
+//! The head tracks the last element directly rather than its `next` slot,
+//! so heads stay movable. Readers may traverse concurrently with one
+//! writer.
+
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Link embedded in each element of a [`StailqHead`].
+#[derive(Debug)]
+pub struct StailqEntry<T> {
+    next: AtomicPtr<T>,
+}
+
+impl<T> StailqEntry<T> {
+    /// Creates an unlinked entry.
+    pub const fn new() -> Self {
+        StailqEntry {
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the following element, or null.
+    #[inline]
+    pub fn next(&self) -> *mut T {
+        self.next.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Default for StailqEntry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Head of a singly-linked tail queue of `T`.
+#[derive(Debug)]
+pub struct StailqHead<T> {
+    first: AtomicPtr<T>,
+    last: AtomicPtr<T>,
+}
+
+impl<T> Default for StailqHead<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline(always)]
+unsafe fn link<'a, T>(elm: *mut T, field: fn(&T) -> &StailqEntry<T>) -> &'a StailqEntry<T> {
+    field(&*elm)
+}
+
+impl<T> StailqHead<T> {
+    /// Creates an empty queue.
+    pub const fn new() -> Self {
+        StailqHead {
+            first: AtomicPtr::new(ptr::null_mut()),
+            last: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns `true` if the queue has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.first.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Returns the first element, or null.
+    #[inline]
+    pub fn first(&self) -> *mut T {
+        self.first.load(Ordering::Acquire)
+    }
+
+    /// Returns the last element, or null.
+    ///
+    /// Only meaningful to the writer.
+    #[inline]
+    pub fn last(&self) -> *mut T {
+        self.last.load(Ordering::Relaxed)
+    }
+
+    #[inline(always)]
+    unsafe fn next_slot(&self, prev: *mut T, field: fn(&T) -> &StailqEntry<T>) -> &AtomicPtr<T> {
+        if prev.is_null() {
+            &self.first
+        } else {
+            &link(prev, field).next
+        }
+    }
+
+    /// Inserts `elm` at the front.
+    ///
+    /// # Safety
+    ///
+    /// `elm` must be valid and on no queue, every element on this queue
+    /// must be valid, and the caller must be the only writer.
+    #[inline]
+    pub unsafe fn insert_head(&self, elm: NonNull<T>, field: fn(&T) -> &StailqEntry<T>) {
+        let first = self.first.load(Ordering::Relaxed);
+        link(elm.as_ptr(), field)
+            .next
+            .store(first, Ordering::Relaxed);
+        if first.is_null() {
+            self.last.store(elm.as_ptr(), Ordering::Relaxed);
+        }
+        self.first.store(elm.as_ptr(), Ordering::Release);
+    }
+
+    /// Inserts `elm` at the back.
+    ///
+    /// # Safety
+    ///
+    /// As for [`insert_head`](Self::insert_head).
+    #[inline]
+    pub unsafe fn insert_tail(&self, elm: NonNull<T>, field: fn(&T) -> &StailqEntry<T>) {
+        link(elm.as_ptr(), field)
+            .next
+            .store(ptr::null_mut(), Ordering::Relaxed);
+        let last = self.last.load(Ordering::Relaxed);
+        self.next_slot(last, field)
+            .store(elm.as_ptr(), Ordering::Release);
+        self.last.store(elm.as_ptr(), Ordering::Relaxed);
+    }
+
+    /// Inserts `elm` directly after `listelm`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`insert_head`](Self::insert_head), with `listelm` on this
+    /// queue.
+    #[inline]
+    pub unsafe fn insert_after(
+        &self,
+        listelm: NonNull<T>,
+        elm: NonNull<T>,
+        field: fn(&T) -> &StailqEntry<T>,
+    ) {
+        let at = link(listelm.as_ptr(), field);
+        let next = at.next.load(Ordering::Relaxed);
+        link(elm.as_ptr(), field)
+            .next
+            .store(next, Ordering::Relaxed);
+        at.next.store(elm.as_ptr(), Ordering::Release);
+        if next.is_null() {
+            self.last.store(elm.as_ptr(), Ordering::Relaxed);
+        }
+    }
+
+    /// Unlinks and returns the first element.
+    ///
+    /// # Safety
+    ///
+    /// Every element on the queue must be valid, and the caller must be the
+    /// only writer.
+    #[inline]
+    pub unsafe fn remove_head(&self, field: fn(&T) -> &StailqEntry<T>) -> Option<NonNull<T>> {
+        let first = NonNull::new(self.first.load(Ordering::Relaxed))?;
+        let next = link(first.as_ptr(), field).next.load(Ordering::Relaxed);
+        self.first.store(next, Ordering::Release);
+        if next.is_null() {
+            self.last.store(ptr::null_mut(), Ordering::Relaxed);
+        }
+        Some(first)
+    }
+
+    /// Unlinks the element following `elm` (the first element if `elm` is
+    /// `None`), returning it.
+    ///
+    /// # Safety
+    ///
+    /// As for [`remove_head`](Self::remove_head), with `elm` on this queue.
+    #[inline]
+    pub unsafe fn remove_after(
+        &self,
+        elm: Option<NonNull<T>>,
+        field: fn(&T) -> &StailqEntry<T>,
+    ) -> Option<NonNull<T>> {
+        let prev = elm.map_or(ptr::null_mut(), NonNull::as_ptr);
+        let slot = self.next_slot(prev, field);
+        let victim = NonNull::new(slot.load(Ordering::Relaxed))?;
+        let next = link(victim.as_ptr(), field).next.load(Ordering::Relaxed);
+        slot.store(next, Ordering::Release);
+        if next.is_null() {
+            self.last.store(prev, Ordering::Relaxed);
+        }
+        Some(victim)
+    }
+
+    /// Unlinks `elm`, searching from the front. Returns `false` if `elm` is
+    /// not on the queue.
+    ///
+    /// # Safety
+    ///
+    /// As for [`remove_head`](Self::remove_head).
+    pub unsafe fn remove(&self, elm: NonNull<T>, field: fn(&T) -> &StailqEntry<T>) -> bool {
+        let mut prev = None;
+        let mut cur = self.first.load(Ordering::Relaxed);
+        while let Some(c) = NonNull::new(cur) {
+            if c == elm {
+                self.remove_after(prev, field);
+                return true;
+            }
+            prev = Some(c);
+            cur = link(cur, field).next.load(Ordering::Relaxed);
+        }
+        false
+    }
+
+    /// Moves every element of `other` to the back of this queue.
+    ///
+    /// # Safety
+    ///
+    /// Every element on both queues must be valid, and the caller must be
+    /// the only writer of both.
+    pub unsafe fn concat(&self, other: &Self, field: fn(&T) -> &StailqEntry<T>) {
+        let first = other.first.load(Ordering::Relaxed);
+        if first.is_null() {
+            return;
+        }
+        let last = self.last.load(Ordering::Relaxed);
+        self.next_slot(last, field).store(first, Ordering::Release);
+        self.last
+            .store(other.last.load(Ordering::Relaxed), Ordering::Relaxed);
+        other.first.store(ptr::null_mut(), Ordering::Release);
+        other.last.store(ptr::null_mut(), Ordering::Relaxed);
+    }
+
+    /// Moves every element of `other` to the front of this queue.
+    ///
+    /// # Safety
+    ///
+    /// As for [`concat`](Self::concat).
+    pub unsafe fn splice(&self, other: &Self, field: fn(&T) -> &StailqEntry<T>) {
+        let (first, last) = (
+            other.first.load(Ordering::Relaxed),
+            other.last.load(Ordering::Relaxed),
+        );
+        if first.is_null() {
+            return;
+        }
+        let old = self.first.load(Ordering::Relaxed);
+        link(last, field).next.store(old, Ordering::Relaxed);
+        if old.is_null() {
+            self.last.store(last, Ordering::Relaxed);
+        }
+        self.first.store(first, Ordering::Release);
+        other.first.store(ptr::null_mut(), Ordering::Release);
+        other.last.store(ptr::null_mut(), Ordering::Relaxed);
+    }
+
+    /// Calls `f` on each element in order.
+    ///
+    /// The successor is read before `f` runs, so `f` may unlink the element
+    /// it is given.
+    ///
+    /// # Safety
+    ///
+    /// Every element on the queue must stay valid until visited.
+    pub unsafe fn foreach(&self, field: fn(&T) -> &StailqEntry<T>, mut f: impl FnMut(NonNull<T>)) {
+        let mut cur = self.first();
+        while let Some(c) = NonNull::new(cur) {
+            cur = link(c.as_ptr(), field).next();
+            f(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::fixture::{self, items, values};
+
+    type Item = fixture::Item<StailqEntry<()>>;
+
+    #[test]
+    fn insert_remove_keeps_last() {
+        let it: [Item; 4] = items();
+        let p = |i: usize| NonNull::from(&it[i]);
+        let h = StailqHead::new();
+        unsafe {
+            h.insert_tail(p(1), |e| &e.link);
+            h.insert_head(p(0), |e| &e.link);
+            h.insert_after(p(1), p(3), |e| &e.link);
+            h.insert_after(p(1), p(2), |e| &e.link);
+            assert_eq!(values(&h), [0, 1, 2, 3]);
+            assert_eq!(h.last(), p(3).as_ptr());
+            assert!(h.remove(p(3), |e| &e.link));
+            assert_eq!(h.last(), p(2).as_ptr());
+            assert_eq!(h.remove_after(Some(p(0)), |e| &e.link), Some(p(1)));
+            assert_eq!(h.remove_head(|e| &e.link), Some(p(0)));
+            assert_eq!(h.remove_head(|e| &e.link), Some(p(2)));
+            assert!(h.last().is_null());
+            h.insert_tail(p(3), |e| &e.link);
+            assert_eq!(values(&h), [3]);
+        }
+    }
+
+    #[test]
+    fn concat_and_splice() {
+        let it: [Item; 6] = items();
+        let p = |i: usize| NonNull::from(&it[i]);
+        let (a, b) = (StailqHead::<Item>::new(), StailqHead::new());
+        unsafe {
+            a.insert_tail(p(2), |e| &e.link);
+            b.insert_tail(p(3), |e| &e.link);
+            b.insert_tail(p(4), |e| &e.link);
+            a.concat(&b, |e| &e.link);
+            assert!(b.is_empty());
+            b.insert_tail(p(0), |e| &e.link);
+            b.insert_tail(p(1), |e| &e.link);
+            a.splice(&b, |e| &e.link);
+            a.insert_tail(p(5), |e| &e.link);
+        }
+        assert_eq!(values(&a), [0, 1, 2, 3, 4, 5]);
+        assert!(b.is_empty() && b.last().is_null());
+    }
+}
@@ -0,0 +1,316 @@
+//! Doubly-linked tail queue (`CK_TAILQ`).
+//!
+//! Unlike the BSD macros, `prev` points at the previous element rather than
+//! at its `next` field, so a head can be moved even while the queue is
+//! non-empty. Readers may traverse forward concurrently with one writer.
+
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Link embedded in each element of a [`TailqHead`].
+#[derive(Debug)]
+pub struct TailqEntry<T> {
+    next: AtomicPtr<T>,
+    prev: AtomicPtr<T>,
+}
+
+impl<T> TailqEntry<T> {
+    /// Creates an unlinked entry.
+    pub const fn new() -> Self {
+        TailqEntry {
+            next: AtomicPtr::new(ptr::null_mut()),
+            prev: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the following element, or null.
+    #[inline]
+    pub fn next(&self) -> *mut T {
+        self.next.load(Ordering::Acquire)
+    }
+
+    /// Returns the preceding element, or null.
+    ///
+    /// Only meaningful to the writer.
+    #[inline]
+    pub fn prev(&self) -> *mut T {
+        self.prev.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Default for TailqEntry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Head of a tail queue of `T`.
+#[derive(Debug)]
+pub struct TailqHead<T> {
+    first: AtomicPtr<T>,
+    last: AtomicPtr<T>,
+}
+
+impl<T> Default for TailqHead<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline(always)]
+unsafe fn link<'a, T>(elm: *mut T, field: fn(&T) -> &TailqEntry<T>) -> &'a TailqEntry<T> {
+    field(&*elm)
+}
+
+impl<T> TailqHead<T> {
+    /// Creates an empty queue.
+    pub const fn new() -> Self {
+        TailqHead {
+            first: AtomicPtr::new(ptr::null_mut()),
+            last: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns `true` if the queue has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.first.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Returns the first element, or null.
+    #[inline]
+    pub fn first(&self) -> *mut T {
+        self.first.load(Ordering::Acquire)
+    }
+
+    /// Returns the last element, or null.
+    ///
+    /// Only meaningful to the writer.
+    #[inline]
+    pub fn last(&self) -> *mut T {
+        self.last.load(Ordering::Relaxed)
+    }
+
+    /// The `next` slot that points at `elm`'s successor position after
+    /// `prev`: the head's `first` when `prev` is null.
+    #[inline(always)]
+    unsafe fn next_slot(&self, prev: *mut T, field: fn(&T) -> &TailqEntry<T>) -> &AtomicPtr<T> {
+        if prev.is_null() {
+            &self.first
+        } else {
+            &link(prev, field).next
+        }
+    }
+
+    /// Links `elm` between `prev` and `next` (either may be null).
+    #[inline(always)]
+    unsafe fn splice(
+        &self,
+        prev: *mut T,
+        elm: *mut T,
+        next: *mut T,
+        field: fn(&T) -> &TailqEntry<T>,
+    ) {
+        let e = link(elm, field);
+        e.next.store(next, Ordering::Relaxed);
+        e.prev.store(prev, Ordering::Relaxed);
+        self.next_slot(prev, field).store(elm, Ordering::Release);
+        if next.is_null() {
+            self.last.store(elm, Ordering::Relaxed);
+        } else {
+            link(next, field).prev.store(elm, Ordering::Relaxed);
+        }
+    }
+
+    /// Inserts `elm` at the front.
+    ///
+    /// # Safety
+    ///
+    /// `elm` must be valid and on no queue, every element on this queue
+    /// must be valid, and the caller must be the only writer.
+    #[inline]
+    pub unsafe fn insert_head(&self, elm: NonNull<T>, field: fn(&T) -> &TailqEntry<T>) {
+        self.splice(
+            ptr::null_mut(),
+            elm.as_ptr(),
+            self.first.load(Ordering::Relaxed),
+            field,
+        );
+    }
+
+    /// Inserts `elm` at the back.
+    ///
+    /// # Safety
+    ///
+    /// As for [`insert_head`](Self::insert_head).
+    #[inline]
+    pub unsafe fn insert_tail(&self, elm: NonNull<T>, field: fn(&T) -> &TailqEntry<T>) {
+        self.splice(
+            self.last.load(Ordering::Relaxed),
+            elm.as_ptr(),
+            ptr::null_mut(),
+            field,
+        );
+    }
+
+    /// Inserts `elm` directly after `listelm`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`insert_head`](Self::insert_head), with `listelm` on this
+    /// queue.
+    #[inline]
+    pub unsafe fn insert_after(
+        &self,
+        listelm: NonNull<T>,
+        elm: NonNull<T>,
+        field: fn(&T) -> &TailqEntry<T>,
+    ) {
+        let next = link(listelm.as_ptr(), field).next.load(Ordering::Relaxed);
+        self.splice(listelm.as_ptr(), elm.as_ptr(), next, field);
+    }
+
+    /// Inserts `elm` directly before `listelm`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`insert_after`](Self::insert_after).
+    #[inline]
+    pub unsafe fn insert_before(
+        &self,
+        listelm: NonNull<T>,
+        elm: NonNull<T>,
+        field: fn(&T) -> &TailqEntry<T>,
+    ) {
+        let prev = link(listelm.as_ptr(), field).prev.load(Ordering::Relaxed);
+        self.splice(prev, elm.as_ptr(), listelm.as_ptr(), field);
+    }
+
+    /// Unlinks `elm`.
+    ///
+    /// Readers positioned on `elm` can still follow its `next` link.
+    ///
+    /// # Safety
+    ///
+    /// `elm` must be on this queue, every element on it must be valid, and
+    /// the caller must be the only writer.
+    #[inline]
+    pub unsafe fn remove(&self, elm: NonNull<T>, field: fn(&T) -> &TailqEntry<T>) {
+        let e = link(elm.as_ptr(), field);
+        let (prev, next) = (
+            e.prev.load(Ordering::Relaxed),
+            e.next.load(Ordering::Relaxed),
+        );
+        self.next_slot(prev, field).store(next, Ordering::Release);
+        if next.is_null() {
+            self.last.store(prev, Ordering::Relaxed);
+        } else {
+            link(next, field).prev.store(prev, Ordering::Relaxed);
+        }
+    }
+
+    /// Moves every element of `other` to the back of this queue.
+    ///
+    /// # Safety
+    ///
+    /// Every element on both queues must be valid, and the caller must be
+    /// the only writer of both.
+    pub unsafe fn concat(&self, other: &Self, field: fn(&T) -> &TailqEntry<T>) {
+        let first = other.first.load(Ordering::Relaxed);
+        if first.is_null() {
+            return;
+        }
+        let last = self.last.load(Ordering::Relaxed);
+        link(first, field).prev.store(last, Ordering::Relaxed);
+        self.next_slot(last, field).store(first, Ordering::Release);
+        self.last
+            .store(other.last.load(Ordering::Relaxed), Ordering::Relaxed);
+        other.first.store(ptr::null_mut(), Ordering::Release);
+        other.last.store(ptr::null_mut(), Ordering::Relaxed);
+    }
+
+    /// Calls `f` on each element from front to back.
+    ///
+    /// The successor is read before `f` runs, so `f` may unlink the element
+    /// it is given.
+    ///
+    /// # Safety
+    ///
+    /// Every element on the queue must stay valid until visited.
+    pub unsafe fn foreach(&self, field: fn(&T) -> &TailqEntry<T>, mut f: impl FnMut(NonNull<T>)) {
+        let mut cur = self.first();
+        while let Some(c) = NonNull::new(cur) {
+            cur = link(c.as_ptr(), field).next();
+            f(c);
+        }
+    }
+
+    /// Calls `f` on each element from back to front.
+    ///
+    /// # Safety
+    ///
+    /// As for [`foreach`](Self::foreach); only the writer may traverse
+    /// backwards.
+    pub unsafe fn foreach_reverse(
+        &self,
+        field: fn(&T) -> &TailqEntry<T>,
+        mut f: impl FnMut(NonNull<T>),
+    ) {
+        let mut cur = self.last();
+        while let Some(c) = NonNull::new(cur) {
+            cur = link(c.as_ptr(), field).prev();
+            f(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::fixture::{self, items, values};
+
+    type Item = fixture::Item<TailqEntry<()>>;
+
+    #[test]
+    fn insert_remove() {
+        let it: [Item; 5] = items();
+        let p = |i: usize| NonNull::from(&it[i]);
+        let h = TailqHead::new();
+        unsafe {
+            h.insert_tail(p(1), |e| &e.link);
+            h.insert_head(p(0), |e| &e.link);
+            h.insert_tail(p(4), |e| &e.link);
+            h.insert_before(p(4), p(3), |e| &e.link);
+            h.insert_after(p(1), p(2), |e| &e.link);
+            assert_eq!(values(&h), [0, 1, 2, 3, 4]);
+            h.remove(p(0), |e| &e.link);
+            h.remove(p(4), |e| &e.link);
+            h.remove(p(2), |e| &e.link);
+            assert_eq!(values(&h), [1, 3]);
+            assert_eq!(h.last(), p(3).as_ptr());
+            h.remove(p(1), |e| &e.link);
+            h.remove(p(3), |e| &e.link);
+        }
+        assert!(h.is_empty() && h.last().is_null());
+    }
+
+    #[test]
+    fn concat_queues() {
+        let it: [Item; 4] = items();
+        let p = |i: usize| NonNull::from(&it[i]);
+        let (a, b) = (TailqHead::<Item>::new(), TailqHead::new());
+        unsafe {
+            a.concat(&b, |e| &e.link);
+            assert!(a.is_empty());
+            b.insert_tail(p(2), |e| &e.link);
+            b.insert_tail(p(3), |e| &e.link);
+            a.concat(&b, |e| &e.link);
+            assert_eq!(values(&a), [2, 3]);
+            b.insert_tail(p(0), |e| &e.link);
+            b.insert_tail(p(1), |e| &e.link);
+            b.concat(&a, |e| &e.link);
+        }
+        assert!(a.is_empty());
+        assert_eq!(values(&b), [0, 1, 2, 3]);
+    }
+}
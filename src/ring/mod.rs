@@ -0,0 +1,447 @@
+//! Bounded FIFO rings modelled on `ck_ring`.
+//!
+//! A ring of `N` slots (a power of two) holds up to `N - 1` values. The
+//! producer publishes through a tail counter and the consumer retires
+//! through a head counter, each on its own cache line, so a side that is
+//! not shared pays for no atomic read-modify-write:
+//!
+//! | type          | producers | consumers |
+//! |---------------|-----------|-----------|
+//! | [`SpscRing`]  | one       | one       |
+//! | [`SpmcRing`]  | one       | many      |
+//! | [`MpscRing`]  | many      | one       |
+//!
+//! Operations on a side that is not shared are `unsafe`: the caller promises
+//! that only one thread performs them at a time.
+//!
+//! Unlike `ck_ring`, whose consumers copy a slot before claiming it and so
+//! race with the producer overwriting it, consumers here claim first and
+//! then stamp the slot once the value is out. A producer does not reuse a
+//! slot until it is stamped, so a consumer stalled between the two makes
+//! the ring look full at that slot.
+//!
+//! [`PtrRing`] carries raw pointers through the same rings with `ck_ring`'s C
+//! function set, for code ported from C.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::cc::{likely, unlikely, CachePadded};
+use crate::pr;
+
+mod mpsc;
+mod ptr_ring;
+mod spmc;
+mod spsc;
+
+pub use self::mpsc::MpscRing;
+pub use self::ptr_ring::PtrRing;
+pub use self::spmc::SpmcRing;
+pub use self::spsc::{SpscRing, WriteChunk};
+
+/// Counters and storage shared by every ring flavour.
+struct Ring<T, const N: usize> {
+    c_head: CachePadded<AtomicUsize>,
+    p_tail: CachePadded<AtomicUsize>,
+    p_head: CachePadded<AtomicUsize>,
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    // Per slot, the lap (index with the low bits clear) whose producer may
+    // write it next.
+    stamps: [AtomicUsize; N],
+}
+
+unsafe impl<T: Send, const N: usize> Send for Ring<T, N> {}
+unsafe impl<T: Send, const N: usize> Sync for Ring<T, N> {}
+
+impl<T, const N: usize> Ring<T, N> {
+    const MASK: usize = N - 1;
+
+    const fn new() -> Self {
+        const {
+            assert!(
+                N >= 2 && N.is_power_of_two(),
+                "ring size must be a power of two"
+            )
+        };
+        Ring {
+            c_head: CachePadded::new(AtomicUsize::new(0)),
+            p_tail: CachePadded::new(AtomicUsize::new(0)),
+            p_head: CachePadded::new(AtomicUsize::new(0)),
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            stamps: [const { AtomicUsize::new(0) }; N],
+        }
+    }
+
+    /// Number of values between the head and the tail.
+    #[inline]
+    fn len(&self) -> usize {
+        let consumer = self.c_head.load(Ordering::Relaxed);
+        let producer = self.p_tail.load(Ordering::Relaxed);
+        producer.wrapping_sub(consumer).min(Self::MASK)
+    }
+
+    #[inline(always)]
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.slots[index & Self::MASK].get()
+    }
+
+    /// Returns `true` once the consumer of the slot's previous lap has
+    /// finished reading it, so the producer at `producer` may write it.
+    #[inline(always)]
+    fn is_free(&self, producer: usize) -> bool {
+        self.stamps[producer & Self::MASK].load(Ordering::Acquire) == producer & !Self::MASK
+    }
+
+    /// Hands the slot read at `consumer` back to the producers.
+    #[inline(always)]
+    fn release(&self, consumer: usize) {
+        self.stamps[consumer & Self::MASK]
+            .store((consumer & !Self::MASK).wrapping_add(N), Ordering::Release);
+    }
+
+    /// Single-producer enqueue, returning the number of values the ring
+    /// held beforehand.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may enqueue concurrently.
+    #[inline]
+    unsafe fn enqueue_sp(&self, value: T) -> Result<usize, T> {
+        let consumer = self.c_head.load(Ordering::Acquire);
+        let producer = self.p_tail.load(Ordering::Relaxed);
+        let delta = producer.wrapping_add(1);
+        if unlikely(delta & Self::MASK == consumer & Self::MASK || !self.is_free(producer)) {
+            return Err(value);
+        }
+        (*self.slot(producer)).write(value);
+        self.p_tail.store(delta, Ordering::Release);
+        Ok(producer.wrapping_sub(consumer))
+    }
+
+    /// Single-producer batch enqueue: fills free slots from `iter`, pulling
+    /// only as many values as fit, and publishes them with one store.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may enqueue concurrently.
+    #[inline]
+    unsafe fn extend_sp(&self, mut iter: impl Iterator<Item = T>) -> usize {
+        let consumer = self.c_head.load(Ordering::Acquire);
+        let producer = self.p_tail.load(Ordering::Relaxed);
+        let free = Self::MASK - producer.wrapping_sub(consumer);
+        let mut n = 0;
+        while n < free && self.is_free(producer.wrapping_add(n)) {
+            let Some(value) = iter.next() else { break };
+            (*self.slot(producer.wrapping_add(n))).write(value);
+            n += 1;
+        }
+        if n != 0 {
+            self.p_tail
+                .store(producer.wrapping_add(n), Ordering::Release);
+        }
+        n
+    }
+
+    /// Reserves up to `n` contiguous free slots for a single producer,
+    /// returning the first slot, the producer index and the slot count.
+    ///
+    /// Only for single-consumer rings, whose consumer stamps a slot before
+    /// moving `c_head` past it, so every slot counted free is stamped.
+    #[inline]
+    fn reserve_sp(&self, n: usize) -> (*mut MaybeUninit<T>, usize, usize) {
+        let consumer = self.c_head.load(Ordering::Acquire);
+        let producer = self.p_tail.load(Ordering::Relaxed);
+        let free = Self::MASK - producer.wrapping_sub(consumer);
+        let contiguous = N - (producer & Self::MASK);
+        (self.slot(producer), producer, n.min(free).min(contiguous))
+    }
+
+    /// Publishes `n` slots filled after [`reserve_sp`](Self::reserve_sp).
+    #[inline]
+    fn commit_sp(&self, producer: usize, n: usize) {
+        self.p_tail
+            .store(producer.wrapping_add(n), Ordering::Release);
+    }
+
+    /// Multi-producer enqueue: reserve a slot on `p_head`, fill it, then
+    /// publish in reservation order through `p_tail`. Returns the number of
+    /// values the ring held when the slot was reserved.
+    #[inline]
+    fn enqueue_mp(&self, value: T) -> Result<usize, T> {
+        let mut producer = self.p_head.load(Ordering::Relaxed);
+        let consumer = loop {
+            let consumer = self.c_head.load(Ordering::Acquire);
+            if likely(producer.wrapping_sub(consumer) < Self::MASK) {
+                match self.p_head.compare_exchange_weak(
+                    producer,
+                    producer.wrapping_add(1),
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break consumer,
+                    Err(p) => producer = p,
+                }
+            } else {
+                // Full, unless our view of `p_head` was stale.
+                let p = self.p_head.load(Ordering::Relaxed);
+                if p == producer {
+                    return Err(value);
+                }
+                producer = p;
+            }
+        };
+        // A consumer of the previous lap may still be reading the slot.
+        pr::load_when(&self.stamps[producer & Self::MASK], producer & !Self::MASK);
+        // SAFETY: the reservation gives us the slot until we publish it.
+        unsafe { (*self.slot(producer)).write(value) };
+        pr::spin_while(&*self.p_tail, |t| t != producer);
+        self.p_tail
+            .store(producer.wrapping_add(1), Ordering::Release);
+        Ok(producer.wrapping_sub(consumer))
+    }
+
+    /// Single-consumer dequeue.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may dequeue concurrently.
+    #[inline]
+    unsafe fn dequeue_sc(&self) -> Option<T> {
+        let consumer = self.c_head.load(Ordering::Relaxed);
+        let producer = self.p_tail.load(Ordering::Acquire);
+        if unlikely(consumer == producer) {
+            return None;
+        }
+        let value = (*self.slot(consumer)).assume_init_read();
+        self.release(consumer);
+        self.c_head
+            .store(consumer.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Returns the oldest value without removing it, for a single consumer.
+    #[inline]
+    fn peek_sc(&self) -> Option<*mut T> {
+        let consumer = self.c_head.load(Ordering::Relaxed);
+        let producer = self.p_tail.load(Ordering::Acquire);
+        if consumer == producer {
+            return None;
+        }
+        Some(self.slot(consumer).cast())
+    }
+
+    /// Multi-consumer dequeue, retrying when another consumer wins.
+    #[inline]
+    fn dequeue_mc(&self) -> Option<T> {
+        loop {
+            let consumer = self.c_head.load(Ordering::Acquire);
+            if unlikely(consumer == self.p_tail.load(Ordering::Acquire)) {
+                return None;
+            }
+            if let Some(value) = self.claim_mc(consumer) {
+                return Some(value);
+            }
+        }
+    }
+
+    /// Multi-consumer dequeue making a single attempt: `None` if the ring
+    /// is empty or another consumer won the race.
+    #[inline]
+    fn try_dequeue_mc(&self) -> Option<T> {
+        let consumer = self.c_head.load(Ordering::Acquire);
+        if unlikely(consumer == self.p_tail.load(Ordering::Acquire)) {
+            return None;
+        }
+        self.claim_mc(consumer)
+    }
+
+    /// Takes the value at `consumer`, a published slot, unless another
+    /// consumer claims it first.
+    #[inline(always)]
+    fn claim_mc(&self, consumer: usize) -> Option<T> {
+        self.c_head
+            .compare_exchange(
+                consumer,
+                consumer.wrapping_add(1),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+            .ok()?;
+        // SAFETY: the claim makes the published slot ours, and producers
+        // leave it alone until `release` stamps it.
+        let value = unsafe { (*self.slot(consumer)).assume_init_read() };
+        self.release(consumer);
+        Some(value)
+    }
+}
+
+impl<T, const N: usize> Drop for Ring<T, N> {
+    fn drop(&mut self) {
+        let mut head = *self.c_head.get_mut();
+        let tail = *self.p_tail.get_mut();
+        while head != tail {
+            // SAFETY: slots between head and tail hold initialized values.
+            unsafe { (*self.slot(head)).assume_init_drop() };
+            head = head.wrapping_add(1);
+        }
+    }
+}
+
+/// Iterator removing every value from a ring, returned by `drain` on the
+/// ring types.
+///
+/// Values not yet yielded when the iterator is dropped are dropped too.
+pub struct Drain<'a, T, const N: usize> {
+    ring: &'a mut Ring<T, N>,
+}
+
+impl<'a, T, const N: usize> Drain<'a, T, N> {
+    fn new(ring: &'a mut Ring<T, N>) -> Self {
+        Drain { ring }
+    }
+}
+
+impl<T, const N: usize> Iterator for Drain<'_, T, N> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        // SAFETY: the exclusive borrow rules out every other consumer.
+        unsafe { self.ring.dequeue_sc() }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.ring.len();
+        (n, Some(n))
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for Drain<'_, T, N> {}
+
+impl<T, const N: usize> FusedIterator for Drain<'_, T, N> {}
+
+impl<T, const N: usize> Drop for Drain<'_, T, N> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+/// Iterator dequeuing values while any are available, returned by
+/// `try_iter` on single-consumer rings.
+///
+/// The producer's tail is re-read only once the values already seen are
+/// used up. Returning `None` just means the ring was empty at that point;
+/// a later call may yield values enqueued since.
+pub struct TryIter<'a, T, const N: usize> {
+    ring: &'a Ring<T, N>,
+    consumer: usize,
+    producer: usize,
+}
+
+impl<'a, T, const N: usize> TryIter<'a, T, N> {
+    fn new(ring: &'a Ring<T, N>) -> Self {
+        let consumer = ring.c_head.load(Ordering::Relaxed);
+        TryIter {
+            ring,
+            consumer,
+            producer: consumer,
+        }
+    }
+}
+
+impl<T, const N: usize> Iterator for TryIter<'_, T, N> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        if self.consumer == self.producer {
+            self.producer = self.ring.p_tail.load(Ordering::Acquire);
+            if self.consumer == self.producer {
+                return None;
+            }
+        }
+        // SAFETY: the slot was published, and `try_iter`'s caller promised
+        // to be the only consumer.
+        let value = unsafe { (*self.ring.slot(self.consumer)).assume_init_read() };
+        self.ring.release(self.consumer);
+        self.consumer = self.consumer.wrapping_add(1);
+        self.ring.c_head.store(self.consumer, Ordering::Release);
+        Some(value)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.producer.wrapping_sub(self.consumer), None)
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for TryIter<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TryIter")
+            .field("consumer", &self.consumer)
+            .field("producer", &self.producer)
+            .finish()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Drain<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Drain")
+            .field("len", &self.ring.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn wraps_and_drops_leftovers() {
+        let rc = Rc::new(());
+        let sp = Ring::<Rc<()>, 4>::new();
+        let mp = Ring::<Rc<()>, 4>::new();
+        unsafe {
+            for _ in 0..10 {
+                sp.enqueue_sp(rc.clone()).unwrap();
+                mp.enqueue_mp(rc.clone()).unwrap();
+                drop(sp.dequeue_mc());
+                drop(mp.dequeue_sc());
+            }
+            for _ in 0..3 {
+                sp.enqueue_sp(rc.clone()).unwrap();
+                mp.enqueue_mp(rc.clone()).unwrap();
+            }
+            assert!(sp.enqueue_sp(rc.clone()).is_err());
+            assert!(mp.enqueue_mp(rc.clone()).is_err());
+        }
+        assert_eq!(Rc::strong_count(&rc), 1 + 6);
+        drop((sp, mp));
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn claimed_slot_is_not_reused_until_read() {
+        let r = Ring::<u32, 4>::new();
+        unsafe {
+            for i in 0..3 {
+                r.enqueue_sp(i).unwrap();
+            }
+            // One consumer has read slot 1; another claimed slot 0 before
+            // it but has not read it yet.
+            r.c_head.store(2, Ordering::Relaxed);
+            r.release(1);
+            assert_eq!(r.enqueue_sp(3), Ok(1));
+            assert_eq!(r.enqueue_sp(4), Err(4));
+            assert_eq!(r.extend_sp(4..), 0);
+            r.release(0);
+            assert_eq!(r.enqueue_sp(4), Ok(2));
+            assert_eq!(r.dequeue_mc(), Some(2));
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Multi-producer, single-consumer ring.
+
+use super::{Drain, Ring, TryIter};
+
+/// A ring with any number of producers and one consumer
+/// (`ck_ring_enqueue_mpsc` / `ck_ring_dequeue_mpsc`).
+pub struct MpscRing<T, const N: usize> {
+    ring: Ring<T, N>,
+}
+
+impl<T, const N: usize> MpscRing<T, N> {
+    /// Creates an empty ring. `N` must be a power of two of at least 2.
+    pub const fn new() -> Self {
+        MpscRing { ring: Ring::new() }
+    }
+
+    /// Returns the number of values the ring can hold, `N - 1`.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    /// Returns the number of values in the ring.
+    ///
+    /// Under concurrent use this is a relaxed snapshot that may already be
+    /// stale.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns `true` if the ring holds no values; approximate like
+    /// [`len`](Self::len).
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns how many more values fit; approximate like
+    /// [`len`](Self::len).
+    #[inline]
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Removes every value; the exclusive borrow stands in for the
+    /// single-consumer contract.
+    #[inline]
+    pub fn drain(&mut self) -> Drain<'_, T, N> {
+        Drain::new(&mut self.ring)
+    }
+
+    /// Appends `value`, handing it back if the ring is full; safe to call
+    /// from any number of threads.
+    #[inline]
+    pub fn enqueue(&self, value: T) -> Result<(), T> {
+        self.ring.enqueue_mp(value).map(drop)
+    }
+
+    /// Returns an iterator dequeuing values while any are available.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may dequeue while the iterator lives.
+    #[inline]
+    pub unsafe fn try_iter(&self) -> TryIter<'_, T, N> {
+        TryIter::new(&self.ring)
+    }
+
+    /// Removes the oldest value.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may dequeue concurrently.
+    #[inline]
+    pub unsafe fn dequeue(&self) -> Option<T> {
+        self.ring.dequeue_sc()
+    }
+
+    /// Returns the oldest value without removing it.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may dequeue concurrently, and the value must not be
+    /// dequeued while the reference lives.
+    #[inline]
+    pub unsafe fn peek(&self) -> Option<&T> {
+        self.ring.peek_sc().map(|p| &*p)
+    }
+
+    /// Returns the oldest value mutably without removing it.
+    ///
+    /// # Safety
+    ///
+    /// As for [`peek`](Self::peek), and no other reference to the value may
+    /// exist.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn peek_mut(&self) -> Option<&mut T> {
+        self.ring.peek_sc().map(|p| &mut *p)
+    }
+}
+
+impl<T, const N: usize> Default for MpscRing<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn per_producer_order_is_kept() {
+        const COUNT: usize = 5_000;
+        const PRODUCERS: usize = 3;
+        let r = MpscRing::<(usize, usize), 16>::new();
+        thread::scope(|s| {
+            for p in 0..PRODUCERS {
+                let r = &r;
+                s.spawn(move || {
+                    for i in 0..COUNT {
+                        let mut v = (p, i);
+                        while let Err(back) = r.enqueue(v) {
+                            v = back;
+                            thread::yield_now();
+                        }
+                    }
+                });
+            }
+            let mut next = [0; PRODUCERS];
+            let mut seen = 0;
+            while seen < PRODUCERS * COUNT {
+                if let Some((p, i)) = unsafe { r.dequeue() } {
+                    assert_eq!(i, next[p]);
+                    next[p] += 1;
+                    seen += 1;
+                } else {
+                    thread::yield_now();
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,244 @@
+//! Untyped pointer ring with the `ck_ring` C interface.
+
+use core::ffi::c_void;
+
+use super::Ring;
+
+/// A ring of `*mut c_void` with `ck_ring`'s function set.
+///
+/// Each `ck_ring_<op>_<flavour>(ring, buffer, ...)` becomes
+/// `<op>_<flavour>(...)`: enqueues take the entry and return `false` when
+/// full, the `_size` forms also store the number of entries held beforehand,
+/// and dequeues write the entry through `data` and return `false` when
+/// empty. As in C, the flavour is chosen per call; the caller must use one
+/// producer flavour and one consumer flavour consistently, and the `unsafe`
+/// operations are those whose side is single-threaded. The buffer lives
+/// inside the ring, so there is no separate `ck_ring_buffer_t`.
+pub struct PtrRing<const N: usize> {
+    ring: Ring<*mut c_void, N>,
+}
+
+// SAFETY: the ring only moves pointer values; what they point to is the
+// caller's business, as in C.
+unsafe impl<const N: usize> Send for PtrRing<N> {}
+unsafe impl<const N: usize> Sync for PtrRing<N> {}
+
+impl<const N: usize> PtrRing<N> {
+    /// Creates an empty ring (`ck_ring_init`). `N` must be a power of two
+    /// of at least 2.
+    pub const fn new() -> Self {
+        PtrRing { ring: Ring::new() }
+    }
+
+    /// Returns the number of entries in the ring (`ck_ring_size`).
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns the buffer size `N` (`ck_ring_capacity`); at most `N - 1`
+    /// entries fit.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    #[inline(always)]
+    fn report(r: Result<usize, *mut c_void>, size: &mut usize) -> bool {
+        match r {
+            Ok(s) => {
+                *size = s;
+                true
+            }
+            Err(_) => {
+                *size = N - 1;
+                false
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn output(r: Option<*mut c_void>, data: &mut *mut c_void) -> bool {
+        r.map(|p| *data = p).is_some()
+    }
+
+    /// `ck_ring_enqueue_spsc`.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may enqueue concurrently.
+    #[inline]
+    pub unsafe fn enqueue_spsc(&self, entry: *const c_void) -> bool {
+        self.ring.enqueue_sp(entry.cast_mut()).is_ok()
+    }
+
+    /// `ck_ring_enqueue_spsc_size`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`enqueue_spsc`](Self::enqueue_spsc).
+    #[inline]
+    pub unsafe fn enqueue_spsc_size(&self, entry: *const c_void, size: &mut usize) -> bool {
+        Self::report(self.ring.enqueue_sp(entry.cast_mut()), size)
+    }
+
+    /// `ck_ring_dequeue_spsc`.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may dequeue concurrently.
+    #[inline]
+    pub unsafe fn dequeue_spsc(&self, data: &mut *mut c_void) -> bool {
+        Self::output(self.ring.dequeue_sc(), data)
+    }
+
+    /// `ck_ring_enqueue_spmc`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`enqueue_spsc`](Self::enqueue_spsc).
+    #[inline]
+    pub unsafe fn enqueue_spmc(&self, entry: *const c_void) -> bool {
+        self.enqueue_spsc(entry)
+    }
+
+    /// `ck_ring_enqueue_spmc_size`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`enqueue_spsc`](Self::enqueue_spsc).
+    #[inline]
+    pub unsafe fn enqueue_spmc_size(&self, entry: *const c_void, size: &mut usize) -> bool {
+        self.enqueue_spsc_size(entry, size)
+    }
+
+    /// `ck_ring_dequeue_spmc`.
+    #[inline]
+    pub fn dequeue_spmc(&self, data: &mut *mut c_void) -> bool {
+        Self::output(self.ring.dequeue_mc(), data)
+    }
+
+    /// `ck_ring_trydequeue_spmc`: also fails if another consumer wins.
+    #[inline]
+    pub fn trydequeue_spmc(&self, data: &mut *mut c_void) -> bool {
+        Self::output(self.ring.try_dequeue_mc(), data)
+    }
+
+    /// `ck_ring_enqueue_mpsc`.
+    #[inline]
+    pub fn enqueue_mpsc(&self, entry: *const c_void) -> bool {
+        self.ring.enqueue_mp(entry.cast_mut()).is_ok()
+    }
+
+    /// `ck_ring_enqueue_mpsc_size`.
+    #[inline]
+    pub fn enqueue_mpsc_size(&self, entry: *const c_void, size: &mut usize) -> bool {
+        Self::report(self.ring.enqueue_mp(entry.cast_mut()), size)
+    }
+
+    /// `ck_ring_dequeue_mpsc`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`dequeue_spsc`](Self::dequeue_spsc).
+    #[inline]
+    pub unsafe fn dequeue_mpsc(&self, data: &mut *mut c_void) -> bool {
+        self.dequeue_spsc(data)
+    }
+
+    /// `ck_ring_enqueue_mpmc`.
+    #[inline]
+    pub fn enqueue_mpmc(&self, entry: *const c_void) -> bool {
+        self.enqueue_mpsc(entry)
+    }
+
+    /// `ck_ring_enqueue_mpmc_size`.
+    #[inline]
+    pub fn enqueue_mpmc_size(&self, entry: *const c_void, size: &mut usize) -> bool {
+        self.enqueue_mpsc_size(entry, size)
+    }
+
+    /// `ck_ring_dequeue_mpmc`.
+    #[inline]
+    pub fn dequeue_mpmc(&self, data: &mut *mut c_void) -> bool {
+        self.dequeue_spmc(data)
+    }
+
+    /// `ck_ring_trydequeue_mpmc`: also fails if another consumer wins.
+    #[inline]
+    pub fn trydequeue_mpmc(&self, data: &mut *mut c_void) -> bool {
+        self.trydequeue_spmc(data)
+    }
+}
+
+impl<const N: usize> Default for PtrRing<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+    use std::thread;
+
+    fn entry(i: usize) -> *const c_void {
+        ptr::without_provenance(i)
+    }
+
+    #[test]
+    fn c_style_calls() {
+        let r = PtrRing::<4>::new();
+        let (mut size, mut data) = (usize::MAX, ptr::null_mut());
+        unsafe {
+            assert!(r.enqueue_spsc_size(entry(1), &mut size));
+            assert_eq!(size, 0);
+            assert!(r.enqueue_spsc(entry(2)));
+            assert!(r.enqueue_spsc_size(entry(3), &mut size));
+            assert_eq!(size, 2);
+            assert!(!r.enqueue_spsc_size(entry(4), &mut size));
+            assert_eq!((size, r.size(), r.capacity()), (3, 3, 4));
+            assert!(r.dequeue_spsc(&mut data));
+            assert_eq!(data.addr(), 1);
+        }
+        assert!(r.trydequeue_spmc(&mut data));
+        assert_eq!(data.addr(), 2);
+        assert!(r.dequeue_mpmc(&mut data) && data.addr() == 3);
+        assert!(!r.dequeue_mpmc(&mut data));
+    }
+
+    #[test]
+    fn mpmc() {
+        const COUNT: usize = 5_000;
+        let r = PtrRing::<16>::new();
+        let total = thread::scope(|s| {
+            for p in 0..2 {
+                let r = &r;
+                s.spawn(move || {
+                    for i in 1..=COUNT {
+                        while !r.enqueue_mpmc(entry(p * COUNT + i)) {
+                            thread::yield_now();
+                        }
+                    }
+                });
+            }
+            let consumers: [_; 2] = core::array::from_fn(|_| {
+                s.spawn(|| {
+                    let (mut sum, mut n, mut data) = (0, 0, ptr::null_mut());
+                    while n < COUNT {
+                        if r.dequeue_mpmc(&mut data) {
+                            sum += data.addr();
+                            n += 1;
+                        } else {
+                            thread::yield_now();
+                        }
+                    }
+                    sum
+                })
+            });
+            consumers.map(|c| c.join().unwrap()).iter().sum::<usize>()
+        });
+        assert_eq!(total, (1..=2 * COUNT).sum());
+    }
+}
@@ -0,0 +1,134 @@
+//! Single-producer, multi-consumer ring.
+
+use super::{Drain, Ring};
+
+/// A ring with one producer and any number of consumers
+/// (`ck_ring_enqueue_spmc` / `ck_ring_dequeue_spmc`).
+pub struct SpmcRing<T, const N: usize> {
+    ring: Ring<T, N>,
+}
+
+impl<T, const N: usize> SpmcRing<T, N> {
+    /// Creates an empty ring. `N` must be a power of two of at least 2.
+    pub const fn new() -> Self {
+        SpmcRing { ring: Ring::new() }
+    }
+
+    /// Returns the number of values the ring can hold, `N - 1`.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    /// Returns the number of values in the ring.
+    ///
+    /// Under concurrent use this is a relaxed snapshot that may already be
+    /// stale.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns `true` if the ring holds no values; approximate like
+    /// [`len`](Self::len).
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns how many more values fit; approximate like
+    /// [`len`](Self::len).
+    #[inline]
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Removes every value; the exclusive borrow stands in for the
+    /// single-consumer contract.
+    #[inline]
+    pub fn drain(&mut self) -> Drain<'_, T, N> {
+        Drain::new(&mut self.ring)
+    }
+
+    /// Appends `value`, handing it back if the ring is full.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may enqueue concurrently.
+    #[inline]
+    pub unsafe fn enqueue(&self, value: T) -> Result<(), T> {
+        self.ring.enqueue_sp(value).map(drop)
+    }
+
+    /// Enqueues values from `iter` until it ends or the ring is full,
+    /// returning how many were enqueued. Values that do not fit are left
+    /// in the iterator, and the whole batch is published at once.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may enqueue concurrently.
+    #[inline]
+    pub unsafe fn extend_from_iter(&self, iter: impl Iterator<Item = T>) -> usize {
+        self.ring.extend_sp(iter)
+    }
+
+    /// Removes the oldest value; safe to call from any number of threads.
+    #[inline]
+    pub fn dequeue(&self) -> Option<T> {
+        self.ring.dequeue_mc()
+    }
+
+    /// Like [`dequeue`](Self::dequeue) but makes a single attempt, also
+    /// returning `None` if another consumer takes the value first
+    /// (`ck_ring_trydequeue_spmc`). It never spins, so its cost is bounded.
+    #[inline]
+    pub fn try_dequeue(&self) -> Option<T> {
+        self.ring.try_dequeue_mc()
+    }
+}
+
+impl<T, const N: usize> Default for SpmcRing<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
+
+    #[test]
+    fn consumers_share_the_stream() {
+        const COUNT: usize = 20_000;
+        let r = SpmcRing::<usize, 32>::new();
+        let (sum, done) = (AtomicUsize::new(0), AtomicBool::new(false));
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| loop {
+                    match r.try_dequeue().or_else(|| r.dequeue()) {
+                        Some(v) => {
+                            sum.fetch_add(v, Ordering::Relaxed);
+                        }
+                        None if done.load(Ordering::Acquire) => break,
+                        None => thread::yield_now(),
+                    }
+                });
+            }
+            for mut i in 0..COUNT {
+                while let Err(v) = unsafe { r.enqueue(i) } {
+                    i = v;
+                    thread::yield_now();
+                }
+            }
+            done.store(true, Ordering::Release);
+        });
+        assert_eq!(sum.into_inner(), (0..COUNT).sum());
+        assert!(r.is_empty());
+        assert_eq!(r.try_dequeue(), None);
+        unsafe { r.enqueue(1).unwrap() };
+        assert_eq!((r.len(), r.remaining_capacity()), (1, 30));
+        assert_eq!(r.try_dequeue(), Some(1));
+    }
+}
@@ -0,0 +1,299 @@
+//! Single-producer, single-consumer ring.
+
+use core::mem::MaybeUninit;
+use core::slice;
+
+use super::{Drain, Ring, TryIter};
+
+/// A ring with one producer and one consumer (`ck_ring_*_spsc`).
+pub struct SpscRing<T, const N: usize> {
+    ring: Ring<T, N>,
+}
+
+impl<T, const N: usize> SpscRing<T, N> {
+    /// Creates an empty ring. `N` must be a power of two of at least 2.
+    pub const fn new() -> Self {
+        SpscRing { ring: Ring::new() }
+    }
+
+    /// Returns the number of values the ring can hold, `N - 1`.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    /// Returns the number of values in the ring.
+    ///
+    /// Under concurrent use this is a relaxed snapshot that may already be
+    /// stale.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns `true` if the ring holds no values; approximate like
+    /// [`len`](Self::len).
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns how many more values fit; approximate like
+    /// [`len`](Self::len).
+    #[inline]
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Removes every value; the exclusive borrow stands in for the
+    /// single-consumer contract.
+    #[inline]
+    pub fn drain(&mut self) -> Drain<'_, T, N> {
+        Drain::new(&mut self.ring)
+    }
+
+    /// Appends `value`, handing it back if the ring is full.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may enqueue concurrently.
+    #[inline]
+    pub unsafe fn enqueue(&self, value: T) -> Result<(), T> {
+        self.ring.enqueue_sp(value).map(drop)
+    }
+
+    /// Reserves up to `n` contiguous slots for in-place writes.
+    ///
+    /// The chunk is shorter than `n` when the ring lacks room or the free
+    /// space wraps around the end of the buffer; nothing becomes visible to
+    /// the consumer until [`WriteChunk::commit`].
+    ///
+    /// # Safety
+    ///
+    /// No other thread may enqueue or reserve until the chunk is dropped.
+    #[inline]
+    pub unsafe fn reserve(&self, n: usize) -> WriteChunk<'_, T, N> {
+        let (first, producer, len) = self.ring.reserve_sp(n);
+        WriteChunk {
+            ring: &self.ring,
+            slots: slice::from_raw_parts_mut(first, len),
+            producer,
+        }
+    }
+
+    /// Enqueues values from `iter` until it ends or the ring is full,
+    /// returning how many were enqueued. Values that do not fit are left
+    /// in the iterator, and the whole batch is published at once.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may enqueue concurrently.
+    #[inline]
+    pub unsafe fn extend_from_iter(&self, iter: impl Iterator<Item = T>) -> usize {
+        self.ring.extend_sp(iter)
+    }
+
+    /// Returns an iterator dequeuing values while any are available.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may dequeue while the iterator lives.
+    #[inline]
+    pub unsafe fn try_iter(&self) -> TryIter<'_, T, N> {
+        TryIter::new(&self.ring)
+    }
+
+    /// Removes the oldest value.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may dequeue concurrently.
+    #[inline]
+    pub unsafe fn dequeue(&self) -> Option<T> {
+        self.ring.dequeue_sc()
+    }
+
+    /// Returns the oldest value without removing it.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may dequeue concurrently, and the value must not be
+    /// dequeued while the reference lives.
+    #[inline]
+    pub unsafe fn peek(&self) -> Option<&T> {
+        self.ring.peek_sc().map(|p| &*p)
+    }
+
+    /// Returns the oldest value mutably without removing it.
+    ///
+    /// # Safety
+    ///
+    /// As for [`peek`](Self::peek), and no other reference to the value may
+    /// exist.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn peek_mut(&self) -> Option<&mut T> {
+        self.ring.peek_sc().map(|p| &mut *p)
+    }
+}
+
+impl<T, const N: usize> Default for SpscRing<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Contiguous free slots reserved by [`SpscRing::reserve`].
+pub struct WriteChunk<'a, T, const N: usize> {
+    ring: &'a Ring<T, N>,
+    slots: &'a mut [MaybeUninit<T>],
+    producer: usize,
+}
+
+impl<T, const N: usize> WriteChunk<'_, T, N> {
+    /// Returns the number of reserved slots.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns `true` if no slot could be reserved.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Returns the reserved slots for writing.
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [MaybeUninit<T>] {
+        self.slots
+    }
+
+    /// Publishes the first `n` slots to the consumer.
+    ///
+    /// Values written past `n` are neither published nor dropped.
+    ///
+    /// # Safety
+    ///
+    /// The first `n` slots must have been initialized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` exceeds [`len`](Self::len).
+    #[inline]
+    pub unsafe fn commit(self, n: usize) {
+        assert!(n <= self.slots.len(), "commit past the reserved chunk");
+        self.ring.commit_sp(self.producer, n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn fifo_across_threads() {
+        const COUNT: usize = 20_000;
+        let r = SpscRing::<usize, 64>::new();
+        assert_eq!(r.capacity(), 63);
+        thread::scope(|s| {
+            s.spawn(|| {
+                for mut i in 0..COUNT {
+                    while let Err(v) = unsafe { r.enqueue(i) } {
+                        i = v;
+                        thread::yield_now();
+                    }
+                }
+            });
+            let mut next = 0;
+            while next < COUNT {
+                if let Some(v) = unsafe { r.dequeue() } {
+                    assert_eq!(v, next);
+                    next += 1;
+                } else {
+                    thread::yield_now();
+                }
+            }
+        });
+        assert_eq!(unsafe { r.dequeue() }, None);
+    }
+
+    #[test]
+    fn drain_and_drop_leftovers() {
+        let rc = std::rc::Rc::new(());
+        let mut r = SpscRing::<_, 8>::new();
+        for _ in 0..5 {
+            unsafe { r.enqueue(rc.clone()).unwrap() };
+        }
+        let mut d = r.drain();
+        assert_eq!(d.len(), 5);
+        drop(d.next());
+        drop(d);
+        assert!(r.is_empty());
+        assert_eq!(std::rc::Rc::strong_count(&rc), 1);
+        unsafe { r.enqueue(rc.clone()).unwrap() };
+        drop(r);
+        assert_eq!(std::rc::Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn batches_through_iterators() {
+        let r = SpscRing::<u32, 8>::new();
+        let mut src = 0..20;
+        unsafe {
+            assert_eq!(r.extend_from_iter(src.by_ref()), 7);
+            assert_eq!(src.start, 7);
+            let first: std::vec::Vec<u32> = r.try_iter().take(3).collect();
+            assert_eq!(first, [0, 1, 2]);
+            assert_eq!(r.extend_from_iter(src.by_ref()), 3);
+            assert!(r.try_iter().eq(3..10));
+            assert_eq!(r.try_iter().next(), None);
+        }
+    }
+
+    #[test]
+    fn peek_before_dequeue() {
+        let r = SpscRing::<u32, 4>::new();
+        unsafe {
+            assert_eq!(r.peek(), None);
+            r.enqueue(1).unwrap();
+            r.enqueue(2).unwrap();
+            *r.peek_mut().unwrap() += 10;
+            assert_eq!(r.peek(), Some(&11));
+            assert_eq!(r.dequeue(), Some(11));
+            assert_eq!(r.peek(), Some(&2));
+        }
+    }
+
+    #[test]
+    fn reserve_and_commit_in_place() {
+        let r = SpscRing::<u32, 8>::new();
+        unsafe {
+            for i in 0..5 {
+                r.enqueue(i).unwrap();
+            }
+            for i in 0..5 {
+                assert_eq!(r.dequeue(), Some(i));
+            }
+            // Three slots are free before the buffer wraps.
+            let mut c = r.reserve(10);
+            assert_eq!(c.len(), 3);
+            for (i, s) in c.as_mut_slice().iter_mut().enumerate() {
+                s.write(10 + i as u32);
+            }
+            c.commit(2);
+            let mut c = r.reserve(10);
+            assert_eq!(c.len(), 1);
+            c.as_mut_slice()[0].write(20);
+            c.commit(1);
+            // Wrapped: four slots are free from the start of the buffer.
+            let mut c = r.reserve(10);
+            assert_eq!(c.len(), 4);
+            c.as_mut_slice()[0].write(30);
+            c.commit(1);
+            let got: [_; 5] = core::array::from_fn(|_| r.dequeue());
+            assert_eq!(got, [Some(10), Some(11), Some(20), Some(30), None]);
+        }
+    }
+}
@@ -0,0 +1,570 @@
+//! The big-reader lock (`ck_brlock`).
+
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+use core::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::backoff::{self, Backoff, BackoffPolicy};
+use crate::cc::CachePadded;
+
+/// One registered reader's counter, kept on a cache line of its own so
+/// that readers on different CPUs do not false-share.
+struct Slot {
+    /// Read sections the owner holds; only the owner writes it.
+    n_readers: AtomicUsize,
+    in_use: AtomicBool,
+    /// The NUMA node of the thread that created the slot.
+    node: usize,
+}
+
+/// The registered slots, sorted by node. Each is allocated on its own and
+/// kept as a raw pointer, so growing the table moves only the pointers and
+/// never a slot a reader is using.
+struct SlotTable(Vec<NonNull<CachePadded<Slot>>>);
+
+impl SlotTable {
+    #[inline]
+    fn get(&self, i: usize) -> &Slot {
+        // SAFETY: every pointer came from `Box::leak` and stays valid
+        // until the table is dropped.
+        unsafe { self.0[i].as_ref() }
+    }
+
+    #[inline]
+    fn iter(&self) -> impl Iterator<Item = &Slot> + Clone {
+        (0..self.0.len()).map(|i| self.get(i))
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl Drop for SlotTable {
+    fn drop(&mut self) {
+        for &slot in &self.0 {
+            // SAFETY: the pointer came from `Box::leak`, and the table's
+            // owner outlives every reader borrowing the slot.
+            drop(unsafe { Box::from_raw(slot.as_ptr()) });
+        }
+    }
+}
+
+/// Returns the NUMA node of the calling thread, or 0 without `std`.
+#[inline]
+fn local_node() -> usize {
+    #[cfg(feature = "std")]
+    {
+        crate::malloc::current_node()
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        0
+    }
+}
+
+/// Iterates over `slots`, which are sorted by node, starting with those on
+/// `node`.
+fn node_first(slots: &SlotTable, node: usize) -> impl Iterator<Item = &Slot> {
+    let start = slots.iter().take_while(|s| s.node < node).count();
+    let end = start
+        + slots
+            .iter()
+            .skip(start)
+            .take_while(|s| s.node == node)
+            .count();
+    (start..end)
+        .chain(0..start)
+        .chain(end..slots.len())
+        .map(|i| slots.get(i))
+}
+
+/// A reader-writer spinlock that makes read acquisition nearly free at the
+/// cost of expensive writes.
+///
+/// Each reader [`register`](Self::register)s once and then locks by
+/// setting a counter of its own, so readers never write a shared cache
+/// line. A writer raises one flag and waits for every registered counter
+/// to drop to zero.
+///
+/// The slot table starts empty and grows as readers register; slots of
+/// dropped [`BrReader`]s are reused. As in `ck_brlock`, registration and
+/// the writer's scan both run under the writer flag, so growing the table
+/// never races with a scan. It follows that [`register`](Self::register)
+/// waits behind a writer, and so behind the readers that writer waits for:
+/// register once per thread, and never while the thread holds a read
+/// section through another reader of the same lock.
+///
+/// With the `std` feature, slots are grouped by the NUMA node of the
+/// registering thread, and a reader reuses only slots from its own node.
+/// A writer scans its own node's group first, so it touches remote cache
+/// lines only after the local readers have drained.
+///
+/// Readers waiting out a writer, and writers waiting for the flag or for
+/// readers to leave, back off with a `B` between polls.
+pub struct BrLock<T: ?Sized, B = Backoff> {
+    writer: AtomicBool,
+    /// Registered slots; only touched while holding `writer`.
+    slots: UnsafeCell<SlotTable>,
+    _backoff: PhantomData<fn() -> B>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send, B> Send for BrLock<T, B> {}
+unsafe impl<T: ?Sized + Send + Sync, B> Sync for BrLock<T, B> {}
+
+impl<T> BrLock<T> {
+    /// Creates an unlocked lock protecting `value`, with no readers.
+    pub const fn new(value: T) -> Self {
+        Self::with_backoff(value)
+    }
+}
+
+impl<T, B: BackoffPolicy> BrLock<T, B> {
+    /// Creates an unlocked lock protecting `value`, with no readers, whose
+    /// waiters back off with a `B` between polls.
+    pub const fn with_backoff(value: T) -> Self {
+        BrLock {
+            writer: AtomicBool::new(false),
+            slots: UnsafeCell::new(SlotTable(Vec::new())),
+            _backoff: PhantomData,
+            data: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T, B> BrLock<T, B> {
+    /// Consumes the lock, returning the protected value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized, B: BackoffPolicy> BrLock<T, B> {
+    /// Raises the writer flag, waiting for any other holder to lower it.
+    #[inline]
+    fn lock_flag(&self) {
+        while self.writer.swap(true, Ordering::Acquire) {
+            backoff::spin_while::<B, _>(&self.writer, |w| w);
+        }
+    }
+
+    /// Runs `f` on the slot table under the writer flag.
+    #[inline]
+    fn with_slots<R>(&self, f: impl FnOnce(&mut SlotTable) -> R) -> R {
+        self.lock_flag();
+        // SAFETY: the writer flag gives us the table to ourselves.
+        let r = f(unsafe { &mut *self.slots.get() });
+        self.writer.store(false, Ordering::Release);
+        r
+    }
+
+    /// Registers a reader, reusing a released slot when there is one.
+    ///
+    /// This waits out a writer holding the lock.
+    pub fn register(&self) -> BrReader<'_, T, B> {
+        self.register_on(local_node())
+    }
+
+    /// Registers a reader with a slot in `node`'s group.
+    fn register_on(&self, node: usize) -> BrReader<'_, T, B> {
+        let slot = self.with_slots(|slots| {
+            let start = slots.iter().take_while(|s| s.node < node).count();
+            let mut end = start;
+            while end < slots.len() && slots.get(end).node == node {
+                let s = slots.get(end);
+                let claimed = s
+                    .in_use
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok();
+                if claimed {
+                    return slots.0[end];
+                }
+                end += 1;
+            }
+            let slot = NonNull::from(Box::leak(Box::new(CachePadded::new(Slot {
+                n_readers: AtomicUsize::new(0),
+                in_use: AtomicBool::new(true),
+                node,
+            }))));
+            slots.0.insert(end, slot);
+            slot
+        });
+        BrReader {
+            lock: self,
+            // SAFETY: the table frees slots only when the lock is dropped.
+            slot: unsafe { slot.as_ref() },
+            _not_sync: PhantomData,
+        }
+    }
+
+    /// Acquires exclusive access, waiting for every registered reader to
+    /// leave.
+    pub fn write(&self) -> BrLockWriteGuard<'_, T, B> {
+        // Looked up first: it may be a system call, which would otherwise
+        // hold every reader off for its duration.
+        let node = local_node();
+        self.lock_flag();
+        // Pairs with the fence in `BrReader::read`: either the reader sees
+        // the flag or we see its counter.
+        fence(Ordering::SeqCst);
+        // SAFETY: the writer flag gives us the table to ourselves.
+        for slot in node_first(unsafe { &*self.slots.get() }, node) {
+            backoff::load_when::<B, _>(&slot.n_readers, 0);
+        }
+        BrLockWriteGuard { lock: self }
+    }
+
+    /// Acquires exclusive access if no writer holds the lock and no
+    /// registered reader is inside, without waiting for either.
+    pub fn try_write(&self) -> Option<BrLockWriteGuard<'_, T, B>> {
+        let node = local_node();
+        if self.writer.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        fence(Ordering::SeqCst);
+        // SAFETY: the writer flag gives us the table to ourselves.
+        let busy = node_first(unsafe { &*self.slots.get() }, node)
+            .any(|s| s.n_readers.load(Ordering::Acquire) != 0);
+        if busy {
+            self.writer.store(false, Ordering::Release);
+            return None;
+        }
+        Some(BrLockWriteGuard { lock: self })
+    }
+
+    /// Runs `f` under the write lock, releasing it when `f` returns or
+    /// unwinds.
+    #[inline]
+    pub fn write_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    /// Returns `true` if a writer holds or is acquiring the lock, or a
+    /// reader is registering.
+    #[inline]
+    pub fn is_write_locked(&self) -> bool {
+        self.writer.load(Ordering::Relaxed)
+    }
+
+    /// Returns the value mutably; the exclusive borrow rules out other
+    /// holders, so no locking is needed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default, B: BackoffPolicy> Default for BrLock<T, B> {
+    fn default() -> Self {
+        Self::with_backoff(T::default())
+    }
+}
+
+impl<T: ?Sized, B: BackoffPolicy> fmt::Debug for BrLock<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Reading needs a registered reader, so only the state is shown.
+        f.debug_struct("BrLock")
+            .field("write_locked", &self.is_write_locked())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A reader registered with a [`BrLock`]; releases its slot on drop.
+///
+/// A handle belongs to one thread at a time. Its read sections nest.
+pub struct BrReader<'a, T: ?Sized, B = Backoff> {
+    lock: &'a BrLock<T, B>,
+    slot: &'a Slot,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl<'a, T: ?Sized, B: BackoffPolicy> BrReader<'a, T, B> {
+    /// Acquires shared access, waiting while a writer holds the lock.
+    pub fn read(&self) -> BrLockReadGuard<'_, T, B> {
+        let n = self.slot.n_readers.load(Ordering::Relaxed);
+        if n != 0 {
+            self.slot.n_readers.store(n + 1, Ordering::Relaxed);
+            return BrLockReadGuard { reader: self };
+        }
+        loop {
+            backoff::spin_while::<B, _>(&self.lock.writer, |w| w);
+            self.slot.n_readers.store(1, Ordering::Relaxed);
+            fence(Ordering::SeqCst);
+            if !self.lock.writer.load(Ordering::Acquire) {
+                return BrLockReadGuard { reader: self };
+            }
+            self.slot.n_readers.store(0, Ordering::Release);
+        }
+    }
+
+    /// Acquires shared access unless a writer holds or is acquiring the
+    /// lock, in which case it fails at once rather than waiting.
+    ///
+    /// A section nested in one this reader already holds always succeeds.
+    #[inline]
+    pub fn try_read(&self) -> Option<BrLockReadGuard<'_, T, B>> {
+        let n = self.slot.n_readers.load(Ordering::Relaxed);
+        if n != 0 {
+            self.slot.n_readers.store(n + 1, Ordering::Relaxed);
+            return Some(BrLockReadGuard { reader: self });
+        }
+        if self.lock.writer.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.slot.n_readers.store(1, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        if self.lock.writer.load(Ordering::Acquire) {
+            self.slot.n_readers.store(0, Ordering::Release);
+            return None;
+        }
+        Some(BrLockReadGuard { reader: self })
+    }
+
+    /// Runs `f` under a read lock, releasing it when `f` returns or
+    /// unwinds.
+    #[inline]
+    pub fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read())
+    }
+
+    /// Returns the lock this reader is registered with.
+    #[inline]
+    pub fn lock(&self) -> &'a BrLock<T, B> {
+        self.lock
+    }
+}
+
+impl<T: ?Sized, B> Drop for BrReader<'_, T, B> {
+    fn drop(&mut self) {
+        self.slot.in_use.store(false, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized, B> fmt::Debug for BrReader<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrReader")
+            .field("depth", &self.slot.n_readers.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Shared access to a [`BrLock`]'s data; releases on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct BrLockReadGuard<'a, T: ?Sized, B = Backoff> {
+    reader: &'a BrReader<'a, T, B>,
+}
+
+impl<T: ?Sized, B> Deref for BrLockReadGuard<'_, T, B> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds a read lock.
+        unsafe { &*self.reader.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, B> Drop for BrLockReadGuard<'_, T, B> {
+    #[inline]
+    fn drop(&mut self) {
+        let n = &self.reader.slot.n_readers;
+        n.store(n.load(Ordering::Relaxed) - 1, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B> fmt::Debug for BrLockReadGuard<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Exclusive access to a [`BrLock`]'s data; releases on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct BrLockWriteGuard<'a, T: ?Sized, B = Backoff> {
+    lock: &'a BrLock<T, B>,
+}
+
+unsafe impl<T: ?Sized + Sync, B> Sync for BrLockWriteGuard<'_, T, B> {}
+
+impl<T: ?Sized, B> Deref for BrLockWriteGuard<'_, T, B> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the write lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, B> DerefMut for BrLockWriteGuard<'_, T, B> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the write lock.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, B> Drop for BrLockWriteGuard<'_, T, B> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.writer.store(false, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B> fmt::Debug for BrLockWriteGuard<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn slot_count<T>(l: &BrLock<T>) -> usize {
+        l.with_slots(|s| s.len())
+    }
+
+    #[test]
+    fn growing_the_table_leaves_slots_in_place() {
+        let l = BrLock::new(0);
+        let first = l.register();
+        let g = first.read();
+        let slot = first.slot as *const Slot;
+        let more: Vec<_> = (0..64).map(|_| l.register()).collect();
+        assert!(core::ptr::eq(first.slot, slot));
+        assert_eq!(first.slot.n_readers.load(Ordering::Relaxed), 1);
+        drop((g, more));
+    }
+
+    #[test]
+    fn table_grows_past_any_fixed_size_and_reuses_slots() {
+        let l = BrLock::new(0);
+        assert_eq!(slot_count(&l), 0);
+        let readers: Vec<_> = (0..100).map(|_| l.register()).collect();
+        assert_eq!(slot_count(&l), 100);
+        let guards: Vec<_> = readers.iter().map(|r| r.read()).collect();
+        assert_eq!(guards.iter().map(|g| **g).sum::<i32>(), 0);
+        drop(guards);
+        drop(readers);
+        let again = l.register();
+        assert_eq!(slot_count(&l), 100);
+        drop(again);
+        *l.write() = 1;
+        assert_eq!(l.into_inner(), 1);
+    }
+
+    #[test]
+    fn slots_do_not_share_cache_lines() {
+        use crate::cc::CACHELINE;
+        let l = BrLock::new(());
+        let readers: Vec<_> = (0..4).map(|_| l.register()).collect();
+        for (a, b) in readers.iter().zip(&readers[1..]) {
+            let (a, b) = (
+                a.slot as *const Slot as usize,
+                b.slot as *const Slot as usize,
+            );
+            assert_eq!(a % CACHELINE, 0);
+            assert!(a.abs_diff(b) >= CACHELINE);
+        }
+    }
+
+    #[test]
+    fn slots_are_grouped_by_node() {
+        let l = BrLock::new(());
+        let readers: Vec<_> = [1, 0, 2, 1, 0].iter().map(|&n| l.register_on(n)).collect();
+        let nodes = |l: &BrLock<()>| l.with_slots(|s| s.iter().map(|s| s.node).collect::<Vec<_>>());
+        assert_eq!(nodes(&l), [0, 0, 1, 1, 2]);
+
+        // A released slot is reused only by a reader on the same node.
+        let freed = readers[2].slot as *const Slot;
+        drop(readers);
+        let r = l.register_on(2);
+        assert_eq!(r.slot as *const Slot, freed);
+        let r1 = l.register_on(3);
+        assert_eq!(nodes(&l), [0, 0, 1, 1, 2, 3]);
+
+        let order: Vec<_> = l.with_slots(|s| node_first(s, 1).map(|s| s.node).collect());
+        assert_eq!(order, [1, 1, 0, 0, 2, 3]);
+        drop((r, r1));
+        drop(l.write());
+    }
+
+    #[test]
+    fn read_sections_nest_and_hold_off_writers() {
+        let l = BrLock::new(1);
+        let r = l.register();
+        let a = r.read();
+        let b = r.read();
+        assert_eq!(*a + *b, 2);
+        drop(a);
+        thread::scope(|s| {
+            let w = s.spawn(|| l.write_with(|v| *v += 1));
+            while !l.is_write_locked() {
+                thread::yield_now();
+            }
+            assert_eq!(*b, 1);
+            drop(b);
+            w.join().unwrap();
+        });
+        assert_eq!(r.read_with(|v| *v), 2);
+    }
+
+    #[test]
+    fn try_variants_abort_instead_of_waiting() {
+        let l = BrLock::new(0);
+        let (r1, r2) = (l.register(), l.register());
+        let g = r1.try_read().unwrap();
+        assert!(l.try_write().is_none());
+        // The failed attempt lowered the flag again.
+        assert!(!l.is_write_locked());
+        let nested = r1.try_read().unwrap();
+        assert!(r2.try_read().is_some());
+        drop((g, nested));
+
+        let mut w = l.try_write().unwrap();
+        *w = 1;
+        assert!(l.try_write().is_none());
+        assert!(r1.try_read().is_none());
+        assert_eq!(r1.slot.n_readers.load(Ordering::Relaxed), 0);
+        drop(w);
+        assert_eq!(*r2.try_read().unwrap(), 1);
+    }
+
+    #[test]
+    fn concurrent_readers_and_writers() {
+        const N: u32 = 200;
+        let l = BrLock::new((0u32, 0u32));
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        l.write_with(|(a, b)| {
+                            *a += 1;
+                            *b += 1;
+                        });
+                    }
+                });
+            }
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        let r = l.register();
+                        r.read_with(|(a, b)| assert_eq!(a, b));
+                    }
+                });
+            }
+        });
+        assert_eq!(l.into_inner(), (2 * N, 2 * N));
+    }
+}
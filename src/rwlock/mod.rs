@@ -0,0 +1,732 @@
+//! Reader-writer spinlocks modelled on `ck_rwlock`.
+//!
+//! An [`RwLock`] admits any number of readers or a single writer. Its state
+//! is one word: bit 0 marks a writer, bit 1 a writer waiting to enter, and
+//! each reader adds `READER`, so both sides acquire with a single
+//! compare-and-swap. The lock is write-biased: once a writer is waiting, new
+//! readers hold off until it has been served, so a steady stream of
+//! overlapping readers cannot starve writers. Like the spinlocks,
+//! it owns its data and hands out RAII guards. [`RecursiveRwLock`] lets its
+//! writer re-acquire the lock, and [`PfLock`] alternates read and write
+//! phases so that neither side can starve the other. [`TfLock`] admits
+//! readers and writers strictly in arrival order. With the `stats`
+//! feature, [`RwLock`] counts acquisitions, failed attempts and the
+//! longest wait like the spinlocks do, and [`TfLock`] counts the tickets
+//! it issues and the readers and writers it admits. With the `alloc` feature, `BrLock` lets registered readers lock
+//! by touching only their own counter, for data that is read far more
+//! often than written.
+//!
+//! Both locks keep their data in the last field, so a sized lock coerces to
+//! an unsized one (`&RwLock<[u8; 4]>` to `&RwLock<[u8]>`, `Box<RwLock<S>>`
+//! to `Box<RwLock<dyn Trait>>`), and `new` is `const` so they can back a
+//! `static`.
+//!
+//! [`RwLock`], [`PfLock`] and `BrLock` take a [`BackoffPolicy`] type
+//! parameter, [`Backoff`] by default, that delays between polls while
+//! waiting; `with_backoff` creates a lock with another policy, such as
+//! [`NoBackoff`](crate::backoff::NoBackoff).
+#![cfg_attr(
+    feature = "std",
+    doc = "
+With the `std` feature, [`try_read_for`](RwLock::try_read_for) and
+[`try_write_for`](RwLock::try_write_for) retry under a bounded [`Backoff`]
+until a timeout, so a caller can report contention instead of spinning
+forever, and [`RwLock::new_blocking`] creates a lock whose waiters park on
+the state word with [`pr::wait`] after a short spin instead of burning CPU
+through long critical sections."
+)]
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use core::sync::atomic::fence;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(feature = "std")]
+use crate::backoff::BackoffConfig;
+use crate::backoff::{self, Backoff, BackoffPolicy};
+use crate::cc::unlikely;
+#[cfg(feature = "std")]
+use crate::pr;
+use crate::spinlock::Contention;
+#[cfg(feature = "stats")]
+use crate::spinlock::{LockStats, StatsCell};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "alloc")]
+mod brlock;
+mod pflock;
+mod recursive;
+mod tflock;
+
+#[cfg(feature = "alloc")]
+pub use self::brlock::{BrLock, BrLockReadGuard, BrLockWriteGuard, BrReader};
+pub use self::pflock::{PfLock, PfLockReadGuard, PfLockWriteGuard};
+pub use self::recursive::{RecursiveReadGuard, RecursiveRwLock, RecursiveWriteGuard};
+#[cfg(feature = "stats")]
+pub use self::tflock::TfLockStats;
+pub use self::tflock::{TfLock, TfLockReadGuard, TfLockUpgradableGuard, TfLockWriteGuard};
+
+/// The writer bit of the state word.
+const WRITER: u32 = 1;
+/// Set by writers waiting for the lock; blocks new readers.
+const WAITING: u32 = 2;
+/// The reader count starts above the writer bits.
+const READER_SHIFT: u32 = 2;
+/// The state increment for one reader.
+const READER: u32 = 1 << READER_SHIFT;
+/// The most readers the state word can count.
+pub const MAX_READERS: u32 = u32::MAX >> READER_SHIFT;
+
+/// Backoff between timed attempts; the low ceiling keeps the overshoot past
+/// a deadline short.
+#[cfg(feature = "std")]
+const TIMED_BACKOFF: BackoffConfig = BackoffConfig::DETERMINISTIC.with_bounds(1 << 2, 1 << 10);
+
+/// Polls a blocking lock's waiter makes before parking.
+#[cfg(feature = "std")]
+const SPINS_BEFORE_PARK: u32 = 100;
+
+/// A reader-writer spinlock protecting a `T`.
+///
+/// Waiters poll the state word with a `B` backing off in between, unless
+/// the lock is blocking.
+pub struct RwLock<T: ?Sized, B = Backoff> {
+    state: AtomicU32,
+    /// Waiters park instead of spinning.
+    #[cfg(feature = "std")]
+    blocking: bool,
+    /// Threads parked on `state`.
+    #[cfg(feature = "std")]
+    sleepers: AtomicU32,
+    #[cfg(feature = "stats")]
+    stats: StatsCell,
+    _backoff: PhantomData<fn() -> B>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send, B> Send for RwLock<T, B> {}
+unsafe impl<T: ?Sized + Send + Sync, B> Sync for RwLock<T, B> {}
+
+impl<T> RwLock<T> {
+    /// Creates an unlocked lock protecting `value`.
+    pub const fn new(value: T) -> Self {
+        Self::with_backoff(value)
+    }
+
+    /// Creates an unlocked lock whose waiters park after a short spin
+    /// instead of spinning until the lock frees up.
+    #[cfg(feature = "std")]
+    pub const fn new_blocking(value: T) -> Self {
+        RwLock {
+            state: AtomicU32::new(0),
+            blocking: true,
+            sleepers: AtomicU32::new(0),
+            #[cfg(feature = "stats")]
+            stats: StatsCell::new(),
+            _backoff: PhantomData,
+            data: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T, B: BackoffPolicy> RwLock<T, B> {
+    /// Creates an unlocked lock protecting `value` whose waiters back off
+    /// with a `B` between polls.
+    pub const fn with_backoff(value: T) -> Self {
+        RwLock {
+            state: AtomicU32::new(0),
+            #[cfg(feature = "std")]
+            blocking: false,
+            #[cfg(feature = "std")]
+            sleepers: AtomicU32::new(0),
+            #[cfg(feature = "stats")]
+            stats: StatsCell::new(),
+            _backoff: PhantomData,
+            data: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T, B> RwLock<T, B> {
+    /// Consumes the lock, returning the protected value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized, B: BackoffPolicy> RwLock<T, B> {
+    /// Acquires shared access, waiting while a writer holds or waits for
+    /// the lock.
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuard<'_, T, B> {
+        let mut c = Contention::default();
+        while unlikely(!self.try_read_counted(&mut c)) {
+            c.failures += 1;
+            c.spins += self.wait_while(|s| s & (WRITER | WAITING) != 0);
+        }
+        #[cfg(feature = "stats")]
+        self.stats.acquired(c);
+        RwLockReadGuard { lock: self }
+    }
+
+    /// Acquires shared access if no writer holds or waits for the lock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`MAX_READERS`] read guards are already live, rather than
+    /// letting the count carry into the writer bits.
+    #[inline]
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T, B>> {
+        let mut c = Contention::default();
+        if !self.try_read_counted(&mut c) {
+            #[cfg(feature = "stats")]
+            self.stats.failed(c.failures + 1);
+            return None;
+        }
+        #[cfg(feature = "stats")]
+        self.stats.acquired(c);
+        Some(RwLockReadGuard { lock: self })
+    }
+
+    /// Takes a read lock if no writer holds or waits for it, counting
+    /// failed compare-and-swaps in `c`.
+    #[inline]
+    fn try_read_counted(&self, c: &mut Contention) -> bool {
+        let mut s = self.state.load(Ordering::Relaxed);
+        while s & (WRITER | WAITING) == 0 {
+            let Some(next) = s.checked_add(READER) else {
+                reader_overflow();
+            };
+            match self
+                .state
+                .compare_exchange_weak(s, next, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                Err(x) => s = x,
+            }
+            c.failures += 1;
+        }
+        false
+    }
+
+    /// Acquires exclusive access, waiting for readers and writers to leave.
+    ///
+    /// While it waits, the writer keeps the waiting bit set so that no new
+    /// readers enter.
+    #[inline]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T, B> {
+        let mut c = Contention::default();
+        while unlikely(!self.try_write_counted(&mut c)) {
+            c.failures += 1;
+            self.state.fetch_or(WAITING, Ordering::Relaxed);
+            c.spins += self.wait_while(|s| s & !WAITING != 0);
+        }
+        #[cfg(feature = "stats")]
+        self.stats.acquired(c);
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// Acquires exclusive access if no reader or writer holds the lock.
+    ///
+    /// Success clears the waiting bit; other waiting writers set it again.
+    #[inline]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T, B>> {
+        let mut c = Contention::default();
+        if !self.try_write_counted(&mut c) {
+            #[cfg(feature = "stats")]
+            self.stats.failed(c.failures + 1);
+            return None;
+        }
+        #[cfg(feature = "stats")]
+        self.stats.acquired(c);
+        Some(RwLockWriteGuard { lock: self })
+    }
+
+    /// Takes the write lock if no reader or writer holds it, counting
+    /// failed compare-and-swaps in `c`.
+    #[inline]
+    fn try_write_counted(&self, c: &mut Contention) -> bool {
+        let mut s = self.state.load(Ordering::Relaxed);
+        while s & !WAITING == 0 {
+            match self
+                .state
+                .compare_exchange_weak(s, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                Err(x) => s = x,
+            }
+            c.failures += 1;
+        }
+        false
+    }
+
+    /// Runs `f` under a read lock, releasing it when `f` returns or
+    /// unwinds.
+    #[inline]
+    pub fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read())
+    }
+
+    /// Runs `f` under the write lock, releasing it when `f` returns or
+    /// unwinds.
+    #[inline]
+    pub fn write_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    /// Waits until `cond` is false for the state, parking if the lock is
+    /// blocking, and returns how many times it found `cond` still true.
+    #[inline]
+    fn wait_while(&self, cond: impl Fn(u32) -> bool) -> u64 {
+        let mut polls = 0;
+        #[cfg(feature = "std")]
+        if self.blocking {
+            loop {
+                let s = self.state.load(Ordering::Acquire);
+                if !cond(s) {
+                    return polls;
+                }
+                polls += 1;
+                if polls <= u64::from(SPINS_BEFORE_PARK) {
+                    pr::stall();
+                    continue;
+                }
+                // Pairs with the fence in `wake`: either the releaser sees
+                // us counted, or the futex sees the state has moved on.
+                self.sleepers.fetch_add(1, Ordering::SeqCst);
+                pr::wait(&self.state, s);
+                self.sleepers.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+        backoff::spin_while::<B, _>(&self.state, |s| {
+            let busy = cond(s);
+            polls += u64::from(busy);
+            busy
+        });
+        polls
+    }
+
+    /// Returns the lock's contention counters, covering readers and
+    /// writers alike.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> LockStats {
+        self.stats.snapshot()
+    }
+}
+
+impl<T: ?Sized, B> RwLock<T, B> {
+    /// Wakes parked waiters after a release.
+    #[inline]
+    fn wake(&self) {
+        #[cfg(feature = "std")]
+        if self.blocking {
+            fence(Ordering::SeqCst);
+            if self.sleepers.load(Ordering::Relaxed) != 0 {
+                pr::wake_all(&self.state);
+            }
+        }
+    }
+
+    /// Returns `true` if waiters park rather than spin.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn is_blocking(&self) -> bool {
+        self.blocking
+    }
+
+    /// Returns the number of live read guards.
+    #[inline]
+    pub fn reader_count(&self) -> u32 {
+        self.state.load(Ordering::Relaxed) >> READER_SHIFT
+    }
+
+    /// Returns `true` if a writer holds the lock.
+    #[inline]
+    pub fn is_write_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+
+    /// Returns `true` if a reader or writer holds the lock.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & !WAITING != 0
+    }
+
+    /// Returns the value mutably; the exclusive borrow rules out other
+    /// holders, so no locking is needed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn reader_overflow() -> ! {
+    panic!("RwLock reader count overflow");
+}
+
+#[cfg(feature = "std")]
+impl<T: ?Sized, B: BackoffPolicy> RwLock<T, B> {
+    /// Like [`try_read`](Self::try_read), retrying until `timeout` elapses.
+    pub fn try_read_for(&self, timeout: Duration) -> Option<RwLockReadGuard<'_, T, B>> {
+        retry_for(timeout, || self.try_read())
+    }
+
+    /// Like [`try_write`](Self::try_write), retrying until `timeout`
+    /// elapses.
+    pub fn try_write_for(&self, timeout: Duration) -> Option<RwLockWriteGuard<'_, T, B>> {
+        retry_for(timeout, || self.try_write())
+    }
+}
+
+/// Calls `attempt` with backoff until it succeeds or `timeout` elapses.
+#[cfg(feature = "std")]
+fn retry_for<G>(timeout: Duration, mut attempt: impl FnMut() -> Option<G>) -> Option<G> {
+    let deadline = Instant::now().checked_add(timeout);
+    let mut backoff = Backoff::with_config(TIMED_BACKOFF);
+    loop {
+        if let Some(g) = attempt() {
+            return Some(g);
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return None;
+        }
+        backoff.spin();
+    }
+}
+
+impl<T: Default, B: BackoffPolicy> Default for RwLock<T, B> {
+    fn default() -> Self {
+        Self::with_backoff(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B: BackoffPolicy> fmt::Debug for RwLock<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_read() {
+            Some(g) => f.debug_tuple("RwLock").field(&&*g).finish(),
+            None => f.write_str("RwLock(<locked>)"),
+        }
+    }
+}
+
+/// Shared access to an [`RwLock`]'s data; releases on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct RwLockReadGuard<'a, T: ?Sized, B = Backoff> {
+    lock: &'a RwLock<T, B>,
+}
+
+unsafe impl<T: ?Sized + Sync, B> Sync for RwLockReadGuard<'_, T, B> {}
+
+impl<T: ?Sized, B> Deref for RwLockReadGuard<'_, T, B> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds a read lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, B> Drop for RwLockReadGuard<'_, T, B> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(READER, Ordering::Release);
+        self.lock.wake();
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B> fmt::Debug for RwLockReadGuard<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Exclusive access to an [`RwLock`]'s data; releases on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct RwLockWriteGuard<'a, T: ?Sized, B = Backoff> {
+    lock: &'a RwLock<T, B>,
+}
+
+unsafe impl<T: ?Sized + Sync, B> Sync for RwLockWriteGuard<'_, T, B> {}
+
+impl<'a, T: ?Sized, B> RwLockWriteGuard<'a, T, B> {
+    /// Turns exclusive access into shared access.
+    ///
+    /// The writer bit is traded for a reader count in one atomic step, so
+    /// no writer can get in between. Other readers may join unless a writer
+    /// is already waiting.
+    #[inline]
+    pub fn downgrade(self) -> RwLockReadGuard<'a, T, B> {
+        let lock = self.lock;
+        core::mem::forget(self);
+        lock.state.fetch_add(READER - WRITER, Ordering::Release);
+        lock.wake();
+        RwLockReadGuard { lock }
+    }
+}
+
+impl<T: ?Sized, B> Deref for RwLockWriteGuard<'_, T, B> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the write lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, B> DerefMut for RwLockWriteGuard<'_, T, B> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the write lock.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, B> Drop for RwLockWriteGuard<'_, T, B> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(WRITER, Ordering::Release);
+        self.lock.wake();
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B> fmt::Debug for RwLockWriteGuard<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn readers_share_writers_exclude() {
+        let l = RwLock::new(1);
+        let a = l.read();
+        let b = l.try_read().unwrap();
+        assert_eq!(*a + *b, 2);
+        assert!(l.try_write().is_none());
+        drop((a, b));
+        let mut w = l.write();
+        *w = 5;
+        assert!(l.is_write_locked());
+        assert!(l.try_read().is_none());
+        assert_eq!(std::format!("{l:?}"), "RwLock(<locked>)");
+        drop(w);
+        assert!(!l.is_locked());
+        let mut l = l;
+        *l.get_mut() += 1;
+        assert_eq!(l.into_inner(), 6);
+    }
+
+    #[test]
+    fn downgrade_keeps_writers_out() {
+        let l = RwLock::new(0);
+        let mut w = l.write();
+        *w = 1;
+        let r = w.downgrade();
+        assert!(!l.is_write_locked());
+        assert!(l.try_write().is_none());
+        assert_eq!(*l.try_read().unwrap(), 1);
+        assert_eq!(*r, 1);
+        drop(r);
+        assert!(!l.is_locked());
+    }
+
+    #[test]
+    fn reader_overflow_panics_without_corrupting_state() {
+        let l = RwLock::new(());
+        let r = l.read();
+        assert_eq!(l.reader_count(), 1);
+        l.state
+            .store(MAX_READERS << READER_SHIFT, Ordering::Relaxed);
+        assert_eq!(l.reader_count(), MAX_READERS);
+        let p = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| l.try_read().map(drop)));
+        assert!(p.is_err());
+        assert_eq!(l.reader_count(), MAX_READERS);
+        assert!(!l.is_write_locked());
+        l.state.store(READER, Ordering::Relaxed);
+        drop(r);
+        assert_eq!(l.reader_count(), 0);
+    }
+
+    #[test]
+    fn waiting_writer_blocks_new_readers() {
+        let l = RwLock::new(0);
+        let r = l.read();
+        thread::scope(|s| {
+            let w = s.spawn(|| *l.write() += 1);
+            while l.state.load(Ordering::Relaxed) & WAITING == 0 {
+                thread::yield_now();
+            }
+            assert!(l.try_read().is_none());
+            drop(r);
+            w.join().unwrap();
+        });
+        assert_eq!(*l.try_read().unwrap(), 1);
+    }
+
+    #[test]
+    fn writers_not_starved_by_overlapping_readers() {
+        use core::sync::atomic::AtomicBool;
+
+        let l = RwLock::new(0);
+        let stop = AtomicBool::new(false);
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    while !stop.load(Ordering::Relaxed) {
+                        let _r = l.read();
+                        thread::yield_now();
+                    }
+                });
+            }
+            for _ in 0..50 {
+                *l.write() += 1;
+                thread::yield_now();
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(l.into_inner(), 50);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_count_readers_and_writers() {
+        let l = RwLock::new(0);
+        let r = l.read();
+        assert!(l.try_write().is_none());
+        drop(r);
+        let w = l.write();
+        assert!(l.try_read().is_none());
+        drop(w);
+        let s = l.stats();
+        assert_eq!((s.acquisitions, s.cas_failures, s.max_spins), (2, 2, 0));
+    }
+
+    #[test]
+    fn no_backoff_waiters() {
+        use crate::backoff::NoBackoff;
+
+        let l: RwLock<u32, NoBackoff> = RwLock::with_backoff(0);
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..200 {
+                        *l.write() += 1;
+                        let _r = l.read();
+                    }
+                });
+            }
+        });
+        assert_eq!(l.into_inner(), 400);
+    }
+
+    #[test]
+    fn closures_release_on_panic() {
+        let l = RwLock::new(1);
+        assert_eq!(l.write_with(|v| core::mem::replace(v, 2)), 1);
+        assert_eq!(l.read_with(|v| *v), 2);
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            l.write_with(|_| panic!("writer failed"))
+        }));
+        assert!(r.is_err());
+        assert!(!l.is_locked());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn timed_attempts_give_up() {
+        use std::time::{Duration, Instant};
+
+        let l = RwLock::new(0);
+        let w = l.write();
+        let start = Instant::now();
+        assert!(l.try_read_for(Duration::from_millis(5)).is_none());
+        assert!(l.try_write_for(Duration::from_millis(5)).is_none());
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        drop(w);
+        let r = l.try_read_for(Duration::ZERO).unwrap();
+        thread::scope(|s| {
+            s.spawn(|| drop(r));
+            assert!(l.try_write_for(Duration::from_secs(10)).is_some());
+        });
+    }
+
+    #[test]
+    fn unsized_data_through_coercion() {
+        static COUNTER: RwLock<u32> = RwLock::new(0);
+        *COUNTER.write() += 1;
+        assert_eq!(*COUNTER.read(), 1);
+
+        let l = RwLock::new([1u8, 2, 3, 4]);
+        let s: &RwLock<[u8]> = &l;
+        s.write()[0] = 9;
+        assert_eq!(s.read().iter().sum::<u8>(), 18);
+        assert_eq!(l.into_inner(), [9, 2, 3, 4]);
+
+        let b: std::boxed::Box<RwLock<dyn fmt::Debug + Send + Sync>> =
+            std::boxed::Box::new(RwLock::new(7u8));
+        assert_eq!(std::format!("{:?}", &*b.read()), "7");
+        let r: &RecursiveRwLock<[u8]> = &RecursiveRwLock::new([0u8; 2]);
+        assert_eq!(r.read().len(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn blocking_waiters_park_and_wake() {
+        let l = RwLock::new_blocking(0);
+        assert!(l.is_blocking() && !RwLock::new(()).is_blocking());
+        let w = l.write();
+        thread::scope(|s| {
+            let r = s.spawn(|| *l.read());
+            let w2 = s.spawn(|| *l.write() += 1);
+            while l.sleepers.load(Ordering::Relaxed) < 2 {
+                thread::yield_now();
+            }
+            drop(w);
+            w2.join().unwrap();
+            assert_eq!(r.join().unwrap(), 1);
+        });
+        assert_eq!(l.sleepers.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn concurrent_readers_and_writers() {
+        let l = RwLock::new([0u64; 4]);
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..300 {
+                        let mut w = l.write();
+                        for x in w.iter_mut() {
+                            *x += 1;
+                        }
+                        let r = w.downgrade();
+                        assert!(r.iter().all(|&x| x == r[0]));
+                    }
+                });
+            }
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..300 {
+                        let r = l.read();
+                        assert!(r.iter().all(|&x| x == r[0]));
+                    }
+                });
+            }
+        });
+        assert_eq!(l.into_inner(), [600; 4]);
+    }
+}
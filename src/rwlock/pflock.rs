@@ -0,0 +1,648 @@
+//! The phase-fair reader-writer lock (`ck_pflock`).
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::backoff::{self, Backoff, BackoffPolicy};
+use crate::pr;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// Clears the writer bits of `rin` when a write phase ends.
+const LSB: u32 = 0xFFFF_FFF0;
+/// The `rin`/`rout` increment for one reader.
+const RINC: u32 = 0x100;
+/// The writer bits of `rin`.
+const WBITS: u32 = 0x3;
+/// Set in `rin` while a writer is present.
+const PRES: u32 = 0x2;
+/// The phase bit of `rin`, taken from the writer's ticket.
+const PHID: u32 = 0x1;
+
+/// A phase-fair reader-writer spinlock protecting a `T`.
+///
+/// Read and write phases alternate: a waiting writer holds off readers
+/// that arrive after it, and readers that arrive during a write phase go
+/// next once it ends, so neither side can starve the other. Writers are
+/// served in ticket order.
+///
+/// `rin` and `rout` count readers in and out, in units above the writer
+/// bits; `win` and `wout` are the writer ticket counters. Waiters poll
+/// them with a `B` backing off in between.
+pub struct PfLock<T: ?Sized, B = Backoff> {
+    rin: AtomicU32,
+    rout: AtomicU32,
+    win: AtomicU32,
+    wout: AtomicU32,
+    _backoff: PhantomData<fn() -> B>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send, B> Send for PfLock<T, B> {}
+unsafe impl<T: ?Sized + Send + Sync, B> Sync for PfLock<T, B> {}
+
+impl<T> PfLock<T> {
+    /// Creates an unlocked lock protecting `value`.
+    pub const fn new(value: T) -> Self {
+        Self::with_backoff(value)
+    }
+}
+
+impl<T, B: BackoffPolicy> PfLock<T, B> {
+    /// Creates an unlocked lock protecting `value` whose waiters back off
+    /// with a `B` between polls.
+    pub const fn with_backoff(value: T) -> Self {
+        PfLock {
+            rin: AtomicU32::new(0),
+            rout: AtomicU32::new(0),
+            win: AtomicU32::new(0),
+            wout: AtomicU32::new(0),
+            _backoff: PhantomData,
+            data: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T, B> PfLock<T, B> {
+    /// Consumes the lock, returning the protected value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized, B: BackoffPolicy> PfLock<T, B> {
+    /// Acquires shared access, waiting out a write phase that is pending
+    /// or under way.
+    pub fn read(&self) -> PfLockReadGuard<'_, T, B> {
+        let w = self.rin.fetch_add(RINC, Ordering::Acquire) & WBITS;
+        if w != 0 {
+            // The phase bit differs from one write phase to the next, so
+            // this ends once the phase we queued behind is over.
+            backoff::spin_while::<B, _>(&self.rin, |r| r & WBITS == w);
+        }
+        PfLockReadGuard { lock: self }
+    }
+
+    /// Acquires shared access if no writer is present.
+    #[inline]
+    pub fn try_read(&self) -> Option<PfLockReadGuard<'_, T, B>> {
+        let mut r = self.rin.load(Ordering::Relaxed);
+        while r & WBITS == 0 {
+            match self.rin.compare_exchange_weak(
+                r,
+                r.wrapping_add(RINC),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(PfLockReadGuard { lock: self }),
+                Err(x) => r = x,
+            }
+        }
+        None
+    }
+
+    /// Like [`try_read`](Self::try_read), polling up to `max_spins` more
+    /// times before giving up.
+    pub fn try_read_bounded(&self, max_spins: u32) -> Option<PfLockReadGuard<'_, T, B>> {
+        bounded(max_spins, || self.try_read())
+    }
+
+    /// Acquires exclusive access, queueing behind earlier writers and then
+    /// waiting for the readers of the current phase to leave.
+    pub fn write(&self) -> PfLockWriteGuard<'_, T, B> {
+        let ticket = self.win.fetch_add(1, Ordering::Relaxed);
+        backoff::load_when::<B, _>(&self.wout, ticket);
+        // Announce the write phase; readers arriving from now on wait.
+        let ticket = self
+            .rin
+            .fetch_add((ticket & PHID) | PRES, Ordering::Relaxed);
+        backoff::load_when::<B, _>(&self.rout, ticket);
+        PfLockWriteGuard { lock: self }
+    }
+
+    /// Acquires exclusive access if no reader or writer holds or waits for
+    /// the lock.
+    ///
+    /// A successful attempt takes the next writer ticket like
+    /// [`write`](Self::write); a failed one leaves no trace.
+    pub fn try_write(&self) -> Option<PfLockWriteGuard<'_, T, B>> {
+        let ticket = self.wout.load(Ordering::Relaxed);
+        self.win
+            .compare_exchange(
+                ticket,
+                ticket.wrapping_add(1),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .ok()?;
+        let r = self.rin.load(Ordering::Relaxed);
+        let acquired = self.rout.load(Ordering::Acquire) == r
+            && self
+                .rin
+                .compare_exchange(
+                    r,
+                    r | (ticket & PHID) | PRES,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .is_ok();
+        if acquired {
+            Some(PfLockWriteGuard { lock: self })
+        } else {
+            // Hand the ticket on as if our write phase had been empty.
+            self.wout.fetch_add(1, Ordering::Release);
+            None
+        }
+    }
+
+    /// Like [`try_write`](Self::try_write), polling up to `max_spins` more
+    /// times before giving up.
+    pub fn try_write_bounded(&self, max_spins: u32) -> Option<PfLockWriteGuard<'_, T, B>> {
+        bounded(max_spins, || self.try_write())
+    }
+
+    /// Runs `f` under a read lock, releasing it when `f` returns or
+    /// unwinds.
+    #[inline]
+    pub fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read())
+    }
+
+    /// Runs `f` under the write lock, releasing it when `f` returns or
+    /// unwinds.
+    #[inline]
+    pub fn write_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    /// Returns `true` if a writer holds or waits for the lock.
+    #[inline]
+    pub fn is_write_pending(&self) -> bool {
+        self.win.load(Ordering::Relaxed) != self.wout.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if a reader or writer holds or waits for the lock.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.is_write_pending()
+            || self.rin.load(Ordering::Relaxed) & !WBITS != self.rout.load(Ordering::Relaxed)
+    }
+
+    /// Returns the value mutably; the exclusive borrow rules out other
+    /// holders, so no locking is needed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: ?Sized, B: BackoffPolicy> PfLock<T, B> {
+    /// Like [`read`](Self::read), giving up once `timeout` elapses.
+    ///
+    /// The reader queues behind a pending write phase as `read` does. On
+    /// timeout it takes its count back out of `rin` while that phase is
+    /// still under way; the writer's ticket never included it, so the
+    /// writer is neither held up nor let in early. If the phase ended
+    /// first, the reader was admitted and keeps the lock.
+    pub fn read_for(&self, timeout: Duration) -> Option<PfLockReadGuard<'_, T, B>> {
+        let expired = deadline(timeout);
+        let w = self.rin.fetch_add(RINC, Ordering::Acquire) & WBITS;
+        if w == 0 {
+            return Some(PfLockReadGuard { lock: self });
+        }
+        let mut r = self.rin.load(Ordering::Acquire);
+        while r & WBITS == w {
+            if !expired() {
+                pr::stall();
+                r = self.rin.load(Ordering::Acquire);
+                continue;
+            }
+            match self.rin.compare_exchange_weak(
+                r,
+                r.wrapping_sub(RINC),
+                Ordering::Relaxed,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return None,
+                Err(x) => r = x,
+            }
+        }
+        Some(PfLockReadGuard { lock: self })
+    }
+
+    /// Like [`write`](Self::write), giving up once `timeout` elapses.
+    ///
+    /// A queued ticket cannot be abandoned, so the writer takes one only
+    /// when no other writer holds or waits for the lock. It then announces
+    /// its phase and waits for the readers to leave; on timeout it ends the
+    /// phase as an unlock would, releasing the readers it held off.
+    pub fn write_for(&self, timeout: Duration) -> Option<PfLockWriteGuard<'_, T, B>> {
+        let expired = deadline(timeout);
+        let ticket = loop {
+            let ticket = self.wout.load(Ordering::Relaxed);
+            if self
+                .win
+                .compare_exchange(
+                    ticket,
+                    ticket.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                break ticket;
+            }
+            if expired() {
+                return None;
+            }
+            pr::stall();
+        };
+        let ticket = self
+            .rin
+            .fetch_add((ticket & PHID) | PRES, Ordering::Relaxed);
+        while self.rout.load(Ordering::Acquire) != ticket {
+            if expired() {
+                self.rin.fetch_and(LSB, Ordering::Release);
+                self.wout.fetch_add(1, Ordering::Release);
+                return None;
+            }
+            pr::stall();
+        }
+        Some(PfLockWriteGuard { lock: self })
+    }
+}
+
+/// Returns a check for whether `timeout` has elapsed since the call.
+#[cfg(feature = "std")]
+fn deadline(timeout: Duration) -> impl Fn() -> bool {
+    let deadline = Instant::now().checked_add(timeout);
+    move || deadline.is_some_and(|d| Instant::now() >= d)
+}
+
+/// Calls `attempt` up to `max_spins + 1` times, stalling in between.
+fn bounded<G>(max_spins: u32, mut attempt: impl FnMut() -> Option<G>) -> Option<G> {
+    for _ in 0..max_spins {
+        if let Some(g) = attempt() {
+            return Some(g);
+        }
+        pr::stall();
+    }
+    attempt()
+}
+
+impl<T: Default, B: BackoffPolicy> Default for PfLock<T, B> {
+    fn default() -> Self {
+        Self::with_backoff(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B: BackoffPolicy> fmt::Debug for PfLock<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_read() {
+            Some(g) => f.debug_tuple("PfLock").field(&&*g).finish(),
+            None => f.write_str("PfLock(<locked>)"),
+        }
+    }
+}
+
+/// Shared access to a [`PfLock`]'s data; releases on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct PfLockReadGuard<'a, T: ?Sized, B = Backoff> {
+    lock: &'a PfLock<T, B>,
+}
+
+unsafe impl<T: ?Sized + Sync, B> Sync for PfLockReadGuard<'_, T, B> {}
+
+impl<T: ?Sized, B> Deref for PfLockReadGuard<'_, T, B> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds a read lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, B> Drop for PfLockReadGuard<'_, T, B> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.rout.fetch_add(RINC, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B> fmt::Debug for PfLockReadGuard<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Exclusive access to a [`PfLock`]'s data; releases on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct PfLockWriteGuard<'a, T: ?Sized, B = Backoff> {
+    lock: &'a PfLock<T, B>,
+}
+
+unsafe impl<T: ?Sized + Sync, B> Sync for PfLockWriteGuard<'_, T, B> {}
+
+impl<'a, T: ?Sized, B> PfLockWriteGuard<'a, T, B> {
+    /// Ends the write phase as a reader of the read phase that follows.
+    ///
+    /// The reader is counted in the same step that clears the writer bits,
+    /// so the next queued writer, let in only afterwards, waits for it like
+    /// any other reader of this phase. Readers that were waiting on the
+    /// write phase join at once.
+    #[inline]
+    pub fn downgrade(self) -> PfLockReadGuard<'a, T, B> {
+        let lock = self.lock;
+        core::mem::forget(self);
+        // Only the writer touches the writer bits, so they cannot change
+        // under us.
+        let bits = lock.rin.load(Ordering::Relaxed) & WBITS;
+        lock.rin
+            .fetch_add(RINC.wrapping_sub(bits), Ordering::Release);
+        lock.wout.fetch_add(1, Ordering::Release);
+        PfLockReadGuard { lock }
+    }
+}
+
+impl<T: ?Sized, B> Deref for PfLockWriteGuard<'_, T, B> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the write lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, B> DerefMut for PfLockWriteGuard<'_, T, B> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the write lock.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, B> Drop for PfLockWriteGuard<'_, T, B> {
+    #[inline]
+    fn drop(&mut self) {
+        // End the write phase, then let the next writer in.
+        self.lock.rin.fetch_and(LSB, Ordering::Release);
+        self.lock.wout.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B> fmt::Debug for PfLockWriteGuard<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Readers that have entered `rin` but not yet left through `rout`,
+    /// whether they hold the lock or wait for a write phase to end.
+    fn readers_in(l: &PfLock<u32>) -> u32 {
+        (l.rin.load(Ordering::Relaxed) & LSB).wrapping_sub(l.rout.load(Ordering::Relaxed)) / RINC
+    }
+
+    /// Writers holding or queued for the lock.
+    fn writers_in(l: &PfLock<u32>) -> u32 {
+        l.win
+            .load(Ordering::Relaxed)
+            .wrapping_sub(l.wout.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn waiting_writer_bounds_later_readers() {
+        let l = PfLock::new(0);
+        let order = AtomicU32::new(0);
+        let r = l.read();
+        thread::scope(|s| {
+            let w = s.spawn(|| l.write_with(|_| order.fetch_add(1, Ordering::Relaxed)));
+            while l.rin.load(Ordering::Relaxed) & PRES == 0 {
+                thread::yield_now();
+            }
+            // Readers arriving behind the writer wait for its phase.
+            assert!(l.try_read().is_none());
+            let late = s.spawn(|| l.read_with(|_| order.fetch_add(1, Ordering::Relaxed)));
+            while readers_in(&l) < 2 {
+                thread::yield_now();
+            }
+            drop(r);
+            assert_eq!(w.join().unwrap(), 0);
+            assert_eq!(late.join().unwrap(), 1);
+        });
+        assert!(!l.is_locked());
+    }
+
+    #[test]
+    fn waiting_readers_bound_later_writers() {
+        let l = PfLock::new(0);
+        let order = AtomicU32::new(0);
+        let w = l.write();
+        thread::scope(|s| {
+            let reader = s.spawn(|| l.read_with(|_| order.fetch_add(1, Ordering::Relaxed)));
+            while readers_in(&l) < 1 {
+                thread::yield_now();
+            }
+            let writer = s.spawn(|| l.write_with(|_| order.fetch_add(1, Ordering::Relaxed)));
+            while writers_in(&l) < 2 {
+                thread::yield_now();
+            }
+            // The queued writer's phase comes after the waiting reader's,
+            // however quickly it claims its ticket.
+            drop(w);
+            assert_eq!(reader.join().unwrap(), 0);
+            assert_eq!(writer.join().unwrap(), 1);
+        });
+        assert!(!l.is_locked());
+    }
+
+    #[test]
+    fn writers_are_served_in_ticket_order() {
+        let l = PfLock::new(0);
+        let w = l.write();
+        thread::scope(|s| {
+            let mut handles = std::vec::Vec::new();
+            for i in 1..=3 {
+                let l = &l;
+                handles.push(s.spawn(move || {
+                    let mut g = l.write();
+                    assert_eq!(*g, i - 1);
+                    *g = i;
+                }));
+                while writers_in(l) < i + 1 {
+                    thread::yield_now();
+                }
+            }
+            drop(w);
+            for h in handles {
+                h.join().unwrap();
+            }
+        });
+        assert_eq!(l.into_inner(), 3);
+    }
+
+    #[test]
+    fn try_variants_respect_the_phase() {
+        let l = PfLock::new(1);
+        let r = l.try_read().unwrap();
+        assert!(l.try_write().is_none());
+        assert!(l.try_write_bounded(10).is_none());
+        assert!(!l.is_write_pending());
+        drop(r);
+        assert!(!l.is_locked());
+
+        let mut w = l.try_write().unwrap();
+        *w = 2;
+        assert!(l.is_write_pending());
+        assert!(l.try_read().is_none());
+        assert!(l.try_read_bounded(10).is_none());
+        assert!(l.try_write().is_none());
+        drop(w);
+        assert_eq!(*l.try_read().unwrap(), 2);
+        assert!(!l.is_locked());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn timed_attempts_roll_back() {
+        use std::time::Duration;
+
+        let ms = Duration::from_millis(5);
+        let l = PfLock::new(0);
+        let r = l.read();
+        thread::scope(|s| {
+            let w = s.spawn(|| l.write_for(Duration::from_millis(50)).is_some());
+            while l.rin.load(Ordering::Relaxed) & PRES == 0 {
+                thread::yield_now();
+            }
+            // A reader queued behind the writer gets in once it gives up.
+            let late = s.spawn(|| l.read_for(Duration::MAX).map(|g| *g));
+            while readers_in(&l) < 2 {
+                thread::yield_now();
+            }
+            assert!(!w.join().unwrap());
+            assert_eq!(late.join().unwrap(), Some(0));
+        });
+        assert!(l.write_for(ms).is_none());
+        assert!(!l.is_write_pending());
+        drop(r);
+        assert!(!l.is_locked());
+
+        // A timed-out reader must not let a waiting writer past an earlier
+        // reader that is still inside.
+        let r = l.read();
+        thread::scope(|s| {
+            let w = s.spawn(|| l.write_with(|v| *v += 1));
+            while l.rin.load(Ordering::Relaxed) & PRES == 0 {
+                thread::yield_now();
+            }
+            assert!(l.read_for(ms).is_none());
+            std::thread::sleep(ms);
+            assert!(!w.is_finished());
+            assert_eq!(*r, 0);
+            drop(r);
+            w.join().unwrap();
+        });
+        assert_eq!(*l.read(), 1);
+        assert!(!l.is_locked());
+        *l.write() -= 1;
+
+        let w = l.write_for(ms).unwrap();
+        assert!(l.read_for(ms).is_none());
+        assert!(l.write_for(ms).is_none());
+        drop(w);
+        assert!(!l.is_locked());
+        *l.write_for(ms).unwrap() += 1;
+        assert_eq!(*l.read_for(ms).unwrap(), 1);
+        assert!(!l.is_locked());
+    }
+
+    #[test]
+    fn downgrade_keeps_the_next_writer_out() {
+        let l = PfLock::new(0);
+        let mut w = l.write();
+        thread::scope(|s| {
+            let writer = s.spawn(|| l.write_with(|v| *v += 1));
+            while writers_in(&l) < 2 {
+                thread::yield_now();
+            }
+            *w = 10;
+            let r = w.downgrade();
+            while l.rin.load(Ordering::Relaxed) & PRES == 0 {
+                thread::yield_now();
+            }
+            // The queued writer now owns the write phase but waits for us.
+            assert_eq!(*r, 10);
+            assert_eq!(readers_in(&l), 1);
+            drop(r);
+            writer.join().unwrap();
+        });
+        assert_eq!(*l.read(), 11);
+        assert!(!l.is_locked());
+    }
+
+    #[test]
+    fn failed_try_write_keeps_tickets_in_step() {
+        let l = PfLock::new(0);
+        for _ in 0..3 {
+            let _r = l.read();
+            assert!(l.try_write().is_none());
+        }
+        *l.write() += 1;
+        *l.try_write().unwrap() += 1;
+        assert_eq!(l.read_with(|v| *v), 2);
+        assert!(!l.is_locked());
+    }
+
+    #[test]
+    fn concurrent_readers_and_writers() {
+        const N: u32 = 200;
+        let l = PfLock::new((0u32, 0u32));
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for i in 0..N {
+                        if i % 2 == 0 {
+                            l.write_with(|(a, b)| {
+                                *a += 1;
+                                *b += 1;
+                            });
+                        } else if let Some(mut g) = l.try_write_bounded(4) {
+                            g.0 += 1;
+                            g.1 += 1;
+                        } else {
+                            l.write_with(|(a, b)| {
+                                *a += 1;
+                                *b += 1;
+                            });
+                        }
+                    }
+                });
+            }
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        let g = l.read();
+                        assert_eq!(g.0, g.1);
+                        drop(g);
+                        if let Some(g) = l.try_read() {
+                            assert_eq!(g.0, g.1);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(l.into_inner(), (2 * N, 2 * N));
+    }
+}
@@ -0,0 +1,236 @@
+//! The recursive reader-writer lock (`ck_rwlock_recursive`).
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::num::NonZeroU32;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::pr;
+
+/// A reader-writer spinlock whose writer may re-acquire it.
+///
+/// Writers identify themselves with a nonzero owner token, as the `tid`
+/// argument does in C; a writer that already owns the lock only bumps a
+/// depth counter. Because a nested write guard can coexist with an outer
+/// one, write guards give shared access only, and the protected value
+/// changes through interior mutability (atomics, locks, ...).
+///
+/// Distinct threads must use distinct tokens. A reader must not hold a read
+/// guard while taking the write lock, or the writer waits on itself.
+pub struct RecursiveRwLock<T: ?Sized> {
+    owner: AtomicU32,
+    readers: AtomicU32,
+    /// Write nesting depth; only touched by the owner.
+    depth: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RecursiveRwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RecursiveRwLock<T> {}
+
+impl<T> RecursiveRwLock<T> {
+    /// Creates an unlocked lock protecting `value`.
+    pub const fn new(value: T) -> Self {
+        RecursiveRwLock {
+            owner: AtomicU32::new(0),
+            readers: AtomicU32::new(0),
+            depth: AtomicU32::new(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes the lock, returning the protected value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RecursiveRwLock<T> {
+    /// Acquires shared access, waiting while any writer owns the lock.
+    pub fn read(&self) -> RecursiveReadGuard<'_, T> {
+        loop {
+            pr::spin_while(&self.owner, |o| o != 0);
+            self.readers.fetch_add(1, Ordering::SeqCst);
+            if self.owner.load(Ordering::SeqCst) == 0 {
+                return RecursiveReadGuard { lock: self };
+            }
+            self.readers.fetch_sub(1, Ordering::Release);
+        }
+    }
+
+    /// Acquires the write lock for `owner`, or nests one level deeper if
+    /// `owner` already holds it.
+    pub fn write(&self, owner: NonZeroU32) -> RecursiveWriteGuard<'_, T> {
+        let owner = owner.get();
+        if self.owner.load(Ordering::Relaxed) != owner {
+            while self
+                .owner
+                .compare_exchange_weak(0, owner, Ordering::SeqCst, Ordering::Relaxed)
+                .is_err()
+            {
+                pr::load_when(&self.owner, 0);
+            }
+            pr::load_when(&self.readers, 0);
+        }
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        RecursiveWriteGuard { lock: self }
+    }
+
+    /// Like [`write`](Self::write), but fails instead of waiting.
+    pub fn try_write(&self, owner: NonZeroU32) -> Option<RecursiveWriteGuard<'_, T>> {
+        let owner = owner.get();
+        if self.owner.load(Ordering::Relaxed) != owner {
+            self.owner
+                .compare_exchange(0, owner, Ordering::SeqCst, Ordering::Relaxed)
+                .ok()?;
+            if self.readers.load(Ordering::SeqCst) != 0 {
+                self.owner.store(0, Ordering::Release);
+                return None;
+            }
+        }
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        Some(RecursiveWriteGuard { lock: self })
+    }
+
+    /// Returns the owner token of the writer, if any.
+    #[inline]
+    pub fn owner(&self) -> Option<NonZeroU32> {
+        NonZeroU32::new(self.owner.load(Ordering::Relaxed))
+    }
+
+    /// Returns the value mutably; the exclusive borrow rules out other
+    /// holders, so no locking is needed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for RecursiveRwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for RecursiveRwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecursiveRwLock")
+            .field("owner", &self.owner())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Shared access to a [`RecursiveRwLock`]'s data; releases on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct RecursiveReadGuard<'a, T: ?Sized> {
+    lock: &'a RecursiveRwLock<T>,
+}
+
+impl<T: ?Sized> Deref for RecursiveReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds a read lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RecursiveReadGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.readers.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// One level of a [`RecursiveRwLock`] write lock; the lock is released when
+/// the outermost level drops.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct RecursiveWriteGuard<'a, T: ?Sized> {
+    lock: &'a RecursiveRwLock<T>,
+}
+
+impl<T: ?Sized> RecursiveWriteGuard<'_, T> {
+    /// Returns how many write levels the owner holds, including this one.
+    #[inline]
+    pub fn depth(&self) -> u32 {
+        self.lock.depth.load(Ordering::Relaxed)
+    }
+}
+
+impl<T: ?Sized> Deref for RecursiveWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard's owner holds the write lock; all of its guards
+        // only hand out shared references.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RecursiveWriteGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.lock.depth.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.lock.owner.store(0, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    const A: NonZeroU32 = NonZeroU32::new(1).unwrap();
+    const B: NonZeroU32 = NonZeroU32::new(2).unwrap();
+
+    #[test]
+    fn owner_reenters() {
+        let l = RecursiveRwLock::new(AtomicUsize::new(0));
+        let outer = l.write(A);
+        let inner = l.write(A);
+        assert_eq!(inner.depth(), 2);
+        inner.fetch_add(1, Ordering::Relaxed);
+        assert!(l.try_write(B).is_none());
+        drop(inner);
+        assert_eq!(l.owner(), Some(A));
+        drop(outer);
+        assert_eq!(l.owner(), None);
+        let r = l.read();
+        assert!(l.try_write(B).is_none());
+        drop(r);
+        assert_eq!(l.try_write(B).unwrap().load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn writers_exclude_readers() {
+        let l = RecursiveRwLock::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+        thread::scope(|s| {
+            for t in 1..3 {
+                let l = &l;
+                s.spawn(move || {
+                    let me = NonZeroU32::new(t).unwrap();
+                    for _ in 0..200 {
+                        let g = l.write(me);
+                        g[0].fetch_add(1, Ordering::Relaxed);
+                        let nested = l.write(me);
+                        nested[1].fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+            s.spawn(|| {
+                for _ in 0..200 {
+                    let r = l.read();
+                    assert_eq!(r[0].load(Ordering::Relaxed), r[1].load(Ordering::Relaxed));
+                }
+            });
+        });
+        let [a, b] = l.into_inner();
+        assert_eq!((a.into_inner(), b.into_inner()), (400, 400));
+    }
+}
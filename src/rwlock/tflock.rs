@@ -0,0 +1,662 @@
+//! The task-fair reader-writer lock (`ck_tflock`).
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::cc::CachePadded;
+use crate::pr;
+#[cfg(feature = "stats")]
+use crate::pr::AtomicU64;
+
+/// The writer half of a ticket word.
+const WRITER_MASK: u32 = 0xFFFF_0000;
+/// The reader half of a ticket word.
+const READER_MASK: u32 = 0x0000_FFFF;
+/// The ticket increment for one writer.
+const WRITER_INC: u32 = 1 << 16;
+/// The ticket increment for one reader.
+const READER_INC: u32 = 1;
+
+/// Adds `delta` to the `mask` half of `target`, wrapping within the half,
+/// and returns the previous value.
+#[inline]
+fn fetch_add_masked(target: &AtomicU32, mask: u32, delta: u32, order: Ordering) -> u32 {
+    let mut snapshot = target.load(Ordering::Relaxed);
+    loop {
+        let goal = (snapshot & !mask) | (snapshot.wrapping_add(delta) & mask);
+        match target.compare_exchange_weak(snapshot, goal, order, Ordering::Relaxed) {
+            Ok(_) => return snapshot,
+            Err(x) => snapshot = x,
+        }
+    }
+}
+
+/// A task-fair reader-writer spinlock protecting a `T`.
+///
+/// Readers and writers draw tickets from one `request` word and are
+/// admitted in arrival order as `completion` catches up. A writer waits for
+/// everyone ahead of it to finish; a reader waits only for the writers
+/// ahead of it, so a run of consecutive readers enters together.
+///
+/// Each word keeps a 16-bit reader count below a 16-bit writer count, so
+/// no more than 65535 readers may hold or wait for the lock at once.
+///
+/// The two words sit on separate cache lines: arrivals hammer `request`
+/// while releases hammer `completion`, and waiters poll only the latter.
+pub struct TfLock<T: ?Sized> {
+    request: CachePadded<AtomicU32>,
+    completion: CachePadded<AtomicU32>,
+    #[cfg(feature = "stats")]
+    stats: StatsCell,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for TfLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for TfLock<T> {}
+
+impl<T> TfLock<T> {
+    /// Creates an unlocked lock protecting `value`.
+    pub const fn new(value: T) -> Self {
+        TfLock {
+            request: CachePadded::new(AtomicU32::new(0)),
+            completion: CachePadded::new(AtomicU32::new(0)),
+            #[cfg(feature = "stats")]
+            stats: StatsCell::new(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes the lock, returning the protected value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> TfLock<T> {
+    /// Acquires shared access once every writer that arrived earlier has
+    /// finished.
+    pub fn read(&self) -> TfLockReadGuard<'_, T> {
+        let writers = fetch_add_masked(&self.request, READER_MASK, READER_INC, Ordering::Relaxed)
+            & WRITER_MASK;
+        pr::spin_while(&*self.completion, |c| c & WRITER_MASK != writers);
+        #[cfg(feature = "stats")]
+        self.stats.admitted(true);
+        TfLockReadGuard { lock: self }
+    }
+
+    /// Acquires exclusive access once every reader and writer that arrived
+    /// earlier has finished.
+    pub fn write(&self) -> TfLockWriteGuard<'_, T> {
+        let previous = fetch_add_masked(&self.request, WRITER_MASK, WRITER_INC, Ordering::Relaxed);
+        pr::load_when(&*self.completion, previous);
+        #[cfg(feature = "stats")]
+        self.stats.admitted(false);
+        TfLockWriteGuard { lock: self }
+    }
+
+    /// Acquires shared access if no writer holds or waits for the lock.
+    ///
+    /// A ticket is drawn only on success, so a failed attempt does not
+    /// queue.
+    #[inline]
+    pub fn try_read(&self) -> Option<TfLockReadGuard<'_, T>> {
+        let writers = self.completion.load(Ordering::Acquire) & WRITER_MASK;
+        let mut r = self.request.load(Ordering::Relaxed);
+        while r & WRITER_MASK == writers {
+            let goal = (r & WRITER_MASK) | (r.wrapping_add(READER_INC) & READER_MASK);
+            match self
+                .request
+                .compare_exchange_weak(r, goal, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => {
+                    #[cfg(feature = "stats")]
+                    self.stats.admitted(true);
+                    return Some(TfLockReadGuard { lock: self });
+                }
+                Err(x) => r = x,
+            }
+        }
+        None
+    }
+
+    /// Acquires exclusive access if no reader or writer holds or waits for
+    /// the lock.
+    ///
+    /// As with [`try_read`](Self::try_read), the ticket is drawn only on
+    /// success.
+    #[inline]
+    pub fn try_write(&self) -> Option<TfLockWriteGuard<'_, T>> {
+        let c = self.completion.load(Ordering::Acquire);
+        let goal = (c & READER_MASK) | (c.wrapping_add(WRITER_INC) & WRITER_MASK);
+        self.request
+            .compare_exchange(c, goal, Ordering::Relaxed, Ordering::Relaxed)
+            .ok()?;
+        #[cfg(feature = "stats")]
+        self.stats.admitted(false);
+        Some(TfLockWriteGuard { lock: self })
+    }
+
+    /// Acquires shared access that can later be upgraded to exclusive.
+    ///
+    /// The guard draws a writer ticket but waits only for earlier writers,
+    /// so it shares the lock with readers admitted before it. Everyone who
+    /// arrives later queues behind it as behind a writer, so an upgrade
+    /// waits only for those earlier readers and never jumps the queue.
+    /// Upgradable guards exclude one another.
+    pub fn upgradable_read(&self) -> TfLockUpgradableGuard<'_, T> {
+        let previous = fetch_add_masked(&self.request, WRITER_MASK, WRITER_INC, Ordering::Relaxed);
+        pr::spin_while(&*self.completion, |c| {
+            c & WRITER_MASK != previous & WRITER_MASK
+        });
+        #[cfg(feature = "stats")]
+        self.stats.admitted(true);
+        TfLockUpgradableGuard {
+            lock: self,
+            previous,
+        }
+    }
+
+    /// Runs `f` under a read lock, releasing it when `f` returns or
+    /// unwinds.
+    #[inline]
+    pub fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read())
+    }
+
+    /// Runs `f` under the write lock, releasing it when `f` returns or
+    /// unwinds.
+    #[inline]
+    pub fn write_with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    /// Returns `true` if a reader or writer holds or waits for the lock.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.request.load(Ordering::Relaxed) != self.completion.load(Ordering::Relaxed)
+    }
+
+    /// Returns a snapshot of the lock's admission counters.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn stats(&self) -> TfLockStats {
+        self.stats.snapshot()
+    }
+
+    /// Returns the value mutably; the exclusive borrow rules out other
+    /// holders, so no locking is needed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+/// A snapshot of a [`TfLock`]'s admission counters (the `stats` feature).
+///
+/// Comparing how many readers were admitted with how many tickets were
+/// drawn shows how well readers batch behind one another.
+#[cfg(feature = "stats")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TfLockStats {
+    /// Reader and writer tickets drawn, including upgradable reads.
+    pub tickets_issued: u64,
+    /// Shared acquisitions, including upgradable reads.
+    pub readers_admitted: u64,
+    /// Exclusive acquisitions, including upgrades.
+    pub writers_admitted: u64,
+}
+
+/// The live counters behind [`TfLockStats`], updated with relaxed atomics.
+#[cfg(feature = "stats")]
+struct StatsCell {
+    tickets_issued: AtomicU64,
+    readers_admitted: AtomicU64,
+    writers_admitted: AtomicU64,
+}
+
+#[cfg(feature = "stats")]
+impl StatsCell {
+    const fn new() -> Self {
+        StatsCell {
+            tickets_issued: AtomicU64::new(0),
+            readers_admitted: AtomicU64::new(0),
+            writers_admitted: AtomicU64::new(0),
+        }
+    }
+
+    /// Records a ticket drawn and honoured, shared or not.
+    #[inline]
+    fn admitted(&self, shared: bool) {
+        self.tickets_issued.fetch_add(1, Ordering::Relaxed);
+        let n = if shared {
+            &self.readers_admitted
+        } else {
+            &self.writers_admitted
+        };
+        n.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records an upgrade, which reuses its guard's ticket.
+    #[inline]
+    fn upgraded(&self) {
+        self.writers_admitted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> TfLockStats {
+        TfLockStats {
+            tickets_issued: self.tickets_issued.load(Ordering::Relaxed),
+            readers_admitted: self.readers_admitted.load(Ordering::Relaxed),
+            writers_admitted: self.writers_admitted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<T: Default> Default for TfLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for TfLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_read() {
+            Some(g) => f.debug_tuple("TfLock").field(&&*g).finish(),
+            None => f.write_str("TfLock(<locked>)"),
+        }
+    }
+}
+
+/// Shared access to a [`TfLock`]'s data; releases on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct TfLockReadGuard<'a, T: ?Sized> {
+    lock: &'a TfLock<T>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for TfLockReadGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for TfLockReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds a read lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for TfLockReadGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        fetch_add_masked(
+            &self.lock.completion,
+            READER_MASK,
+            READER_INC,
+            Ordering::Release,
+        );
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for TfLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Shared access to a [`TfLock`]'s data that can be upgraded; releases on
+/// drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct TfLockUpgradableGuard<'a, T: ?Sized> {
+    lock: &'a TfLock<T>,
+    /// The request word before our ticket.
+    previous: u32,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for TfLockUpgradableGuard<'_, T> {}
+
+impl<'a, T: ?Sized> TfLockUpgradableGuard<'a, T> {
+    /// Waits for the readers admitted before this guard to leave, then
+    /// turns it into exclusive access.
+    pub fn upgrade(self) -> TfLockWriteGuard<'a, T> {
+        let (lock, previous) = (self.lock, self.previous);
+        core::mem::forget(self);
+        pr::load_when(&*lock.completion, previous);
+        #[cfg(feature = "stats")]
+        lock.stats.upgraded();
+        TfLockWriteGuard { lock }
+    }
+
+    /// Like [`upgrade`](Self::upgrade), but hands the guard back if
+    /// earlier readers are still inside.
+    pub fn try_upgrade(self) -> Result<TfLockWriteGuard<'a, T>, Self> {
+        if self.lock.completion.load(Ordering::Acquire) != self.previous {
+            return Err(self);
+        }
+        let lock = self.lock;
+        core::mem::forget(self);
+        #[cfg(feature = "stats")]
+        lock.stats.upgraded();
+        Ok(TfLockWriteGuard { lock })
+    }
+}
+
+impl<T: ?Sized> Deref for TfLockUpgradableGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard excludes writers, as a read lock does.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for TfLockUpgradableGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        // Retire the writer ticket; earlier readers may still be inside,
+        // which only later readers can join.
+        fetch_add_masked(
+            &self.lock.completion,
+            WRITER_MASK,
+            WRITER_INC,
+            Ordering::Release,
+        );
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for TfLockUpgradableGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Exclusive access to a [`TfLock`]'s data; releases on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct TfLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a TfLock<T>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for TfLockWriteGuard<'_, T> {}
+
+impl<'a, T: ?Sized> TfLockWriteGuard<'a, T> {
+    /// Turns exclusive access into shared access.
+    ///
+    /// One step retires the writer ticket and leaves the reader half of
+    /// `completion` one short, as if a reader ahead of everyone queued were
+    /// still inside. Readers queued behind the writer enter at once;
+    /// writers queued behind it keep waiting until the returned guard
+    /// drops and settles the count.
+    #[inline]
+    pub fn downgrade(self) -> TfLockReadGuard<'a, T> {
+        let lock = self.lock;
+        core::mem::forget(self);
+        let _ = lock
+            .completion
+            .fetch_update(Ordering::Release, Ordering::Relaxed, |c| {
+                Some(
+                    (c.wrapping_add(WRITER_INC) & WRITER_MASK)
+                        | (c.wrapping_sub(READER_INC) & READER_MASK),
+                )
+            });
+        TfLockReadGuard { lock }
+    }
+}
+
+impl<T: ?Sized> Deref for TfLockWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the write lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for TfLockWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the write lock.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for TfLockWriteGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        fetch_add_masked(
+            &self.lock.completion,
+            WRITER_MASK,
+            WRITER_INC,
+            Ordering::Release,
+        );
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for TfLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Tickets drawn but not yet completed, as (readers, writers).
+    fn waiting(l: &TfLock<u32>) -> (u32, u32) {
+        let d = l
+            .request
+            .load(Ordering::Relaxed)
+            .wrapping_sub(l.completion.load(Ordering::Relaxed));
+        (d & READER_MASK, d >> 16)
+    }
+
+    #[test]
+    fn masked_add_wraps_within_its_half() {
+        let a = AtomicU32::new(0x0001_FFFF);
+        assert_eq!(
+            fetch_add_masked(&a, READER_MASK, READER_INC, Ordering::Relaxed),
+            0x0001_FFFF
+        );
+        assert_eq!(a.load(Ordering::Relaxed), 0x0001_0000);
+        fetch_add_masked(&a, WRITER_MASK, 0xFFFF << 16, Ordering::Relaxed);
+        assert_eq!(a.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn try_variants_draw_no_ticket_on_failure() {
+        let l = TfLock::new(1);
+        let r = l.try_read().unwrap();
+        assert!(l.try_write().is_none());
+        let r2 = l.try_read().unwrap();
+        assert_eq!(waiting(&l), (2, 0));
+        drop((r, r2));
+
+        let mut w = l.try_write().unwrap();
+        *w = 2;
+        assert!(l.try_read().is_none());
+        assert!(l.try_write().is_none());
+        assert_eq!(waiting(&l), (0, 1));
+        assert_eq!(std::format!("{l:?}"), "TfLock(<locked>)");
+        drop(w);
+        assert!(!l.is_locked());
+        assert_eq!(std::format!("{l:?}"), "TfLock(2)");
+    }
+
+    #[test]
+    fn try_read_fails_behind_a_queued_writer() {
+        let l = TfLock::new(0);
+        let r = l.read();
+        thread::scope(|s| {
+            let w = s.spawn(|| l.write_with(|v| *v += 1));
+            while waiting(&l) != (1, 1) {
+                thread::yield_now();
+            }
+            assert!(l.try_read().is_none());
+            drop(r);
+            w.join().unwrap();
+        });
+        assert_eq!(*l.try_read().unwrap(), 1);
+    }
+
+    #[test]
+    fn downgrade_admits_queued_readers_but_not_writers() {
+        let l = TfLock::new(0);
+        let mut w = l.write();
+        thread::scope(|s| {
+            let reader = s.spawn(|| *l.read());
+            while waiting(&l) != (1, 1) {
+                thread::yield_now();
+            }
+            let writer = s.spawn(|| l.write_with(|v| *v += 1));
+            while waiting(&l) != (1, 2) {
+                thread::yield_now();
+            }
+            *w = 10;
+            let r = w.downgrade();
+            assert_eq!(reader.join().unwrap(), 10);
+            assert!(l.try_read().is_none());
+            assert_eq!(*r, 10);
+            drop(r);
+            writer.join().unwrap();
+        });
+        assert!(!l.is_locked());
+        assert_eq!(l.into_inner(), 11);
+    }
+
+    #[test]
+    fn upgradable_read_shares_with_earlier_readers_only() {
+        let l = TfLock::new(0);
+        let r = l.read();
+        let u = l.upgradable_read();
+        assert_eq!(*u + *r, 0);
+        // Later arrivals queue behind the upgradable guard.
+        assert!(l.try_read().is_none());
+        let u = u.try_upgrade().unwrap_err();
+        thread::scope(|s| {
+            // The upgrade waits for the earlier reader only.
+            let up = s.spawn(move || *u.upgrade() += 1);
+            thread::yield_now();
+            assert!(!up.is_finished());
+            drop(r);
+            up.join().unwrap();
+        });
+        assert_eq!(*l.upgradable_read().try_upgrade().ok().unwrap(), 1);
+        drop(l.upgradable_read());
+        assert!(!l.is_locked());
+    }
+
+    #[test]
+    fn counters_sit_on_their_own_lines() {
+        use crate::cc::CACHELINE;
+        let l = TfLock::new(0u8);
+        let (r, c) = (
+            &*l.request as *const AtomicU32 as usize,
+            &*l.completion as *const AtomicU32 as usize,
+        );
+        assert!(r.abs_diff(c) >= CACHELINE);
+        assert!(core::mem::size_of_val(&l) >= 2 * CACHELINE);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_count_admissions() {
+        let l = TfLock::new(0);
+        drop((l.read(), l.try_read().unwrap()));
+        drop(l.write());
+        drop(l.try_write().unwrap());
+        drop(l.upgradable_read().upgrade());
+        assert!(l.try_write().is_some());
+        assert_eq!(
+            l.stats(),
+            TfLockStats {
+                tickets_issued: 6,
+                readers_admitted: 3,
+                writers_admitted: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn consecutive_readers_enter_together() {
+        let l = TfLock::new(1);
+        let a = l.read();
+        let order = AtomicU32::new(0);
+        thread::scope(|s| {
+            // Readers behind a reader get in at once, each holding the lock
+            // while the next one enters.
+            let b = s.spawn(|| {
+                let g = l.read();
+                order.fetch_add(1, Ordering::Relaxed);
+                while order.load(Ordering::Relaxed) < 2 {
+                    thread::yield_now();
+                }
+                *g
+            });
+            let c = s.spawn(|| {
+                let g = l.read();
+                order.fetch_add(1, Ordering::Relaxed);
+                while order.load(Ordering::Relaxed) < 2 {
+                    thread::yield_now();
+                }
+                *g
+            });
+            assert_eq!(b.join().unwrap() + c.join().unwrap(), 2);
+        });
+        assert_eq!(*a, 1);
+        drop(a);
+        assert!(!l.is_locked());
+    }
+
+    #[test]
+    fn arrivals_are_served_in_order() {
+        let l = TfLock::new(0);
+        let r = l.read();
+        thread::scope(|s| {
+            let w = s.spawn(|| l.write_with(|v| *v += 1));
+            while waiting(&l) != (1, 1) {
+                thread::yield_now();
+            }
+            // A reader arriving behind the waiting writer sees its write.
+            let late = s.spawn(|| *l.read());
+            while waiting(&l) != (2, 1) {
+                thread::yield_now();
+            }
+            assert_eq!(*r, 0);
+            drop(r);
+            w.join().unwrap();
+            assert_eq!(late.join().unwrap(), 1);
+        });
+        assert!(!l.is_locked());
+    }
+
+    #[test]
+    fn concurrent_readers_and_writers() {
+        const N: u32 = 200;
+        let l = TfLock::new((0u32, 0u32));
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        l.write_with(|(a, b)| {
+                            *a += 1;
+                            *b += 1;
+                        });
+                    }
+                });
+            }
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        l.read_with(|(a, b)| assert_eq!(a, b));
+                    }
+                });
+            }
+        });
+        assert!(!l.is_locked());
+        assert_eq!(l.into_inner(), (2 * N, 2 * N));
+    }
+}
@@ -0,0 +1,164 @@
+//! A value protected by its own sequence lock.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::{SeqLock, Starved};
+use crate::pr;
+
+/// A `Copy` value readers can snapshot without blocking the writer.
+///
+/// Writers take an internal flag, so [`write`](Self::write) may be called
+/// from any number of threads. Readers copy the value and retry while a
+/// write overlaps, so `T` should be small.
+pub struct SeqLockData<T: Copy> {
+    lock: SeqLock,
+    writer: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Copy + Send> Send for SeqLockData<T> {}
+unsafe impl<T: Copy + Send> Sync for SeqLockData<T> {}
+
+impl<T: Copy> SeqLockData<T> {
+    /// Wraps `value`.
+    pub const fn new(value: T) -> Self {
+        SeqLockData {
+            lock: SeqLock::new(),
+            writer: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns a consistent copy of the value.
+    #[inline]
+    pub fn read(&self) -> T {
+        loop {
+            let version = self.lock.read_begin();
+            let value = self.copy();
+            if !self.lock.read_retry(version) {
+                // SAFETY: no write overlapped the copy.
+                return unsafe { value.assume_init() };
+            }
+        }
+    }
+
+    /// Copies the value, which a concurrent write may tear; callers
+    /// `assume_init` only once the sequence check has passed.
+    #[inline(always)]
+    fn copy(&self) -> MaybeUninit<T> {
+        // SAFETY: the pointer is valid, and the copy stays uninitialised
+        // until it is known to be whole. As in `ck_sequence`, the racing
+        // read is volatile so it stays a copy.
+        unsafe { ptr::read_volatile(self.value.get() as *const MaybeUninit<T>) }
+    }
+
+    /// Like [`read`](Self::read) but gives up after `max_retries` failed
+    /// attempts.
+    #[inline]
+    pub fn read_bounded(&self, max_retries: u32) -> Result<T, Starved> {
+        let value = self.lock.read_bounded(max_retries, || self.copy())?;
+        // SAFETY: `read_bounded` returns only a copy no write overlapped.
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// Reads optimistically up to `max_retries + 1` times, then takes the
+    /// writer flag and reads under it, so readers cannot livelock behind a
+    /// steady stream of writers.
+    pub fn read_or_lock(&self, max_retries: u32) -> T {
+        self.read_bounded(max_retries).unwrap_or_else(|Starved| {
+            self.lock_writer();
+            // SAFETY: no write section can be open while we hold the flag.
+            let value = unsafe { self.copy().assume_init() };
+            self.unlock_writer();
+            value
+        })
+    }
+
+    fn lock_writer(&self) {
+        while self
+            .writer
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            pr::stall();
+        }
+    }
+
+    fn unlock_writer(&self) {
+        self.writer.store(false, Ordering::Release);
+    }
+
+    /// Replaces the value.
+    pub fn write(&self, value: T) {
+        self.lock_writer();
+        // SAFETY: the flag serializes writers, and readers discard copies
+        // that overlap this section.
+        unsafe {
+            let _section = self.lock.write_begin();
+            ptr::write_volatile(self.value.get(), value);
+        }
+        self.unlock_writer();
+    }
+
+    /// Returns the value mutably; the exclusive borrow rules out readers.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the wrapper, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Copy + Default> Default for SeqLockData<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for SeqLockData<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SeqLockData").field(&self.read()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn readers_never_see_torn_values() {
+        let d = SeqLockData::new([0u64; 4]);
+        thread::scope(|s| {
+            for t in 1..3u64 {
+                let d = &d;
+                s.spawn(move || {
+                    for i in 0..2000 {
+                        d.write([t * 10_000 + i; 4]);
+                    }
+                });
+            }
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..2000 {
+                        let v = d.read();
+                        assert!(v.iter().all(|&x| x == v[0]));
+                        let v = d.read_or_lock(1);
+                        assert!(v.iter().all(|&x| x == v[0]));
+                    }
+                });
+            }
+        });
+        let mut d = d;
+        d.get_mut()[0] = 7;
+        assert_eq!(d.into_inner()[0], 7);
+    }
+}
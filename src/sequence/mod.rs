@@ -0,0 +1,273 @@
+//! Sequence locks modelled on `ck_sequence`.
+//!
+//! A [`SeqLock`] lets readers run without writing shared memory: they
+//! snapshot an even sequence number, read the protected data, and retry if
+//! the number changed meanwhile. Writers make the number odd for the length
+//! of their update. Writers must be serialized by other means;
+//! [`SeqLockData`] bundles a lock, its data and that serialization.
+//! [`DoubleBufferedSeqLock`] keeps two copies so readers never wait.
+//!
+//! Under `--cfg loom` the counter is a `loom` atomic so the protocol can be
+//! model-checked (`RUSTFLAGS="--cfg loom" cargo test --lib sequence`).
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+#[cfg(not(all(loom, test)))]
+use crate::pr::stall;
+#[cfg(not(all(loom, test)))]
+use core::sync::atomic::{fence, AtomicU32};
+#[cfg(all(loom, test))]
+use loom::sync::atomic::{fence, AtomicU32};
+#[cfg(all(loom, test))]
+use loom::thread::yield_now as stall;
+
+#[cfg(not(all(loom, test)))]
+mod data;
+#[cfg(not(all(loom, test)))]
+mod double;
+
+#[cfg(not(all(loom, test)))]
+pub use self::data::SeqLockData;
+#[cfg(not(all(loom, test)))]
+pub use self::double::DoubleBufferedSeqLock;
+
+/// A bounded read gave up after its retry budget ran out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Starved;
+
+impl fmt::Display for Starved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sequence read starved by writers")
+    }
+}
+
+/// A sequence counter (`ck_sequence_t`).
+#[derive(Debug, Default)]
+pub struct SeqLock {
+    sequence: AtomicU32,
+}
+
+impl SeqLock {
+    /// Creates a sequence with no write in progress.
+    #[cfg(not(all(loom, test)))]
+    pub const fn new() -> Self {
+        SeqLock {
+            sequence: AtomicU32::new(0),
+        }
+    }
+
+    #[cfg(all(loom, test))]
+    pub fn new() -> Self {
+        SeqLock {
+            sequence: AtomicU32::new(0),
+        }
+    }
+
+    /// Waits for any write in progress and returns the version to pass to
+    /// [`read_retry`](Self::read_retry).
+    #[inline]
+    pub fn read_begin(&self) -> u32 {
+        loop {
+            let version = self.sequence.load(Ordering::Acquire);
+            if version & 1 == 0 {
+                return version;
+            }
+            stall();
+        }
+    }
+
+    /// Returns `true` if a write overlapped the read section that began
+    /// with `version`, so the data read must be discarded.
+    ///
+    /// The acquire fence pairs with the release fence in
+    /// [`write_begin`](Self::write_begin): if a read in the section saw a
+    /// write made after that fence, the counter load here sees it changed.
+    #[inline]
+    pub fn read_retry(&self, version: u32) -> bool {
+        fence(Ordering::Acquire);
+        self.sequence.load(Ordering::Relaxed) != version
+    }
+
+    /// Runs the read section `f` until it completes without overlapping a
+    /// write, making at most `max_retries + 1` attempts.
+    ///
+    /// An attempt that finds a write in progress counts as failed without
+    /// running `f`. `f` may observe torn data, so it must only copy.
+    pub fn read_bounded<T>(
+        &self,
+        max_retries: u32,
+        mut f: impl FnMut() -> T,
+    ) -> Result<T, Starved> {
+        for _ in 0..=max_retries {
+            let version = self.sequence.load(Ordering::Acquire);
+            if version & 1 == 0 {
+                let value = f();
+                if !self.read_retry(version) {
+                    return Ok(value);
+                }
+            }
+            stall();
+        }
+        Err(Starved)
+    }
+
+    /// Starts a write section, which lasts until the returned guard is
+    /// dropped, including by a panic or early return.
+    ///
+    /// The odd counter is published with an `AcqRel` increment, so the
+    /// section's data writes cannot be hoisted above it, followed by a
+    /// release fence that pairs with the acquire fence in
+    /// [`read_retry`](Self::read_retry).
+    ///
+    /// # Safety
+    ///
+    /// Writers must be serialized: no other write section may be open.
+    #[inline]
+    pub unsafe fn write_begin(&self) -> SeqWriteGuard<'_> {
+        self.sequence.fetch_add(1, Ordering::AcqRel);
+        fence(Ordering::Release);
+        SeqWriteGuard { lock: self }
+    }
+
+    /// Ends the write section; only called by [`SeqWriteGuard`].
+    #[inline]
+    fn write_end(&self) {
+        self.sequence.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// An open write section of a [`SeqLock`] (`ck_sequence_write_end` on drop).
+#[must_use = "dropping the guard ends the write section at once"]
+#[derive(Debug)]
+pub struct SeqWriteGuard<'a> {
+    lock: &'a SeqLock,
+}
+
+impl Drop for SeqWriteGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.write_end();
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_invalidates_reads() {
+        let s = SeqLock::new();
+        let v = s.read_begin();
+        assert!(!s.read_retry(v));
+        drop(unsafe { s.write_begin() });
+        assert!(s.read_retry(v));
+        let w = s.read_begin();
+        assert_eq!(w, v + 2);
+        assert!(!s.read_retry(w));
+    }
+
+    #[test]
+    fn bounded_read_starves_behind_writer() {
+        let s = SeqLock::new();
+        assert_eq!(s.read_bounded(0, || 5), Ok(5));
+        let g = unsafe { s.write_begin() };
+        let mut calls = 0;
+        assert_eq!(s.read_bounded(3, || calls += 1), Err(Starved));
+        assert_eq!(calls, 0);
+        drop(g);
+        assert_eq!(s.read_bounded(0, || 6), Ok(6));
+    }
+
+    #[test]
+    fn panicking_writer_still_ends_section() {
+        let s = SeqLock::new();
+        let r = std::panic::catch_unwind(|| {
+            let _g = unsafe { s.write_begin() };
+            panic!("writer failed");
+        });
+        assert!(r.is_err());
+        assert_eq!(s.read_begin() & 1, 0);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::atomic::AtomicU32;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    /// Two words a writer keeps equal; a reader that passes `read_retry`
+    /// must never see them differ.
+    struct Pair {
+        lock: SeqLock,
+        a: AtomicU32,
+        b: AtomicU32,
+    }
+
+    impl Pair {
+        fn new() -> Self {
+            Pair {
+                lock: SeqLock::new(),
+                a: AtomicU32::new(0),
+                b: AtomicU32::new(0),
+            }
+        }
+
+        fn write(&self, v: u32) {
+            // SAFETY: each model has a single writer thread.
+            let _g = unsafe { self.lock.write_begin() };
+            self.a.store(v, Ordering::Relaxed);
+            self.b.store(v, Ordering::Relaxed);
+        }
+
+        fn try_read(&self) -> Option<(u32, u32)> {
+            self.lock
+                .read_bounded(0, || {
+                    (
+                        self.a.load(Ordering::Relaxed),
+                        self.b.load(Ordering::Relaxed),
+                    )
+                })
+                .ok()
+        }
+    }
+
+    #[test]
+    fn reader_never_sees_torn_write() {
+        loom::model(|| {
+            let p = Arc::new(Pair::new());
+            let w = {
+                let p = p.clone();
+                thread::spawn(move || p.write(1))
+            };
+            if let Some((a, b)) = p.try_read() {
+                assert_eq!(a, b);
+            }
+            w.join().unwrap();
+            assert_eq!(p.try_read(), Some((1, 1)));
+        });
+    }
+
+    #[test]
+    fn reader_across_two_writes() {
+        loom::model(|| {
+            let p = Arc::new(Pair::new());
+            let w = {
+                let p = p.clone();
+                thread::spawn(move || {
+                    p.write(1);
+                    p.write(2);
+                })
+            };
+            let v = p.lock.read_begin();
+            let (a, b) = (p.a.load(Ordering::Relaxed), p.b.load(Ordering::Relaxed));
+            if !p.lock.read_retry(v) {
+                assert_eq!(a, b);
+                assert_eq!(a, v / 2);
+            }
+            w.join().unwrap();
+        });
+    }
+}
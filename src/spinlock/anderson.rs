@@ -0,0 +1,214 @@
+//! The Anderson array lock (`ck_spinlock_anderson`).
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use super::{CriticalSectionHook, IrqSaveGuard};
+use crate::cc::CachePadded;
+use crate::pr;
+
+/// A fair array-based spinlock protecting a `T`, for up to `N` concurrent
+/// lockers.
+///
+/// Each waiter takes a ticket and spins on its own cache-padded slot, which
+/// the previous holder sets on release; no queue nodes are needed. `N` must
+/// be a power of two. Threads beyond the first `N` wait to be admitted
+/// before taking a ticket, so slots are never shared.
+pub struct AndersonLock<T: ?Sized, const N: usize> {
+    slots: [CachePadded<AtomicBool>; N],
+    next: CachePadded<AtomicUsize>,
+    waiters: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send, const N: usize> Send for AndersonLock<T, N> {}
+unsafe impl<T: ?Sized + Send, const N: usize> Sync for AndersonLock<T, N> {}
+
+impl<T, const N: usize> AndersonLock<T, N> {
+    /// Creates an unlocked lock protecting `value`.
+    pub const fn new(value: T) -> Self {
+        const {
+            assert!(
+                N.is_power_of_two(),
+                "AndersonLock slot count must be a power of two"
+            )
+        };
+        let mut slots = [const { CachePadded::new(AtomicBool::new(false)) }; N];
+        slots[0] = CachePadded::new(AtomicBool::new(true));
+        AndersonLock {
+            slots,
+            next: CachePadded::new(AtomicUsize::new(0)),
+            waiters: AtomicUsize::new(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes the lock, returning the protected value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized, const N: usize> AndersonLock<T, N> {
+    const MASK: usize = N - 1;
+
+    /// Acquires the lock.
+    pub fn lock(&self) -> AndersonGuard<'_, T, N> {
+        let mut w = self.waiters.load(Ordering::Relaxed);
+        loop {
+            if w < N {
+                match self.waiters.compare_exchange_weak(
+                    w,
+                    w + 1,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(x) => w = x,
+                }
+            } else {
+                pr::stall();
+                w = self.waiters.load(Ordering::Relaxed);
+            }
+        }
+        self.enter()
+    }
+
+    /// Acquires the lock inside an `H` critical section, which ends after
+    /// the lock is released.
+    #[inline]
+    pub fn lock_irqsave<H: CriticalSectionHook>(&self) -> IrqSaveGuard<AndersonGuard<'_, T, N>, H> {
+        IrqSaveGuard::acquire(|| self.lock())
+    }
+
+    /// Acquires the lock if no thread holds or waits for it.
+    pub fn try_lock(&self) -> Option<AndersonGuard<'_, T, N>> {
+        self.waiters
+            .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        Some(self.enter())
+    }
+
+    /// Returns `true` if the lock is held.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.waiters.load(Ordering::Relaxed) != 0
+    }
+
+    /// Returns the value mutably; the exclusive borrow rules out other
+    /// holders, so no locking is needed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn enter(&self) -> AndersonGuard<'_, T, N> {
+        let position = self.next.fetch_add(1, Ordering::Relaxed) & Self::MASK;
+        pr::load_when(&*self.slots[position], true);
+        AndersonGuard {
+            lock: self,
+            position,
+        }
+    }
+
+    fn unlock(&self, position: usize) {
+        self.slots[position].store(false, Ordering::Relaxed);
+        self.slots[(position + 1) & Self::MASK].store(true, Ordering::Release);
+        self.waiters.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<T: Default, const N: usize> Default for AndersonLock<T, N> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, const N: usize> fmt::Debug for AndersonLock<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_lock() {
+            Some(g) => f.debug_tuple("AndersonLock").field(&&*g).finish(),
+            None => f.write_str("AndersonLock(<locked>)"),
+        }
+    }
+}
+
+/// Exclusive access to an [`AndersonLock`]'s data; releases the lock on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct AndersonGuard<'a, T: ?Sized, const N: usize> {
+    lock: &'a AndersonLock<T, N>,
+    position: usize,
+}
+
+unsafe impl<T: ?Sized + Sync, const N: usize> Sync for AndersonGuard<'_, T, N> {}
+
+impl<T: ?Sized, const N: usize> Deref for AndersonGuard<'_, T, N> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, const N: usize> DerefMut for AndersonGuard<'_, T, N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, const N: usize> Drop for AndersonGuard<'_, T, N> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.unlock(self.position);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, const N: usize> fmt::Debug for AndersonGuard<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn slots_wrap_around() {
+        let l = AndersonLock::<_, 2>::new(0);
+        for _ in 0..5 {
+            *l.lock() += 1;
+        }
+        let g = l.try_lock().unwrap();
+        assert_eq!(*g, 5);
+        assert!(l.is_locked());
+        assert!(l.try_lock().is_none());
+    }
+
+    #[test]
+    fn more_threads_than_slots() {
+        const N: usize = 200;
+        let l = AndersonLock::<_, 2>::new(0usize);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        *l.lock() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(*l.lock(), 4 * N);
+        let mut l = l;
+        *l.get_mut() += 1;
+        assert_eq!(l.into_inner(), 4 * N + 1);
+    }
+}
@@ -0,0 +1,127 @@
+//! The compare-and-swap lock (`ck_spinlock_cas`).
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "debug-locks")]
+use super::debug::Owner;
+use crate::backoff::{self, Backoff, BackoffPolicy};
+use crate::cc::unlikely;
+
+/// A compare-and-swap spinlock protecting a `T`.
+///
+/// Like [`FasLock`](super::FasLock), but takes the flag with a
+/// compare-and-swap, which fails without writing when the lock is held.
+pub struct CasLock<T: ?Sized, B = Backoff> {
+    locked: AtomicBool,
+    #[cfg(feature = "debug-locks")]
+    owner: Owner,
+    _backoff: PhantomData<fn() -> B>,
+    data: UnsafeCell<T>,
+}
+
+impl<T> CasLock<T> {
+    /// Creates an unlocked lock protecting `value`.
+    pub const fn new(value: T) -> Self {
+        Self::with_backoff(value)
+    }
+}
+
+impl<T, B: BackoffPolicy> CasLock<T, B> {
+    /// Creates an unlocked lock protecting `value` that waits with a `B`
+    /// between polls.
+    pub const fn with_backoff(value: T) -> Self {
+        CasLock {
+            locked: AtomicBool::new(false),
+            #[cfg(feature = "debug-locks")]
+            owner: Owner::new(),
+            _backoff: PhantomData,
+            data: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized, B: BackoffPolicy> CasLock<T, B> {
+    /// Acquires the lock.
+    #[inline]
+    #[track_caller]
+    pub fn lock(&self) -> CasGuard<'_, T, B> {
+        #[cfg(feature = "debug-locks")]
+        self.owner.before_lock("CasLock");
+        while unlikely(
+            self.locked
+                .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err(),
+        ) {
+            backoff::load_when::<B, _>(&self.locked, false);
+        }
+        #[cfg(feature = "debug-locks")]
+        self.owner.acquired();
+        CasGuard {
+            lock: self,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Acquires the lock if it is free.
+    #[inline]
+    pub fn try_lock(&self) -> Option<CasGuard<'_, T, B>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        #[cfg(feature = "debug-locks")]
+        self.owner.acquired();
+        Some(CasGuard {
+            lock: self,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: ?Sized, B> CasLock<T, B> {
+    /// Returns `true` if the lock is held.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub(super) fn unlock(&self) {
+        #[cfg(feature = "debug-locks")]
+        self.owner.before_unlock("CasLock", self.is_locked());
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+guarded_lock!(
+    CasLock,
+    /// Exclusive access to a [`CasLock`]'s data; releases the lock on drop.
+    CasGuard
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn mutual_exclusion() {
+        const N: usize = 500;
+        let l = CasLock::new(0usize);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        *l.lock() += 1;
+                    }
+                });
+            }
+        });
+        let g = l.try_lock().unwrap();
+        assert_eq!(*g, 4 * N);
+        assert!(l.is_locked());
+        assert!(l.try_lock().is_none());
+    }
+}
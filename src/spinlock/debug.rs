@@ -0,0 +1,69 @@
+//! Lock misuse detection (the `debug-locks` feature).
+//!
+//! Each tracked lock records which thread holds it. Acquiring a lock the
+//! thread already holds, releasing a lock that is not held and releasing
+//! from a thread other than the holder all panic instead of deadlocking or
+//! corrupting the lock. Guards are `!Send` while the feature is on, so an
+//! RAII release always happens on the acquiring thread.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+std::thread_local! {
+    static MARKER: u8 = const { 0 };
+}
+
+/// A nonzero identifier unique among live threads.
+fn current() -> usize {
+    MARKER.with(|m| m as *const u8 as usize)
+}
+
+/// The holder of a lock, or 0.
+#[derive(Default)]
+pub(super) struct Owner(AtomicUsize);
+
+impl Owner {
+    pub(super) const fn new() -> Self {
+        Owner(AtomicUsize::new(0))
+    }
+
+    /// Panics if the calling thread already holds the lock.
+    #[track_caller]
+    pub(super) fn before_lock(&self, lock: &str) {
+        if self.0.load(Ordering::Relaxed) == current() {
+            panic!("recursive acquisition of a non-reentrant {lock}");
+        }
+    }
+
+    /// Records the calling thread as the holder.
+    #[inline]
+    pub(super) fn acquired(&self) {
+        self.0.store(current(), Ordering::Relaxed);
+    }
+
+    /// Panics unless the calling thread holds the lock, then clears the
+    /// holder. `locked` is the lock's own view of whether it is held.
+    #[track_caller]
+    pub(super) fn before_unlock(&self, lock: &str, locked: bool) {
+        if !locked {
+            panic!("unlock of an unlocked {lock}");
+        }
+        if self.0.load(Ordering::Relaxed) != current() {
+            panic!("{lock} unlocked by a thread that does not hold it");
+        }
+        self.0.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn ids_differ_between_threads() {
+        let here = current();
+        assert_ne!(here, 0);
+        assert_eq!(current(), here);
+        assert_ne!(thread::spawn(current).join().unwrap(), here);
+    }
+}
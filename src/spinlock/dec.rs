@@ -0,0 +1,124 @@
+//! The decrement lock (`ck_spinlock_dec`).
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(feature = "debug-locks")]
+use super::debug::Owner;
+use crate::backoff::{self, Backoff, BackoffPolicy};
+use crate::cc::unlikely;
+
+/// A decrement-based spinlock protecting a `T`.
+///
+/// The word is 1 when free. A locker decrements it and owns the lock if it
+/// reached 0; otherwise it waits for the word to return to 1 and tries
+/// again.
+pub struct DecLock<T: ?Sized, B = Backoff> {
+    value: AtomicU32,
+    #[cfg(feature = "debug-locks")]
+    owner: Owner,
+    _backoff: PhantomData<fn() -> B>,
+    data: UnsafeCell<T>,
+}
+
+impl<T> DecLock<T> {
+    /// Creates an unlocked lock protecting `value`.
+    pub const fn new(value: T) -> Self {
+        Self::with_backoff(value)
+    }
+}
+
+impl<T, B: BackoffPolicy> DecLock<T, B> {
+    /// Creates an unlocked lock protecting `value` that waits with a `B`
+    /// between polls.
+    pub const fn with_backoff(value: T) -> Self {
+        DecLock {
+            value: AtomicU32::new(1),
+            #[cfg(feature = "debug-locks")]
+            owner: Owner::new(),
+            _backoff: PhantomData,
+            data: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized, B: BackoffPolicy> DecLock<T, B> {
+    /// Acquires the lock.
+    #[inline]
+    #[track_caller]
+    pub fn lock(&self) -> DecGuard<'_, T, B> {
+        #[cfg(feature = "debug-locks")]
+        self.owner.before_lock("DecLock");
+        while unlikely(self.value.fetch_sub(1, Ordering::Acquire) != 1) {
+            backoff::load_when::<B, _>(&self.value, 1);
+        }
+        #[cfg(feature = "debug-locks")]
+        self.owner.acquired();
+        DecGuard {
+            lock: self,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Acquires the lock if it is free.
+    #[inline]
+    pub fn try_lock(&self) -> Option<DecGuard<'_, T, B>> {
+        self.value
+            .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        #[cfg(feature = "debug-locks")]
+        self.owner.acquired();
+        Some(DecGuard {
+            lock: self,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: ?Sized, B> DecLock<T, B> {
+    /// Returns `true` if the lock is held.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.value.load(Ordering::Relaxed) != 1
+    }
+
+    #[inline]
+    #[track_caller]
+    pub(super) fn unlock(&self) {
+        #[cfg(feature = "debug-locks")]
+        self.owner.before_unlock("DecLock", self.is_locked());
+        self.value.store(1, Ordering::Release);
+    }
+}
+
+guarded_lock!(
+    DecLock,
+    /// Exclusive access to a [`DecLock`]'s data; releases the lock on drop.
+    DecGuard
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn mutual_exclusion() {
+        const N: usize = 500;
+        let l = DecLock::new(0usize);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        *l.lock() += 1;
+                    }
+                });
+            }
+        });
+        let g = l.try_lock().unwrap();
+        assert_eq!(*g, 4 * N);
+        assert!(l.is_locked());
+        assert!(l.try_lock().is_none());
+    }
+}
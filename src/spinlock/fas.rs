@@ -0,0 +1,196 @@
+//! The fetch-and-store lock (`ck_spinlock_fas`).
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+
+#[cfg(feature = "stats")]
+use super::stats::StatsCell;
+#[cfg(feature = "stats")]
+use super::LockStats;
+use super::{RawSpinLock, SpinPolicy};
+use crate::backoff::{Backoff, BackoffPolicy};
+
+/// A test-and-set spinlock protecting a `T`.
+///
+/// Acquisition swaps the flag in and, while it was already set, spins
+/// reading it before swapping again.
+pub struct FasLock<T: ?Sized, B = Backoff> {
+    raw: RawSpinLock,
+    policy: SpinPolicy,
+    _backoff: PhantomData<fn() -> B>,
+    #[cfg(feature = "stats")]
+    stats: StatsCell,
+    data: UnsafeCell<T>,
+}
+
+impl<T> FasLock<T> {
+    /// Creates an unlocked lock protecting `value`.
+    pub const fn new(value: T) -> Self {
+        Self::with_policy(value, SpinPolicy::DEFAULT)
+    }
+
+    /// Creates an unlocked lock protecting `value` that waits as `policy`
+    /// says.
+    pub const fn with_policy(value: T, policy: SpinPolicy) -> Self {
+        Self::with_policy_and_backoff(value, policy)
+    }
+}
+
+impl<T, B: BackoffPolicy> FasLock<T, B> {
+    /// Creates an unlocked lock protecting `value` that backs off with a
+    /// `B` between polls.
+    pub const fn with_backoff(value: T) -> Self {
+        Self::with_policy_and_backoff(value, SpinPolicy::DEFAULT)
+    }
+
+    /// Creates an unlocked lock protecting `value` that waits as `policy`
+    /// says and backs off with a `B` between polls.
+    pub const fn with_policy_and_backoff(value: T, policy: SpinPolicy) -> Self {
+        FasLock {
+            raw: RawSpinLock::new(),
+            policy,
+            _backoff: PhantomData,
+            #[cfg(feature = "stats")]
+            stats: StatsCell::new(),
+            data: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized, B: BackoffPolicy> FasLock<T, B> {
+    /// Acquires the lock.
+    #[inline]
+    pub fn lock(&self) -> FasGuard<'_, T, B> {
+        let _c = self.raw.lock_counted::<B>(&self.policy);
+        #[cfg(feature = "stats")]
+        self.stats.acquired(_c);
+        FasGuard {
+            lock: self,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Acquires the lock if it is free.
+    #[inline]
+    pub fn try_lock(&self) -> Option<FasGuard<'_, T, B>> {
+        if !self.raw.try_lock() {
+            #[cfg(feature = "stats")]
+            self.stats.failed(1);
+            return None;
+        }
+        #[cfg(feature = "stats")]
+        self.stats.acquired(Default::default());
+        Some(FasGuard {
+            lock: self,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the lock's spin policy.
+    #[inline]
+    pub fn policy(&self) -> &SpinPolicy {
+        &self.policy
+    }
+
+    /// Returns `true` if the lock is held.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+
+    /// Returns the lock's contention counters.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> LockStats {
+        self.stats.snapshot()
+    }
+}
+
+impl<T: ?Sized, B> FasLock<T, B> {
+    #[inline]
+    pub(super) fn unlock(&self) {
+        // SAFETY: callers hold the lock.
+        unsafe { self.raw.unlock() }
+    }
+}
+
+guarded_lock!(
+    FasLock,
+    /// Exclusive access to a [`FasLock`]'s data; releases the lock on drop.
+    FasGuard
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn mutual_exclusion() {
+        const N: usize = 500;
+        let l = FasLock::new(0usize);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        *l.lock() += 1;
+                    }
+                });
+            }
+        });
+        let g = l.try_lock().unwrap();
+        assert_eq!(*g, 4 * N);
+        assert!(l.try_lock().is_none());
+        assert_eq!(std::format!("{l:?}"), "FasLock(<locked>)");
+        drop(g);
+        assert_eq!(std::format!("{l:?}"), std::format!("FasLock({})", 4 * N));
+        let mut l = l;
+        *l.get_mut() += 1;
+        assert_eq!(l.into_inner(), 4 * N + 1);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_count_acquisitions() {
+        let l = FasLock::new(());
+        drop(l.lock());
+        let g = l.lock();
+        assert!(l.try_lock().is_none());
+        drop(g);
+        let s = l.stats();
+        assert_eq!((s.acquisitions, s.cas_failures, s.max_spins), (2, 1, 0));
+    }
+
+    #[test]
+    fn no_backoff() {
+        use crate::backoff::NoBackoff;
+
+        let l: FasLock<usize, NoBackoff> = FasLock::with_backoff(0);
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..200 {
+                        *l.lock() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(l.into_inner(), 400);
+    }
+
+    #[test]
+    fn immediate_swap_policy() {
+        let policy = SpinPolicy::DEFAULT.with_test_before_swap(false);
+        let l = FasLock::with_policy(0usize, policy);
+        assert_eq!(l.policy(), &policy);
+        thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for _ in 0..200 {
+                        *l.lock() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(l.into_inner(), 400);
+    }
+}
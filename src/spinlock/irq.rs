@@ -0,0 +1,143 @@
+//! Interrupt-safe acquisition for kernel and bare-metal use.
+
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+
+/// Enters and leaves a context in which the lock holder cannot be
+/// preempted by code that takes the same lock, typically by masking
+/// interrupts.
+///
+/// `lock_irqsave` calls [`disable`](Self::disable) before spinning and
+/// [`restore`](Self::restore) after the lock is released, like Linux's
+/// `spin_lock_irqsave`/`spin_unlock_irqrestore`.
+pub trait CriticalSectionHook {
+    /// What `disable` saved, such as the previous interrupt mask.
+    type State;
+
+    /// Enters the critical section, returning the state to restore.
+    fn disable() -> Self::State;
+
+    /// Leaves the critical section entered by the matching `disable`.
+    fn restore(state: Self::State);
+}
+
+/// A lock guard taken inside a [`CriticalSectionHook`] section.
+///
+/// Dropping it releases the lock first and then restores the saved state.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct IrqSaveGuard<G, H: CriticalSectionHook> {
+    guard: ManuallyDrop<G>,
+    state: ManuallyDrop<H::State>,
+}
+
+impl<G, H: CriticalSectionHook> IrqSaveGuard<G, H> {
+    /// Runs `lock` after `H::disable`.
+    #[inline]
+    pub(super) fn acquire(lock: impl FnOnce() -> G) -> Self {
+        let state = H::disable();
+        IrqSaveGuard {
+            guard: ManuallyDrop::new(lock()),
+            state: ManuallyDrop::new(state),
+        }
+    }
+
+    /// Runs `try_lock` after `H::disable`, restoring at once if it fails.
+    #[inline]
+    pub(super) fn try_acquire(try_lock: impl FnOnce() -> Option<G>) -> Option<Self> {
+        let state = H::disable();
+        match try_lock() {
+            Some(guard) => Some(IrqSaveGuard {
+                guard: ManuallyDrop::new(guard),
+                state: ManuallyDrop::new(state),
+            }),
+            None => {
+                H::restore(state);
+                None
+            }
+        }
+    }
+}
+
+impl<G: Deref, H: CriticalSectionHook> Deref for IrqSaveGuard<G, H> {
+    type Target = G::Target;
+
+    #[inline]
+    fn deref(&self) -> &G::Target {
+        &self.guard
+    }
+}
+
+impl<G: DerefMut, H: CriticalSectionHook> DerefMut for IrqSaveGuard<G, H> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut G::Target {
+        &mut self.guard
+    }
+}
+
+impl<G, H: CriticalSectionHook> Drop for IrqSaveGuard<G, H> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: both fields are taken exactly once, here.
+        unsafe {
+            ManuallyDrop::drop(&mut self.guard);
+            H::restore(ManuallyDrop::take(&mut self.state));
+        }
+    }
+}
+
+impl<G: fmt::Debug, H: CriticalSectionHook> fmt::Debug for IrqSaveGuard<G, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.guard, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{FasLock, McsLock, McsNode};
+    use super::*;
+    use core::cell::Cell;
+
+    std::thread_local! {
+        static MASKED: Cell<bool> = const { Cell::new(false) };
+    }
+
+    struct Irq;
+
+    impl CriticalSectionHook for Irq {
+        type State = bool;
+
+        fn disable() -> bool {
+            MASKED.with(|m| m.replace(true))
+        }
+
+        fn restore(state: bool) {
+            MASKED.with(|m| m.set(state));
+        }
+    }
+
+    fn masked() -> bool {
+        MASKED.with(Cell::get)
+    }
+
+    #[test]
+    fn restores_after_release() {
+        let l = FasLock::new(0);
+        {
+            let mut g = l.lock_irqsave::<Irq>();
+            *g += 1;
+            assert!(masked());
+            assert!(l.try_lock_irqsave::<Irq>().is_none());
+            assert!(masked());
+            let m = McsLock::new(());
+            let mut node = McsNode::new();
+            // SAFETY: the guard is dropped at once.
+            drop(unsafe { m.lock_irqsave::<Irq>(&mut node) });
+            assert!(masked());
+        }
+        assert!(!masked());
+        assert!(!l.is_locked());
+        assert_eq!(*l.try_lock_irqsave::<Irq>().unwrap(), 1);
+        assert!(!masked());
+    }
+}
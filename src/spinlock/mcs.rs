@@ -0,0 +1,273 @@
+//! The MCS queue lock (`ck_spinlock_mcs`).
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+use super::{CriticalSectionHook, IrqSaveGuard};
+use crate::pr;
+
+/// A waiter's queue entry for an [`McsLock`].
+///
+/// Each acquisition borrows a node mutably until its guard drops, so a node
+/// usually lives on the acquiring thread's stack. The lock keeps a pointer
+/// to the node for as long as it is queued, which is why the node-taking
+/// methods are `unsafe`; [`McsLock::with_lock`] keeps a node of its own.
+#[derive(Debug, Default)]
+pub struct McsNode {
+    locked: AtomicBool,
+    next: AtomicPtr<McsNode>,
+}
+
+impl McsNode {
+    /// Creates an unqueued node.
+    pub const fn new() -> Self {
+        McsNode {
+            locked: AtomicBool::new(false),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+/// A fair queue spinlock protecting a `T`.
+///
+/// Waiters form a FIFO list of [`McsNode`]s and each spins on its own node,
+/// so a release touches only the next waiter's cache line.
+pub struct McsLock<T: ?Sized> {
+    tail: AtomicPtr<McsNode>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for McsLock<T> {}
+unsafe impl<T: ?Sized + Send> Sync for McsLock<T> {}
+
+impl<T> McsLock<T> {
+    /// Creates an unlocked lock protecting `value`.
+    pub const fn new(value: T) -> Self {
+        McsLock {
+            tail: AtomicPtr::new(ptr::null_mut()),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes the lock, returning the protected value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> McsLock<T> {
+    /// Runs `f` under the lock, queueing on a node on this stack frame.
+    #[inline]
+    pub fn with_lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut node = McsNode::new();
+        // SAFETY: the guard is dropped before `node`, on return or unwind.
+        let mut guard = unsafe { self.lock(&mut node) };
+        f(&mut *guard)
+    }
+
+    /// Like [`with_lock`](Self::with_lock), but returns `None` instead of
+    /// waiting if any thread holds or waits for the lock.
+    #[inline]
+    pub fn try_with_lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut node = McsNode::new();
+        // SAFETY: the guard is dropped before `node`, on return or unwind.
+        let r = unsafe { self.try_lock(&mut node) }.map(|mut g| f(&mut *g));
+        r
+    }
+
+    /// Acquires the lock, queueing behind earlier waiters on `node`.
+    ///
+    /// # Safety
+    ///
+    /// The guard must be dropped, not leaked with [`core::mem::forget`] or
+    /// a reference cycle: the lock points at `node` until the guard
+    /// releases it, and would otherwise go on to write through a dangling
+    /// pointer once `node` is gone.
+    pub unsafe fn lock<'a>(&'a self, node: &'a mut McsNode) -> McsGuard<'a, T> {
+        node.locked = AtomicBool::new(true);
+        node.next = AtomicPtr::new(ptr::null_mut());
+        let prev = self.tail.swap(node, Ordering::AcqRel);
+        if !prev.is_null() {
+            // SAFETY: `prev` stays valid until its owner has handed the lock
+            // on, which it cannot do before seeing this link.
+            unsafe { (*prev).next.store(node, Ordering::Release) };
+            pr::load_when(&node.locked, false);
+        }
+        McsGuard { lock: self, node }
+    }
+
+    /// Acquires the lock on `node` inside an `H` critical section, which
+    /// ends after the lock is released.
+    ///
+    /// # Safety
+    ///
+    /// As for [`lock`](Self::lock), the guard must not be leaked.
+    #[inline]
+    pub unsafe fn lock_irqsave<'a, H: CriticalSectionHook>(
+        &'a self,
+        node: &'a mut McsNode,
+    ) -> IrqSaveGuard<McsGuard<'a, T>, H> {
+        // SAFETY: the caller keeps the guard, and with it this one, alive.
+        IrqSaveGuard::acquire(|| unsafe { self.lock(node) })
+    }
+
+    /// Acquires the lock if no thread holds or waits for it.
+    ///
+    /// # Safety
+    ///
+    /// As for [`lock`](Self::lock), the guard must not be leaked.
+    pub unsafe fn try_lock<'a>(&'a self, node: &'a mut McsNode) -> Option<McsGuard<'a, T>> {
+        node.locked = AtomicBool::new(false);
+        node.next = AtomicPtr::new(ptr::null_mut());
+        self.tail
+            .compare_exchange(ptr::null_mut(), node, Ordering::AcqRel, Ordering::Relaxed)
+            .ok()?;
+        Some(McsGuard { lock: self, node })
+    }
+
+    /// Returns `true` if the lock is held.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        !self.tail.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Returns the value mutably; the exclusive borrow rules out other
+    /// holders, so no locking is needed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn unlock(&self, node: &McsNode) {
+        let mut next = node.next.load(Ordering::Acquire);
+        if next.is_null() {
+            let me = node as *const McsNode as *mut McsNode;
+            if self
+                .tail
+                .compare_exchange(me, ptr::null_mut(), Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                return;
+            }
+            // A waiter swapped itself in but has not linked to us yet.
+            next = pr::spin_while(&node.next, |n| n.is_null());
+        }
+        // SAFETY: the successor spins until this store and so is still alive.
+        unsafe { (*next).locked.store(false, Ordering::Release) };
+    }
+}
+
+impl<T: Default> Default for McsLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for McsLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.try_with_lock(|v| f.debug_tuple("McsLock").field(&&*v).finish());
+        r.unwrap_or_else(|| f.write_str("McsLock(<locked>)"))
+    }
+}
+
+/// Exclusive access to an [`McsLock`]'s data; releases the lock on drop.
+#[must_use = "dropping the guard releases the lock at once"]
+pub struct McsGuard<'a, T: ?Sized> {
+    lock: &'a McsLock<T>,
+    node: &'a mut McsNode,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for McsGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for McsGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for McsGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for McsGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.unlock(self.node);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for McsGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn try_lock_fails_while_held() {
+        let l = McsLock::new(1);
+        let (mut a, mut b) = (McsNode::new(), McsNode::new());
+        // SAFETY: every guard in this test is dropped.
+        unsafe {
+            let mut g = l.lock(&mut a);
+            *g += 1;
+            assert!(l.is_locked());
+            assert!(l.try_lock(&mut b).is_none());
+            assert!(l.try_with_lock(|_| ()).is_none());
+            drop(g);
+            assert_eq!(*l.try_lock(&mut b).unwrap(), 2);
+        }
+        assert!(!l.is_locked());
+        assert_eq!(l.try_with_lock(|v| *v), Some(2));
+    }
+
+    #[test]
+    fn with_lock_releases_on_panic() {
+        let l = McsLock::new(0);
+        let r = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+            l.with_lock(|v| {
+                *v += 1;
+                panic!("boom");
+            })
+        }));
+        assert!(r.is_err());
+        assert!(!l.is_locked());
+        assert_eq!(l.with_lock(|v| *v), 1);
+    }
+
+    #[test]
+    fn mutual_exclusion() {
+        const N: usize = 200;
+        let l = McsLock::new(0usize);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        l.with_lock(|v| *v += 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(l.with_lock(|v| *v), 4 * N);
+        assert_eq!(std::format!("{l:?}"), std::format!("McsLock({})", 4 * N));
+        let mut l = l;
+        *l.get_mut() += 1;
+        assert_eq!(l.into_inner(), 4 * N + 1);
+    }
+}
@@ -0,0 +1,189 @@
+//! Spinlocks modelled on `ck_spinlock`.
+//!
+//! Each lock owns the data it protects and hands out an RAII guard, in the
+//! style of `std::sync::Mutex`. Queue locks such as [`McsLock`] take a
+//! caller-provided node that lives on the waiter's stack for as long as the
+//! guard does; since a leaked guard would leave the lock pointing at a dead
+//! node, those methods are `unsafe` and `McsLock::with_lock` is the safe
+//! entry point. [`RawSpinLock`] and [`RawTicketLock`] carry no data, for
+//! embedding into structures whose fields they protect.
+//!
+//! [`FasLock`] and [`TicketLock`] wait according to a per-instance
+//! [`SpinPolicy`] chosen with `with_policy`. The simple locks also take a
+//! [`BackoffPolicy`] type parameter, [`Backoff`] by default, that delays
+//! between polls of a busy lock; `with_backoff` creates one with another
+//! policy, such as [`NoBackoff`](crate::backoff::NoBackoff).
+//!
+//! With the `lock_api` feature the simple locks also implement
+//! `lock_api::RawMutex`; see `FasMutex` and friends. With the `stats`
+//! feature [`FasLock`] and [`TicketLock`] count acquisitions, failed
+//! attempts and the longest wait, readable through `stats()`. The
+//! `debug-locks` feature makes the simple locks panic on recursive
+//! acquisition, double unlock and unlock by a thread that is not the holder.
+//!
+//! Every lock also has `lock_irqsave`, which wraps acquisition in a
+//! [`CriticalSectionHook`] such as an interrupt mask.
+//!
+//! | Lock            | `ck_spinlock` | Fair | Spins on         |
+//! |-----------------|---------------|------|------------------|
+//! | [`FasLock`]     | `fas`         | no   | the lock word    |
+//! | [`CasLock`]     | `cas`         | no   | the lock word    |
+//! | [`DecLock`]     | `dec`         | no   | the lock word    |
+//! | [`TicketLock`]  | `ticket`      | yes  | the owner word   |
+//! | [`AndersonLock`]| `anderson`    | yes  | a private slot   |
+//! | [`McsLock`]     | `mcs`         | yes  | a private node   |
+
+use crate::backoff::{Backoff, BackoffPolicy};
+
+/// Implements the guard type, `into_inner`/`get_mut` and the
+/// `Send`/`Sync`/`Default`/`Debug` impls for a lock whose guard only needs
+/// the lock reference. The lock provides `with_backoff`, `try_lock` and a
+/// private `unlock` free of `BackoffPolicy` bounds, and keeps its value in
+/// a `data` cell.
+macro_rules! guarded_lock {
+    ($lock:ident, $(#[$meta:meta])* $guard:ident) => {
+        impl<T, B> $lock<T, B> {
+            /// Consumes the lock, returning the protected value.
+            #[inline]
+            pub fn into_inner(self) -> T {
+                self.data.into_inner()
+            }
+        }
+
+        impl<T: ?Sized, B: super::BackoffPolicy> $lock<T, B> {
+            /// Acquires the lock inside an `H` critical section, which
+            /// ends after the lock is released.
+            #[inline]
+            pub fn lock_irqsave<H: super::CriticalSectionHook>(
+                &self,
+            ) -> super::IrqSaveGuard<$guard<'_, T, B>, H> {
+                super::IrqSaveGuard::acquire(|| self.lock())
+            }
+
+            /// Like `try_lock`, inside an `H` critical section that is left
+            /// at once if the lock is busy.
+            #[inline]
+            pub fn try_lock_irqsave<H: super::CriticalSectionHook>(
+                &self,
+            ) -> Option<super::IrqSaveGuard<$guard<'_, T, B>, H>> {
+                super::IrqSaveGuard::try_acquire(|| self.try_lock())
+            }
+
+            /// Returns the value mutably; the exclusive borrow rules out
+            /// other holders, so no locking is needed.
+            #[inline]
+            pub fn get_mut(&mut self) -> &mut T {
+                self.data.get_mut()
+            }
+        }
+
+        unsafe impl<T: ?Sized + Send, B> Send for $lock<T, B> {}
+        unsafe impl<T: ?Sized + Send, B> Sync for $lock<T, B> {}
+
+        impl<T: Default, B: super::BackoffPolicy> Default for $lock<T, B> {
+            fn default() -> Self {
+                Self::with_backoff(T::default())
+            }
+        }
+
+        impl<T: ?Sized + core::fmt::Debug, B: super::BackoffPolicy> core::fmt::Debug
+            for $lock<T, B>
+        {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match self.try_lock() {
+                    Some(g) => f.debug_tuple(stringify!($lock)).field(&&*g).finish(),
+                    None => f.write_str(concat!(stringify!($lock), "(<locked>)")),
+                }
+            }
+        }
+
+        $(#[$meta])*
+        #[must_use = "dropping the guard releases the lock at once"]
+        pub struct $guard<'a, T: ?Sized, B = super::Backoff> {
+            lock: &'a $lock<T, B>,
+            _marker: super::GuardMarker,
+        }
+
+        unsafe impl<T: ?Sized + Sync, B> Sync for $guard<'_, T, B> {}
+
+        impl<T: ?Sized, B> core::ops::Deref for $guard<'_, T, B> {
+            type Target = T;
+
+            #[inline]
+            fn deref(&self) -> &T {
+                // SAFETY: the guard holds the lock.
+                unsafe { &*self.lock.data.get() }
+            }
+        }
+
+        impl<T: ?Sized, B> core::ops::DerefMut for $guard<'_, T, B> {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut T {
+                // SAFETY: the guard holds the lock.
+                unsafe { &mut *self.lock.data.get() }
+            }
+        }
+
+        impl<T: ?Sized, B> Drop for $guard<'_, T, B> {
+            #[inline]
+            fn drop(&mut self) {
+                self.lock.unlock();
+            }
+        }
+
+        impl<T: ?Sized + core::fmt::Debug, B> core::fmt::Debug for $guard<'_, T, B> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::Debug::fmt(&**self, f)
+            }
+        }
+    };
+}
+
+/// What one acquisition ran into: failed atomic attempts and polls of the
+/// lock word while waiting.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Contention {
+    #[cfg_attr(not(feature = "stats"), allow(dead_code))]
+    pub(crate) failures: u64,
+    #[cfg_attr(not(feature = "stats"), allow(dead_code))]
+    pub(crate) spins: u64,
+}
+
+/// Makes guards `!Send` under `debug-locks`, so they release on the thread
+/// that acquired them.
+#[cfg(not(feature = "debug-locks"))]
+type GuardMarker = core::marker::PhantomData<()>;
+#[cfg(feature = "debug-locks")]
+type GuardMarker = core::marker::PhantomData<*const ()>;
+
+mod anderson;
+mod cas;
+#[cfg(feature = "debug-locks")]
+mod debug;
+mod dec;
+mod fas;
+mod irq;
+mod mcs;
+mod policy;
+mod raw;
+#[cfg(feature = "lock_api")]
+mod raw_mutex;
+#[cfg(feature = "stats")]
+mod stats;
+mod ticket;
+
+pub use self::anderson::{AndersonGuard, AndersonLock};
+pub use self::cas::{CasGuard, CasLock};
+pub use self::dec::{DecGuard, DecLock};
+pub use self::fas::{FasGuard, FasLock};
+pub use self::irq::{CriticalSectionHook, IrqSaveGuard};
+pub use self::mcs::{McsGuard, McsLock, McsNode};
+pub use self::policy::SpinPolicy;
+pub use self::raw::{RawSpinLock, RawTicketLock};
+#[cfg(feature = "lock_api")]
+pub use self::raw_mutex::{CasMutex, DecMutex, FasMutex, TicketMutex};
+#[cfg(feature = "stats")]
+pub use self::stats::LockStats;
+#[cfg(feature = "stats")]
+pub(crate) use self::stats::StatsCell;
+pub use self::ticket::{TicketGuard, TicketLock};
@@ -0,0 +1,96 @@
+//! Per-instance spin-wait tuning.
+
+use crate::pr::{AtomicLoad, StallPolicy};
+
+/// How a lock waits while it is held by another thread.
+///
+/// Stored in each [`FasLock`](super::FasLock) and
+/// [`TicketLock`](super::TicketLock) (see their `with_policy`), so
+/// latency-critical and throughput-oriented locks in one program can be
+/// tuned independently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpinPolicy {
+    /// How to stall between polls of the lock word.
+    pub stall: StallPolicy,
+    /// Polls after which each further wait yields the thread instead of
+    /// stalling. Only honoured with the `std` feature; `u32::MAX` never
+    /// yields.
+    pub yield_after: u32,
+    /// Read the lock word until it looks free before every atomic attempt
+    /// (test-and-test-and-set). When `false`, a failed attempt is retried
+    /// after one stall, which hands over faster but bounces the cache line.
+    /// Ticket locks take no such attempts and ignore this.
+    pub test_before_swap: bool,
+}
+
+impl SpinPolicy {
+    /// Test-and-test-and-set with one spin-loop hint per poll; what locks
+    /// use unless told otherwise.
+    pub const DEFAULT: SpinPolicy = SpinPolicy {
+        stall: StallPolicy::DEFAULT,
+        yield_after: u32::MAX,
+        test_before_swap: true,
+    };
+
+    /// Returns the policy with a different stall.
+    pub const fn with_stall(self, stall: StallPolicy) -> Self {
+        SpinPolicy { stall, ..self }
+    }
+
+    /// Returns the policy yielding after `polls` polls.
+    pub const fn with_yield_after(self, polls: u32) -> Self {
+        SpinPolicy {
+            yield_after: polls,
+            ..self
+        }
+    }
+
+    /// Returns the policy with test-and-test-and-set on or off.
+    pub const fn with_test_before_swap(self, test_before_swap: bool) -> Self {
+        SpinPolicy {
+            test_before_swap,
+            ..self
+        }
+    }
+
+    /// Waits once, `polls` polls into an acquisition, while `target` holds
+    /// `current`.
+    #[inline]
+    pub(super) fn wait<A>(&self, polls: u64, target: &A, current: A::Value)
+    where
+        A: AtomicLoad + ?Sized,
+    {
+        #[cfg(feature = "std")]
+        if polls >= u64::from(self.yield_after) {
+            std::thread::yield_now();
+            return;
+        }
+        #[cfg(not(feature = "std"))]
+        let _ = polls;
+        self.stall.stall_on(target, current);
+    }
+}
+
+impl Default for SpinPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builders() {
+        let p = SpinPolicy::DEFAULT
+            .with_stall(StallPolicy::pause(8))
+            .with_yield_after(100)
+            .with_test_before_swap(false);
+        assert_eq!(p.stall, StallPolicy::pause(8));
+        assert_eq!(p.yield_after, 100);
+        assert!(!p.test_before_swap);
+        assert_eq!(SpinPolicy::default(), SpinPolicy::DEFAULT);
+        p.wait(0, &core::sync::atomic::AtomicBool::new(true), false);
+    }
+}
@@ -0,0 +1,313 @@
+//! Spinlocks without embedded data.
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+#[cfg(feature = "debug-locks")]
+use super::debug::Owner;
+use super::{Contention, SpinPolicy};
+use crate::backoff::{BackoffPolicy, NoBackoff};
+use crate::cc::{likely, unlikely};
+
+/// A data-less fetch-and-store spinlock.
+///
+/// For protecting state the lock cannot own, such as a field of an existing
+/// `#[repr(C)]` structure. The layout is a single `bool`-sized word unless
+/// the `debug-locks` feature adds a holder field.
+/// [`FasLock`](super::FasLock) is this lock plus the value it guards.
+#[cfg_attr(not(feature = "debug-locks"), repr(transparent))]
+#[cfg_attr(feature = "debug-locks", repr(C))]
+#[derive(Default)]
+pub struct RawSpinLock {
+    locked: AtomicBool,
+    #[cfg(feature = "debug-locks")]
+    owner: Owner,
+}
+
+impl RawSpinLock {
+    /// Creates an unlocked lock.
+    pub const fn new() -> Self {
+        RawSpinLock {
+            locked: AtomicBool::new(false),
+            #[cfg(feature = "debug-locks")]
+            owner: Owner::new(),
+        }
+    }
+
+    /// Acquires the lock.
+    #[inline]
+    #[track_caller]
+    pub fn lock(&self) {
+        self.lock_counted::<NoBackoff>(&SpinPolicy::DEFAULT);
+    }
+
+    /// Acquires the lock, waiting as `policy` says.
+    #[inline]
+    #[track_caller]
+    pub fn lock_with(&self, policy: &SpinPolicy) {
+        self.lock_counted::<NoBackoff>(policy);
+    }
+
+    /// Acquires the lock, backing off with a `B` after each failed swap and
+    /// reporting the failed swaps and polls it took.
+    #[inline]
+    #[track_caller]
+    pub(super) fn lock_counted<B: BackoffPolicy>(&self, policy: &SpinPolicy) -> Contention {
+        #[cfg(feature = "debug-locks")]
+        self.owner.before_lock("RawSpinLock");
+        let mut c = Contention::default();
+        let mut backoff = B::default();
+        while unlikely(self.locked.swap(true, Ordering::Acquire)) {
+            c.failures += 1;
+            backoff.spin();
+            if policy.test_before_swap {
+                while self.locked.load(Ordering::Relaxed) {
+                    c.spins += 1;
+                    policy.wait(c.spins, &self.locked, true);
+                }
+            } else {
+                c.spins += 1;
+                policy.wait(c.spins, &self.locked, true);
+            }
+        }
+        #[cfg(feature = "debug-locks")]
+        self.owner.acquired();
+        c
+    }
+
+    /// Acquires the lock if it is free, returning `true` on success.
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        let ok = !self.locked.swap(true, Ordering::Acquire);
+        #[cfg(feature = "debug-locks")]
+        if ok {
+            self.owner.acquired();
+        }
+        ok
+    }
+
+    /// Releases the lock.
+    ///
+    /// # Safety
+    ///
+    /// The caller must hold the lock.
+    #[inline]
+    #[track_caller]
+    pub unsafe fn unlock(&self) {
+        #[cfg(feature = "debug-locks")]
+        self.owner.before_unlock("RawSpinLock", self.is_locked());
+        self.locked.store(false, Ordering::Release);
+    }
+
+    /// Returns `true` if the lock is held.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for RawSpinLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawSpinLock")
+            .field("locked", &self.is_locked())
+            .finish()
+    }
+}
+
+/// A data-less ticket lock.
+///
+/// The layout is two `u32` words, `next` then `owner`, followed by a holder
+/// field under the `debug-locks` feature.
+/// [`TicketLock`](super::TicketLock) is this lock plus the value it guards.
+#[repr(C)]
+#[derive(Default)]
+pub struct RawTicketLock {
+    next: AtomicU32,
+    owner: AtomicU32,
+    #[cfg(feature = "debug-locks")]
+    holder: Owner,
+}
+
+impl RawTicketLock {
+    /// Creates an unlocked lock.
+    pub const fn new() -> Self {
+        RawTicketLock {
+            next: AtomicU32::new(0),
+            owner: AtomicU32::new(0),
+            #[cfg(feature = "debug-locks")]
+            holder: Owner::new(),
+        }
+    }
+
+    /// Acquires the lock, waiting behind earlier lockers.
+    #[inline]
+    #[track_caller]
+    pub fn lock(&self) {
+        self.lock_counted::<NoBackoff>(&SpinPolicy::DEFAULT);
+    }
+
+    /// Acquires the lock, waiting as `policy` says.
+    #[inline]
+    #[track_caller]
+    pub fn lock_with(&self, policy: &SpinPolicy) {
+        self.lock_counted::<NoBackoff>(policy);
+    }
+
+    /// Acquires the lock, backing off with a `B` between polls of `owner`
+    /// and reporting the polls it took.
+    #[inline]
+    #[track_caller]
+    pub(super) fn lock_counted<B: BackoffPolicy>(&self, policy: &SpinPolicy) -> Contention {
+        #[cfg(feature = "debug-locks")]
+        self.holder.before_lock("RawTicketLock");
+        let mut c = Contention::default();
+        let mut backoff = B::default();
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+        loop {
+            let owner = self.owner.load(Ordering::Acquire);
+            if likely(owner == ticket) {
+                break;
+            }
+            c.spins += 1;
+            policy.wait(c.spins, &self.owner, owner);
+            backoff.spin();
+        }
+        #[cfg(feature = "debug-locks")]
+        self.holder.acquired();
+        c
+    }
+
+    /// Acquires the lock if no thread holds or waits for it, returning
+    /// `true` on success.
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        let owner = self.owner.load(Ordering::Acquire);
+        let ok = self
+            .next
+            .compare_exchange(
+                owner,
+                owner.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok();
+        #[cfg(feature = "debug-locks")]
+        if ok {
+            self.holder.acquired();
+        }
+        ok
+    }
+
+    /// Releases the lock to the next waiter.
+    ///
+    /// # Safety
+    ///
+    /// The caller must hold the lock.
+    #[inline]
+    #[track_caller]
+    pub unsafe fn unlock(&self) {
+        #[cfg(feature = "debug-locks")]
+        self.holder.before_unlock("RawTicketLock", self.is_locked());
+        // Only the holder writes `owner`.
+        let owner = self.owner.load(Ordering::Relaxed);
+        self.owner.store(owner.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Returns `true` if the lock is held.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.next.load(Ordering::Relaxed) != self.owner.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for RawTicketLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawTicketLock")
+            .field("locked", &self.is_locked())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::UnsafeCell;
+    use std::thread;
+
+    #[repr(C)]
+    struct Shared {
+        spin: RawSpinLock,
+        ticket: RawTicketLock,
+        a: UnsafeCell<usize>,
+        b: UnsafeCell<usize>,
+    }
+
+    unsafe impl Sync for Shared {}
+
+    #[test]
+    fn protect_external_fields() {
+        const N: usize = 200;
+        let s = Shared {
+            spin: RawSpinLock::new(),
+            ticket: RawTicketLock::new(),
+            a: UnsafeCell::new(0),
+            b: UnsafeCell::new(0),
+        };
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                let s = &s;
+                scope.spawn(move || {
+                    for _ in 0..N {
+                        s.spin.lock();
+                        unsafe {
+                            *s.a.get() += 1;
+                            s.spin.unlock();
+                        }
+                        s.ticket.lock();
+                        unsafe {
+                            *s.b.get() += 1;
+                            s.ticket.unlock();
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(s.a.into_inner(), 4 * N);
+        assert_eq!(s.b.into_inner(), 4 * N);
+        assert!(s.ticket.try_lock());
+        assert!(!s.ticket.try_lock());
+        assert!(s.spin.try_lock());
+        assert!(s.spin.is_locked());
+    }
+
+    #[cfg(feature = "debug-locks")]
+    #[test]
+    #[should_panic(expected = "recursive acquisition")]
+    fn recursive_lock_panics() {
+        let l = RawTicketLock::new();
+        l.lock();
+        l.lock();
+    }
+
+    #[cfg(feature = "debug-locks")]
+    #[test]
+    #[should_panic(expected = "unlock of an unlocked RawSpinLock")]
+    fn double_unlock_panics() {
+        let l = RawSpinLock::new();
+        l.lock();
+        unsafe {
+            l.unlock();
+            l.unlock();
+        }
+    }
+
+    #[cfg(feature = "debug-locks")]
+    #[test]
+    fn unlock_from_other_thread_panics() {
+        let l = RawSpinLock::new();
+        l.lock();
+        let r = thread::scope(|s| s.spawn(|| unsafe { l.unlock() }).join());
+        assert!(r.is_err());
+        assert!(l.is_locked());
+    }
+}
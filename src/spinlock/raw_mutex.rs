@@ -0,0 +1,100 @@
+//! `lock_api` integration.
+//!
+//! The data-less instance `L<()>` of each simple spinlock implements
+//! [`lock_api::RawMutex`], so `lock_api::Mutex<FasLock<()>, T>` (aliased as
+//! [`FasMutex<T>`]) gets mapped guards and interoperates with code written
+//! against `parking_lot`. [`McsLock`](super::McsLock) and
+//! [`AndersonLock`](super::AndersonLock) keep per-acquisition state in their
+//! guards, which `RawMutex::unlock` has no way to receive, so they are not
+//! covered.
+
+use core::mem;
+
+use super::{CasLock, DecLock, FasLock, TicketLock};
+
+macro_rules! raw_mutex {
+    ($($lock:ident => $(#[$meta:meta])* $alias:ident),* $(,)?) => {$(
+        unsafe impl lock_api::RawMutex for $lock<()> {
+            #[allow(clippy::declare_interior_mutable_const)]
+            const INIT: Self = $lock::new(());
+
+            #[cfg(not(feature = "debug-locks"))]
+            type GuardMarker = lock_api::GuardSend;
+            #[cfg(feature = "debug-locks")]
+            type GuardMarker = lock_api::GuardNoSend;
+
+            #[inline]
+            fn lock(&self) {
+                mem::forget($lock::lock(self));
+            }
+
+            #[inline]
+            fn try_lock(&self) -> bool {
+                $lock::try_lock(self).map(mem::forget).is_some()
+            }
+
+            #[inline]
+            unsafe fn unlock(&self) {
+                $lock::unlock(self);
+            }
+
+            #[inline]
+            fn is_locked(&self) -> bool {
+                $lock::is_locked(self)
+            }
+        }
+
+        $(#[$meta])*
+        pub type $alias<T> = lock_api::Mutex<$lock<()>, T>;
+    )*};
+}
+
+raw_mutex!(
+    FasLock => /// A `lock_api` mutex built on [`FasLock`].
+    FasMutex,
+    CasLock => /// A `lock_api` mutex built on [`CasLock`].
+    CasMutex,
+    DecLock => /// A `lock_api` mutex built on [`DecLock`].
+    DecMutex,
+    TicketLock => /// A `lock_api` mutex built on [`TicketLock`].
+    TicketMutex,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lock_api::MutexGuard;
+    use std::thread;
+
+    static COUNTER: TicketMutex<usize> = TicketMutex::new(0);
+
+    #[test]
+    fn const_mutex_excludes() {
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..200 {
+                        *COUNTER.lock() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(*COUNTER.lock(), 800);
+    }
+
+    #[test]
+    fn mapped_guard() {
+        let m = FasMutex::new((1, 2));
+        {
+            let mut second = MutexGuard::map(m.lock(), |p| &mut p.1);
+            *second += 1;
+            assert!(m.is_locked());
+            assert!(m.try_lock().is_none());
+        }
+        assert_eq!(*m.lock(), (1, 3));
+        let d = DecMutex::new(0);
+        let c = CasMutex::new(0);
+        *d.lock() += *c.lock() + 1;
+        assert_eq!(d.into_inner(), 1);
+    }
+}
@@ -0,0 +1,97 @@
+//! Per-lock contention counters (the `stats` feature).
+
+use core::sync::atomic::Ordering;
+
+use super::Contention;
+use crate::pr::AtomicU64;
+
+/// A snapshot of a lock's contention counters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LockStats {
+    /// Successful acquisitions.
+    pub acquisitions: u64,
+    /// Atomic operations that failed to take the lock, including failed
+    /// `try_lock` calls.
+    pub cas_failures: u64,
+    /// The most polls of the lock word made by a single acquisition.
+    pub max_spins: u64,
+}
+
+/// The live counters behind [`LockStats`], updated with relaxed atomics.
+pub(crate) struct StatsCell {
+    acquisitions: AtomicU64,
+    cas_failures: AtomicU64,
+    max_spins: AtomicU64,
+}
+
+impl StatsCell {
+    pub(crate) const fn new() -> Self {
+        StatsCell {
+            acquisitions: AtomicU64::new(0),
+            cas_failures: AtomicU64::new(0),
+            max_spins: AtomicU64::new(0),
+        }
+    }
+
+    /// Records one acquisition that met `c`.
+    #[inline]
+    pub(crate) fn acquired(&self, c: Contention) {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        self.failed(c.failures);
+        let mut max = self.max_spins.load(Ordering::Relaxed);
+        while c.spins > max {
+            match self.max_spins.compare_exchange_weak(
+                max,
+                c.spins,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(m) => max = m,
+            }
+        }
+    }
+
+    /// Records `n` failed attempts.
+    #[inline]
+    pub(crate) fn failed(&self, n: u64) {
+        if n != 0 {
+            self.cas_failures.fetch_add(n, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> LockStats {
+        LockStats {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            cas_failures: self.cas_failures.load(Ordering::Relaxed),
+            max_spins: self.max_spins.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_maximum_spins() {
+        let s = StatsCell::new();
+        s.acquired(Contention {
+            failures: 2,
+            spins: 7,
+        });
+        s.acquired(Contention {
+            failures: 0,
+            spins: 3,
+        });
+        s.failed(1);
+        assert_eq!(
+            s.snapshot(),
+            LockStats {
+                acquisitions: 2,
+                cas_failures: 3,
+                max_spins: 7,
+            }
+        );
+    }
+}
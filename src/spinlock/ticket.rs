@@ -0,0 +1,147 @@
+//! The ticket lock (`ck_spinlock_ticket`).
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+
+#[cfg(feature = "stats")]
+use super::stats::StatsCell;
+#[cfg(feature = "stats")]
+use super::LockStats;
+use super::{RawTicketLock, SpinPolicy};
+use crate::backoff::{Backoff, BackoffPolicy};
+
+/// A fair FIFO spinlock protecting a `T`.
+///
+/// Lockers take a ticket from `next` and wait for `owner` to reach it;
+/// releasing advances `owner` by one.
+pub struct TicketLock<T: ?Sized, B = Backoff> {
+    raw: RawTicketLock,
+    policy: SpinPolicy,
+    _backoff: PhantomData<fn() -> B>,
+    #[cfg(feature = "stats")]
+    stats: StatsCell,
+    data: UnsafeCell<T>,
+}
+
+impl<T> TicketLock<T> {
+    /// Creates an unlocked lock protecting `value`.
+    pub const fn new(value: T) -> Self {
+        Self::with_policy(value, SpinPolicy::DEFAULT)
+    }
+
+    /// Creates an unlocked lock protecting `value` that waits as `policy`
+    /// says.
+    pub const fn with_policy(value: T, policy: SpinPolicy) -> Self {
+        Self::with_policy_and_backoff(value, policy)
+    }
+}
+
+impl<T, B: BackoffPolicy> TicketLock<T, B> {
+    /// Creates an unlocked lock protecting `value` that backs off with a
+    /// `B` between polls.
+    pub const fn with_backoff(value: T) -> Self {
+        Self::with_policy_and_backoff(value, SpinPolicy::DEFAULT)
+    }
+
+    /// Creates an unlocked lock protecting `value` that waits as `policy`
+    /// says and backs off with a `B` between polls.
+    pub const fn with_policy_and_backoff(value: T, policy: SpinPolicy) -> Self {
+        TicketLock {
+            raw: RawTicketLock::new(),
+            policy,
+            _backoff: PhantomData,
+            #[cfg(feature = "stats")]
+            stats: StatsCell::new(),
+            data: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized, B: BackoffPolicy> TicketLock<T, B> {
+    /// Acquires the lock.
+    #[inline]
+    pub fn lock(&self) -> TicketGuard<'_, T, B> {
+        let _c = self.raw.lock_counted::<B>(&self.policy);
+        #[cfg(feature = "stats")]
+        self.stats.acquired(_c);
+        TicketGuard {
+            lock: self,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Acquires the lock if no thread holds or waits for it.
+    #[inline]
+    pub fn try_lock(&self) -> Option<TicketGuard<'_, T, B>> {
+        if !self.raw.try_lock() {
+            #[cfg(feature = "stats")]
+            self.stats.failed(1);
+            return None;
+        }
+        #[cfg(feature = "stats")]
+        self.stats.acquired(Default::default());
+        Some(TicketGuard {
+            lock: self,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the lock's spin policy.
+    #[inline]
+    pub fn policy(&self) -> &SpinPolicy {
+        &self.policy
+    }
+
+    /// Returns `true` if the lock is held.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+
+    /// Returns the lock's contention counters.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> LockStats {
+        self.stats.snapshot()
+    }
+}
+
+impl<T: ?Sized, B> TicketLock<T, B> {
+    #[inline]
+    pub(super) fn unlock(&self) {
+        // SAFETY: callers hold the lock.
+        unsafe { self.raw.unlock() }
+    }
+}
+
+guarded_lock!(
+    TicketLock,
+    /// Exclusive access to a [`TicketLock`]'s data; releases the lock on drop.
+    TicketGuard
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn mutual_exclusion() {
+        const N: usize = 200;
+        let l = TicketLock::with_policy(0usize, SpinPolicy::DEFAULT.with_yield_after(16));
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..N {
+                        *l.lock() += 1;
+                    }
+                });
+            }
+        });
+        let g = l.try_lock().unwrap();
+        assert_eq!(*g, 4 * N);
+        assert!(l.is_locked());
+        assert!(l.try_lock().is_none());
+        #[cfg(feature = "stats")]
+        assert_eq!(l.stats().acquisitions, 4 * N as u64 + 1);
+    }
+}
@@ -0,0 +1,488 @@
+//! Lock-free intrusive stack modelled on `ck_stack`.
+//!
+//! A [`Stack`] links caller-owned [`StackEntry`] values, usually embedded in
+//! a larger element and recovered with an
+//! [`IntrusiveAdapter`](crate::cc::IntrusiveAdapter). Pushing is always safe
+//! to do concurrently. Popping dereferences the current head, so callers
+//! must guarantee popped entries are neither freed nor pushed again while
+//! another thread may still be inside `pop`; `OwnedStack` handles that
+//! through a pluggable `Reclaimer` (epoch by default, or `Leaky`).
+//! [`VersionedStack`] relaxes the rule to allow recycling entries by
+//! tagging its head with a generation count.
+//!
+//! The `stack-len-hint` feature adds a relaxed element counter, read with
+//! `Stack::len_hint`, at the cost of one extra atomic per operation.
+
+use core::ptr::{self, NonNull};
+#[cfg(feature = "stack-len-hint")]
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[cfg(feature = "alloc")]
+mod owned;
+#[cfg(feature = "alloc")]
+mod reclaim;
+mod versioned;
+
+#[cfg(feature = "alloc")]
+pub use self::owned::{EpochReclaimer, OwnedStack};
+#[cfg(feature = "alloc")]
+pub use self::reclaim::{Leaky, Reclaimer};
+pub use self::versioned::VersionedStack;
+
+/// Link embedded in every element of a [`Stack`].
+#[derive(Debug, Default)]
+pub struct StackEntry {
+    next: AtomicPtr<StackEntry>,
+}
+
+impl StackEntry {
+    /// Creates an unlinked entry.
+    pub const fn new() -> Self {
+        StackEntry {
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns the entry below this one, or null.
+    #[inline]
+    pub fn next(&self) -> *mut StackEntry {
+        self.next.load(Ordering::Relaxed)
+    }
+}
+
+/// A multi-producer, multi-consumer intrusive stack.
+#[derive(Debug, Default)]
+pub struct Stack {
+    head: AtomicPtr<StackEntry>,
+    #[cfg(feature = "stack-len-hint")]
+    len: AtomicUsize,
+}
+
+impl Stack {
+    /// Creates an empty stack.
+    pub const fn new() -> Self {
+        Stack {
+            head: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(feature = "stack-len-hint")]
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    // Pushes count up before publishing and pops count down after
+    // detaching, so the counter never drops below zero.
+    #[inline(always)]
+    fn count_up(&self, _n: usize) {
+        #[cfg(feature = "stack-len-hint")]
+        self.len.fetch_add(_n, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    fn count_down(&self, _n: usize) {
+        #[cfg(feature = "stack-len-hint")]
+        self.len.fetch_sub(_n, Ordering::Relaxed);
+    }
+
+    /// Returns the approximate number of entries.
+    ///
+    /// The count is updated separately from the stack itself, so it may be
+    /// briefly too high while pushes and pops are in flight.
+    #[cfg(feature = "stack-len-hint")]
+    #[inline]
+    pub fn len_hint(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the stack has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Returns the current top entry, or null.
+    #[inline]
+    pub fn head(&self) -> *mut StackEntry {
+        self.head.load(Ordering::Acquire)
+    }
+
+    /// Pushes `entry`.
+    ///
+    /// # Safety
+    ///
+    /// `entry` must be valid and not on any stack until it is popped.
+    #[inline]
+    pub unsafe fn push(&self, entry: NonNull<StackEntry>) {
+        let entry = entry.as_ptr();
+        self.count_up(1);
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            (*entry).next.store(head, Ordering::Relaxed);
+            match self
+                .head
+                .compare_exchange_weak(head, entry, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Pushes `entry` without a CAS loop.
+    ///
+    /// # Safety
+    ///
+    /// As for [`push`](Self::push), and no other thread may push or pop
+    /// concurrently (single producer, no consumer).
+    #[inline]
+    pub unsafe fn push_spnc(&self, entry: NonNull<StackEntry>) {
+        let entry = entry.as_ptr();
+        self.count_up(1);
+        (*entry)
+            .next
+            .store(self.head.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.head.store(entry, Ordering::Release);
+    }
+
+    /// Pushes the pre-linked chain `head..=tail` with a single CAS.
+    ///
+    /// `head` ends up on top; the chain keeps its order below it.
+    ///
+    /// # Safety
+    ///
+    /// Every entry must be valid and on no stack, and following
+    /// [`StackEntry::next`] from `head` must reach `tail`.
+    #[inline]
+    pub unsafe fn push_chain(&self, head: NonNull<StackEntry>, tail: NonNull<StackEntry>) {
+        let (head, tail) = (head.as_ptr(), tail.as_ptr());
+        #[cfg(feature = "stack-len-hint")]
+        {
+            let mut n = 1;
+            let mut p = head;
+            while p != tail {
+                p = (*p).next();
+                n += 1;
+            }
+            self.count_up(n);
+        }
+        let mut top = self.head.load(Ordering::Relaxed);
+        loop {
+            (*tail).next.store(top, Ordering::Relaxed);
+            match self
+                .head
+                .compare_exchange_weak(top, head, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(h) => top = h,
+            }
+        }
+    }
+
+    /// Pops the top entry.
+    ///
+    /// # Safety
+    ///
+    /// No entry popped by any thread may be freed or pushed again while
+    /// another `pop` may still be reading it.
+    #[inline]
+    pub unsafe fn pop(&self) -> Option<NonNull<StackEntry>> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let entry = NonNull::new(head)?;
+            let next = entry.as_ref().next.load(Ordering::Relaxed);
+            match self
+                .head
+                .compare_exchange_weak(head, next, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    self.count_down(1);
+                    return Some(entry);
+                }
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Pops the top entry without a CAS loop.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may push or pop concurrently (no producer, single
+    /// consumer).
+    #[inline]
+    pub unsafe fn pop_npsc(&self) -> Option<NonNull<StackEntry>> {
+        let entry = NonNull::new(self.head.load(Ordering::Acquire))?;
+        self.head.store(
+            entry.as_ref().next.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        self.count_down(1);
+        Some(entry)
+    }
+
+    /// Pops up to `n` entries with a single CAS, returning the first of a
+    /// null-terminated chain in LIFO order (null if the stack is empty or
+    /// `n` is zero).
+    ///
+    /// # Safety
+    ///
+    /// As for [`pop`](Self::pop).
+    pub unsafe fn pop_many(&self, n: usize) -> *mut StackEntry {
+        if n == 0 {
+            return ptr::null_mut();
+        }
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            if head.is_null() {
+                return head;
+            }
+            let mut last = head;
+            let mut taken = 1;
+            while taken < n {
+                let next = (*last).next.load(Ordering::Acquire);
+                if next.is_null() {
+                    break;
+                }
+                last = next;
+                taken += 1;
+            }
+            let rest = (*last).next.load(Ordering::Relaxed);
+            match self
+                .head
+                .compare_exchange_weak(head, rest, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    (*last).next.store(ptr::null_mut(), Ordering::Relaxed);
+                    self.count_down(taken);
+                    return head;
+                }
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Detaches every entry, returning the former top (null if empty).
+    ///
+    /// The entries stay linked through [`StackEntry::next`] in LIFO order.
+    #[inline]
+    pub fn pop_all(&self) -> *mut StackEntry {
+        let head = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        #[cfg(feature = "stack-len-hint")]
+        {
+            let mut n = 0;
+            let mut p = head;
+            while !p.is_null() {
+                // SAFETY: the swap detached the chain; it is ours to walk.
+                p = unsafe { (*p).next() };
+                n += 1;
+            }
+            self.count_down(n);
+        }
+        head
+    }
+
+    /// Detaches every entry and iterates over them, most recent first.
+    ///
+    /// Use [`ChainIter::reversed`] for push order.
+    #[inline]
+    pub fn drain(&self) -> ChainIter {
+        // SAFETY: pop_all returns a null-terminated chain of pushed entries.
+        unsafe { ChainIter::new(self.pop_all()) }
+    }
+}
+
+/// Consuming iterator over a detached, null-terminated chain of entries.
+///
+/// Each entry's successor is read before the entry is yielded, so the
+/// caller may free or reuse yielded entries immediately.
+#[derive(Debug)]
+pub struct ChainIter {
+    next: *mut StackEntry,
+}
+
+impl ChainIter {
+    /// Iterates over the chain starting at `head` (null for none).
+    ///
+    /// # Safety
+    ///
+    /// The chain must be null-terminated and owned by the caller, as
+    /// returned by [`Stack::pop_all`] or [`Stack::pop_many`].
+    #[inline]
+    pub unsafe fn new(head: *mut StackEntry) -> Self {
+        ChainIter { next: head }
+    }
+
+    /// Relinks the remaining entries in reverse, e.g. to turn a LIFO chain
+    /// into push (FIFO) order.
+    pub fn reversed(self) -> Self {
+        let mut prev = ptr::null_mut();
+        let mut p = self.next;
+        while !p.is_null() {
+            // SAFETY: the caller owns every entry on the chain.
+            unsafe {
+                let next = (*p).next();
+                (*p).next.store(prev, Ordering::Relaxed);
+                prev = p;
+                p = next;
+            }
+        }
+        ChainIter { next: prev }
+    }
+}
+
+impl Iterator for ChainIter {
+    type Item = NonNull<StackEntry>;
+
+    #[inline]
+    fn next(&mut self) -> Option<NonNull<StackEntry>> {
+        let entry = NonNull::new(self.next)?;
+        // SAFETY: entries on the chain are valid until yielded.
+        self.next = unsafe { entry.as_ref().next() };
+        Some(entry)
+    }
+}
+
+impl core::iter::FusedIterator for ChainIter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn lifo() {
+        let entries: [StackEntry; 3] = Default::default();
+        let s = Stack::new();
+        assert!(s.is_empty());
+        unsafe {
+            for e in &entries {
+                s.push(NonNull::from(e));
+            }
+            assert_eq!(
+                s.pop().unwrap().as_ptr() as *const _,
+                &entries[2] as *const _
+            );
+        }
+        let top = s.pop_all();
+        assert_eq!(top as *const _, &entries[1] as *const _);
+        assert_eq!(
+            unsafe { (*top).next() } as *const _,
+            &entries[0] as *const _
+        );
+        assert!(s.is_empty());
+        assert!(unsafe { s.pop() }.is_none());
+    }
+
+    fn collect(mut p: *mut StackEntry) -> Vec<*mut StackEntry> {
+        let mut v = Vec::new();
+        while !p.is_null() {
+            v.push(p);
+            p = unsafe { (*p).next() };
+        }
+        v
+    }
+
+    #[test]
+    fn batches() {
+        let e: [StackEntry; 5] = Default::default();
+        let p = |i: usize| &e[i] as *const StackEntry as *mut StackEntry;
+        let s = Stack::new();
+        unsafe {
+            s.push(NonNull::from(&e[4]));
+            e[0].next.store(p(1), Ordering::Relaxed);
+            e[1].next.store(p(2), Ordering::Relaxed);
+            s.push_chain(NonNull::from(&e[0]), NonNull::from(&e[2]));
+            assert!(s.pop_many(0).is_null());
+            assert_eq!(collect(s.pop_many(2)), [p(0), p(1)]);
+            assert_eq!(collect(s.pop_many(9)), [p(2), p(4)]);
+            assert!(s.pop_many(1).is_null());
+        }
+    }
+
+    #[test]
+    fn single_sided() {
+        let e: [StackEntry; 2] = Default::default();
+        let s = Stack::new();
+        unsafe {
+            s.push_spnc(NonNull::from(&e[0]));
+            s.push_spnc(NonNull::from(&e[1]));
+            assert_eq!(s.pop_npsc(), Some(NonNull::from(&e[1])));
+            assert_eq!(s.pop_npsc(), Some(NonNull::from(&e[0])));
+            assert_eq!(s.pop_npsc(), None);
+        }
+    }
+
+    #[test]
+    fn drain_in_both_orders() {
+        let e: [StackEntry; 3] = Default::default();
+        let p = |i: usize| NonNull::from(&e[i]);
+        let s = Stack::new();
+        for i in 0..3 {
+            unsafe { s.push(p(i)) };
+        }
+        assert!(s.drain().eq([p(2), p(1), p(0)]));
+        for i in 0..3 {
+            unsafe { s.push(p(i)) };
+        }
+        assert!(s.drain().reversed().eq([p(0), p(1), p(2)]));
+        assert_eq!(s.drain().next(), None);
+        unsafe { s.push_chain(p(0), p(0)) };
+        assert!(unsafe { ChainIter::new(s.pop_many(5)) }.eq([p(0)]));
+    }
+
+    #[cfg(feature = "stack-len-hint")]
+    #[test]
+    fn len_hint() {
+        let e: [StackEntry; 4] = Default::default();
+        let s = Stack::new();
+        unsafe {
+            for x in &e {
+                s.push(NonNull::from(x));
+            }
+            assert_eq!(s.len_hint(), 4);
+            s.pop();
+            s.pop_many(2);
+            assert_eq!(s.len_hint(), 1);
+            s.push_chain(NonNull::from(&e[1]), NonNull::from(&e[1]));
+            assert_eq!(s.len_hint(), 2);
+        }
+        s.pop_all();
+        assert_eq!(s.len_hint(), 0);
+    }
+
+    #[test]
+    fn concurrent_push_pop() {
+        const N: usize = 1000;
+        let entries: Vec<StackEntry> = (0..4 * N).map(|_| StackEntry::new()).collect();
+        let s = Stack::new();
+        let popped = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for chunk in entries.chunks(N) {
+                let s = &s;
+                scope.spawn(move || {
+                    for e in chunk {
+                        unsafe { s.push(NonNull::from(e)) };
+                    }
+                });
+            }
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    // Entries are never freed or re-pushed, so pop is sound.
+                    while popped.load(Ordering::Relaxed) < 2 * N {
+                        if unsafe { s.pop() }.is_some() {
+                            popped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+        let mut rest = 0;
+        let mut p = s.pop_all();
+        while !p.is_null() {
+            rest += 1;
+            p = unsafe { (*p).next() };
+        }
+        assert!(popped.load(Ordering::Relaxed) + rest == 4 * N);
+    }
+}
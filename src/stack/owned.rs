@@ -0,0 +1,186 @@
+//! Safe stack of owned values.
+
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
+use core::ptr;
+
+use super::{Reclaimer, Stack, StackEntry};
+use crate::epoch::{self, Epoch};
+use crate::malloc::{DeferredAllocator, GlobalAllocator, MIN_ALIGN};
+
+#[repr(C)]
+struct Node<T> {
+    entry: StackEntry,
+    value: ManuallyDrop<T>,
+}
+
+/// The default [`OwnedStack`] reclaimer: the global allocator behind an
+/// epoch domain.
+pub type EpochReclaimer<'e> = DeferredAllocator<'e, GlobalAllocator>;
+
+/// A lock-free stack of `T` values.
+///
+/// Nodes are allocated from the [`Reclaimer`] `R` and, once popped, freed
+/// through it with `defer` set, so `pop` needs no `unsafe`. By default `R`
+/// is an [`EpochReclaimer`] on [`epoch::global`]; each `pop` then registers
+/// with the epoch domain for its duration. Types aligned to more than
+/// [`MIN_ALIGN`] are rejected at compile time.
+pub struct OwnedStack<T, R: Reclaimer = EpochReclaimer<'static>> {
+    stack: Stack,
+    alloc: R,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send, R: Reclaimer + Send> Send for OwnedStack<T, R> {}
+unsafe impl<T: Send, R: Reclaimer + Sync> Sync for OwnedStack<T, R> {}
+
+impl<T> OwnedStack<T> {
+    /// Creates an empty stack reclaiming through [`epoch::global`].
+    pub fn new() -> Self {
+        Self::with_epoch(epoch::global())
+    }
+}
+
+impl<'e, T> OwnedStack<T, EpochReclaimer<'e>> {
+    /// Creates an empty stack reclaiming through `epoch`.
+    pub fn with_epoch(epoch: &'e Epoch) -> Self {
+        Self::with_reclaimer(DeferredAllocator::new(GlobalAllocator, epoch))
+    }
+}
+
+impl<T, R: Reclaimer> OwnedStack<T, R> {
+    const SIZE: usize = mem::size_of::<Node<T>>();
+
+    /// Creates an empty stack allocating and reclaiming through `alloc`.
+    pub fn with_reclaimer(alloc: R) -> Self {
+        const { assert!(mem::align_of::<Node<T>>() <= MIN_ALIGN) };
+        OwnedStack {
+            stack: Stack::new(),
+            alloc,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the stack is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Returns the approximate number of values.
+    #[cfg(feature = "stack-len-hint")]
+    #[inline]
+    pub fn len_hint(&self) -> usize {
+        self.stack.len_hint()
+    }
+
+    /// Pushes `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node cannot be allocated.
+    pub fn push(&self, value: T) {
+        let node = self
+            .alloc
+            .malloc(Self::SIZE)
+            .expect("OwnedStack node allocation failed")
+            .cast::<Node<T>>();
+        // SAFETY: the block is large and aligned enough for a node, and a
+        // fresh node is on no stack.
+        unsafe {
+            node.as_ptr().write(Node {
+                entry: StackEntry::new(),
+                value: ManuallyDrop::new(value),
+            });
+            self.stack.push(node.cast());
+        }
+    }
+
+    /// Pops the most recently pushed value.
+    pub fn pop(&self) -> Option<T> {
+        // SAFETY: popped nodes are freed with `defer`, so none is released
+        // while this section runs, and never pushed again.
+        let node = self
+            .alloc
+            .protected(|| unsafe { self.stack.pop() })?
+            .cast::<Node<T>>();
+        // SAFETY: the successful pop gave us the node; its value is read once.
+        unsafe {
+            let value = ManuallyDrop::into_inner(ptr::read(&(*node.as_ptr()).value));
+            self.alloc.free(node.cast(), Self::SIZE, true);
+            Some(value)
+        }
+    }
+}
+
+impl<T> Default for OwnedStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, R: Reclaimer> Drop for OwnedStack<T, R> {
+    fn drop(&mut self) {
+        for entry in self.stack.drain() {
+            let node = entry.cast::<Node<T>>();
+            // SAFETY: `&mut self` rules out concurrent pops.
+            unsafe {
+                ManuallyDrop::drop(&mut (*node.as_ptr()).value);
+                self.alloc.free(node.cast(), Self::SIZE, false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stack::Leaky;
+    use std::boxed::Box;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn push_pop_drop() {
+        let s = OwnedStack::new();
+        s.push(Box::new(1));
+        s.push(Box::new(2));
+        assert_eq!(s.pop().as_deref(), Some(&2));
+        s.push(Box::new(3));
+        assert!(!s.is_empty());
+    }
+
+    #[test]
+    fn leaky() {
+        let s = OwnedStack::with_reclaimer(Leaky(GlobalAllocator));
+        s.push(1u64);
+        assert_eq!(s.pop(), Some(1));
+        assert_eq!(s.pop(), None);
+    }
+
+    #[test]
+    fn concurrent() {
+        const N: usize = 2000;
+        let epoch = Epoch::new();
+        let s = OwnedStack::with_epoch(&epoch);
+        let sum = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for t in 0..4 {
+                let (s, sum) = (&s, &sum);
+                scope.spawn(move || {
+                    let mut got = Vec::new();
+                    for i in 0..N {
+                        s.push(t * N + i);
+                        if i % 2 == 1 {
+                            got.extend(s.pop());
+                        }
+                    }
+                    sum.fetch_add(got.into_iter().sum(), Ordering::Relaxed);
+                });
+            }
+        });
+        let rest: usize = core::iter::from_fn(|| s.pop()).sum();
+        assert_eq!(sum.into_inner() + rest, (0..4 * N).sum());
+    }
+}
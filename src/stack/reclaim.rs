@@ -0,0 +1,89 @@
+//! Reclamation backends for [`OwnedStack`](super::OwnedStack).
+
+use core::ptr::NonNull;
+
+use crate::malloc::{AllocError, Allocator, DeferredAllocator};
+
+/// An [`Allocator`] whose deferred frees respect read-side sections.
+///
+/// # Safety
+///
+/// A block passed to `free` with `defer == true` must stay valid until
+/// every [`protected`](Self::protected) call that was running when it was
+/// freed has returned.
+pub unsafe trait Reclaimer: Allocator {
+    /// Runs `f` in a read-side section.
+    fn protected<R>(&self, f: impl FnOnce() -> R) -> R;
+}
+
+unsafe impl<A: Allocator> Reclaimer for DeferredAllocator<'_, A> {
+    #[inline]
+    fn protected<R>(&self, f: impl FnOnce() -> R) -> R {
+        let local = self.epoch().register();
+        let _guard = local.pin();
+        f()
+    }
+}
+
+/// A reclaimer that never frees deferred blocks.
+///
+/// Read-side sections cost nothing, and memory passed to deferred frees is
+/// leaked. Suitable for short-lived structures or bounded workloads.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Leaky<A>(pub A);
+
+unsafe impl<A: Allocator> Allocator for Leaky<A> {
+    #[inline]
+    fn malloc(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        self.0.malloc(size)
+    }
+
+    #[inline]
+    fn malloc_zeroed(&self, size: usize) -> Result<NonNull<u8>, AllocError> {
+        self.0.malloc_zeroed(size)
+    }
+
+    #[inline]
+    unsafe fn realloc(
+        &self,
+        ptr: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        may_move: bool,
+    ) -> Result<NonNull<u8>, AllocError> {
+        self.0.realloc(ptr, old_size, new_size, may_move)
+    }
+
+    #[inline]
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize, defer: bool) {
+        if !defer {
+            self.0.free(ptr, size, false)
+        }
+    }
+}
+
+unsafe impl<A: Allocator> Reclaimer for Leaky<A> {
+    #[inline(always)]
+    fn protected<R>(&self, f: impl FnOnce() -> R) -> R {
+        f()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::malloc::{CountingAllocator, GlobalAllocator};
+
+    #[test]
+    fn leaky_keeps_deferred_blocks() {
+        let c = CountingAllocator::new(GlobalAllocator);
+        let l = Leaky(&c);
+        let (p, q) = (l.malloc(8).unwrap(), l.malloc(8).unwrap());
+        unsafe {
+            l.free(p, 8, true);
+            l.free(q, 8, false);
+        }
+        assert_eq!(l.protected(|| c.stats().live_bytes), 8);
+        unsafe { GlobalAllocator.free(p, 8, false) };
+    }
+}
@@ -0,0 +1,232 @@
+//! Stack with a generation-counted head.
+
+use core::ptr::NonNull;
+use core::sync::atomic::Ordering;
+
+use super::{ChainIter, StackEntry};
+
+/// On 64-bit targets the tag takes the top 16 bits of the head word.
+#[cfg(target_pointer_width = "64")]
+type Head = crate::pr::AtomicTaggedPtr<StackEntry>;
+
+/// On narrower targets the head is a double-width word: the pointer in the
+/// low half and a full-width tag in the high half.
+#[cfg(not(target_pointer_width = "64"))]
+use self::wide::Head;
+
+#[cfg(not(target_pointer_width = "64"))]
+mod wide {
+    use core::fmt;
+    use core::sync::atomic::Ordering;
+
+    use crate::pr::AtomicU64;
+    use crate::stack::StackEntry;
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub(super) struct Tagged(u64);
+
+    impl Tagged {
+        #[inline]
+        pub(super) fn ptr(self) -> *mut StackEntry {
+            self.0 as u32 as usize as *mut StackEntry
+        }
+
+        #[inline]
+        pub(super) fn tag(self) -> usize {
+            (self.0 >> 32) as usize
+        }
+
+        #[inline]
+        pub(super) fn successor(self, ptr: *mut StackEntry) -> Self {
+            let tag = (self.0 >> 32).wrapping_add(1) & 0xFFFF_FFFF;
+            Tagged(tag << 32 | ptr as usize as u64)
+        }
+    }
+
+    #[derive(Default)]
+    pub(super) struct Head(AtomicU64);
+
+    impl Head {
+        pub(super) const fn null() -> Self {
+            Head(AtomicU64::new(0))
+        }
+
+        #[inline]
+        pub(super) fn load(&self, order: Ordering) -> Tagged {
+            Tagged(self.0.load(order))
+        }
+
+        #[inline]
+        pub(super) fn compare_exchange_weak(
+            &self,
+            current: Tagged,
+            new: Tagged,
+            success: Ordering,
+            failure: Ordering,
+        ) -> Result<Tagged, Tagged> {
+            self.0
+                .compare_exchange_weak(current.0, new.0, success, failure)
+                .map(Tagged)
+                .map_err(Tagged)
+        }
+    }
+
+    impl fmt::Debug for Head {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let v = self.load(Ordering::Relaxed);
+            f.debug_struct("Head")
+                .field("ptr", &v.ptr())
+                .field("tag", &v.tag())
+                .finish()
+        }
+    }
+}
+
+/// A multi-producer, multi-consumer intrusive stack whose pop is ABA-safe.
+///
+/// The head carries a generation tag bumped by every update, as
+/// `ck_stack_pop_mpmc` does with its double-width CAS, so a pop that raced
+/// with another pop and a re-push of the same entry fails its CAS instead of
+/// corrupting the stack. Entries may therefore be recycled freely. They may
+/// still not be freed while a `pop` could be reading them; use `OwnedStack`
+/// for that.
+///
+/// On 64-bit targets the tag has 16 bits, packed above a 48-bit address as
+/// described for [`TaggedPtr`](crate::pr::TaggedPtr). On 32-bit targets the
+/// head is a 64-bit word holding the pointer and a 32-bit tag, updated with
+/// a double-width CAS where the target has one and through the lock of
+/// [`pr::AtomicU64`](crate::pr::AtomicU64) where it does not.
+#[derive(Debug, Default)]
+pub struct VersionedStack {
+    head: Head,
+}
+
+impl VersionedStack {
+    /// Creates an empty stack.
+    pub const fn new() -> Self {
+        VersionedStack { head: Head::null() }
+    }
+
+    /// Returns `true` if the stack has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Relaxed).ptr().is_null()
+    }
+
+    /// Returns the number of head updates so far, modulo the tag width.
+    #[inline]
+    pub fn generation(&self) -> usize {
+        self.head.load(Ordering::Relaxed).tag()
+    }
+
+    /// Pushes `entry`.
+    ///
+    /// # Safety
+    ///
+    /// `entry` must be valid and not on any stack until it is popped.
+    #[inline]
+    pub unsafe fn push(&self, entry: NonNull<StackEntry>) {
+        let entry = entry.as_ptr();
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            (*entry).next.store(head.ptr(), Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                head.successor(entry),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Pops the top entry.
+    ///
+    /// # Safety
+    ///
+    /// Every entry ever pushed must stay allocated while another thread may
+    /// be inside `pop`; entries may be pushed again at any time.
+    #[inline]
+    pub unsafe fn pop(&self) -> Option<NonNull<StackEntry>> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let entry = NonNull::new(head.ptr())?;
+            let next = entry.as_ref().next.load(Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                head.successor(next),
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(entry),
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Detaches every entry and iterates over them, most recent first.
+    pub fn drain(&self) -> ChainIter {
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            match self.head.compare_exchange_weak(
+                head,
+                head.successor(core::ptr::null_mut()),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                // SAFETY: the chain is detached and null-terminated.
+                Ok(_) => return unsafe { ChainIter::new(head.ptr()) },
+                Err(h) => head = h,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn generation_counts_updates() {
+        let e: [StackEntry; 2] = Default::default();
+        let s = VersionedStack::new();
+        unsafe {
+            s.push(NonNull::from(&e[0]));
+            s.push(NonNull::from(&e[1]));
+            assert_eq!(s.pop(), Some(NonNull::from(&e[1])));
+        }
+        assert_eq!(s.generation(), 3);
+        assert!(s.drain().eq([NonNull::from(&e[0])]));
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn recycle_entries_concurrently() {
+        let entries: Vec<StackEntry> = (0..64).map(|_| StackEntry::new()).collect();
+        let s = VersionedStack::new();
+        for e in &entries {
+            unsafe { s.push(NonNull::from(e)) };
+        }
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..20_000 {
+                        // Pop and immediately recycle: the pattern that
+                        // corrupts an untagged stack.
+                        if let Some(e) = unsafe { s.pop() } {
+                            unsafe { s.push(e) };
+                        }
+                    }
+                });
+            }
+        });
+        let mut seen: Vec<_> = s.drain().collect();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), entries.len());
+    }
+}